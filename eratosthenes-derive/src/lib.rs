@@ -0,0 +1,254 @@
+//! `#[derive(PrimeField)]`: arithmetic modulo a prime fixed at compile time, for a newtype over
+//! `u64`.
+//!
+//! ```ignore
+//! use eratosthenes::PrimeField;
+//!
+//! #[derive(PrimeField, Debug, Clone, Copy, PartialEq, Eq)]
+//! #[prime = 17]
+//! struct Fp(u64);
+//!
+//! let a = Fp::new(5);
+//! assert_eq!(a * a.inv().unwrap(), Fp::ONE);
+//! assert_eq!((a - Fp::new(6)).value(), 16);
+//! ```
+//!
+//! The derive adds `MODULUS`, `ZERO`, `ONE`, `new`, `value`, `pow` and `inv`, the operators
+//! `+ - * /` and unary `-` with their assigning forms, `From<u64>` and `Display`. Division by zero
+//! panics, as it does for integers. The modulus is checked for primality when the derive
+//! expands, and every operation reduces by it as a constant, so the compiler can strength-reduce
+//! the division; below 2^32 products are taken in u64 rather than through
+//! `eratosthenes::arith::mul_mod`.
+//!
+//! The value must stay reduced into `0..MODULUS` for the operators to be right: build elements
+//! with `new` or `from` rather than the tuple constructor. The generated code calls into the
+//! `eratosthenes` crate, which must be a dependency; it re-exports this derive behind its
+//! `derive` feature.
+
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+#[proc_macro_derive(PrimeField, attributes(prime))]
+pub fn derive_prime_field(input: TokenStream) -> TokenStream {
+    match parse(input).map(|(name, p)| expand(&name, p)) {
+        Ok(code) => code.parse().unwrap(),
+        Err(msg) => format!("::core::compile_error!({:?});", msg).parse().unwrap(),
+    }
+}
+
+// The struct's name and the value of its `#[prime = P]` attribute.
+fn parse(input: TokenStream) -> Result<(String, u64), String> {
+    let mut prime = None;
+    let mut name = None;
+    let mut field = None;
+    let mut tokens = input.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                if let Some(TokenTree::Group(group)) = tokens.next() {
+                    let attr: Vec<TokenTree> = group.stream().into_iter().collect();
+                    if let [TokenTree::Ident(key), TokenTree::Punct(eq), TokenTree::Literal(value)] = &attr[..] {
+                        if key.to_string() == "prime" && eq.as_char() == '=' {
+                            prime = Some(parse_u64(&value.to_string()).ok_or("#[prime = P] needs an integer P that fits in a u64")?);
+                        }
+                    } else if matches!(attr.first(), Some(TokenTree::Ident(key)) if key.to_string() == "prime") {
+                        return Err("expected #[prime = P]".to_string());
+                    }
+                }
+            }
+            TokenTree::Ident(ident) if ident.to_string() == "struct" => match tokens.next() {
+                Some(TokenTree::Ident(ident)) => name = Some(ident.to_string()),
+                _ => return Err("expected a struct name".to_string()),
+            },
+            TokenTree::Ident(ident) if ident.to_string() == "enum" || ident.to_string() == "union" => {
+                return Err("PrimeField can only be derived for a struct".to_string());
+            }
+            TokenTree::Group(group) if name.is_some() && field.is_none() => {
+                let inner: Vec<String> = group.stream().into_iter().map(|t| t.to_string()).collect();
+                let ty = inner.iter().filter(|t| !matches!(t.as_str(), "pub" | ",")).cloned().collect::<Vec<_>>();
+                if group.delimiter() != Delimiter::Parenthesis || ty != ["u64"] {
+                    return Err("PrimeField needs a tuple struct with a single u64 field, like `struct Fp(u64);`".to_string());
+                }
+                field = Some(());
+            }
+            TokenTree::Punct(punct) if punct.as_char() == '<' && name.is_some() => {
+                return Err("PrimeField cannot be derived for a generic struct".to_string());
+            }
+            _ => {}
+        }
+    }
+    let name = name.ok_or("PrimeField can only be derived for a struct")?;
+    if field.is_none() {
+        return Err("PrimeField needs a tuple struct with a single u64 field, like `struct Fp(u64);`".to_string());
+    }
+    let p = prime.ok_or("PrimeField needs the modulus as #[prime = P]")?;
+    if !is_prime(p) {
+        return Err(format!("{} is not prime", p));
+    }
+    Ok((name, p))
+}
+
+// An integer literal, allowing underscores and a u64 suffix.
+fn parse_u64(literal: &str) -> Option<u64> {
+    let digits: String = literal.trim_end_matches("u64").chars().filter(|&c| c != '_').collect();
+    digits.parse().ok()
+}
+
+fn expand(name: &str, p: u64) -> String {
+    let mul = if p <= u32::MAX as u64 {
+        format!("{}(self.0 * rhs.0 % {}u64)", name, p)
+    } else {
+        format!("{}(::eratosthenes::arith::mul_mod(self.0, rhs.0, {}u64))", name, p)
+    };
+    format!(
+        r#"
+impl {name} {{
+    /// The prime this field is modulo.
+    pub const MODULUS: u64 = {p}u64;
+    pub const ZERO: Self = {name}(0);
+    pub const ONE: Self = {name}(1);
+
+    /// `v` reduced modulo the prime.
+    #[must_use]
+    pub const fn new(v: u64) -> Self {{
+        {name}(v % {p}u64)
+    }}
+
+    /// The element as an integer in `0..MODULUS`.
+    #[must_use]
+    pub const fn value(self) -> u64 {{
+        self.0
+    }}
+
+    #[must_use]
+    pub fn pow(self, e: u64) -> Self {{
+        {name}(::eratosthenes::arith::pow_mod(self.0, e, {p}u64))
+    }}
+
+    /// Multiplicative inverse by Fermat's little theorem. Zero has none.
+    #[must_use]
+    pub fn inv(self) -> ::core::option::Option<Self> {{
+        if self.0 == 0 {{
+            ::core::option::Option::None
+        }} else {{
+            ::core::option::Option::Some(self.pow({p}u64 - 2))
+        }}
+    }}
+}}
+
+impl ::core::ops::Add for {name} {{
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {{
+        let (sum, carry) = self.0.overflowing_add(rhs.0);
+        {name}(if carry || sum >= {p}u64 {{ sum.wrapping_sub({p}u64) }} else {{ sum }})
+    }}
+}}
+
+impl ::core::ops::Sub for {name} {{
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {{
+        {name}(if self.0 >= rhs.0 {{ self.0 - rhs.0 }} else {{ self.0.wrapping_sub(rhs.0).wrapping_add({p}u64) }})
+    }}
+}}
+
+impl ::core::ops::Mul for {name} {{
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {{
+        {mul}
+    }}
+}}
+
+impl ::core::ops::Div for {name} {{
+    type Output = Self;
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, rhs: Self) -> Self {{
+        self * rhs.inv().expect("division by zero in GF({p})")
+    }}
+}}
+
+impl ::core::ops::Neg for {name} {{
+    type Output = Self;
+    fn neg(self) -> Self {{
+        {name}(if self.0 == 0 {{ 0 }} else {{ {p}u64 - self.0 }})
+    }}
+}}
+
+impl ::core::ops::AddAssign for {name} {{
+    fn add_assign(&mut self, rhs: Self) {{
+        *self = {name}(self.0) + rhs;
+    }}
+}}
+
+impl ::core::ops::SubAssign for {name} {{
+    fn sub_assign(&mut self, rhs: Self) {{
+        *self = {name}(self.0) - rhs;
+    }}
+}}
+
+impl ::core::ops::MulAssign for {name} {{
+    fn mul_assign(&mut self, rhs: Self) {{
+        *self = {name}(self.0) * rhs;
+    }}
+}}
+
+impl ::core::ops::DivAssign for {name} {{
+    fn div_assign(&mut self, rhs: Self) {{
+        *self = {name}(self.0) / rhs;
+    }}
+}}
+
+impl ::core::convert::From<u64> for {name} {{
+    fn from(v: u64) -> Self {{
+        {name}::new(v)
+    }}
+}}
+
+impl ::core::fmt::Display for {name} {{
+    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {{
+        ::core::fmt::Display::fmt(&self.0, f)
+    }}
+}}
+"#
+    )
+}
+
+// Deterministic Miller-Rabin for u64, as in `eratosthenes::is_prime`, which a proc-macro crate
+// cannot depend on without building the whole library for the host.
+fn is_prime(n: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    let mul_mod = |a: u64, b: u64| (a as u128 * b as u128 % n as u128) as u64;
+    let pow_mod = |mut base: u64, mut exp: u64| {
+        let mut result = 1;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = mul_mod(result, base);
+            }
+            base = mul_mod(base, base);
+            exp >>= 1;
+        }
+        result
+    };
+    if n < 2 {
+        return false;
+    }
+    for p in BASES {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    'witness: for a in BASES {
+        let mut x = pow_mod(a, d);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x);
+            if x == n - 1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
@@ -0,0 +1,50 @@
+// Pinning dispatch queue workers to CPUs, compiled in with the `thread-affinity` feature on
+// Linux. Elsewhere, or without the feature, there are no CPUs to pin to and pinning does nothing.
+
+#[cfg(all(feature = "thread-affinity", target_os = "linux"))]
+mod imp {
+    use std::mem::size_of;
+    use std::os::raw::c_int;
+
+    // glibc's cpu_set_t: a bitmask over CPU_SETSIZE = 1024 CPUs.
+    type CpuSet = [u64; 1024 / 64];
+
+    extern "C" {
+        fn sched_getaffinity(pid: c_int, size: usize, mask: *mut CpuSet) -> c_int;
+        fn sched_setaffinity(pid: c_int, size: usize, mask: *const CpuSet) -> c_int;
+    }
+
+    pub fn allowed_cpus() -> Vec<usize> {
+        let mut mask: CpuSet = [0; 16];
+        // Pid 0 is the calling thread; the set is inherited, so it is the process's unless
+        // someone has narrowed this thread's.
+        if unsafe { sched_getaffinity(0, size_of::<CpuSet>(), &mut mask) } != 0 {
+            return Vec::new();
+        }
+        (0..mask.len() * 64).filter(|&cpu| mask[cpu / 64] >> (cpu % 64) & 1 == 1).collect()
+    }
+
+    pub fn pin_current_thread(cpu: usize) -> bool {
+        let mut mask: CpuSet = [0; 16];
+        if cpu >= mask.len() * 64 {
+            return false;
+        }
+        mask[cpu / 64] |= 1 << (cpu % 64);
+        unsafe { sched_setaffinity(0, size_of::<CpuSet>(), &mask) == 0 }
+    }
+}
+
+#[cfg(not(all(feature = "thread-affinity", target_os = "linux")))]
+mod imp {
+    pub fn allowed_cpus() -> Vec<usize> {
+        Vec::new()
+    }
+
+    pub fn pin_current_thread(_cpu: usize) -> bool {
+        false
+    }
+}
+
+// allowed_cpus: the CPUs this process may run on, ascending; empty where pinning is unsupported.
+// pin_current_thread(cpu): restricts the calling thread to `cpu`, returning whether that took.
+pub(crate) use imp::{allowed_cpus, pin_current_thread};
@@ -0,0 +1,250 @@
+#[must_use]
+pub fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    ((a as u128 * b as u128) % m as u128) as u64
+}
+
+#[must_use]
+pub fn pow_mod(mut base: u64, mut exp: u64, m: u64) -> u64 {
+    if m == 1 {
+        return 0;
+    }
+    let mut result = 1;
+    base %= m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+#[must_use]
+pub fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+/// Inverse of `a` modulo `m` by the extended Euclidean algorithm, if `gcd(a, m) == 1`.
+#[must_use]
+pub fn inv_mod(a: u64, m: u64) -> Option<u64> {
+    let (mut old_r, mut r) = (a as i128 % m as i128, m as i128);
+    let (mut old_s, mut s) = (1i128, 0i128);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_s, s) = (s, old_s - q * s);
+    }
+    if old_r != 1 {
+        return None;
+    }
+    Some(old_s.rem_euclid(m as i128) as u64)
+}
+
+// Miller-Rabin with the first 12 prime bases is deterministic for all u64.
+const MILLER_RABIN_BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+#[must_use]
+pub fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for &p in MILLER_RABIN_BASES.iter() {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+
+    'witness: for &a in MILLER_RABIN_BASES.iter() {
+        let mut x = pow_mod(a, d, n);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+
+    true
+}
+
+/// The smallest prime greater than `n`, or `None` past the largest u64 prime.
+#[must_use]
+pub fn next_prime(n: u64) -> Option<u64> {
+    if n < 2 {
+        return Some(2);
+    }
+    // Step through odd candidates only.
+    let mut candidate = n.checked_add(1)? | 1;
+    loop {
+        if is_prime(candidate) {
+            return Some(candidate);
+        }
+        candidate = candidate.checked_add(2)?;
+    }
+}
+
+/// The prime closest to `n`, the smaller of the two on a tie; 2 for `n <= 2`.
+///
+/// ```
+/// use eratosthenes::nearest_prime;
+///
+/// assert_eq!(nearest_prime(10), 11);
+/// assert_eq!(nearest_prime(9), 7);
+/// ```
+#[must_use]
+pub fn nearest_prime(n: u64) -> u64 {
+    match n {
+        0..=2 => return 2,
+        3 | 4 => return 3,
+        _ => {}
+    }
+    // Walk the 6k +/- 1 candidates outward from n on both sides, always testing the nearer one.
+    // The walk down stops at 5 at the latest; the walk up ends at u64::MAX.
+    let mut below = match n % 6 {
+        0 | 2 => n - 1,
+        3 => n - 2,
+        4 => n - 3,
+        _ => n,
+    };
+    let mut above = n.checked_add(match n % 6 {
+        0 | 4 => 1,
+        3 | 5 => 2,
+        2 => 3,
+        _ => 4,
+    });
+    loop {
+        match above {
+            Some(up) if up - n < n - below => {
+                if is_prime(up) {
+                    return up;
+                }
+                above = up.checked_add(if up % 6 == 1 { 4 } else { 2 });
+            }
+            _ => {
+                if is_prime(below) {
+                    return below;
+                }
+                below -= if below % 6 == 1 { 2 } else { 4 };
+            }
+        }
+    }
+}
+
+/// Total number of divisors of `1..=n`, by the Dirichlet hyperbola method in O(sqrt(n)):
+/// `2 * sum(floor(n / i) for i <= sqrt(n)) - floor(sqrt(n))^2`. The total grows as `n ln n`,
+/// past u64 from about `n = 4.5e17`, so it is returned as a u128.
+///
+/// ```
+/// use eratosthenes::number_of_divisors_up_to;
+///
+/// // d(1) + d(2) + ... + d(10) = 1 + 2 + 2 + 3 + 2 + 4 + 2 + 4 + 3 + 4.
+/// assert_eq!(number_of_divisors_up_to(10), 27);
+/// ```
+#[must_use]
+pub fn number_of_divisors_up_to(n: u64) -> u128 {
+    let s = n.isqrt();
+    let sum: u128 = (1..=s).map(|i| (n / i) as u128).sum();
+    2 * sum - s as u128 * s as u128
+}
+
+// F(93) is the largest Fibonacci number that fits in a u64.
+const MAX_U64_FIBONACCI_INDEX: u64 = 93;
+
+/// The Fibonacci primes `(i, F(i))` for indices `i <= n`, with `F(1) = F(2) = 1`.
+///
+/// `F(i)` is composite whenever `i` is composite (other than `F(4) = 3`), so only prime indices
+/// are tested. Values past `F(93)` overflow u64 and are not searched: larger `n` gives the same
+/// result as `n = 93`.
+#[must_use]
+pub fn prime_recurrence_fibonacci(n: u64) -> Vec<(u64, u64)> {
+    let mut fibonacci_primes = Vec::new();
+    let (mut a, mut b) = (0u64, 1u64);
+    for i in 1..=n.min(MAX_U64_FIBONACCI_INDEX) {
+        (a, b) = (b, a.wrapping_add(b));
+        if (i == 4 || is_prime(i)) && is_prime(a) {
+            fibonacci_primes.push((i, a));
+        }
+    }
+    fibonacci_primes
+}
+
+// The largest k with k(2k - 1) <= n, from the root of 2k^2 - k - n.
+fn hexagonal_index_floor(n: u64) -> u64 {
+    ((1 + (1 + 8 * n as u128).isqrt()) / 4) as u64
+}
+
+/// The `k` for which `n` is the hexagonal number `k(2k - 1)`, if it is one (`0` for `n = 0`).
+///
+/// ```
+/// use eratosthenes::hexagonal_root;
+///
+/// assert_eq!(hexagonal_root(1), Some(1));
+/// assert_eq!(hexagonal_root(45), Some(5));
+/// assert_eq!(hexagonal_root(46), None);
+/// ```
+#[must_use]
+pub fn hexagonal_root(n: u64) -> Option<u64> {
+    let k = hexagonal_index_floor(n);
+    (k as u128 * (2 * k as u128).saturating_sub(1) == n as u128).then_some(k)
+}
+
+/// The hexagonal numbers `k(2k - 1) <= n` that are prime, testing only the `O(sqrt(n))`
+/// hexagonal numbers rather than sieving.
+///
+/// The answer is always empty: `k(2k - 1)` has the factors `k` and `2k - 1`, both above 1 once
+/// `k >= 2`, and the one hexagonal number left, 1, is not prime. The search confirms that rather
+/// than assuming it.
+///
+/// ```
+/// use eratosthenes::prime_hexagonal_numbers;
+///
+/// assert!(prime_hexagonal_numbers(1_000_000_000).is_empty());
+/// ```
+#[must_use]
+pub fn prime_hexagonal_numbers(n: u64) -> Vec<u64> {
+    (1..=hexagonal_index_floor(n))
+        .map(|k| k * (2 * k - 1))
+        .filter(|&h| is_prime(h))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The sum of d(k) for k <= n, counting each k's divisors directly.
+    fn divisors_summed(n: u64) -> u128 {
+        (1..=n).map(|k| (1..=k).filter(|d| k.is_multiple_of(*d)).count() as u128).sum()
+    }
+
+    #[test]
+    fn number_of_divisors_up_to_small_n() {
+        assert_eq!(number_of_divisors_up_to(0), 0);
+        assert_eq!(number_of_divisors_up_to(1), 1);
+        assert_eq!(number_of_divisors_up_to(10), 27);
+        assert_eq!(number_of_divisors_up_to(100), 482);
+        for n in 0..300 {
+            assert_eq!(number_of_divisors_up_to(n), divisors_summed(n), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn number_of_divisors_up_to_large_n() {
+        // OEIS A057494: D(10^k).
+        assert_eq!(number_of_divisors_up_to(10u64.pow(6)), 13_970_034);
+        assert_eq!(number_of_divisors_up_to(10u64.pow(12)), 27_785_452_449_086);
+    }
+}
@@ -0,0 +1,47 @@
+use arrow_array::Int64Array;
+
+use crate::sieve::Sieve;
+
+impl Sieve {
+    /// The primes up to `n` as an Arrow `Int64Array` (the type `arrow::array::Int64Array`
+    /// re-exports), for query engines built on arrow-rs such as DataFusion. The values are
+    /// written straight from [`primes`](Self::primes) into the array's buffer, with no
+    /// intermediate `Vec`.
+    ///
+    /// Needs the `arrow` feature.
+    ///
+    /// ```
+    /// let primes = eratosthenes::Sieve::new(100).unwrap().as_arrow_array();
+    /// assert_eq!(primes.len(), 25);
+    /// assert_eq!(primes.values().iter().sum::<i64>(), 1060);
+    /// ```
+    ///
+    /// Summing them with a DataFusion SQL query (with `datafusion` and `tokio` as dependencies):
+    ///
+    /// ```ignore
+    /// use std::sync::Arc;
+    ///
+    /// use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    /// use datafusion::arrow::record_batch::RecordBatch;
+    /// use datafusion::prelude::SessionContext;
+    /// use eratosthenes::Sieve;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> datafusion::error::Result<()> {
+    ///     let primes = Sieve::new(1_000_000).unwrap().as_arrow_array();
+    ///     let schema = Arc::new(Schema::new(vec![Field::new("prime", DataType::Int64, false)]));
+    ///     let batch = RecordBatch::try_new(schema, vec![Arc::new(primes)])?;
+    ///
+    ///     let ctx = SessionContext::new();
+    ///     ctx.register_batch("primes", batch)?;
+    ///     ctx.sql("SELECT SUM(prime) AS total FROM primes").await?.show().await?;
+    ///     // total: 37550402023
+    ///     Ok(())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn as_arrow_array(&self) -> Int64Array {
+        // Every u64 prime a sieve can hold fits in an i64.
+        Int64Array::from_iter_values(self.primes().map(|p| p as i64))
+    }
+}
@@ -0,0 +1,142 @@
+// `bench`: a scaling sweep for reports, sieving every bound in --limits with every thread count
+// in --threads, --repeat times each, in this process through the library. Each run is one CSV
+// row: wall time, numbers sieved per second, the primes found and the peak resident set size
+// during the run, where the platform can reset and report it (Linux).
+//
+// --warmup runs each setting that many times unrecorded first. --reuse-buffer keeps the marks
+// of one run for the next, so repetitions measure the sieve rather than the page faults of a
+// fresh allocation.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use eratosthenes::{default_threads, sieve_each_in, MarkAllocator, SieveError};
+
+use crate::bound::parse_bound;
+use crate::exit::{self, Kind};
+use crate::stats::{peak_rss_bytes, reset_peak_rss};
+use crate::{fail, fail_sieve, interrupt, report_write_error, usage_error};
+
+const HEADER: &str = "limit,threads,repetition,seconds,numbers_per_second,primes,peak_rss_bytes";
+
+// Hands out the marks it was given back, cleared, rather than allocating anew.
+#[derive(Default)]
+struct Recycled(Mutex<Option<Vec<bool>>>);
+
+impl MarkAllocator for Recycled {
+    type Marks = Vec<bool>;
+
+    fn allocate_marks(&self, len: usize) -> Option<Vec<bool>> {
+        let mut marks = self.0.lock().unwrap().take().unwrap_or_default();
+        marks.clear();
+        marks.try_reserve_exact(len).ok()?;
+        marks.resize(len, false);
+        Some(marks)
+    }
+}
+
+fn list<T>(arg: Option<String>, flag: &str, parse: impl Fn(&str) -> Result<T, String>) -> Vec<T> {
+    let arg = arg.unwrap_or_else(|| usage_error(&format!("{} needs a comma-separated list", flag)));
+    arg.split(',').map(|item| parse(item).unwrap_or_else(|e| usage_error(&format!("{}: {}", flag, e)))).collect()
+}
+
+fn count(arg: Option<String>, flag: &str) -> u32 {
+    let arg = arg.unwrap_or_else(|| usage_error(&format!("{} needs a number", flag)));
+    arg.trim().parse().unwrap_or_else(|_| usage_error(&format!("{} needs a whole number, not {}", flag, arg)))
+}
+
+pub fn bench(mut args: impl Iterator<Item = String>) {
+    let mut limits = None;
+    let mut threads = vec![0];
+    let mut repeat = 3;
+    let mut warmup = 0;
+    let mut reuse = false;
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--limits" => limits = Some(list(args.next(), "--limits", parse_bound)),
+            "--threads" => {
+                threads = list(args.next(), "--threads", |item| {
+                    item.trim().parse().map_err(|_| format!("{} is not a thread count", item))
+                });
+            }
+            "--repeat" => repeat = count(args.next(), "--repeat").max(1),
+            "--warmup" => warmup = count(args.next(), "--warmup"),
+            "--reuse-buffer" => reuse = true,
+            "--output" => output = Some(args.next().unwrap_or_else(|| usage_error("--output needs a FILE"))),
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option: {}", arg)),
+            _ => usage_error(&format!("unexpected argument: {}", arg)),
+        }
+    }
+    let Some(limits) = limits else {
+        usage_error("bench needs --limits N,N...");
+    };
+
+    let mut out: Box<dyn Write> = match &output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(io::BufWriter::new(file)),
+            Err(e) => fail(format_args!("{}: {}", path, e)),
+        },
+        None => Box::new(io::stdout().lock()),
+    };
+    if let Err(e) = writeln!(out, "{}", HEADER) {
+        report_write_error(e);
+        return;
+    }
+    let alloc = Recycled::default();
+    for &limit in &limits {
+        for &threads in &threads {
+            let threads = if threads == 0 { default_threads() } else { threads };
+            for _ in 0..warmup {
+                run(limit, threads, &alloc, reuse).unwrap_or_else(|e| fail_sieve(e));
+                stop_if_interrupted(&mut out);
+            }
+            for repetition in 1..=repeat {
+                let reset = reset_peak_rss();
+                let started = Instant::now();
+                let primes = run(limit, threads, &alloc, reuse).unwrap_or_else(|e| fail_sieve(e));
+                let seconds = started.elapsed().as_secs_f64();
+                stop_if_interrupted(&mut out);
+                let peak = peak_rss_bytes().filter(|_| reset);
+                let row = format!(
+                    "{},{},{},{:.6},{:.0},{},{}",
+                    limit,
+                    threads,
+                    repetition,
+                    seconds,
+                    limit as f64 / seconds.max(f64::MIN_POSITIVE),
+                    primes,
+                    peak.map_or_else(String::new, |bytes| bytes.to_string())
+                );
+                if let Err(e) = writeln!(out, "{}", row).and_then(|()| out.flush()) {
+                    report_write_error(e);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// One sieve up to limit, counting the primes. With reuse, the marks are kept for the next run.
+fn run(limit: u64, threads: usize, alloc: &Recycled, reuse: bool) -> Result<u64, SieveError> {
+    let mut primes = 0;
+    let marks = sieve_each_in(limit, threads, interrupt::flag(), alloc, |_| {
+        primes += 1;
+        ControlFlow::Continue(())
+    })?;
+    if reuse {
+        *alloc.0.lock().unwrap() = marks;
+    }
+    Ok(primes)
+}
+
+// A run cut short by Ctrl+C is left out; the rows before it stand.
+fn stop_if_interrupted(out: &mut dyn Write) {
+    if interrupt::requested() {
+        let _ = out.flush();
+        exit::report(Kind::Interrupted, "interrupted: the rows written so far are complete runs", &[]);
+    }
+}
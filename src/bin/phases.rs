@@ -0,0 +1,70 @@
+// Times the two phases of the wheel sieve apart: marking the composites, then walking the marks
+// to collect the primes, which is single-threaded whatever --threads says. The ratio shows which
+// one a bound is bound by.
+//
+//     cargo run --release --bin phases -- [--threads T] [--runs R] [N...]
+//
+// Each phase is run R times (default 5) per bound and the median is reported.
+
+use std::process;
+use std::time::{Duration, Instant};
+
+use eratosthenes::{prime_count_upper_bound, sieve_bitmap_only};
+
+const DEFAULT_BOUNDS: [u64; 4] = [1_000_000, 10_000_000, 100_000_000, 1_000_000_000];
+
+fn usage() -> ! {
+    eprintln!("usage: phases [--threads T] [--runs R] [N...]");
+    process::exit(2);
+}
+
+fn number(arg: Option<String>) -> u64 {
+    arg.and_then(|arg| arg.trim().parse().ok()).unwrap_or_else(|| usage())
+}
+
+fn median(mut times: Vec<Duration>) -> Duration {
+    times.sort();
+    times[times.len() / 2]
+}
+
+fn main() {
+    let mut threads = 0;
+    let mut runs = 5;
+    let mut bounds = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--threads" => threads = number(args.next()) as usize,
+            "--runs" => runs = number(args.next()).max(1) as usize,
+            _ => bounds.push(number(Some(arg))),
+        }
+    }
+    if bounds.is_empty() {
+        bounds.extend(DEFAULT_BOUNDS);
+    }
+
+    println!("{:>14} {:>12} {:>12} {:>14}", "n", "mark ms", "collect ms", "collect/mark");
+    for n in bounds {
+        let mut mark = Vec::with_capacity(runs);
+        let mut collect = Vec::with_capacity(runs);
+        for _ in 0..runs {
+            let started = Instant::now();
+            let sieve = sieve_bitmap_only(n, threads).unwrap_or_else(|e| panic!("{}", e));
+            mark.push(started.elapsed());
+
+            let started = Instant::now();
+            let mut primes = Vec::with_capacity(prime_count_upper_bound(n));
+            primes.extend(sieve.primes());
+            collect.push(started.elapsed());
+            std::hint::black_box(primes);
+        }
+        let (mark, collect) = (median(mark), median(collect));
+        println!(
+            "{:>14} {:>12.1} {:>12.1} {:>14.2}",
+            n,
+            mark.as_secs_f64() * 1e3,
+            collect.as_secs_f64() * 1e3,
+            collect.as_secs_f64() / mark.as_secs_f64()
+        );
+    }
+}
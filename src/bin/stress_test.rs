@@ -0,0 +1,69 @@
+// Runs every sieve in the crate on the same bound and checks that they all agree.
+//
+//     cargo run --release --bin stress_test -- 1000000
+
+use std::ops::ControlFlow;
+use std::process;
+
+use eratosthenes::{reference_primes_up_to, segmented_sieve_each, sieve_of_atkin, sieve_of_eratosthenes, sundaram_sieve};
+
+// Primes shown on either side of the first difference.
+const CONTEXT: usize = 3;
+
+type Sieve = fn(u64) -> Vec<u64>;
+
+fn segmented(n: u64) -> Vec<u64> {
+    let mut primes = Vec::new();
+    segmented_sieve_each(n, |p| {
+        primes.push(p);
+        ControlFlow::Continue(())
+    })
+    .unwrap_or_else(|e| panic!("{}", e));
+    primes
+}
+
+fn show(primes: &[u64], at: usize) -> String {
+    let lo = at.saturating_sub(CONTEXT);
+    let hi = (at + CONTEXT + 1).min(primes.len());
+    let shown: Vec<String> = (lo..hi)
+        .map(|i| if i == at { format!("[{}]", primes[i]) } else { primes[i].to_string() })
+        .collect();
+    let end = if at >= primes.len() { " [end]" } else { "" };
+    format!("{}{}", shown.join(" "), end)
+}
+
+fn main() {
+    let n: u64 = match std::env::args().nth(1).map(|arg| arg.trim().parse()) {
+        Some(Ok(n)) => n,
+        _ => {
+            eprintln!("usage: stress_test N");
+            process::exit(2);
+        }
+    };
+
+    let expected = sieve_of_eratosthenes(n).unwrap_or_else(|e| panic!("{}", e));
+    let others: [(&str, Sieve); 4] = [
+        ("sundaram_sieve", sundaram_sieve),
+        ("sieve_of_atkin", sieve_of_atkin),
+        ("segmented", segmented),
+        ("reference", reference_primes_up_to),
+    ];
+
+    let mut failed = false;
+    for (name, sieve) in others {
+        let found = sieve(n);
+        let len = expected.len().max(found.len());
+        match (0..len).find(|&i| expected.get(i) != found.get(i)) {
+            None => println!("{:<16} ok ({} primes)", name, found.len()),
+            Some(i) => {
+                failed = true;
+                println!("{:<16} differs at prime #{}", name, i + 1);
+                println!("  sieve_of_eratosthenes: {}", show(&expected, i));
+                println!("  {:<21}: {}", name, show(&found, i));
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
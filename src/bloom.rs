@@ -0,0 +1,145 @@
+use crate::sieve::Sieve;
+
+/// A Bloom filter over u64 values: `contains` never misses an inserted value and wrongly accepts
+/// others at roughly the rate it was sized for. Useful as a cheap pre-filter in front of an exact
+/// primality check.
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    hashes: u32,
+    inserted: u64,
+}
+
+impl BloomFilter {
+    /// An empty filter sized for `expected_items` values at `false_positive_rate`, using the
+    /// standard `m = -n ln p / (ln 2)^2` bits and `k = (m / n) ln 2` hash functions.
+    ///
+    /// # Panics
+    ///
+    /// If `false_positive_rate` is not strictly between 0 and 1.
+    #[must_use]
+    pub fn new(expected_items: u64, false_positive_rate: f64) -> Self {
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "false positive rate must be between 0 and 1, got {}",
+            false_positive_rate
+        );
+        let items = expected_items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-items * false_positive_rate.ln() / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let hashes = ((num_bits as f64 / items) * ln2).round().max(1.0) as u32;
+        let words = usize::try_from(num_bits.div_ceil(64)).expect("bloom filter too large for this platform");
+        BloomFilter { bits: vec![0; words], num_bits, hashes, inserted: 0 }
+    }
+
+    pub fn insert(&mut self, v: u64) {
+        for bit in self.positions(v) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+        self.inserted += 1;
+    }
+
+    /// False if `v` was never inserted; true if it was, or for a false positive.
+    #[must_use]
+    pub fn contains(&self, v: u64) -> bool {
+        self.positions(v).all(|bit| self.bits[(bit / 64) as usize] >> (bit % 64) & 1 == 1)
+    }
+
+    /// The expected false-positive rate for the values inserted so far, `(1 - e^(-kn/m))^k`.
+    #[must_use]
+    pub fn false_positive_rate_estimate(&self) -> f64 {
+        let k = self.hashes as f64;
+        (1.0 - (-k * self.inserted as f64 / self.num_bits as f64).exp()).powf(k)
+    }
+
+    #[must_use]
+    pub fn num_bits(&self) -> u64 {
+        self.num_bits
+    }
+
+    #[must_use]
+    pub fn hashes(&self) -> u32 {
+        self.hashes
+    }
+
+    // Kirsch-Mitzenmacher double hashing: bit i is h1 + i * h2 from two independent mixes of v.
+    fn positions(&self, v: u64) -> impl Iterator<Item = u64> {
+        let h1 = splitmix64(v);
+        let h2 = splitmix64(h1) | 1;
+        let num_bits = self.num_bits;
+        (0..self.hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+}
+
+fn splitmix64(v: u64) -> u64 {
+    let mut z = v.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl Sieve {
+    /// A [`BloomFilter`] holding this sieve's primes, sized for `false_positive_rate`.
+    ///
+    /// # Panics
+    ///
+    /// If `false_positive_rate` is not strictly between 0 and 1.
+    #[must_use]
+    pub fn to_bloom_filter(&self, false_positive_rate: f64) -> BloomFilter {
+        let mut filter = BloomFilter::new(self.count_primes_up_to(self.n()), false_positive_rate);
+        self.primes().for_each(|p| filter.insert(p));
+        filter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_prime_is_missed() {
+        let sieve = Sieve::new(1_000_000).unwrap();
+        for rate in [0.5, 0.01, 1e-6] {
+            let filter = sieve.to_bloom_filter(rate);
+            assert!(sieve.primes().all(|p| filter.contains(p)), "rate {}", rate);
+        }
+    }
+
+    #[test]
+    fn false_positives_come_at_about_the_requested_rate() {
+        // The 921_503 non-primes up to 10^6 measure the rate closely; the margin is for k being
+        // rounded to a whole number of hashes.
+        let sieve = Sieve::new(1_000_000).unwrap();
+        for rate in [0.1, 0.01, 0.001] {
+            let filter = sieve.to_bloom_filter(rate);
+            let composites = (0..=1_000_000).filter(|&v| !sieve.is_prime(v));
+            let (tested, accepted) = composites.fold((0, 0), |(t, a), v| (t + 1, a + filter.contains(v) as u64));
+            let measured = accepted as f64 / tested as f64;
+            assert!(measured > rate * 0.75 && measured < rate * 1.25, "rate {}: measured {}", rate, measured);
+            let estimate = filter.false_positive_rate_estimate();
+            assert!(estimate > rate * 0.9 && estimate < rate * 1.1, "rate {}: estimated {}", rate, estimate);
+        }
+    }
+
+    #[test]
+    fn size_follows_the_standard_formula() {
+        // m = ceil(-n ln p / (ln 2)^2) and k = round((m / n) ln 2), worked out by hand.
+        let cases = [(1_000, 0.01, 9_586, 7), (78_498, 0.01, 752_408, 7), (1_000_000, 0.001, 14_377_588, 10)];
+        for (items, rate, bits, hashes) in cases {
+            let filter = BloomFilter::new(items, rate);
+            assert_eq!((filter.num_bits(), filter.hashes()), (bits, hashes), "{} items at {}", items, rate);
+            assert_eq!(filter.bits.len() as u64, bits.div_ceil(64));
+        }
+        // Never fewer than 64 bits or 1 hash, and an empty filter is sized as for one item.
+        assert_eq!((BloomFilter::new(1, 0.5).num_bits(), BloomFilter::new(1, 0.5).hashes()), (64, 44));
+        assert_eq!(BloomFilter::new(0, 0.01).num_bits(), BloomFilter::new(1, 0.01).num_bits());
+        assert_eq!(BloomFilter::new(1_000, 0.99).hashes(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "false positive rate must be between 0 and 1")]
+    fn a_rate_of_1_panics() {
+        let _ = BloomFilter::new(1_000, 1.0);
+    }
+}
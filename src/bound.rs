@@ -0,0 +1,204 @@
+// Parsing of the upper bound, shared by command-line arguments, batch input and the prompt.
+
+/// Appended to rejections of malformed input so the user can see what would have worked.
+pub const ACCEPTED_FORMS: &str = "accepted forms: 1000000, 1_000_000, 1e6, 2.5e9, 10k, 1.5M, 4G, 1T \
+                                  (powers of 1000) and 64Ki, 16Mi, 4Gi, 1Ti (powers of 1024)";
+
+const SUFFIXES: [(&str, u128); 9] = [
+    ("Ki", 1 << 10),
+    ("Mi", 1 << 20),
+    ("Gi", 1 << 30),
+    ("Ti", 1 << 40),
+    ("k", 1_000),
+    ("K", 1_000),
+    ("M", 1_000_000),
+    ("G", 1_000_000_000),
+    ("T", 1_000_000_000_000),
+];
+
+/// Parses a non-negative whole bound: plain digits with optional `_` separators between them, an
+/// optional fraction and decimal exponent (`1e9`, `2.5e3`) and an optional SI or binary suffix,
+/// so long as the value comes out whole and fits in a u64.
+pub fn parse_bound(arg: &str) -> Result<u64, String> {
+    let arg = arg.trim();
+    let reject = |why: String| Err(format!("{}; {}", why, ACCEPTED_FORMS));
+    if arg.is_empty() {
+        return reject("no bound given".to_string());
+    }
+    if let Some(rest) = arg.strip_prefix('-') {
+        if rest.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+            return Err(format!("{}: the bound must be non-negative (there are no primes below 2)", arg));
+        }
+    }
+
+    let (number, multiplier) = SUFFIXES
+        .iter()
+        .find_map(|&(suffix, m)| arg.strip_suffix(suffix).map(|number| (number.trim_end(), m)))
+        .unwrap_or((arg, 1));
+    let (mantissa, exponent) = match number.find(['e', 'E']) {
+        Some(i) => (&number[..i], Some(&number[i + 1..])),
+        None => (number, None),
+    };
+    let (whole, fraction) = match mantissa.split_once('.') {
+        Some((whole, fraction)) => (whole, fraction),
+        None => (mantissa, ""),
+    };
+
+    let not_a_number = || reject(format!("{:?} is not a whole number", arg));
+    let (Some(whole), Some(fraction)) = (strip_separators(whole), strip_separators(fraction)) else {
+        return not_a_number();
+    };
+    if whole.is_empty() && fraction.is_empty() {
+        return not_a_number();
+    }
+    let exponent: i32 = match exponent {
+        None => 0,
+        Some(e) => match e.strip_prefix('+').unwrap_or(e).parse() {
+            Ok(e) if (-40..=40).contains(&e) => e,
+            Ok(_) => return Err(format!("{}: the exponent is out of range", arg)),
+            Err(_) => return not_a_number(),
+        },
+    };
+
+    // value = digits * 10^(exponent - fraction digits) * multiplier, computed exactly.
+    let too_large = || Err(format!("{}: the bound is too large; the largest supported is {}", arg, u64::MAX));
+    let digits = format!("{}{}", whole, fraction);
+    let significant = digits.trim_start_matches('0');
+    let digits_value = if significant.is_empty() { Some(0) } else { significant.parse::<u128>().ok() };
+    let Some(mut value) = digits_value.and_then(|v| v.checked_mul(multiplier)) else {
+        return too_large();
+    };
+    let scale = exponent - fraction.len() as i32;
+    if scale >= 0 {
+        match 10u128.checked_pow(scale as u32).and_then(|p| value.checked_mul(p)) {
+            Some(v) => value = v,
+            None if value == 0 => {}
+            None => return too_large(),
+        }
+    } else {
+        let divisor = 10u128.checked_pow(scale.unsigned_abs()).unwrap_or(u128::MAX);
+        if value % divisor != 0 {
+            return Err(format!("{}: the bound must be a whole number; did you mean {}?", arg, value / divisor));
+        }
+        value /= divisor;
+    }
+    u64::try_from(value).or_else(|_| too_large())
+}
+
+/// Parses a range `LO..HI`, both ends included, each end as [`parse_bound`] takes it.
+pub fn parse_range(arg: &str) -> Result<(u64, u64), String> {
+    let (lo, hi) = arg.split_once("..").ok_or_else(|| format!("expected LO..HI, got {:?}", arg))?;
+    let (lo, hi) = (parse_bound(lo)?, parse_bound(hi)?);
+    if lo > hi {
+        return Err(format!("{} is above {}", lo, hi));
+    }
+    Ok((lo, hi))
+}
+
+// Removes `_` separators, which must each sit between two digits; None if anything else is off.
+fn strip_separators(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut digits = String::with_capacity(s.len());
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            b'0'..=b'9' => digits.push(b as char),
+            b'_' if i > 0 && bytes[i - 1].is_ascii_digit() && bytes.get(i + 1).is_some_and(u8::is_ascii_digit) => {}
+            _ => return None,
+        }
+    }
+    Some(digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_bounds_must_be_non_negative() {
+        for arg in ["-5", "-0", "-1e9", " -3 ", "-.5", "-10k"] {
+            let e = parse_bound(arg).unwrap_err();
+            assert_eq!(e, format!("{}: the bound must be non-negative (there are no primes below 2)", arg.trim()));
+        }
+        // A lone '-' is not a number at all.
+        assert!(parse_bound("-").unwrap_err().starts_with("\"-\" is not a whole number; accepted forms: "));
+    }
+
+    #[test]
+    fn fractional_bounds_must_be_whole() {
+        let cases = [("3.14", 3), ("0.5", 0), ("2.5e0", 2), ("1.0001k", 1_000), ("1.3Ki", 1_331), ("7e-1", 0)];
+        for (arg, suggestion) in cases {
+            let e = parse_bound(arg).unwrap_err();
+            assert_eq!(e, format!("{}: the bound must be a whole number; did you mean {}?", arg, suggestion));
+        }
+        // Fractions that come out whole are fine.
+        assert_eq!(parse_bound("2.5e3"), Ok(2_500));
+        assert_eq!(parse_bound("3.0"), Ok(3));
+        assert_eq!(parse_bound("1.5k"), Ok(1_500));
+        assert_eq!(parse_bound("1.5Ki"), Ok(1_536));
+    }
+
+    #[test]
+    fn accepted_forms() {
+        let cases = [
+            ("0", 0),
+            ("1000000", 1_000_000),
+            ("1_000_000", 1_000_000),
+            (" 42 \r\n", 42),
+            ("007", 7),
+            ("1e9", 1_000_000_000),
+            ("1E3", 1_000),
+            ("1e+3", 1_000),
+            ("2.5e9", 2_500_000_000),
+            ("1_000e-3", 1),
+            (".5e1", 5),
+            ("10k", 10_000),
+            ("10K", 10_000),
+            ("1.5M", 1_500_000),
+            ("4G", 4_000_000_000),
+            ("1T", 1_000_000_000_000),
+            ("2 M", 2_000_000),
+            ("64Ki", 65_536),
+            ("16Mi", 16 << 20),
+            ("4Gi", 4 << 30),
+            ("1Ti", 1 << 40),
+            ("0e40", 0),
+            ("18446744073709551615", u64::MAX),
+            ("18_446_744_073_709_551_615", u64::MAX),
+        ];
+        for (arg, value) in cases {
+            assert_eq!(parse_bound(arg), Ok(value), "{:?}", arg);
+        }
+    }
+
+    #[test]
+    fn malformed_bounds_list_the_accepted_forms() {
+        let cases = ["abc", "1__000", "_1", "1_", "1_.5", "1e", "e9", "1.2.3", ".", "10Q", "0x10", "1e9.5", "k"];
+        for arg in cases.into_iter().chain(["1 000", "1,000"]) {
+            assert_eq!(parse_bound(arg), Err(format!("{:?} is not a whole number; {}", arg, ACCEPTED_FORMS)));
+        }
+        for arg in ["", "  ", "\r\n"] {
+            assert_eq!(parse_bound(arg), Err(format!("no bound given; {}", ACCEPTED_FORMS)), "{:?}", arg);
+        }
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        let huge = "99999999999999999999999999999999999999999";
+        for arg in ["18446744073709551616", "1e20", "20000000T", "16777216Ti", huge] {
+            let e = parse_bound(arg).unwrap_err();
+            assert_eq!(e, format!("{}: the bound is too large; the largest supported is {}", arg, u64::MAX));
+        }
+        assert_eq!(parse_bound("1e41").unwrap_err(), "1e41: the exponent is out of range");
+        assert_eq!(parse_bound("1e-41").unwrap_err(), "1e-41: the exponent is out of range");
+    }
+
+    #[test]
+    fn ranges_take_bounds_at_both_ends() {
+        assert_eq!(parse_range("1e6..2M"), Ok((1_000_000, 2_000_000)));
+        assert_eq!(parse_range("7..7"), Ok((7, 7)));
+        assert_eq!(parse_range("5-10").unwrap_err(), "expected LO..HI, got \"5-10\"");
+        assert_eq!(parse_range("10..5").unwrap_err(), "10 is above 5");
+        assert!(parse_range("..5").unwrap_err().starts_with("no bound given"));
+        assert!(parse_range("1..-5").unwrap_err().contains("must be non-negative"));
+    }
+}
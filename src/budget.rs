@@ -0,0 +1,144 @@
+use std::mem::size_of;
+use std::ops::ControlFlow;
+
+use crate::{
+    checked_backward5, default_threads, prime_count_upper_bound, sieve_where, DispatchFn, SieveError, Segments,
+    DEFAULT_SEGMENT_SIZE,
+};
+
+/// Smallest segment, in wheel positions, a plan will fall back to. Below this the per-segment
+/// cost of walking every base prime outweighs the sieving itself.
+pub const MIN_SEGMENT_SIZE: usize = 1 << 12;
+
+/// How a sieve up to some `n` fits a memory budget, as chosen by [`plan_within`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Plan {
+    /// One byte of marks per wheel position for the whole range.
+    Monolithic { bytes: usize },
+    /// The base primes up to `sqrt(n)` plus one segment of `segment_size` wheel positions,
+    /// reused for each of the `segments`.
+    Segmented { segment_size: usize, segments: usize, bytes: usize },
+}
+
+fn positions(n: u64) -> usize {
+    checked_backward5(n).and_then(|c| c.checked_add(1)).unwrap_or(usize::MAX)
+}
+
+impl Plan {
+    /// The whole-range sieve up to `n`, with `output_bytes` accumulated by the caller.
+    #[must_use]
+    pub fn monolithic(n: u64, output_bytes: usize) -> Plan {
+        Plan::Monolithic { bytes: positions(n).saturating_add(output_bytes) }
+    }
+
+    /// The segmented sieve up to `n` in segments of `segment_size` wheel positions, with
+    /// `output_bytes` accumulated by the caller.
+    #[must_use]
+    pub fn segmented(n: u64, segment_size: usize, output_bytes: usize) -> Plan {
+        let segments = (positions(n) - 1).div_ceil(segment_size);
+        let bytes = base_bytes(n).saturating_add(output_bytes).saturating_add(segment_size);
+        Plan::Segmented { segment_size, segments, bytes }
+    }
+
+    /// Peak bytes the plan expects to use, including the caller's output.
+    #[must_use]
+    pub fn bytes(&self) -> usize {
+        match *self {
+            Plan::Monolithic { bytes } | Plan::Segmented { bytes, .. } => bytes,
+        }
+    }
+
+    /// The segment size for a segmented plan.
+    #[must_use]
+    pub fn segment_size(&self) -> Option<usize> {
+        match *self {
+            Plan::Monolithic { .. } => None,
+            Plan::Segmented { segment_size, .. } => Some(segment_size),
+        }
+    }
+}
+
+/// Picks a strategy for sieving up to `n` within `max_bytes`, of which `output_bytes` are
+/// reserved for whatever the caller accumulates (0 when streaming).
+///
+/// The whole-range sieve is used if its marks fit, since it is the faster one. Otherwise the
+/// segment takes what is left after the output and the base primes, capped at
+/// [`DEFAULT_SEGMENT_SIZE`], one byte per wheel position (about 3.75 integers). If that leaves
+/// less than [`MIN_SEGMENT_SIZE`], the budget is refused.
+pub fn plan_within(n: u64, max_bytes: usize, output_bytes: usize) -> Result<Plan, SieveError> {
+    let monolithic = Plan::monolithic(n, output_bytes);
+    if monolithic.bytes() <= max_bytes {
+        return Ok(monolithic);
+    }
+
+    let fixed = base_bytes(n).saturating_add(output_bytes);
+    let segment_size = max_bytes.saturating_sub(fixed).min(DEFAULT_SEGMENT_SIZE);
+    if segment_size < MIN_SEGMENT_SIZE {
+        return Err(SieveError::MemoryLimitExceeded {
+            n,
+            required: fixed.saturating_add(MIN_SEGMENT_SIZE),
+            limit: max_bytes,
+        });
+    }
+    Ok(Plan::segmented(n, segment_size, output_bytes))
+}
+
+// Resident memory per marking worker: the stack pages it touches and its thread bookkeeping.
+// Each thread reserves a larger stack, but only address space.
+const WORKER_BYTES: usize = 64 << 10;
+
+/// Roughly the bytes the whole-range sieve up to `n` holds at its peak on
+/// [`default_threads`] workers: one byte of marks per wheel position (not packed bits), plus the
+/// marking workers and, at worst, a queued job for every base prime. The primes themselves, if
+/// collected, come on top, at 8 bytes each.
+///
+/// Past 1 GB of marks (n above about 3.75 billion) the monolithic sieve is usually the wrong
+/// tool: the segmented one needs a few MB whatever `n` is; see [`plan_within`].
+///
+/// ```
+/// use eratosthenes::memory_usage_estimate;
+///
+/// // 266,666,668 wheel positions, plus the workers and queue.
+/// assert!((266_666_668..300_000_000).contains(&memory_usage_estimate(1_000_000_000)));
+/// ```
+#[must_use]
+pub fn memory_usage_estimate(n: u64) -> usize {
+    let threads = default_threads();
+    let queue = if threads > 1 {
+        // A boxed closure holding the marks' Arc, p and n, and its pointer in the queue.
+        let job = 3 * size_of::<u64>() + size_of::<Box<DispatchFn>>();
+        prime_count_upper_bound(n.isqrt()).saturating_mul(job)
+    } else {
+        0
+    };
+    positions(n).saturating_add(threads.saturating_mul(WORKER_BYTES)).saturating_add(queue)
+}
+
+// The base primes up to sqrt(n), and the transient marks used to find them.
+fn base_bytes(n: u64) -> usize {
+    let root = n.isqrt();
+    positions(root).saturating_add(prime_count_upper_bound(root).saturating_mul(size_of::<u64>()))
+}
+
+/// Returns the primes up to `n` using at most about `max_bytes`, switching to the segmented
+/// sieve when the whole-range marks would not fit; see [`plan_within`] for how the budget is
+/// split. The result vector is counted against the budget at its worst-case size, so a budget
+/// that cannot hold the primes themselves is an error rather than an overrun.
+pub fn primes_up_to_within(n: u64, max_bytes: usize) -> Result<Vec<u64>, SieveError> {
+    let capacity = prime_count_upper_bound(n);
+    match plan_within(n, max_bytes, capacity.saturating_mul(size_of::<u64>()))? {
+        Plan::Monolithic { .. } => sieve_where(n, |_| true, capacity),
+        Plan::Segmented { segment_size, .. } => {
+            let mut primes = Vec::new();
+            primes.try_reserve_exact(capacity).map_err(|_| SieveError::AllocationFailed { n })?;
+            let mut segments = Segments::new(n, segment_size)?;
+            for k in 0..segments.len() {
+                let _ = segments.sieve_segment(k, &mut |p| {
+                    primes.push(p);
+                    ControlFlow::Continue(())
+                });
+            }
+            Ok(primes)
+        }
+    }
+}
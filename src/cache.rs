@@ -0,0 +1,96 @@
+//! A process-wide [`Sieve`] shared by every [`cached_primes`] iterator, for interactive sessions
+//! that walk the primes again and again, each time a little further. The cached sieve only ever
+//! grows: an iterator that runs off its end extends it, sieving just the range past the old bound,
+//! and every later iterator reads the longer one. [`clear_sieve_cache`] hands the memory back.
+
+use std::sync::{Arc, PoisonError, RwLock};
+
+use crate::sieve::Sieve;
+
+// The first bound the cache is built to; each extension at least doubles it.
+const CACHE_START: u64 = 1 << 20;
+
+static CACHE: RwLock<Option<Arc<Sieve>>> = RwLock::new(None);
+
+// The cached sieve, grown first if it does not reach `n`.
+fn cached_sieve(n: u64) -> Arc<Sieve> {
+    // A sieve is only ever swapped in whole, so a lock poisoned by a failed extension still
+    // holds a good one.
+    if let Some(sieve) = CACHE.read().unwrap_or_else(PoisonError::into_inner).as_ref().filter(|s| s.n() >= n) {
+        return Arc::clone(sieve);
+    }
+    let mut cache = CACHE.write().unwrap_or_else(PoisonError::into_inner);
+    let grown = match cache.as_ref() {
+        // Another iterator grew it while this one waited.
+        Some(sieve) if sieve.n() >= n => return Arc::clone(sieve),
+        Some(sieve) => sieve.extended_to(n.max(sieve.n().saturating_mul(2))),
+        None => Sieve::new(n.max(CACHE_START)),
+    };
+    let grown = Arc::new(grown.unwrap_or_else(|e| panic!("{}", e)));
+    *cache = Some(Arc::clone(&grown));
+    grown
+}
+
+/// Every prime in ascending order, like
+/// [`prime_enumerator_infinite`](crate::prime_enumerator_infinite), but read from the shared
+/// cached sieve rather than sieved afresh: an iterator only sieves once it passes the furthest
+/// any iterator has gone before, and then it extends the cache for all of them. Iterators on
+/// other threads share it too; one that has to extend it holds up any other that needs it
+/// extended, or is being created, until it is done.
+///
+/// The cache holds the marks up to the furthest prime asked for, about 0.27 bytes per integer,
+/// until [`clear_sieve_cache`]; for a single long walk, the bounded memory of
+/// `prime_enumerator_infinite` is the better choice.
+///
+/// ```
+/// use eratosthenes::{cached_primes, clear_sieve_cache};
+///
+/// // The second walk reads what the first one sieved.
+/// assert_eq!(cached_primes().take_while(|&p| p <= 1_000_000).count(), 78_498);
+/// assert_eq!(cached_primes().nth(78_497), Some(999_983));
+/// clear_sieve_cache();
+/// ```
+///
+/// # Panics
+///
+/// If the cache cannot be grown far enough.
+#[must_use]
+pub fn cached_primes() -> CachedPrimes {
+    CachedPrimes { sieve: cached_sieve(0), next: 0 }
+}
+
+/// Empties the cache behind [`cached_primes`], freeing its sieve once no iterator still reads
+/// it; the next iterator starts a new one.
+pub fn clear_sieve_cache() {
+    *CACHE.write().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// Iterator over the cached primes; see [`cached_primes`].
+pub struct CachedPrimes {
+    // The cached sieve as of the last extension this iterator saw, kept so that a clear or
+    // another extension never pulls it out from under the walk.
+    sieve: Arc<Sieve>,
+    // Where the walk resumes: one past the last prime returned.
+    next: u64,
+}
+
+impl Iterator for CachedPrimes {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        loop {
+            if self.next > self.sieve.n() {
+                self.sieve = cached_sieve(self.next);
+            }
+            // Skipping is constant time, so resuming costs no more than carrying a cursor.
+            let mut primes = self.sieve.primes();
+            primes.skip_to(self.next);
+            if let Some(p) = primes.next() {
+                self.next = p + 1;
+                return Some(p);
+            }
+            // Nothing more below the bound: the next pass extends the cache past it.
+            self.next = self.sieve.n() + 1;
+        }
+    }
+}
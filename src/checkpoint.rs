@@ -0,0 +1,207 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::shard::ShardInfo;
+
+const CHECKPOINT_HEADER: &str = "eratosthenes-checkpoint 1";
+
+/// Where a segmented run got to: enough to continue it from the next segment after the process
+/// dies, and to tell whether a later invocation is the same run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub n: u64,
+    pub segment_size: usize,
+    pub format: String,
+    pub base_primes: usize,
+    pub base_primes_hash: u64,
+    pub next_segment: usize,
+    pub count: u64,
+    pub last_prime: u64,
+    pub output_offset: u64,
+    /// Primes per shard with `--shard-size`, or 0. With shards, `output_offset` is into the last.
+    pub shard_size: u64,
+    pub shards: Vec<ShardInfo>,
+}
+
+impl Checkpoint {
+    fn body(&self) -> String {
+        let mut body = format!(
+            "{}\nn={}\nsegment_size={}\nformat={}\nbase_primes={}\nbase_primes_hash={:016x}\n\
+             next_segment={}\ncount={}\nlast_prime={}\noutput_offset={}\n",
+            CHECKPOINT_HEADER,
+            self.n,
+            self.segment_size,
+            self.format,
+            self.base_primes,
+            self.base_primes_hash,
+            self.next_segment,
+            self.count,
+            self.last_prime,
+            self.output_offset,
+        );
+        // Unsharded checkpoints stop at output_offset, as they always have.
+        if self.shard_size > 0 {
+            body.push_str(&format!("shard_size={}\n", self.shard_size));
+            for shard in &self.shards {
+                body.push_str(&format!("shard={} {} {}\n", shard.first, shard.last, shard.count));
+            }
+        }
+        body
+    }
+
+    /// Writes the checkpoint to a temporary file, syncs it and renames it over `path`, so a crash
+    /// leaves either the previous checkpoint or this one, never a torn mix.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let body = self.body();
+        let mut tmp = PathBuf::from(path);
+        tmp.as_mut_os_string().push(".tmp");
+        let mut file = File::create(&tmp)?;
+        writeln!(file, "{}checksum={:016x}", body, fnv1a(body.as_bytes()))?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Checkpoint> {
+        let text = fs::read_to_string(path)?;
+        let corrupt = |what: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("checkpoint {} is corrupt: {}", path.display(), what))
+        };
+
+        let body_len = text.trim_end_matches('\n').rfind('\n').map_or(0, |i| i + 1);
+        let (body, checksum) = text.split_at(body_len);
+        let checksum = checksum
+            .trim_end()
+            .strip_prefix("checksum=")
+            .and_then(|c| u64::from_str_radix(c, 16).ok())
+            .ok_or_else(|| corrupt("missing checksum"))?;
+        if checksum != fnv1a(body.as_bytes()) {
+            return Err(corrupt("checksum mismatch"));
+        }
+
+        let mut lines = body.lines();
+        if lines.next() != Some(CHECKPOINT_HEADER) {
+            return Err(corrupt("unrecognized header"));
+        }
+        let mut field = |key: &str| {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(key))
+                .and_then(|line| line.strip_prefix('='))
+                .map(str::to_string)
+                .ok_or_else(|| corrupt(&format!("missing {}", key)))
+        };
+        let number = |value: String, key: &str| value.parse::<u64>().map_err(|_| corrupt(&format!("bad {}", key)));
+
+        let n = number(field("n")?, "n")?;
+        let segment_size = number(field("segment_size")?, "segment_size")? as usize;
+        let format = field("format")?;
+        let base_primes = number(field("base_primes")?, "base_primes")? as usize;
+        let base_primes_hash = u64::from_str_radix(&field("base_primes_hash")?, 16)
+            .map_err(|_| corrupt("bad base_primes_hash"))?;
+        let next_segment = number(field("next_segment")?, "next_segment")? as usize;
+        let count = number(field("count")?, "count")?;
+        let last_prime = number(field("last_prime")?, "last_prime")?;
+        let output_offset = number(field("output_offset")?, "output_offset")?;
+        let (shard_size, shards) = match field("shard_size") {
+            Ok(value) => {
+                let shards = lines.map(|line| parse_shard(line).ok_or_else(|| corrupt("bad shard")));
+                (number(value, "shard_size")?, shards.collect::<Result<_, _>>()?)
+            }
+            // Only sharded runs save shards.
+            Err(_) => (0, Vec::new()),
+        };
+
+        Ok(Checkpoint {
+            n,
+            segment_size,
+            format,
+            base_primes,
+            base_primes_hash,
+            next_segment,
+            count,
+            last_prime,
+            output_offset,
+            shard_size,
+            shards,
+        })
+    }
+}
+
+// A `shard=FIRST LAST COUNT` line.
+fn parse_shard(line: &str) -> Option<ShardInfo> {
+    let mut values = line.strip_prefix("shard=")?.split(' ').map(|v| v.parse::<u64>().ok());
+    let shard = ShardInfo { first: values.next()??, last: values.next()??, count: values.next()?? };
+    values.next().is_none().then_some(shard)
+}
+
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+pub fn hash_primes(primes: &[u64]) -> u64 {
+    let bytes: Vec<u8> = primes.iter().flat_map(|p| p.to_le_bytes()).collect();
+    fnv1a(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint() -> Checkpoint {
+        Checkpoint {
+            n: 100_000_000,
+            segment_size: 50_428,
+            format: "text".to_string(),
+            base_primes: 1_229,
+            base_primes_hash: hash_primes(&[2, 3, 5, 7]),
+            next_segment: 191,
+            count: 2_211_123,
+            last_prime: 36_119_053,
+            output_offset: 19_146_012,
+            shard_size: 0,
+            shards: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn saved_checkpoints_load_unchanged() {
+        let dir = std::env::temp_dir().join(format!("eratosthenes-checkpoint-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("run.checkpoint");
+        let sharded = Checkpoint {
+            shard_size: 1_000,
+            shards: vec![
+                ShardInfo { first: 2, last: 7_919, count: 1_000 },
+                ShardInfo { first: 7_927, last: 8_161, count: 30 },
+            ],
+            ..checkpoint()
+        };
+        for saved in [checkpoint(), sharded] {
+            saved.save(&path).unwrap();
+            assert_eq!(Checkpoint::load(&path).unwrap(), saved);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn damaged_checkpoints_are_corrupt() {
+        let dir = std::env::temp_dir().join(format!("eratosthenes-checkpoint-damaged-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("run.checkpoint");
+        checkpoint().save(&path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let damaged = [
+            (text.replace("count=2211123", "count=2211124"), "checksum mismatch"),
+            (text[..text.rfind("checksum=").unwrap()].to_string(), "missing checksum"),
+            (String::new(), "missing checksum"),
+        ];
+        for (text, what) in damaged {
+            fs::write(&path, text).unwrap();
+            let e = Checkpoint::load(&path).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(e.to_string(), format!("checkpoint {} is corrupt: {}", path.display(), what));
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+// Textbook sieves, kept simple and unoptimized as independent cross-checks for the wheel sieve.
+
+use crate::WheelWalk;
+
+/// The primes up to `n` by the sieve of Sundaram: every odd composite `2k + 1` has
+/// `k = i + j + 2ij` for some `1 <= i <= j`, and the unmarked `k` give the odd primes.
+#[must_use]
+pub fn sundaram_sieve(n: u64) -> Vec<u64> {
+    if n < 2 {
+        return Vec::new();
+    }
+    let k = ((n - 1) / 2) as usize;
+    let mut marked = vec![false; k + 1];
+    let mut i = 1;
+    while i + i + 2 * i * i <= k {
+        let mut m = i + i + 2 * i * i;
+        while m <= k {
+            marked[m] = true;
+            m += 2 * i + 1;
+        }
+        i += 1;
+    }
+
+    let mut primes = vec![2];
+    primes.extend((1..=k).filter(|&m| !marked[m]).map(|m| 2 * m as u64 + 1));
+    primes
+}
+
+/// The primes up to `n` by the sieve of Atkin: candidates are toggled once per solution of the
+/// three quadratic forms, then multiples of prime squares are cleared.
+#[must_use]
+pub fn sieve_of_atkin(n: u64) -> Vec<u64> {
+    let limit = n as usize;
+    let mut is_prime = vec![false; limit + 1];
+
+    let mut x = 1;
+    while x * x <= limit {
+        let mut y = 1;
+        while y * y <= limit {
+            let m = 4 * x * x + y * y;
+            if m <= limit && (m % 12 == 1 || m % 12 == 5) {
+                is_prime[m] ^= true;
+            }
+            let m = 3 * x * x + y * y;
+            if m <= limit && m % 12 == 7 {
+                is_prime[m] ^= true;
+            }
+            if x > y {
+                let m = 3 * x * x - y * y;
+                if m <= limit && m % 12 == 11 {
+                    is_prime[m] ^= true;
+                }
+            }
+            y += 1;
+        }
+        x += 1;
+    }
+
+    let mut r = 5;
+    while r * r <= limit {
+        if is_prime[r] {
+            let mut m = r * r;
+            while m <= limit {
+                is_prime[m] = false;
+                m += r * r;
+            }
+        }
+        r += 1;
+    }
+
+    let mut primes: Vec<u64> = [2, 3].into_iter().filter(|&p| p <= n).collect();
+    primes.extend((5..=limit).filter(|&m| is_prime[m]).map(|m| m as u64));
+    primes
+}
+
+/// Every prime in ascending order by trial division of the wheel-30 candidates, with no bitset:
+/// each candidate is tested against the primes up to its square root, which come from a second,
+/// lazily started generator of the same kind. Memory is `O(π(sqrt(p)))` for the current `p`, at
+/// the cost of being far slower than sieving.
+pub fn prime_gen_trial_division() -> impl Iterator<Item = u64> {
+    TrialDivision::new()
+}
+
+struct TrialDivision {
+    small: usize,
+    walk: WheelWalk,
+    // The primes from 7 up whose squares candidates have reached.
+    divisors: Vec<u64>,
+    // The next prime to join `divisors`, once a candidate reaches its square.
+    next_divisor: u64,
+    source: Option<Box<TrialDivision>>,
+}
+
+impl TrialDivision {
+    fn new() -> Self {
+        TrialDivision { small: 0, walk: WheelWalk::new(), divisors: Vec::new(), next_divisor: 7, source: None }
+    }
+}
+
+impl Iterator for TrialDivision {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        const SMALL_PRIMES: [u64; 3] = [2, 3, 5];
+        if let Some(&p) = SMALL_PRIMES.get(self.small) {
+            self.small += 1;
+            return Some(p);
+        }
+        loop {
+            let candidate = self.walk.advance();
+            while self.next_divisor.checked_mul(self.next_divisor).is_some_and(|square| square <= candidate) {
+                self.divisors.push(self.next_divisor);
+                // The wheel already excludes 2, 3 and 5, and 7 was the first divisor: start at 11.
+                let source = self.source.get_or_insert_with(|| {
+                    let mut source = TrialDivision::new();
+                    source.nth(3);
+                    Box::new(source)
+                });
+                self.next_divisor = source.next()?;
+            }
+            if self.divisors.iter().all(|&d| !candidate.is_multiple_of(d)) {
+                return Some(candidate);
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use parquet::basic::Encoding;
+use parquet::column::reader::get_typed_column_reader;
+use parquet::data_type::Int64Type;
+use parquet::errors::{ParquetError, Result};
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use parquet::schema::types::Type;
+
+use crate::sieve::Sieve;
+use crate::{backward5, checked_backward5};
+
+// The one column, as Parquet's schema language has it.
+const SCHEMA: &str = "message primes { REQUIRED INT64 prime; }";
+// Where save_parquet keeps the bound, which the primes alone do not give.
+const BOUND_KEY: &str = "eratosthenes.n";
+// Primes per row group: 8 MiB of values before encoding.
+const ROW_GROUP: usize = 1 << 20;
+
+impl Sieve {
+    /// Writes the primes up to `n`, in order, as a Parquet file with a single required `INT64`
+    /// column named `prime`, for Pandas, Spark and the like to load without parsing text. The
+    /// bound goes in the file's key-value metadata as `eratosthenes.n`, so that
+    /// [`load_parquet`](Self::load_parquet) gets back the same sieve.
+    ///
+    /// Needs the `parquet` feature. Only a sieve from 0 can be saved; one over a range (see
+    /// [`range`](Self::range)) is refused.
+    pub fn save_parquet(&self, path: &Path) -> Result<()> {
+        if self.lo() > 0 {
+            return Err(invalid(format!("a sieve over a range, from {}, cannot be saved", self.lo())));
+        }
+        let schema = Arc::new(parse_message_type(SCHEMA)?);
+        let bound = KeyValue::new(BOUND_KEY.to_string(), self.n().to_string());
+        // Consecutive primes differ by little, so delta encoding shrinks the column to a few bits
+        // per prime without a compression codec.
+        let properties = WriterProperties::builder()
+            .set_key_value_metadata(Some(vec![bound]))
+            .set_dictionary_enabled(false)
+            .set_encoding(Encoding::DELTA_BINARY_PACKED)
+            .build();
+        let mut writer = SerializedFileWriter::new(File::create(path)?, schema, Arc::new(properties))?;
+
+        let mut primes = self.primes().peekable();
+        let mut values = Vec::with_capacity(ROW_GROUP);
+        while primes.peek().is_some() {
+            values.clear();
+            // Every u64 prime a sieve can hold fits in an i64.
+            values.extend(primes.by_ref().take(ROW_GROUP).map(|p| p as i64));
+            let mut row_group = writer.next_row_group()?;
+            let mut column = row_group.next_column()?.expect("the schema has one column");
+            column.typed::<Int64Type>().write_batch(&values, None, None)?;
+            column.close()?;
+            row_group.close()?;
+        }
+        writer.close()?;
+        Ok(())
+    }
+
+    /// Reads a file with a single required `INT64` column named `prime`, as
+    /// [`save_parquet`](Self::save_parquet) writes, back into a sieve. The bound comes from the
+    /// `eratosthenes.n` metadata if present and is otherwise the last prime, so a file written
+    /// by other tools loads too. The values must be increasing, within the bound, and include 2,
+    /// 3 and 5 as far as it reaches; beyond that they are taken on trust, as with
+    /// [`from_json`](Self::from_json).
+    ///
+    /// Needs the `parquet` feature.
+    pub fn load_parquet(path: &Path) -> Result<Sieve> {
+        let reader = SerializedFileReader::new(File::open(path)?)?;
+        let metadata = reader.metadata();
+        let file = metadata.file_metadata();
+        let expected = parse_message_type(SCHEMA)?;
+        if !same_columns(file.schema(), &expected) {
+            return Err(invalid(format!("expected the single column of `{}`", SCHEMA)));
+        }
+
+        let mut primes = Vec::new();
+        for i in 0..metadata.num_row_groups() {
+            let row_group = reader.get_row_group(i)?;
+            let rows = usize::try_from(row_group.metadata().num_rows()).map_err(|_| invalid("negative row count"))?;
+            let mut column = get_typed_column_reader::<Int64Type>(row_group.get_column_reader(0)?);
+            let mut values = Vec::with_capacity(rows);
+            while values.len() < rows {
+                let (read, _, _) = column.read_records(rows - values.len(), None, None, &mut values)?;
+                if read == 0 {
+                    return Err(invalid(format!("row group {} ends after {} of its {} rows", i, values.len(), rows)));
+                }
+            }
+            primes.extend(values);
+        }
+
+        let bound = file.key_value_metadata().and_then(|pairs| pairs.iter().find(|pair| pair.key == BOUND_KEY));
+        let n = match bound.and_then(|pair| pair.value.as_deref()) {
+            Some(n) => n.parse::<u64>().map_err(|_| invalid(format!("{} is not a bound: {:?}", BOUND_KEY, n)))?,
+            None => primes.last().map_or(1, |&p| p as u64),
+        };
+        marks_from_primes(n, &primes)
+    }
+}
+
+fn same_columns(found: &Type, expected: &Type) -> bool {
+    match (found.get_fields(), expected.get_fields()) {
+        ([found], [expected]) => found.as_ref() == expected.as_ref(),
+        _ => false,
+    }
+}
+
+fn marks_from_primes(n: u64, primes: &[i64]) -> Result<Sieve> {
+    let cardinality = checked_backward5(n)
+        .and_then(|c| c.checked_add(1))
+        .ok_or_else(|| invalid(format!("the bound {} is too large to sieve", n)))?;
+    let mut not_prime = Vec::new();
+    not_prime.try_reserve_exact(cardinality).map_err(|_| invalid(format!("no memory for a sieve up to {}", n)))?;
+    not_prime.resize(cardinality, true);
+
+    let small = [2, 3, 5].into_iter().filter(|&p| p <= n);
+    if !primes.iter().map(|&p| p as u64).take(small.clone().count()).eq(small) {
+        return Err(invalid("the primes must start 2, 3, 5 as far as the bound reaches"));
+    }
+    let mut previous = 0;
+    for &p in primes {
+        let p = u64::try_from(p).map_err(|_| invalid(format!("{} is negative", p)))?;
+        if p <= previous {
+            return Err(invalid(format!("{} is followed by {}, out of order", previous, p)));
+        }
+        if p > n {
+            return Err(invalid(format!("{} is above the bound {}", p, n)));
+        }
+        if p >= 7 {
+            if p % 2 == 0 || p % 3 == 0 || p % 5 == 0 {
+                return Err(invalid(format!("{} is not prime", p)));
+            }
+            not_prime[backward5(p)] = false;
+        }
+        previous = p;
+    }
+    Ok(Sieve::from_marks(n, not_prime))
+}
+
+fn invalid(reason: impl Into<String>) -> ParquetError {
+    ParquetError::General(reason.into())
+}
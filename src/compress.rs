@@ -0,0 +1,103 @@
+// `--compress gzip|zstd [--compress-level N]`: the output stream wrapped in an encoder. Encoding
+// needs the `compression` feature; reading compressed files back goes through the library's
+// `decompress`.
+
+use std::io::{self, Write};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Gzip,
+    Zstd,
+}
+
+impl Codec {
+    pub fn parse(name: &str) -> Option<Codec> {
+        match name {
+            "gzip" => Some(Codec::Gzip),
+            "zstd" => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+
+    // zlib's and zstd's own defaults: most of the ratio for little of the time.
+    pub fn default_level(self) -> i32 {
+        match self {
+            Codec::Gzip => 6,
+            Codec::Zstd => 3,
+        }
+    }
+
+    pub fn levels(self) -> std::ops::RangeInclusive<i32> {
+        match self {
+            Codec::Gzip => 0..=9,
+            Codec::Zstd => 1..=22,
+        }
+    }
+}
+
+/// Where printed primes go: a plain writer or an encoder around one. Call `finish` rather than
+/// just dropping it, since a compressed stream is only complete once its trailer is written.
+pub struct Output {
+    inner: Inner,
+}
+
+enum Inner {
+    Plain(Box<dyn Write>),
+    #[cfg(feature = "compression")]
+    Gzip(flate2::write::GzEncoder<Box<dyn Write>>),
+    #[cfg(feature = "compression")]
+    Zstd(zstd::stream::write::Encoder<'static, Box<dyn Write>>),
+}
+
+impl Output {
+    pub fn plain(writer: Box<dyn Write>) -> Output {
+        Output { inner: Inner::Plain(writer) }
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn compressed(writer: Box<dyn Write>, codec: Codec, level: i32) -> io::Result<Output> {
+        let inner = match codec {
+            Codec::Gzip => Inner::Gzip(flate2::write::GzEncoder::new(writer, flate2::Compression::new(level as u32))),
+            Codec::Zstd => Inner::Zstd(zstd::stream::write::Encoder::new(writer, level)?),
+        };
+        Ok(Output { inner })
+    }
+
+    #[cfg(not(feature = "compression"))]
+    pub fn compressed(_writer: Box<dyn Write>, _codec: Codec, _level: i32) -> io::Result<Output> {
+        unreachable!("--compress is rejected up front without the compression feature")
+    }
+
+    /// Ends the stream, writing any trailer, and flushes it.
+    pub fn finish(self) -> io::Result<()> {
+        match self.inner {
+            Inner::Plain(mut writer) => writer.flush(),
+            #[cfg(feature = "compression")]
+            Inner::Gzip(encoder) => encoder.finish()?.flush(),
+            #[cfg(feature = "compression")]
+            Inner::Zstd(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            Inner::Plain(writer) => writer.write(buf),
+            #[cfg(feature = "compression")]
+            Inner::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "compression")]
+            Inner::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            Inner::Plain(writer) => writer.flush(),
+            #[cfg(feature = "compression")]
+            Inner::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "compression")]
+            Inner::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
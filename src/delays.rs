@@ -0,0 +1,32 @@
+//! Scheduling noise for the dispatch queue, compiled in only with the `test-delays` feature. It
+//! is for testing only: never enable it in a build anyone relies on.
+//!
+//! Each `test_delay!()` in the queue and its workers does nothing, yields, or sleeps for up to
+//! half a millisecond, chosen pseudo-randomly per call. That stretches the windows between
+//! handing off a task, running it and reporting it finished, where a lost wake-up or a wrong
+//! `is_finished` would otherwise show up only once in many runs. Run the usual checks with it on
+//! and more threads than cores:
+//!
+//! ```text
+//! cargo run --release --features test-delays -- --threads 8 --verify 1000000
+//! cargo run --release --features test-delays --bin stress_test -- 1000000
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+static CALLS: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn pause() {
+    // splitmix64 over a shared call counter: cheap, lock-free, and different on every call.
+    let mut x = CALLS.fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 31;
+    match x % 8 {
+        0..=3 => {}
+        4..=6 => thread::yield_now(),
+        _ => thread::sleep(Duration::from_micros((x >> 3) % 500)),
+    }
+}
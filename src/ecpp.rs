@@ -0,0 +1,174 @@
+//! Elliptic curve arithmetic for ECPP, the Goldwasser-Kilian-Atkin primality proof: enough to
+//! check a certificate, not yet to produce one.
+//!
+//! A certificate for `n` is a chain of steps, each giving a curve `y^2 = x^3 + ax + b` modulo
+//! `n`, a point `P` on it and an `m` with a prime factor `q > (n^(1/4) + 1)^2` such that
+//! `mP` is the point at infinity while `(m/q)P` is not. If `q` is prime then so is `n`, and the
+//! next step proves `q`, until one is small enough to check directly. Finding the curves, which
+//! needs point counting or complex multiplication, is the part still missing.
+//!
+//! Arithmetic is modulo `p` as though it were prime. When it is not, an inverse can fail to
+//! exist; the operations then return the divisor they ran into, which proves `p` composite.
+
+use crate::arith::{gcd, inv_mod, is_prime, mul_mod};
+
+/// The curve `y^2 = x^3 + ax + b` over the integers modulo `p`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EllipticCurve {
+    pub a: u64,
+    pub b: u64,
+    pub p: u64,
+}
+
+/// An affine point. The point at infinity, the group's identity, is `None` wherever a
+/// `Option<Point>` is taken or returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Point {
+    pub x: u64,
+    pub y: u64,
+}
+
+impl EllipticCurve {
+    fn add(&self, a: u64, b: u64) -> u64 {
+        ((a as u128 + b as u128) % self.p as u128) as u64
+    }
+
+    fn sub(&self, a: u64, b: u64) -> u64 {
+        self.add(a % self.p, self.p - b % self.p)
+    }
+
+    fn mul(&self, a: u64, b: u64) -> u64 {
+        mul_mod(a, b, self.p)
+    }
+
+    // a / b, or the divisor of p shared with b when b has no inverse.
+    fn div(&self, a: u64, b: u64) -> Result<u64, u64> {
+        match inv_mod(b % self.p, self.p) {
+            Some(inv) => Ok(self.mul(a, inv)),
+            None => Err(gcd(b % self.p, self.p)),
+        }
+    }
+
+    /// Whether `point` satisfies the curve equation.
+    #[must_use]
+    pub fn contains(&self, point: Point) -> bool {
+        let (x, y) = (point.x % self.p, point.y % self.p);
+        let rhs = self.add(self.mul(self.mul(x, x), x), self.add(self.mul(self.a, x), self.b));
+        self.mul(y, y) == rhs
+    }
+
+    /// Whether the discriminant `4a^3 + 27b^2` is a unit modulo `p`, so the curve has no
+    /// singular point.
+    #[must_use]
+    pub fn is_nonsingular(&self) -> bool {
+        let a3 = self.mul(self.mul(self.a, self.a), self.a);
+        let b2 = self.mul(self.b, self.b);
+        let discriminant = self.add(self.mul(4, a3), self.mul(27, b2));
+        gcd(discriminant, self.p) == 1
+    }
+}
+
+/// `p + q` on `curve`. `Err(d)` if a slope's denominator shares the divisor `d` with the modulus,
+/// which only happens when the modulus is composite.
+pub fn point_add(curve: &EllipticCurve, p: Option<Point>, q: Option<Point>) -> Result<Option<Point>, u64> {
+    let (p, q) = match (p, q) {
+        (None, other) | (other, None) => return Ok(other),
+        (Some(p), Some(q)) => (p, q),
+    };
+    if p.x % curve.p == q.x % curve.p {
+        if curve.add(p.y, q.y) == 0 {
+            return Ok(None);
+        }
+        return point_double(curve, Some(p));
+    }
+    let slope = curve.div(curve.sub(q.y, p.y), curve.sub(q.x, p.x))?;
+    Ok(Some(through(curve, slope, p, q.x)))
+}
+
+/// `2p` on `curve`, failing as [`point_add`] does.
+pub fn point_double(curve: &EllipticCurve, p: Option<Point>) -> Result<Option<Point>, u64> {
+    let Some(p) = p else {
+        return Ok(None);
+    };
+    if p.y % curve.p == 0 {
+        return Ok(None);
+    }
+    let numerator = curve.add(curve.mul(3, curve.mul(p.x, p.x)), curve.a);
+    let slope = curve.div(numerator, curve.mul(2, p.y))?;
+    Ok(Some(through(curve, slope, p, p.x)))
+}
+
+// The third point on the line of `slope` through p and the point with x-coordinate qx,
+// reflected.
+fn through(curve: &EllipticCurve, slope: u64, p: Point, qx: u64) -> Point {
+    let x = curve.sub(curve.sub(curve.mul(slope, slope), p.x), qx);
+    let y = curve.sub(curve.mul(slope, curve.sub(p.x, x)), p.y);
+    Point { x, y }
+}
+
+/// `k * p` on `curve` by double-and-add, failing as [`point_add`] does.
+///
+/// ```
+/// use eratosthenes::ecpp::{point_add, scalar_mul, EllipticCurve, Point};
+///
+/// // y^2 = x^3 + 2x + 3 over GF(97) has 100 points.
+/// let curve = EllipticCurve { a: 2, b: 3, p: 97 };
+/// let p = Some(Point { x: 3, y: 6 });
+/// assert_eq!(scalar_mul(&curve, 100, p), Ok(None));
+/// assert_eq!(scalar_mul(&curve, 3, p), point_add(&curve, p, point_add(&curve, p, p).unwrap()));
+/// ```
+pub fn scalar_mul(curve: &EllipticCurve, mut k: u64, p: Option<Point>) -> Result<Option<Point>, u64> {
+    let mut result = None;
+    let mut addend = p;
+    while k > 0 {
+        if k & 1 == 1 {
+            result = point_add(curve, result, addend)?;
+        }
+        k >>= 1;
+        if k > 0 {
+            addend = point_double(curve, addend)?;
+        }
+    }
+    Ok(result)
+}
+
+/// One link of an ECPP certificate: the claim that `curve.p` is prime if `q` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EcppStep {
+    pub curve: EllipticCurve,
+    pub point: Point,
+    /// A multiple of the point's order, usually the order of the curve's group.
+    pub m: u64,
+    /// A prime factor of `m`, proved by the next step.
+    pub q: u64,
+}
+
+/// Checks an ECPP certificate that `n` is prime: each step must be about the previous step's
+/// `q` (the first about `n`), and the last `q` must pass [`is_prime`]. Only the checking is
+/// here; no certificates are generated yet.
+#[must_use]
+pub fn ecpp_verify_certificate(n: u64, steps: &[EcppStep]) -> bool {
+    let mut claim = n;
+    for step in steps {
+        if step.curve.p != claim || !step_holds(step) {
+            return false;
+        }
+        claim = step.q;
+    }
+    !steps.is_empty() && is_prime(claim)
+}
+
+fn step_holds(step: &EcppStep) -> bool {
+    let EcppStep { curve, point, m, q } = *step;
+    if curve.p < 2 || q < 2 || m % q != 0 || !curve.is_nonsingular() || !curve.contains(point) {
+        return false;
+    }
+    // q > (n^(1/4) + 1)^2, checked against the integer fourth root rounded up.
+    let root = curve.p.isqrt().isqrt();
+    let root = if root.pow(4) == curve.p { root } else { root + 1 };
+    if (q as u128) <= ((root + 1) as u128).pow(2) {
+        return false;
+    }
+    let point = Some(point);
+    matches!(scalar_mul(&curve, m / q, point), Ok(Some(_))) && scalar_mul(&curve, m, point) == Ok(None)
+}
@@ -0,0 +1,52 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SieveError {
+    /// The marking buffer or result vector for bound `n` could not be allocated.
+    AllocationFailed { n: u64 },
+    /// Sieving up to `n` needs at least `required` bytes, more than the `limit` allowed.
+    MemoryLimitExceeded { n: u64, required: usize, limit: usize },
+    /// A modulus of zero was given where residues are taken.
+    ZeroModulus,
+    /// Serialized sieve data could not be read back.
+    InvalidJson { reason: String },
+    /// A list given as base primes is not the primes from 2 in ascending order.
+    InvalidBasePrimes { reason: String },
+    /// Sieving up to `n` needs the base primes up to `sqrt(n)`, but they only go up to `limit`.
+    BasePrimesTooShort { n: u64, limit: u64 },
+    /// Two sieves being merged disagree about whether `v`, which both cover, is prime.
+    SievesDisagree { v: u64 },
+    /// A sieve up to `n` cannot be carried on from `lo`: the values in between are in neither.
+    SievesDisjoint { n: u64, lo: u64 },
+}
+
+impl fmt::Display for SieveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SieveError::AllocationFailed { n } => {
+                write!(f, "could not allocate sieve storage for n = {}", n)
+            }
+            SieveError::MemoryLimitExceeded { n, required, limit } => write!(
+                f,
+                "sieving up to n = {} needs at least {} bytes, more than the limit of {}",
+                n, required, limit
+            ),
+            SieveError::ZeroModulus => write!(f, "modulus must be positive"),
+            SieveError::InvalidJson { reason } => write!(f, "invalid sieve JSON: {}", reason),
+            SieveError::InvalidBasePrimes { reason } => write!(f, "invalid base primes: {}", reason),
+            SieveError::BasePrimesTooShort { n, limit } => write!(
+                f,
+                "sieving up to n = {} needs base primes up to {}, but they only cover up to {}",
+                n,
+                n.isqrt(),
+                limit
+            ),
+            SieveError::SievesDisagree { v } => write!(f, "the sieves being merged disagree about {}", v),
+            SieveError::SievesDisjoint { n, lo } => {
+                write!(f, "a sieve up to {} cannot be merged with one from {}: the values between are missing", n, lo)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SieveError {}
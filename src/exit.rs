@@ -0,0 +1,106 @@
+// Exit statuses, and the one place an error reaches the user: as prose on stderr or, with
+// `--error-format json`, as one line of JSON on stderr:
+//
+//     {"code":3,"kind":"resource","message":"...","context":{"n":...}}
+//
+// `context` holds whatever else is known about the failure, and may be empty.
+
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::serve::json_string;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    // Anything else that went wrong at run time: I/O, bad input files, failed checks.
+    Failure,
+    // Bad arguments.
+    Usage,
+    // Not enough memory, or more than --memory-limit allows.
+    Resource,
+    // Stopped by Ctrl+C.
+    Interrupted,
+    // A bug: a panic, reported instead of the default backtrace.
+    Internal,
+}
+
+impl Kind {
+    pub const fn code(self) -> i32 {
+        match self {
+            Kind::Failure => 1,
+            Kind::Usage => 2,
+            Kind::Resource => 3,
+            Kind::Interrupted => 4,
+            Kind::Internal => 5,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Failure => "failure",
+            Kind::Usage => "usage",
+            Kind::Resource => "resource",
+            Kind::Interrupted => "interrupted",
+            Kind::Internal => "internal",
+        }
+    }
+}
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Takes `--error-format text|json` out of the arguments wherever it appears, so that it covers
+/// every subcommand and the argument errors themselves. Returns the rest of the arguments, or
+/// the bad format.
+pub fn take_error_format(mut args: impl Iterator<Item = String>) -> Result<Vec<String>, String> {
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg != "--error-format" {
+            rest.push(arg);
+            continue;
+        }
+        match args.next().as_deref() {
+            Some("text") => JSON.store(false, Ordering::Relaxed),
+            Some("json") => JSON.store(true, Ordering::Relaxed),
+            Some(other) => return Err(format!("unknown error format: {}", other)),
+            None => return Err("--error-format needs a value".to_string()),
+        }
+    }
+    Ok(rest)
+}
+
+/// Reports panics, from any thread, as internal errors instead of letting them unwind to the
+/// default message and exit status.
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let message = match (info.payload().downcast_ref::<&str>(), info.payload().downcast_ref::<String>()) {
+            (Some(message), _) => message.to_string(),
+            (_, Some(message)) => message.clone(),
+            _ => "panic".to_string(),
+        };
+        let location = info.location().map(|at| format!("{}:{}", at.file(), at.line()));
+        let context: Vec<(&str, String)> = location.iter().map(|at| ("location", json_string(at))).collect();
+        report(Kind::Internal, &format!("internal error: {}", message), &context);
+    }));
+}
+
+/// Prints `message` and exits with `kind`'s status. `context` is extra fields for the JSON form,
+/// as names and JSON values; prose shows the message alone, followed by the usage for
+/// [`Kind::Usage`].
+pub fn report(kind: Kind, message: &str, context: &[(&str, String)]) -> ! {
+    if JSON.load(Ordering::Relaxed) {
+        let context: Vec<String> = context.iter().map(|(name, value)| format!("{}:{}", json_string(name), value)).collect();
+        eprintln!(
+            "{{\"code\":{},\"kind\":\"{}\",\"message\":{},\"context\":{{{}}}}}",
+            kind.code(),
+            kind.name(),
+            json_string(message),
+            context.join(",")
+        );
+    } else {
+        eprintln!("{}", message);
+        if kind == Kind::Usage {
+            eprintln!("{}", crate::USAGE);
+        }
+    }
+    std::process::exit(kind.code());
+}
@@ -0,0 +1,598 @@
+use std::collections::BTreeMap;
+
+use crate::arith::{gcd, is_prime, mul_mod, pow_mod};
+use crate::field::tonelli_shanks;
+
+// Primes below this are found by trial division before Pollard rho takes over.
+const TRIAL_DIVISION_LIMIT: u64 = 1 << 10;
+const QS_INTERVAL: u64 = 1 << 16;
+const QS_MAX_INTERVALS: u64 = 4096;
+const QS_EXTRA_RELATIONS: usize = 16;
+
+/// Splits a composite `n` into a non-trivial factor pair `(a, b)`, `a <= b`, with a simplified
+/// quadratic sieve: the primes up to a smoothness bound `B` that have `n` as a quadratic residue
+/// form the factor base, `Q(x) = (x + ceil(sqrt(n)))^2 - n` is sieved for smooth values, and
+/// Gaussian elimination over GF(2) on their exponent parities yields a congruence of squares.
+///
+/// Returns `None` for primes, for `n < 4`, or if no congruence splits `n` within the search limit.
+/// A perfect square `r * r`, which no congruence of squares splits, gives `(r, r)`.
+#[must_use]
+pub fn quadratic_sieve_factor(n: u64) -> Option<(u64, u64)> {
+    if n < 4 || is_prime(n) {
+        return None;
+    }
+    if n.is_multiple_of(2) {
+        return Some((2, n / 2));
+    }
+    let r = n.isqrt();
+    if r * r == n {
+        return Some((r, r));
+    }
+
+    let bound = qs_smoothness_bound(n);
+    let mut factor_base = Vec::new();
+    for p in crate::sieve_of_eratosthenes(bound).ok()? {
+        if n.is_multiple_of(p) {
+            return Some((p, n / p));
+        }
+        if p == 2 || pow_mod(n % p, (p - 1) >> 1, p) == 1 {
+            factor_base.push(p);
+        }
+    }
+
+    let s = r + 1;
+    let relations = qs_collect_relations(n, s, &factor_base)?;
+    for dependency in gf2_dependencies(&relations, factor_base.len()) {
+        let mut x = 1;
+        let mut exponents = vec![0u32; factor_base.len()];
+        for &i in &dependency {
+            x = mul_mod(x, relations[i].root % n, n);
+            for (total, e) in exponents.iter_mut().zip(&relations[i].exponents) {
+                *total += e;
+            }
+        }
+        let mut y = 1;
+        for (&p, &e) in factor_base.iter().zip(&exponents) {
+            y = mul_mod(y, pow_mod(p, (e >> 1) as u64, n), n);
+        }
+        let f = gcd((x + n - y) % n, n);
+        if f > 1 && f < n {
+            return Some((f.min(n / f), f.max(n / f)));
+        }
+    }
+
+    None
+}
+
+/// Pollard's p - 1 method: finds a factor of `n` when some prime `p | n` has a `bound`-smooth
+/// `p - 1`, by raising 2 to every prime power up to `bound` modulo `n` and taking
+/// `gcd(2^M - 1, n)`, where `M` is the lcm of those prime powers.
+///
+/// Returns `None` if `n` is prime, below 4, or has no such factor for this bound.
+#[must_use]
+pub fn pollard_p1(n: u64, bound: u64) -> Option<u64> {
+    if n < 4 || is_prime(n) {
+        return None;
+    }
+    if n.is_multiple_of(2) {
+        return Some(2);
+    }
+
+    let mut a = 2;
+    for p in crate::sieve_of_eratosthenes(bound).ok()? {
+        let mut q = p;
+        while let Some(next) = q.checked_mul(p).filter(|&next| next <= bound) {
+            q = next;
+        }
+        a = pow_mod(a, q, n);
+        // Checking after each prime rather than once at the end keeps a factor whose p - 1 is
+        // covered early from being swallowed when the cofactor's order divides M too.
+        // a is a power of 2 and n is odd, so a >= 1.
+        match gcd(a - 1, n) {
+            1 => {}
+            g if g == n => return None,
+            g => return Some(g),
+        }
+    }
+
+    None
+}
+
+/// The prime factors of `n` in ascending order, with multiplicity: trial division by the small
+/// primes, then Pollard's rho (Brent's variant) on whatever composite cofactor remains.
+///
+/// `0` and `1` have no prime factors and give an empty list.
+#[must_use]
+pub fn factorize(n: u64) -> Vec<u64> {
+    let mut factors = factor_tree(n).primes();
+    factors.sort_unstable();
+    factors
+}
+
+/// Which method split a composite in a [`FactorTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FactorMethod {
+    /// Division by one of the primes below 2^10.
+    TrialDivision,
+    /// Pollard's rho, or the square root of a perfect square met on the way to it.
+    PollardRho,
+}
+
+impl FactorMethod {
+    /// `"trial"` or `"rho"`.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            FactorMethod::TrialDivision => "trial",
+            FactorMethod::PollardRho => "rho",
+        }
+    }
+}
+
+/// How [`factorize`] took `n` apart: each composite is split into two factors by the method that
+/// found them, down to primes at the leaves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FactorTree {
+    /// A prime, or `0` or `1` at the root.
+    Leaf(u64),
+    /// `n = left * right`, with `left` the factor `method` found.
+    Split { n: u64, method: FactorMethod, left: Box<FactorTree>, right: Box<FactorTree> },
+}
+
+impl FactorTree {
+    /// The number this tree factors.
+    #[must_use]
+    pub fn n(&self) -> u64 {
+        match *self {
+            FactorTree::Leaf(n) | FactorTree::Split { n, .. } => n,
+        }
+    }
+
+    /// The prime leaves, left to right; empty for `0` and `1`.
+    #[must_use]
+    pub fn primes(&self) -> Vec<u64> {
+        let mut primes = Vec::new();
+        let mut pending = vec![self];
+        while let Some(tree) = pending.pop() {
+            match tree {
+                FactorTree::Leaf(n) if *n < 2 => {}
+                FactorTree::Leaf(p) => primes.push(*p),
+                FactorTree::Split { left, right, .. } => pending.extend([right.as_ref(), left.as_ref()]),
+            }
+        }
+        primes
+    }
+
+    /// Whether splitting `n` needed Pollard's rho anywhere, rather than trial division alone.
+    #[must_use]
+    pub fn used_rho(&self) -> bool {
+        match self {
+            FactorTree::Leaf(_) => false,
+            FactorTree::Split { method: FactorMethod::PollardRho, .. } => true,
+            FactorTree::Split { left, right, .. } => left.used_rho() || right.used_rho(),
+        }
+    }
+}
+
+/// [`factorize`] with its provenance: trial division peels the small primes off one at a time,
+/// and Pollard's rho splits what is left into two cofactors that are factored in turn.
+///
+/// ```
+/// use eratosthenes::{factor_tree, FactorMethod, FactorTree};
+///
+/// let tree = factor_tree(2 * 1_000_003 * 1_000_033);
+/// let FactorTree::Split { method, left, right, .. } = &tree else { panic!() };
+/// assert_eq!((*method, left.n()), (FactorMethod::TrialDivision, 2));
+/// assert!(matches!(**right, FactorTree::Split { method: FactorMethod::PollardRho, .. }));
+/// assert_eq!(tree.primes().iter().product::<u64>(), tree.n());
+/// ```
+#[must_use]
+pub fn factor_tree(n: u64) -> FactorTree {
+    let small_primes = crate::sieve_of_eratosthenes(TRIAL_DIVISION_LIMIT).unwrap_or_default();
+    split(n, &small_primes)
+}
+
+fn split(n: u64, small_primes: &[u64]) -> FactorTree {
+    let trial = small_primes.iter().take_while(|&&p| p * p <= n).find(|&&p| n.is_multiple_of(p));
+    let (method, d) = match trial {
+        Some(&p) => (FactorMethod::TrialDivision, p),
+        None if n < 2 || is_prime(n) => return FactorTree::Leaf(n),
+        None => (FactorMethod::PollardRho, pollard_rho(n)),
+    };
+    let left = Box::new(split(d, small_primes));
+    let right = Box::new(split(n / d, small_primes));
+    FactorTree::Split { n, method, left, right }
+}
+
+/// [`factorize`] as a map from each prime factor to its exponent; empty for `0` and `1`.
+#[must_use]
+pub fn factorize_map(n: u64) -> BTreeMap<u64, u32> {
+    let mut exponents = BTreeMap::new();
+    for p in factorize(n) {
+        *exponents.entry(p).or_insert(0) += 1;
+    }
+    exponents
+}
+
+/// The number of divisors of `n`, `d(n)`: the product of `e + 1` over its prime powers `p^e`.
+/// `0` for `n = 0`, which every integer divides.
+#[must_use]
+pub fn divisor_count(n: u64) -> u64 {
+    if n == 0 {
+        return 0;
+    }
+    factorize_map(n).values().map(|&e| e as u64 + 1).product()
+}
+
+/// The sum of the divisors of `n`, `σ(n)`: the product of `(p^(e+1) - 1) / (p - 1)` over its
+/// prime powers `p^e`. `0` for `n = 0`.
+///
+/// # Panics
+///
+/// If `σ(n)` overflows u64, which only happens for `n` within a small factor of `u64::MAX`.
+#[must_use]
+pub fn divisor_sum(n: u64) -> u64 {
+    if n == 0 {
+        return 0;
+    }
+    factorize_map(n)
+        .into_iter()
+        .map(|(p, e)| (0..=e).fold(0u128, |sum, _| sum * p as u128 + 1))
+        .try_fold(1u64, |sigma, term| u64::try_from(term).ok().and_then(|term| sigma.checked_mul(term)))
+        .expect("divisor sum overflows u64")
+}
+
+/// `Some((p, k))` if `n == p^k` for a prime `p` and `k >= 1`, that is if `n` has exactly one
+/// prime factor; `None` otherwise, including for `0` and `1`.
+///
+/// ```
+/// use eratosthenes::is_prime_power;
+///
+/// assert_eq!(is_prime_power(8), Some((2, 3)));
+/// assert_eq!(is_prime_power(12), None);
+/// ```
+#[must_use]
+pub fn is_prime_power(n: u64) -> Option<(u64, u32)> {
+    let factors = factorize_map(n);
+    match factors.len() {
+        1 => factors.into_iter().next(),
+        _ => None,
+    }
+}
+
+// A non-trivial factor of the odd composite `n`, retrying with a new polynomial x^2 + c until
+// one is found.
+fn pollard_rho(n: u64) -> u64 {
+    const BATCH: u64 = 128;
+    let r = n.isqrt();
+    if r * r == n {
+        return r;
+    }
+    for c in 1.. {
+        let f = |x: u64| ((x as u128 * x as u128 + c) % n as u128) as u64;
+        let (mut x, mut y, mut ys) = (2, 2, 2);
+        let mut q = 1;
+        let mut g = 1;
+        let mut length = 1;
+        while g == 1 {
+            x = y;
+            for _ in 0..length {
+                y = f(y);
+            }
+            let mut k = 0;
+            while k < length && g == 1 {
+                ys = y;
+                for _ in 0..BATCH.min(length - k) {
+                    y = f(y);
+                    q = mul_mod(q, x.abs_diff(y), n);
+                }
+                g = gcd(q, n);
+                k += BATCH;
+            }
+            length *= 2;
+        }
+        if g == n {
+            // The batched product hit 0 mod n; step back through the last batch one at a time.
+            loop {
+                ys = f(ys);
+                g = gcd(x.abs_diff(ys), n);
+                if g > 1 {
+                    break;
+                }
+            }
+        }
+        if g != n {
+            return g;
+        }
+    }
+    unreachable!("x^2 + c splits every odd composite for some c")
+}
+
+fn qs_smoothness_bound(n: u64) -> u64 {
+    let ln_n = (n as f64).ln();
+    let b = (0.6 * (ln_n * ln_n.ln()).sqrt()).exp();
+    (b as u64).clamp(500, 100_000)
+}
+
+struct Relation {
+    // x + ceil(sqrt(n)), whose square is congruent to the smooth value Q(x).
+    root: u64,
+    exponents: Vec<u32>,
+}
+
+fn qs_collect_relations(n: u64, s: u64, factor_base: &[u64]) -> Option<Vec<Relation>> {
+    let target = factor_base.len() + QS_EXTRA_RELATIONS;
+
+    // Sieve positions of each prime: x with (x + s)^2 ≡ n (mod p).
+    let mut offsets = Vec::with_capacity(factor_base.len());
+    for &p in factor_base {
+        let t = tonelli_shanks(n % p, p)?;
+        let s_mod = s % p;
+        let first = (t + p - s_mod) % p;
+        let second = (p - t + p - s_mod) % p;
+        let logp = (p as f32).ln();
+        if first == second {
+            offsets.push((p, logp, vec![first]));
+        } else {
+            offsets.push((p, logp, vec![first, second]));
+        }
+    }
+
+    // Allow for the prime powers and large primes that a prime-only sieve misses.
+    let slack = 2.0 * (*factor_base.last().unwrap() as f32).ln();
+
+    let mut relations = Vec::new();
+    let mut logs = vec![0f32; QS_INTERVAL as usize];
+    for chunk in 0..QS_MAX_INTERVALS {
+        let x0 = chunk * QS_INTERVAL;
+        logs.iter_mut().for_each(|l| *l = 0.0);
+        for (p, logp, roots) in &offsets {
+            for &root in roots {
+                let mut i = ((root + p - x0 % p) % p) as usize;
+                while i < logs.len() {
+                    logs[i] += logp;
+                    i += *p as usize;
+                }
+            }
+        }
+
+        for (i, &l) in logs.iter().enumerate() {
+            let root = s + x0 + i as u64;
+            let q = root as u128 * root as u128 - n as u128;
+            if l < (q as f32).ln() - slack {
+                continue;
+            }
+            if let Some(exponents) = qs_factor_over_base(q, factor_base) {
+                relations.push(Relation { root, exponents });
+                if relations.len() >= target {
+                    return Some(relations);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+fn qs_factor_over_base(mut q: u128, factor_base: &[u64]) -> Option<Vec<u32>> {
+    let mut exponents = vec![0; factor_base.len()];
+    for (e, &p) in exponents.iter_mut().zip(factor_base) {
+        let p = p as u128;
+        while q.is_multiple_of(p) {
+            q /= p;
+            *e += 1;
+        }
+    }
+    if q == 1 {
+        Some(exponents)
+    } else {
+        None
+    }
+}
+
+// Returns sets of relation indices whose exponent vectors sum to zero modulo 2.
+fn gf2_dependencies(relations: &[Relation], columns: usize) -> Vec<Vec<usize>> {
+    let words = |bits: usize| bits.div_ceil(64);
+    let mut rows: Vec<(Vec<u64>, Vec<u64>)> = relations
+        .iter()
+        .enumerate()
+        .map(|(i, relation)| {
+            let mut parity = vec![0u64; words(columns)];
+            for (c, &e) in relation.exponents.iter().enumerate() {
+                if e & 1 == 1 {
+                    parity[c / 64] |= 1 << (c % 64);
+                }
+            }
+            let mut history = vec![0u64; words(relations.len())];
+            history[i / 64] |= 1 << (i % 64);
+            (parity, history)
+        })
+        .collect();
+
+    let mut is_pivot = vec![false; rows.len()];
+    let has_bit = |bits: &[u64], c: usize| (bits[c / 64] >> (c % 64)) & 1 == 1;
+    for c in 0..columns {
+        let Some(pivot) = (0..rows.len()).find(|&r| !is_pivot[r] && has_bit(&rows[r].0, c)) else {
+            continue;
+        };
+        is_pivot[pivot] = true;
+        let (pivot_parity, pivot_history) = rows[pivot].clone();
+        for (r, row) in rows.iter_mut().enumerate() {
+            if r != pivot && has_bit(&row.0, c) {
+                row.0.iter_mut().zip(&pivot_parity).for_each(|(a, b)| *a ^= b);
+                row.1.iter_mut().zip(&pivot_history).for_each(|(a, b)| *a ^= b);
+            }
+        }
+    }
+
+    rows.iter()
+        .zip(&is_pivot)
+        .filter(|((parity, _), &pivot)| !pivot && parity.iter().all(|&w| w == 0))
+        .map(|((_, history), _)| {
+            (0..relations.len()).filter(|&i| has_bit(history, i)).collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift64: reproducible 64-bit values without a dependency.
+    fn random_u64s(count: usize) -> Vec<u64> {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        (0..count)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state
+            })
+            .collect()
+    }
+
+    // Every split multiplies out to its number, trial splits take off a small prime, and the
+    // leaves are primes.
+    fn check_tree(tree: &FactorTree) {
+        match tree {
+            FactorTree::Leaf(n) => assert!(*n < 2 || is_prime(*n), "leaf {} is not prime", n),
+            FactorTree::Split { n, method, left, right } => {
+                assert!(left.n() > 1 && right.n() > 1, "{} split trivially", n);
+                assert_eq!(left.n() as u128 * right.n() as u128, *n as u128);
+                if *method == FactorMethod::TrialDivision {
+                    assert!(left.n() < TRIAL_DIVISION_LIMIT && is_prime(left.n()), "{} by {}", n, left.n());
+                }
+                check_tree(left);
+                check_tree(right);
+            }
+        }
+    }
+
+    #[test]
+    fn quadratic_sieve_splits_8_digit_semiprimes() {
+        // Both factors are above the smallest smoothness bound, 500, so none is found by
+        // division over the factor base: every split comes from a congruence of squares.
+        let semiprimes = [
+            (9_973, 9_967),
+            (7_919, 7_907),
+            (3_001, 29_989),
+            (1_013, 98_479),
+            (5_039, 10_007),
+            (2_003, 4_999),
+            (6_007, 13_001),
+            (1_201, 83_227),
+        ];
+        for (p, q) in semiprimes {
+            let n = p * q;
+            assert!((10_000_000..100_000_000).contains(&n));
+            let (a, b) = quadratic_sieve_factor(n).unwrap_or_else(|| panic!("{} was not split", n));
+            assert_eq!(a * b, n);
+            assert!(1 < a && a <= b && b < n, "{} split as {} * {}", n, a, b);
+            assert_eq!((a, b), (p.min(q), p.max(q)));
+        }
+        // Small factors come out of the factor base, and even numbers before it.
+        assert_eq!(quadratic_sieve_factor(3 * 33_333_331), Some((3, 33_333_331)));
+        assert_eq!(quadratic_sieve_factor(2 * 49_999_991), Some((2, 49_999_991)));
+    }
+
+    #[test]
+    fn quadratic_sieve_leaves_primes_and_small_n() {
+        for n in [0, 1, 2, 3, 5, 97, 10_000_019, 99_999_989, u64::MAX - 58] {
+            assert_eq!(quadratic_sieve_factor(n), None, "{}", n);
+        }
+        // A perfect square has no congruence of squares to find; it splits as its root.
+        for r in [2, 3, 9_973, 65_521, 4_294_967_291] {
+            assert_eq!(quadratic_sieve_factor(r * r), Some((r, r)), "{}^2", r);
+        }
+    }
+
+    #[test]
+    fn pollard_p1_needs_the_largest_prime_power_of_the_order() {
+        // (p, B): B is the largest prime power in the order of 2 mod p, so p is found at B
+        // and not at B - 1. The cofactors are safe primes, whose orders no small bound covers.
+        let cases = [(10_005_929, 59), (10_007_297, 128), (10_000_651, 121), (10_003_501, 125), (10_003_951, 47)];
+        for (p, bound) in cases {
+            for q in [10_000_223, 10_000_643, 10_000_763] {
+                assert_eq!(pollard_p1(p * q, bound), Some(p), "{} * {} at B = {}", p, q, bound);
+                assert_eq!(pollard_p1(p * q, bound - 1), None, "{} * {} at B = {}", p, q, bound - 1);
+            }
+        }
+    }
+
+    #[test]
+    fn pollard_p1_leaves_primes_and_small_n() {
+        for n in [0, 1, 2, 3, 97, 10_000_223, u64::MAX - 58] {
+            assert_eq!(pollard_p1(n, 1_000), None, "{}", n);
+        }
+        assert_eq!(pollard_p1(4, 1), Some(2));
+        assert_eq!(pollard_p1(2 * 10_000_223, 1), Some(2));
+    }
+
+    #[test]
+    fn divisor_functions() {
+        assert_eq!((divisor_sum(6), divisor_count(12)), (12, 6));
+        assert_eq!((divisor_count(1), divisor_sum(1)), (1, 1));
+        assert_eq!((divisor_count(0), divisor_sum(0)), (0, 0));
+        assert_eq!(factorize_map(360), BTreeMap::from([(2, 3), (3, 2), (5, 1)]));
+        assert!(factorize_map(0).is_empty() && factorize_map(1).is_empty());
+
+        for n in 1..=2_000u64 {
+            let divisors: Vec<u64> = (1..=n).filter(|d| n.is_multiple_of(*d)).collect();
+            assert_eq!(divisor_count(n), divisors.len() as u64, "d({})", n);
+            assert_eq!(divisor_sum(n), divisors.iter().sum::<u64>(), "σ({})", n);
+        }
+
+        let p = u64::MAX - 58;
+        assert_eq!((divisor_count(p), divisor_sum(p)), (2, p + 1));
+        assert_eq!((divisor_count(1 << 63), divisor_sum(1 << 63)), (64, u64::MAX));
+        // The largest highly composite number below 2^64.
+        assert_eq!(divisor_count(18_401_055_938_125_660_800), 184_320);
+    }
+
+    #[test]
+    #[should_panic(expected = "divisor sum overflows u64")]
+    fn divisor_sum_overflow_panics() {
+        let _ = divisor_sum(18_401_055_938_125_660_800);
+    }
+
+    #[test]
+    fn tree_leaves_multiply_to_n() {
+        let special = [
+            0,
+            1,
+            2,
+            1_024,
+            u64::MAX,
+            1 << 63,
+            u64::MAX - 58,
+            4_294_967_291 * 4_294_967_291,
+            1_000_003 * 1_000_033 * 1_009,
+            (1 << 61) - 1,
+        ];
+        for n in special.into_iter().chain(random_u64s(200)) {
+            let tree = factor_tree(n);
+            assert_eq!(tree.n(), n);
+            check_tree(&tree);
+            let primes = tree.primes();
+            if n > 1 {
+                assert_eq!(primes.iter().map(|&p| p as u128).product::<u128>(), n as u128, "{}", n);
+            } else {
+                assert!(primes.is_empty());
+            }
+            let mut sorted = primes.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, factorize(n));
+        }
+    }
+
+    #[test]
+    fn tree_methods_record_who_split_what() {
+        assert_eq!(factor_tree(97), FactorTree::Leaf(97));
+        let tree = factor_tree(4_294_967_291 * 4_294_967_291);
+        assert!(matches!(tree, FactorTree::Split { method: FactorMethod::PollardRho, .. }));
+        assert!(tree.used_rho());
+        let tree = factor_tree(2 * 3 * 1_021);
+        assert!(!tree.used_rho());
+        assert_eq!(tree.primes(), [2, 3, 1_021]);
+    }
+}
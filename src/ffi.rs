@@ -0,0 +1,107 @@
+//! A C interface to the sieve, declared in `src/include/eratosthenes.h`, for building the crate
+//! as a `cdylib` and calling it from C, C++, C# and the like.
+//!
+//! Every function returns a status, [`ERAT_OK`] or one of the negative `ERAT_ERR_*` codes, and
+//! none lets a panic unwind into the caller: one is caught and reported as [`ERAT_ERR_PANIC`]
+//! (a build with `panic = "abort"` aborts instead). Outputs are written only on success.
+//!
+//! Ownership: the array [`erat_sieve`] hands out belongs to the caller, who must give it back,
+//! with the length it came with, to [`erat_free`] and nothing else; it was not allocated with
+//! `malloc`.
+
+use std::ops::ControlFlow;
+use std::os::raw::c_int;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use crate::{is_prime, sieve_each, sieve_of_eratosthenes};
+
+/// Success.
+pub const ERAT_OK: c_int = 0;
+/// An output pointer was null.
+pub const ERAT_ERR_NULL: c_int = -1;
+/// The sieve or its result could not be allocated.
+pub const ERAT_ERR_ALLOC: c_int = -2;
+/// A bug: the library panicked, and the panic was stopped at the boundary.
+pub const ERAT_ERR_PANIC: c_int = -3;
+
+// Runs `f`, turning a panic into ERAT_ERR_PANIC.
+fn guard<F: FnOnce() -> c_int>(f: F) -> c_int {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(ERAT_ERR_PANIC)
+}
+
+/// The primes up to and including `n`, ascending, as a new array in `*out` of `*len` entries.
+/// An empty result sets `*out` to null and `*len` to 0. Release the array with [`erat_free`].
+///
+/// # Safety
+///
+/// `out` and `len` must each be null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn erat_sieve(n: u64, out: *mut *mut u64, len: *mut usize) -> c_int {
+    if out.is_null() || len.is_null() {
+        return ERAT_ERR_NULL;
+    }
+    guard(|| {
+        let Ok(primes) = sieve_of_eratosthenes(n) else {
+            return ERAT_ERR_ALLOC;
+        };
+        let count = primes.len();
+        let array = match count {
+            0 => ptr::null_mut(),
+            // A boxed slice has no spare capacity, so the length alone is enough to free it.
+            _ => Box::into_raw(primes.into_boxed_slice()).cast::<u64>(),
+        };
+        // SAFETY: both were checked non-null, and the caller vouches they are writable.
+        unsafe {
+            *out = array;
+            *len = count;
+        }
+        ERAT_OK
+    })
+}
+
+/// Releases an array from [`erat_sieve`]; `len` must be the length it came with. Null is
+/// ignored.
+///
+/// # Safety
+///
+/// `ptr` must be null or an array from [`erat_sieve`] not already freed, with `len` its length.
+#[no_mangle]
+pub unsafe extern "C" fn erat_free(ptr: *mut u64, len: usize) {
+    if ptr.is_null() {
+        return;
+    }
+    // SAFETY: the caller vouches that this is the boxed slice erat_sieve leaked.
+    drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len)) });
+}
+
+/// The number of primes up to and including `n`, in `*out_count`, counted without storing them.
+///
+/// # Safety
+///
+/// `out_count` must be null or valid for a write.
+#[no_mangle]
+pub unsafe extern "C" fn erat_count(n: u64, out_count: *mut u64) -> c_int {
+    if out_count.is_null() {
+        return ERAT_ERR_NULL;
+    }
+    guard(|| {
+        let mut count = 0;
+        let counted = sieve_each(n, |_| {
+            count += 1;
+            ControlFlow::Continue(())
+        });
+        if counted.is_err() {
+            return ERAT_ERR_ALLOC;
+        }
+        // SAFETY: checked non-null, and the caller vouches it is writable.
+        unsafe { *out_count = count };
+        ERAT_OK
+    })
+}
+
+/// 1 if `n` is prime and 0 if not, by deterministic Miller-Rabin; no sieve is built.
+#[no_mangle]
+pub extern "C" fn erat_is_prime(n: u64) -> c_int {
+    guard(|| c_int::from(is_prime(n)))
+}
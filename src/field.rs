@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+
+use crate::arith::{gcd, inv_mod, is_prime, mul_mod, pow_mod};
+
+/// Arithmetic modulo a prime `p`. Every operation returns a value reduced into `0..p`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrimeField {
+    p: u64,
+}
+
+/// Returns the field of integers modulo `p`, or `None` if `p` is not prime.
+#[must_use]
+pub fn mod_prime_field(p: u64) -> Option<PrimeField> {
+    if !is_prime(p) {
+        return None;
+    }
+    Some(PrimeField { p })
+}
+
+impl PrimeField {
+    #[must_use]
+    pub fn modulus(&self) -> u64 {
+        self.p
+    }
+
+    #[must_use]
+    pub fn add(&self, a: u64, b: u64) -> u64 {
+        ((a as u128 + b as u128) % self.p as u128) as u64
+    }
+
+    #[must_use]
+    pub fn sub(&self, a: u64, b: u64) -> u64 {
+        let a = a % self.p;
+        let b = b % self.p;
+        if a >= b {
+            a - b
+        } else {
+            self.p - (b - a)
+        }
+    }
+
+    #[must_use]
+    pub fn mul(&self, a: u64, b: u64) -> u64 {
+        mul_mod(a, b, self.p)
+    }
+
+    #[must_use]
+    pub fn pow(&self, a: u64, e: u64) -> u64 {
+        pow_mod(a, e, self.p)
+    }
+
+    /// Multiplicative inverse by Fermat's little theorem, `a^(p-2)`. Zero has none.
+    #[must_use]
+    pub fn inv(&self, a: u64) -> Option<u64> {
+        let a = a % self.p;
+        if a == 0 {
+            return None;
+        }
+        Some(self.pow(a, self.p - 2))
+    }
+
+    #[must_use]
+    pub fn div(&self, a: u64, b: u64) -> Option<u64> {
+        self.inv(b).map(|b_inv| self.mul(a, b_inv))
+    }
+
+    /// Returns the smallest primitive root modulo `p`.
+    #[must_use]
+    pub fn generator(&self) -> u64 {
+        if self.p == 2 {
+            return 1;
+        }
+        let order = self.p - 1;
+        let factors = distinct_prime_factors(order);
+        (2..self.p)
+            .find(|&g| factors.iter().all(|&q| self.pow(g, order / q) != 1))
+            .unwrap()
+    }
+}
+
+/// Finds `x` with `x^2 ≡ n (mod p)` for prime `p`, or `None` if `n` is a non-residue.
+#[must_use]
+pub fn tonelli_shanks(n: u64, p: u64) -> Option<u64> {
+    if !is_prime(p) {
+        return None;
+    }
+    let n = n % p;
+    if p == 2 || n == 0 {
+        return Some(n);
+    }
+    if pow_mod(n, (p - 1) >> 1, p) != 1 {
+        return None;
+    }
+
+    let s = (p - 1).trailing_zeros();
+    let q = (p - 1) >> s;
+    if s == 1 {
+        return Some(pow_mod(n, (p + 1) >> 2, p));
+    }
+
+    let z = (2..p).find(|&z| pow_mod(z, (p - 1) >> 1, p) == p - 1).unwrap();
+    let mut m = s;
+    let mut c = pow_mod(z, q, p);
+    let mut t = pow_mod(n, q, p);
+    let mut r = pow_mod(n, (q + 1) >> 1, p);
+    while t != 1 {
+        let mut i = 0;
+        let mut t2i = t;
+        while t2i != 1 {
+            t2i = mul_mod(t2i, t2i, p);
+            i += 1;
+        }
+        let b = pow_mod(c, 1 << (m - i - 1), p);
+        m = i;
+        c = mul_mod(b, b, p);
+        t = mul_mod(t, c, p);
+        r = mul_mod(r, b, p);
+    }
+    Some(r)
+}
+
+/// Finds `x` with `x^k ≡ n (mod p)` for prime `p`, or `None` if no such root exists.
+///
+/// Square roots use Tonelli-Shanks. Other exponents follow Adleman-Manders-Miller: `n` is split
+/// into its components in the Sylow subgroups of the multiplicative group, the part of order
+/// coprime to `k` is rooted by inverting `k`, and each `r`-Sylow part with `r | k` is rooted
+/// through a discrete logarithm in that subgroup.
+#[must_use]
+pub fn discrete_root(n: u64, k: u64, p: u64) -> Option<u64> {
+    if !is_prime(p) {
+        return None;
+    }
+    let n = n % p;
+    if k == 0 {
+        return if n == 1 % p { Some(1 % p) } else { None };
+    }
+    if n == 0 {
+        return Some(0);
+    }
+    if k == 2 {
+        return tonelli_shanks(n, p);
+    }
+
+    let m = p - 1;
+    let d = gcd(k, m);
+    if pow_mod(n, m / d, p) != 1 {
+        return None;
+    }
+
+    let mut x = 1;
+    let mut rest = m;
+    for r in distinct_prime_factors(d) {
+        let mut q = 1;
+        while rest.is_multiple_of(r) {
+            rest /= r;
+            q *= r;
+        }
+        let n_r = pow_mod(n, sylow_projector(m, q), p);
+        x = mul_mod(x, sylow_root(n_r, k, r, q, p), p);
+    }
+    if rest > 1 {
+        let n_rest = pow_mod(n, sylow_projector(m, rest), p);
+        x = mul_mod(x, pow_mod(n_rest, inv_mod(k % rest, rest).unwrap(), p), p);
+    }
+
+    Some(x)
+}
+
+// Exponent e with e ≡ 1 (mod q) and e ≡ 0 (mod m / q), projecting onto the subgroup of order q.
+fn sylow_projector(m: u64, q: u64) -> u64 {
+    let cofactor = m / q;
+    cofactor * inv_mod(cofactor % q, q).unwrap()
+}
+
+// Solves y^k = a inside the cyclic subgroup of order q = r^s, given that a solution exists.
+fn sylow_root(a: u64, k: u64, r: u64, q: u64, p: u64) -> u64 {
+    let mut a_exp = 0;
+    let mut k_part = k;
+    while k_part.is_multiple_of(r) {
+        k_part /= r;
+        a_exp += 1;
+    }
+    let mut r_a = 1u64;
+    for _ in 0..a_exp {
+        r_a = r_a.saturating_mul(r);
+        if r_a >= q {
+            // Every element of the subgroup is a k-th root of unity; solvability forces a = 1.
+            return 1;
+        }
+    }
+
+    let m = p - 1;
+    let rho = (2..p).find(|&rho| pow_mod(rho, m / r, p) != 1).unwrap();
+    let g = pow_mod(rho, m / q, p);
+    let log = sylow_log(g, a, r, q, p);
+    let y = mul_mod(log / r_a, inv_mod(k_part % (q / r_a), q / r_a).unwrap(), q / r_a);
+    pow_mod(g, y, p)
+}
+
+// Pohlig-Hellman discrete logarithm of a to base g, where g generates a group of order q = r^s.
+fn sylow_log(g: u64, a: u64, r: u64, q: u64, p: u64) -> u64 {
+    let gamma = pow_mod(g, q / r, p);
+    let mut log = 0;
+    let mut r_i = 1;
+    while r_i < q {
+        let g_inv_log = pow_mod(g, q - log, p);
+        let h = pow_mod(mul_mod(g_inv_log, a, p), q / r / r_i, p);
+        log += baby_step_giant_step(gamma, h, r, p) * r_i;
+        r_i *= r;
+    }
+    log % q
+}
+
+// Finds j < order with base^j = target, where base has the given (small) order.
+fn baby_step_giant_step(base: u64, target: u64, order: u64, p: u64) -> u64 {
+    let step = (order as f64).sqrt().ceil() as u64;
+    let mut baby_steps = HashMap::with_capacity(step as usize);
+    let mut e = 1;
+    for j in 0..step {
+        baby_steps.entry(e).or_insert(j);
+        e = mul_mod(e, base, p);
+    }
+    let giant = pow_mod(base, order - step % order, p);
+    let mut gamma = target;
+    for i in 0..=step {
+        if let Some(&j) = baby_steps.get(&gamma) {
+            return (i * step + j) % order;
+        }
+        gamma = mul_mod(gamma, giant, p);
+    }
+    unreachable!("target is not in the subgroup generated by base")
+}
+
+fn distinct_prime_factors(mut n: u64) -> Vec<u64> {
+    let mut factors = Vec::new();
+    let mut d = 2;
+    let mut is_cofactor_prime = is_prime(n);
+    while !is_cofactor_prime && d <= n / d {
+        if n.is_multiple_of(d) {
+            factors.push(d);
+            while n.is_multiple_of(d) {
+                n /= d;
+            }
+            is_cofactor_prime = is_prime(n);
+        }
+        d += if d == 2 { 1 } else { 2 };
+    }
+    if n > 1 {
+        factors.push(n);
+    }
+    factors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tonelli_shanks_root_of_5_mod_41() {
+        // 41 - 1 = 2^3 * 5, so the root takes the full Tonelli-Shanks loop.
+        let r = tonelli_shanks(5, 41).unwrap();
+        assert!(r == 13 || r == 28, "{}", r);
+        assert_eq!(r * r % 41, 5);
+    }
+
+    #[test]
+    fn tonelli_shanks_roots_every_residue() {
+        for p in [2, 3, 5, 7, 13, 17, 41, 73, 97, 113, 257, 7_681, 65_537] {
+            let mut squares = vec![false; p as usize];
+            for x in 0..p {
+                squares[(x * x % p) as usize] = true;
+            }
+            for n in 0..p {
+                match tonelli_shanks(n, p) {
+                    Some(r) => assert_eq!(r * r % p, n, "root of {} mod {}", n, p),
+                    None => assert!(!squares[n as usize], "{} is a square mod {}", n, p),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tonelli_shanks_near_u64_max() {
+        // 2^64 - 59 is the last prime below 2^64.
+        let p = u64::MAX - 58;
+        for n in [2, 3, 10, 12_345_678_901, p - 1] {
+            if let Some(r) = tonelli_shanks(n, p) {
+                assert_eq!(mul_mod(r, r, p), n);
+            }
+        }
+        let r = tonelli_shanks(mul_mod(987_654_321, 987_654_321, p), p).unwrap();
+        assert!(r == 987_654_321 || r == p - 987_654_321);
+        assert_eq!(tonelli_shanks(5, 42), None);
+    }
+}
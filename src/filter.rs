@@ -0,0 +1,250 @@
+// `--filter EXPR`: which primes to emit, as conditions joined by commas, all of which must hold:
+//
+//     mod M = R             p % M == R
+//     lastdigit D           p % 10 == D
+//     digitsum mod M = R    the sum of p's decimal digits % M == R
+//     twin                  p + 2 is prime too
+//
+// The filter sits between the sieve and the output, so memory stays flat. `twin` looks one prime
+// ahead, so a prime is held back until the next one arrives.
+
+use std::fmt;
+use std::ops::ControlFlow;
+
+use eratosthenes::is_prime;
+
+enum Condition {
+    Mod { m: u64, r: u64 },
+    LastDigit(u64),
+    DigitSumMod { m: u64, r: u64 },
+}
+
+impl Condition {
+    fn holds(&self, p: u64) -> bool {
+        match *self {
+            Condition::Mod { m, r } => p % m == r,
+            Condition::LastDigit(d) => p % 10 == d,
+            Condition::DigitSumMod { m, r } => digit_sum(p) % m == r,
+        }
+    }
+}
+
+fn digit_sum(mut p: u64) -> u64 {
+    let mut sum = 0;
+    while p > 0 {
+        sum += p % 10;
+        p /= 10;
+    }
+    sum
+}
+
+pub struct Filter {
+    expr: String,
+    conditions: Vec<Condition>,
+    twin: bool,
+}
+
+const FORMS: &str = "expected `mod M = R`, `lastdigit D`, `digitsum mod M = R` or `twin`";
+
+impl Filter {
+    pub fn parse(expr: &str) -> Result<Filter, String> {
+        let mut filter = Filter { expr: expr.trim().to_string(), conditions: Vec::new(), twin: false };
+        for clause in expr.split(',') {
+            let spaced = clause.replace('=', " = ");
+            let words: Vec<&str> = spaced.split_whitespace().collect();
+            let condition = match words[..] {
+                [] => return Err(format!("empty condition in {:?}; {}", expr, FORMS)),
+                ["twin"] => {
+                    filter.twin = true;
+                    continue;
+                }
+                ["mod", m, "=", r] => {
+                    let (m, r) = residue(m, r)?;
+                    Condition::Mod { m, r }
+                }
+                ["lastdigit", d] => match number(d)? {
+                    d if d <= 9 => Condition::LastDigit(d),
+                    d => return Err(format!("lastdigit takes a digit from 0 to 9, not {}", d)),
+                },
+                ["digitsum", "mod", m, "=", r] => {
+                    let (m, r) = residue(m, r)?;
+                    Condition::DigitSumMod { m, r }
+                }
+                _ => return Err(format!("bad condition {:?}; {}", clause.trim(), FORMS)),
+            };
+            filter.conditions.push(condition);
+        }
+        Ok(filter)
+    }
+
+    // Everything but `twin`, which needs the next prime.
+    fn holds(&self, p: u64) -> bool {
+        self.conditions.iter().all(|condition| condition.holds(p))
+    }
+
+    /// Wraps `emit` so that it only sees the primes that pass. Feed every prime to
+    /// [`Filtered::push`] in order, then call [`Filtered::finish`].
+    pub fn wrap<F: FnMut(u64) -> ControlFlow<()>>(&self, emit: F) -> Filtered<'_, F> {
+        Filtered { filter: self, emit, held: None, stopped: false }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expr)
+    }
+}
+
+fn number(word: &str) -> Result<u64, String> {
+    word.parse().map_err(|_| format!("{:?} is not a whole number", word))
+}
+
+fn residue(m: &str, r: &str) -> Result<(u64, u64), String> {
+    let (m, r) = (number(m)?, number(r)?);
+    if m == 0 {
+        return Err("mod 0 matches nothing; the modulus must be positive".to_string());
+    }
+    if r >= m {
+        return Err(format!("no remainder mod {} is {}; it must be below {}", m, r, m));
+    }
+    Ok((m, r))
+}
+
+pub struct Filtered<'a, F> {
+    filter: &'a Filter,
+    emit: F,
+    // With `twin`: a passing prime waiting to see whether the next prime is 2 above it.
+    held: Option<u64>,
+    stopped: bool,
+}
+
+impl<F: FnMut(u64) -> ControlFlow<()>> Filtered<'_, F> {
+    pub fn push(&mut self, p: u64) -> ControlFlow<()> {
+        let flow = self.pass(p);
+        self.stopped = flow.is_break();
+        flow
+    }
+
+    fn pass(&mut self, p: u64) -> ControlFlow<()> {
+        if !self.filter.twin {
+            return if self.filter.holds(p) { (self.emit)(p) } else { ControlFlow::Continue(()) };
+        }
+        if let Some(q) = self.held.take() {
+            if p == q + 2 {
+                (self.emit)(q)?;
+            }
+        }
+        if self.filter.holds(p) {
+            self.held = Some(p);
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Settles the last prime held back by `twin`, whose twin lies past the sieved range, unless
+    /// the output already asked to stop.
+    pub fn finish(mut self) {
+        if let (Some(q), false) = (self.held, self.stopped) {
+            if q.checked_add(2).is_some_and(is_prime) {
+                let _ = (self.emit)(q);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const N: u64 = 100_000;
+
+    type Predicate = fn(u64) -> bool;
+
+    // The primes up to n by trial division, apart from the sieve.
+    fn primes_by_trial_division(n: u64) -> Vec<u64> {
+        (2..=n).filter(|&v| is_prime_by_trial_division(v)).collect()
+    }
+
+    fn is_prime_by_trial_division(v: u64) -> bool {
+        v >= 2 && (2..).take_while(|d| d * d <= v).all(|d| !v.is_multiple_of(d))
+    }
+
+    // The primes up to n through the filter `expr`, fed from the sieve as a run would.
+    fn filtered(expr: &str, n: u64) -> Vec<u64> {
+        let filter = Filter::parse(expr).unwrap();
+        let mut passed = Vec::new();
+        let mut filtered = filter.wrap(|p| {
+            passed.push(p);
+            ControlFlow::Continue(())
+        });
+        for p in eratosthenes::primes_up_to(n) {
+            let _ = filtered.push(p);
+        }
+        filtered.finish();
+        passed
+    }
+
+    fn expected(pred: impl Fn(u64) -> bool) -> Vec<u64> {
+        primes_by_trial_division(N).into_iter().filter(|&p| pred(p)).collect()
+    }
+
+    // Digit sums from the decimal string, not the filter's arithmetic.
+    fn digits(p: u64) -> u64 {
+        p.to_string().bytes().map(|b| u64::from(b - b'0')).sum()
+    }
+
+    #[test]
+    fn each_condition_matches_its_definition() {
+        let cases: [(&str, Predicate); 9] = [
+            ("mod 4 = 1", |p| p % 4 == 1),
+            ("mod 4 = 3", |p| p % 4 == 3),
+            ("mod 6 = 5", |p| p % 6 == 5),
+            ("mod 1 = 0", |_| true),
+            ("lastdigit 7", |p| p.to_string().ends_with('7')),
+            ("lastdigit 2", |p| p == 2),
+            ("lastdigit 0", |_| false),
+            ("digitsum mod 3 = 1", |p| digits(p) % 3 == 1),
+            ("twin", |p| is_prime_by_trial_division(p + 2)),
+        ];
+        for (expr, pred) in cases {
+            assert_eq!(filtered(expr, N), expected(pred), "{}", expr);
+        }
+        assert_eq!(filtered("twin", N).len(), 1_224);
+    }
+
+    #[test]
+    fn commas_join_conditions_with_and() {
+        let both = filtered("mod 4 = 1, lastdigit 3", N);
+        assert_eq!(both, expected(|p| p % 4 == 1 && p % 10 == 3));
+        let all = filtered("twin,digitsum mod 2 = 1,mod 3=2", N);
+        assert_eq!(all, expected(|p| is_prime_by_trial_division(p + 2) && digits(p) % 2 == 1 && p % 3 == 2));
+        assert!(!all.is_empty());
+    }
+
+    #[test]
+    fn twin_looks_past_the_end_of_the_range() {
+        // 101's twin is 103, beyond n.
+        assert_eq!(filtered("twin", 101), [3, 5, 11, 17, 29, 41, 59, 71, 101]);
+        assert_eq!(filtered("twin", 102), filtered("twin", 101));
+        assert_eq!(filtered("twin", 4), [3]);
+    }
+
+    #[test]
+    fn malformed_expressions_are_explained() {
+        let cases = [
+            ("", format!("empty condition in \"\"; {}", FORMS)),
+            ("twin,", format!("empty condition in \"twin,\"; {}", FORMS)),
+            ("mod 4", format!("bad condition \"mod 4\"; {}", FORMS)),
+            ("prime", format!("bad condition \"prime\"; {}", FORMS)),
+            (" digitsum mod 3 ", format!("bad condition \"digitsum mod 3\"; {}", FORMS)),
+            ("mod x = 1", "\"x\" is not a whole number".to_string()),
+            ("mod 0 = 0", "mod 0 matches nothing; the modulus must be positive".to_string()),
+            ("mod 4 = 4", "no remainder mod 4 is 4; it must be below 4".to_string()),
+            ("digitsum mod 9 = 12", "no remainder mod 9 is 12; it must be below 9".to_string()),
+            ("lastdigit 10", "lastdigit takes a digit from 0 to 9, not 10".to_string()),
+            ("lastdigit -1", "\"-1\" is not a whole number".to_string()),
+        ];
+        for (expr, message) in cases {
+            assert_eq!(Filter::parse(expr).err(), Some(message), "{:?}", expr);
+        }
+    }
+}
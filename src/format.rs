@@ -0,0 +1,556 @@
+use std::io::{self, BufReader, Read, Write};
+use std::ops::ControlFlow;
+
+// bin-delta layout: magic, version byte, start value and prime count as little-endian u64,
+// then one LEB128 varint per prime holding its distance from the previous prime (or the start).
+pub const DELTA_MAGIC: [u8; 4] = *b"ERDL";
+pub const DELTA_VERSION: u8 = 1;
+/// Byte offset of the prime count in the header, for appending in place.
+pub const DELTA_COUNT_OFFSET: u64 = 4 + 1 + 8;
+
+// Prime file layout: magic, version byte, the bound n and the prime count as little-endian u64,
+// then every prime as a little-endian u64. Bigger than bin-delta, but loads without decoding.
+pub const PRIME_FILE_MAGIC: [u8; 4] = *b"ERPF";
+pub const PRIME_FILE_VERSION: u8 = 1;
+/// Byte offsets of the bound and the prime count in the header, for filling them in last.
+pub const PRIME_FILE_BOUND_OFFSET: u64 = 4 + 1;
+pub const PRIME_FILE_COUNT_OFFSET: u64 = 4 + 1 + 8;
+
+/// Accumulates ascending primes as varint gaps, to be written out with their header on `finish`.
+pub struct DeltaEncoder {
+    start: u64,
+    last: u64,
+    count: u64,
+    gaps: Vec<u8>,
+}
+
+impl DeltaEncoder {
+    /// `start` must be below the first prime pushed; 0 for a list beginning at 2.
+    pub fn new(start: u64) -> Self {
+        DeltaEncoder { start, last: start, count: 0, gaps: Vec::new() }
+    }
+
+    pub fn push(&mut self, p: u64) {
+        assert!(p > self.last, "primes must be strictly increasing");
+        write_varint(&mut self.gaps, p - self.last);
+        self.last = p;
+        self.count += 1;
+    }
+
+    #[must_use]
+    pub fn prime_count(&self) -> u64 {
+        self.count
+    }
+
+    /// The encoded gaps so far, without a header: what to append to an existing stream whose
+    /// last prime was this encoder's `start`.
+    #[must_use]
+    pub fn gaps(&self) -> &[u8] {
+        &self.gaps
+    }
+
+    pub fn finish<W: Write>(self, writer: W) -> io::Result<()> {
+        self.write(writer)
+    }
+
+    /// Writes the stream so far, header and all, keeping the encoder for more primes: for
+    /// snapshots of a list still being built.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&DELTA_MAGIC)?;
+        writer.write_all(&[DELTA_VERSION])?;
+        writer.write_all(&self.start.to_le_bytes())?;
+        writer.write_all(&self.count.to_le_bytes())?;
+        writer.write_all(&self.gaps)?;
+        writer.flush()
+    }
+}
+
+pub fn write_delta_primes<W: Write>(writer: W, start: u64, primes: &[u64]) -> io::Result<()> {
+    let mut encoder = DeltaEncoder::new(start);
+    for &p in primes {
+        encoder.push(p);
+    }
+    encoder.finish(writer)
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "bin-delta stream is truncated")
+}
+
+fn read_exact_or_truncated<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<()> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => truncated(),
+        _ => e,
+    })
+}
+
+/// Streams primes back out of a bin-delta source. Wrap unbuffered sources in a `BufReader`, and
+/// possibly compressed ones in [`decompress`].
+pub struct DeltaReader<R> {
+    reader: R,
+    start: u64,
+    count: u64,
+    remaining: u64,
+    last: u64,
+}
+
+impl<R: Read> DeltaReader<R> {
+    /// Reads and validates the header.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        read_exact_or_truncated(&mut reader, &mut magic)?;
+        if magic != DELTA_MAGIC {
+            return Err(invalid_data("not a bin-delta prime stream (bad magic)"));
+        }
+        let mut version = [0; 1];
+        read_exact_or_truncated(&mut reader, &mut version)?;
+        if version[0] != DELTA_VERSION {
+            return Err(invalid_data("unsupported bin-delta version"));
+        }
+        let mut word = [0; 8];
+        read_exact_or_truncated(&mut reader, &mut word)?;
+        let start = u64::from_le_bytes(word);
+        read_exact_or_truncated(&mut reader, &mut word)?;
+        let count = u64::from_le_bytes(word);
+
+        Ok(DeltaReader { reader, start, count, remaining: count, last: start })
+    }
+
+    #[must_use]
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    #[must_use]
+    pub fn prime_count(&self) -> u64 {
+        self.count
+    }
+
+    /// The last prime read so far, or the start before any.
+    #[must_use]
+    pub fn last_prime(&self) -> u64 {
+        self.last
+    }
+
+    /// The underlying reader, positioned after the primes read so far.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_varint(&mut self) -> io::Result<u64> {
+        let mut v = 0u64;
+        let mut shift = 0;
+        loop {
+            let mut byte = [0; 1];
+            read_exact_or_truncated(&mut self.reader, &mut byte)?;
+            let bits = (byte[0] & 0x7f) as u64;
+            if shift > 63 || (shift == 63 && bits > 1) {
+                return Err(invalid_data("bin-delta gap overflows u64"));
+            }
+            v |= bits << shift;
+            if byte[0] & 0x80 == 0 {
+                return Ok(v);
+            }
+            shift += 7;
+        }
+    }
+}
+
+impl<R: Read> Iterator for DeltaReader<R> {
+    type Item = io::Result<u64>;
+
+    fn next(&mut self) -> Option<io::Result<u64>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let gap = match self.read_varint() {
+            Ok(gap) => gap,
+            Err(e) => {
+                self.remaining = 0;
+                return Some(Err(e));
+            }
+        };
+        self.remaining -= 1;
+        match self.last.checked_add(gap) {
+            Some(p) if gap > 0 => {
+                self.last = p;
+                Some(Ok(p))
+            }
+            _ => {
+                self.remaining = 0;
+                Some(Err(invalid_data("bin-delta primes are not strictly increasing")))
+            }
+        }
+    }
+}
+
+// Leading bytes of the compressed containers `decompress` recognizes.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// `reader` decompressed if it holds a gzip or zstd stream, going by its first bytes, and passed
+/// through untouched otherwise, so prime files load the same whether or not they were written
+/// with `--compress`. Decompression needs the `compression` feature; without it, compressed
+/// input is an `Unsupported` error rather than garbage.
+pub fn decompress<'a, R: Read + 'a>(mut reader: R) -> io::Result<Box<dyn Read + 'a>> {
+    let mut head = [0; 4];
+    let mut len = 0;
+    while len < head.len() {
+        match reader.read(&mut head[len..]) {
+            Ok(0) => break,
+            Ok(read) => len += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    let head = &head[..len];
+    let whole = io::Cursor::new(head.to_vec()).chain(reader);
+    if head.starts_with(&GZIP_MAGIC) {
+        #[cfg(feature = "compression")]
+        return Ok(Box::new(flate2::read::MultiGzDecoder::new(whole)));
+        #[cfg(not(feature = "compression"))]
+        return Err(needs_compression("gzip"));
+    }
+    if head.starts_with(&ZSTD_MAGIC) {
+        #[cfg(feature = "compression")]
+        return Ok(Box::new(zstd::stream::read::Decoder::new(whole)?));
+        #[cfg(not(feature = "compression"))]
+        return Err(needs_compression("zstd"));
+    }
+    Ok(Box::new(whole))
+}
+
+#[cfg(not(feature = "compression"))]
+fn needs_compression(codec: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("input is {}-compressed; decompressing it needs the `compression` feature", codec),
+    )
+}
+
+/// Decodes a whole bin-delta stream, rejecting bad headers, truncation and trailing bytes. A
+/// compressed stream is decompressed first; see [`decompress`].
+pub fn read_delta_primes<R: Read>(reader: R) -> io::Result<Vec<u64>> {
+    let mut reader = DeltaReader::new(BufReader::new(decompress(reader)?))?;
+    let mut primes = Vec::with_capacity(reader.prime_count().min(1 << 20) as usize);
+    for p in &mut reader {
+        primes.push(p?);
+    }
+    if reader.reader.read(&mut [0; 1])? != 0 {
+        return Err(invalid_data("unexpected data after the last bin-delta prime"));
+    }
+    Ok(primes)
+}
+
+/// Writes `primes`, found by sieving up to `n`, as a prime file: a header with the bound and the
+/// count, then the primes at 8 bytes each. See [`read_prime_file`].
+pub fn write_prime_file<W: Write>(mut writer: W, n: u64, primes: &[u64]) -> io::Result<()> {
+    writer.write_all(&PRIME_FILE_MAGIC)?;
+    writer.write_all(&[PRIME_FILE_VERSION])?;
+    writer.write_all(&n.to_le_bytes())?;
+    writer.write_all(&(primes.len() as u64).to_le_bytes())?;
+    for &p in primes {
+        writer.write_all(&p.to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+/// Reads a prime file back as its bound and its primes, decompressing it first if need be (see
+/// [`decompress`]). Rejects a bad header, a count that disagrees with the primes present, primes
+/// out of order and primes past the bound.
+///
+/// ```
+/// use eratosthenes::{primes_up_to, read_prime_file, write_prime_file};
+///
+/// let mut file = Vec::new();
+/// write_prime_file(&mut file, 100, &primes_up_to(100)).unwrap();
+/// assert_eq!(file.len(), 4 + 1 + 8 + 8 + 25 * 8);
+/// assert_eq!(read_prime_file(&file[..]).unwrap(), (100, primes_up_to(100)));
+///
+/// let mut past_bound = Vec::new();
+/// write_prime_file(&mut past_bound, 90, &primes_up_to(100)).unwrap();
+/// assert!(read_prime_file(&past_bound[..]).is_err());
+/// ```
+pub fn read_prime_file<R: Read>(reader: R) -> io::Result<(u64, Vec<u64>)> {
+    let mut reader = PrimeFileReader::new(BufReader::new(decompress(reader)?))?;
+    let mut primes = Vec::with_capacity(reader.prime_count().min(1 << 20) as usize);
+    for p in &mut reader {
+        primes.push(p?);
+    }
+    if reader.reader.read(&mut [0; 1])? != 0 {
+        return Err(invalid_data("unexpected data after the last prime in the prime file"));
+    }
+    Ok((reader.bound(), primes))
+}
+
+fn prime_file_truncated(e: io::Error) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => io::Error::new(io::ErrorKind::UnexpectedEof, "prime file is truncated"),
+        _ => e,
+    }
+}
+
+/// Streams primes back out of a prime file, checking each against the header as
+/// [`read_prime_file`] does. Wrap unbuffered sources in a `BufReader`, and possibly compressed
+/// ones in [`decompress`].
+pub struct PrimeFileReader<R> {
+    reader: R,
+    n: u64,
+    count: u64,
+    remaining: u64,
+    last: u64,
+}
+
+impl<R: Read> PrimeFileReader<R> {
+    /// Reads and validates the header.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 5];
+        reader.read_exact(&mut magic).map_err(prime_file_truncated)?;
+        if magic[..4] != PRIME_FILE_MAGIC {
+            return Err(invalid_data("not a prime file (bad magic)"));
+        }
+        if magic[4] != PRIME_FILE_VERSION {
+            return Err(invalid_data("unsupported prime file version"));
+        }
+        let mut word = [0; 8];
+        reader.read_exact(&mut word).map_err(prime_file_truncated)?;
+        let n = u64::from_le_bytes(word);
+        reader.read_exact(&mut word).map_err(prime_file_truncated)?;
+        let count = u64::from_le_bytes(word);
+        if count > n / 2 + 1 {
+            return Err(invalid_data("prime file holds more primes than there are up to its bound"));
+        }
+        Ok(PrimeFileReader { reader, n, count, remaining: count, last: 0 })
+    }
+
+    /// The bound the primes were sieved up to.
+    #[must_use]
+    pub fn bound(&self) -> u64 {
+        self.n
+    }
+
+    #[must_use]
+    pub fn prime_count(&self) -> u64 {
+        self.count
+    }
+
+    fn read_prime(&mut self) -> io::Result<u64> {
+        let mut word = [0; 8];
+        self.reader.read_exact(&mut word).map_err(prime_file_truncated)?;
+        let p = u64::from_le_bytes(word);
+        if p <= self.last {
+            return Err(invalid_data("prime file primes are not strictly increasing"));
+        }
+        if p > self.n {
+            return Err(invalid_data(&format!("prime file lists {}, past its bound of {}", p, self.n)));
+        }
+        Ok(p)
+    }
+}
+
+impl<R: Read> Iterator for PrimeFileReader<R> {
+    type Item = io::Result<u64>;
+
+    fn next(&mut self) -> Option<io::Result<u64>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        match self.read_prime() {
+            Ok(p) => {
+                self.remaining -= 1;
+                self.last = p;
+                Some(Ok(p))
+            }
+            Err(e) => {
+                self.remaining = 0;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// The primality of every integer in `0..=n` as run lengths: a run of non-primes starting at 0,
+/// then alternately primes and non-primes. Prime runs are 1 long, except for 2 and 3, and the
+/// non-prime runs are the prime gaps less one, so every run fits a u32.
+///
+/// That is two words per prime, or 64 bits: more than a bitmap of `n` bits until primes thin
+/// out past one in 64, far beyond u64. The runs are small and repetitive, though, which general
+/// compressors make much of.
+///
+/// ```
+/// use eratosthenes::{primes_up_to, rle_decode_primes, rle_encode_primes};
+///
+/// assert_eq!(rle_encode_primes(12), [2, 2, 1, 1, 1, 1, 3, 1, 1]);
+/// assert_eq!(rle_decode_primes(&rle_encode_primes(1000)), primes_up_to(1000));
+/// ```
+///
+/// # Panics
+///
+/// If the sieve cannot be allocated.
+#[must_use]
+pub fn rle_encode_primes(n: u64) -> Vec<u32> {
+    let mut runs = Vec::new();
+    // The next integer to be accounted for, and whether it would extend a prime run.
+    let mut next = 0;
+    let mut in_primes = false;
+    crate::sieve_each(n, |p| {
+        if in_primes && p == next {
+            *runs.last_mut().unwrap() += 1;
+        } else {
+            runs.push((p - next) as u32);
+            runs.push(1);
+        }
+        next = p + 1;
+        in_primes = true;
+        ControlFlow::Continue(())
+    })
+    .unwrap_or_else(|e| panic!("{}", e));
+    if next <= n {
+        runs.push((n - next + 1) as u32);
+    }
+    runs
+}
+
+/// The primes in a run-length encoding from [`rle_encode_primes`].
+#[must_use]
+pub fn rle_decode_primes(runs: &[u32]) -> Vec<u64> {
+    let mut primes = Vec::new();
+    let mut next = 0;
+    for (i, &run) in runs.iter().enumerate() {
+        if i % 2 == 1 {
+            primes.extend(next..next + run as u64);
+        }
+        next += run as u64;
+    }
+    primes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primes_up_to;
+
+    fn encode(start: u64, primes: &[u64]) -> Vec<u8> {
+        let mut stream = Vec::new();
+        write_delta_primes(&mut stream, start, primes).unwrap();
+        stream
+    }
+
+    #[test]
+    fn delta_round_trip_below_10_to_the_7() {
+        let primes = primes_up_to(10_000_000);
+        let stream = encode(0, &primes);
+        assert_eq!(read_delta_primes(&stream[..]).unwrap(), primes);
+
+        let mut reader = DeltaReader::new(&stream[..]).unwrap();
+        assert_eq!((reader.start(), reader.prime_count()), (0, 664_579));
+        assert_eq!(reader.by_ref().map(Result::unwrap).collect::<Vec<_>>(), primes);
+        assert_eq!(reader.last_prime(), 9_999_991);
+
+        // Every gap below 10^7 but a handful fits one byte, against eight for a raw u64: just
+        // under 8x smaller once the header and the two-byte gaps are in.
+        let raw = primes.len() * 8;
+        let header = 4 + 1 + 8 + 8;
+        assert!(stream.len() - header < primes.len() + primes.len() / 1_000, "{} bytes", stream.len());
+        assert!(raw as f64 / stream.len() as f64 > 7.99, "{} bytes against {} raw", stream.len(), raw);
+    }
+
+    #[test]
+    fn delta_gaps_past_a_byte_take_more() {
+        // 436273009 is followed by the first prime gap over 255, of 282 = 0b10_0011010.
+        let stream = encode(436_273_000, &[436_273_009, 436_273_291]);
+        assert_eq!(stream[4 + 1 + 8 + 8..], [9, 0x9a, 0x02]);
+        assert_eq!(read_delta_primes(&stream[..]).unwrap(), [436_273_009, 436_273_291]);
+
+        // The encoder takes any ascending values, up to gaps of the full width of a u64.
+        let values = [2, 3, 3 + 127, 130 + 255, 130 + 256, 130 + 256 + 16_384, u64::MAX];
+        let stream = encode(0, &values);
+        assert_eq!(read_delta_primes(&stream[..]).unwrap(), values);
+        let gap_bytes = [1, 1, 1, 2, 1, 3, 10];
+        assert_eq!(stream.len(), 4 + 1 + 8 + 8 + gap_bytes.iter().sum::<usize>());
+    }
+
+    #[test]
+    fn delta_first_prime_is_a_gap_from_the_start() {
+        assert_eq!(encode(0, &[2, 3, 5])[4 + 1 + 8 + 8..], [2, 1, 2]);
+        assert_eq!(encode(89, &[97, 101])[4 + 1 + 8 + 8..], [8, 4]);
+        assert_eq!(read_delta_primes(&encode(89, &[97, 101])[..]).unwrap(), [97, 101]);
+        assert_eq!(read_delta_primes(&encode(7, &[])[..]).unwrap(), []);
+    }
+
+    #[test]
+    fn delta_rejects_truncation() {
+        let stream = encode(0, &[2, 3, 5, 7, 436_273_009, 436_273_291]);
+        for len in 0..stream.len() {
+            let err = read_delta_primes(&stream[..len]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof, "{} bytes: {}", len, err);
+        }
+        assert!(read_delta_primes(&stream[..]).is_ok());
+    }
+
+    #[test]
+    fn delta_rejects_bad_headers_and_streams() {
+        let stream = encode(0, &[2, 3, 5]);
+        let with = |at: usize, byte: u8| {
+            let mut bad = stream.clone();
+            bad[at] = byte;
+            read_delta_primes(&bad[..]).unwrap_err().to_string()
+        };
+        assert_eq!(with(0, b'X'), "not a bin-delta prime stream (bad magic)");
+        assert_eq!(with(4, DELTA_VERSION + 1), "unsupported bin-delta version");
+        // A count above the gaps present is truncation; one below leaves trailing bytes.
+        assert_eq!(with(DELTA_COUNT_OFFSET as usize, 4), "bin-delta stream is truncated");
+        assert_eq!(with(DELTA_COUNT_OFFSET as usize, 2), "unexpected data after the last bin-delta prime");
+        assert_eq!(with(stream.len() - 1, 0), "bin-delta primes are not strictly increasing");
+
+        let mut overflow = stream[..stream.len() - 1].to_vec();
+        overflow.extend([0xff; 9].iter().chain(&[0x01]));
+        let err = read_delta_primes(&overflow[..]).unwrap_err();
+        assert_eq!(err.to_string(), "bin-delta primes are not strictly increasing");
+        *overflow.last_mut().unwrap() = 0x02;
+        assert_eq!(read_delta_primes(&overflow[..]).unwrap_err().to_string(), "bin-delta gap overflows u64");
+    }
+
+    #[test]
+    fn rle_round_trip() {
+        for n in 0..=200 {
+            let runs = rle_encode_primes(n);
+            assert_eq!(runs.iter().map(|&run| run as u64).sum::<u64>(), n + 1, "n = {}", n);
+            assert!(runs.iter().skip(1).all(|&run| run > 0), "n = {}: {:?}", n, runs);
+            assert_eq!(rle_decode_primes(&runs), primes_up_to(n), "n = {}", n);
+        }
+        assert_eq!(rle_encode_primes(0), [1]);
+        assert_eq!(rle_encode_primes(2), [2, 1]);
+        assert_eq!(rle_encode_primes(3), [2, 2]);
+        assert_eq!(rle_decode_primes(&[]), []);
+    }
+
+    #[test]
+    fn rle_size_below_10_to_the_7() {
+        let n = 10_000_000;
+        let runs = rle_encode_primes(n);
+        let primes = primes_up_to(n);
+        assert_eq!(rle_decode_primes(&runs), primes);
+
+        // Two runs per prime but one, since 2 and 3 share a run, and a last run of non-primes
+        // after 9999991: two u32s per prime, no smaller than the primes as raw u64s.
+        assert_eq!(runs.len(), 2 * primes.len() - 2 + 1);
+        let (rle, raw, bitmap) = (runs.len() * 4, primes.len() * 8, n as usize / 8);
+        assert_eq!(rle, raw - 4);
+        // Against a bit per integer it is over 4x bigger: the runs only pay off once compressed.
+        assert!(rle > 4 * bitmap, "{} bytes of runs against {} of bitmap", rle, bitmap);
+        assert_eq!(runs.iter().max(), Some(&153));
+    }
+}
@@ -0,0 +1,233 @@
+// `--serve --listen ADDR`, with the `http` feature: the `--serve` queries over a minimal HTTP/1.1
+// handler on std's TcpListener, one thread and one request per connection.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use eratosthenes::{is_prime, Sieve};
+
+use crate::bound::parse_bound;
+use crate::serve::{json_string, next_sieve_bound};
+
+// Largest bound a request may make the server sieve to: about 270 MB of marks.
+const MAX_N: u64 = 1_000_000_000;
+// Most primes one /primes response carries, and how many it carries unless asked for fewer.
+const MAX_LIMIT: usize = 10_000;
+// Longest request line or header accepted, and how many headers.
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serves, until killed:
+///
+/// ```text
+/// GET /count?n=N                     {"n":N,"count":C}
+/// GET /isprime?n=N                   {"n":N,"prime":true}
+/// GET /primes?lo=LO&hi=HI[&limit=L][&after=P]
+///                                    {"lo":LO,"hi":HI,"primes":[...],"next":P}
+/// ```
+///
+/// `/primes` returns at most `limit` primes (capped at 10000); when more remain, `next` is the
+/// last one returned, to be passed back as `after` for the next page, and otherwise null. Bad
+/// requests get a 4xx status and `{"error":"..."}`.
+pub fn listen(addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("listening on http://{}", listener.local_addr()?);
+    let cache = Arc::new(SieveCache { sieve: Mutex::new(None) });
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("accept failed: {}", e);
+                continue;
+            }
+        };
+        let cache = Arc::clone(&cache);
+        thread::spawn(move || {
+            // The client hanging up early is its own business.
+            let _ = handle(stream, &cache);
+        });
+    }
+    Ok(())
+}
+
+// The sieve shared by every connection. Growing it holds the lock, so concurrent requests that
+// need a bigger sieve wait for one rebuild instead of each starting their own; requests the
+// current sieve already covers only hold it long enough to clone the Arc.
+struct SieveCache {
+    sieve: Mutex<Option<Arc<Sieve>>>,
+}
+
+impl SieveCache {
+    fn sieve_up_to(&self, n: u64) -> Result<Arc<Sieve>, Response> {
+        if n > MAX_N {
+            return Err(Response::error(422, &format!("n must be at most {}", MAX_N)));
+        }
+        let mut sieve = self.sieve.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let current = sieve.as_ref().map_or(0, |sieve| sieve.n());
+        if n > current {
+            let n = next_sieve_bound(current, n).min(MAX_N);
+            *sieve = None;
+            let built = Sieve::new(n).map_err(|e| Response::error(500, &e.to_string()))?;
+            *sieve = Some(Arc::new(built));
+        }
+        Ok(Arc::clone(sieve.as_ref().unwrap()))
+    }
+
+    // What the current sieve says about n, without growing it; None if it does not reach n.
+    fn is_prime_cached(&self, n: u64) -> Option<bool> {
+        let sieve = self.sieve.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        sieve.as_ref().filter(|sieve| n <= sieve.n()).map(|sieve| sieve.is_prime(n))
+    }
+}
+
+struct Response {
+    status: u16,
+    body: String,
+}
+
+impl Response {
+    fn ok(body: String) -> Self {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, msg: &str) -> Self {
+        Response { status, body: format!("{{\"error\":{}}}", json_string(msg)) }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            422 => "Unprocessable Content",
+            431 => "Request Header Fields Too Large",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+fn handle(stream: TcpStream, cache: &SieveCache) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let response = match read_request(&mut reader)? {
+        Ok(target) => answer(&target, cache).unwrap_or_else(|response| response),
+        Err(response) => response,
+    };
+    let mut out = &stream;
+    write!(
+        out,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}\n",
+        response.status,
+        response.reason(),
+        response.body.len() + 1,
+        response.body
+    )?;
+    out.flush()
+}
+
+// Reads the request line and headers, returning the target of a GET. Any body is ignored, since
+// the connection closes after the response.
+fn read_request<R: BufRead>(reader: &mut R) -> io::Result<Result<String, Response>> {
+    let request_line = match read_line(reader)? {
+        Some(line) => line,
+        None => return Ok(Err(Response::error(431, "request line too long"))),
+    };
+    for _ in 0..MAX_HEADERS {
+        match read_line(reader)? {
+            Some(header) if header.is_empty() => {
+                let mut parts = request_line.split(' ');
+                return Ok(match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                    (Some("GET"), Some(target), Some(version), None) if version.starts_with("HTTP/1.") => {
+                        Ok(target.to_string())
+                    }
+                    (Some(_), Some(_), Some(_), None) => Err(Response::error(405, "only GET is supported")),
+                    _ => Err(Response::error(400, "malformed request line")),
+                });
+            }
+            Some(_) => {}
+            None => return Ok(Err(Response::error(431, "header too long"))),
+        }
+    }
+    Ok(Err(Response::error(431, "too many headers")))
+}
+
+// One CRLF- or LF-terminated line without its terminator, or None if it runs past MAX_LINE.
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    let read = reader.by_ref().take(MAX_LINE as u64 + 1).read_until(b'\n', &mut line)?;
+    if read == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed mid-request"));
+    }
+    if line.last() != Some(&b'\n') {
+        return Ok(None);
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+fn answer(target: &str, cache: &SieveCache) -> Result<Response, Response> {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let params: Vec<(&str, &str)> =
+        query.split('&').filter(|pair| !pair.is_empty()).map(|pair| pair.split_once('=').unwrap_or((pair, ""))).collect();
+    let param = |name: &str| -> Result<Option<u64>, Response> {
+        match params.iter().find(|&&(key, _)| key == name) {
+            Some(&(_, value)) => parse_bound(value).map(Some).map_err(|e| Response::error(400, &format!("{}: {}", name, e))),
+            None => Ok(None),
+        }
+    };
+    let required = |name: &str| -> Result<u64, Response> {
+        param(name)?.ok_or_else(|| Response::error(400, &format!("missing parameter {}", name)))
+    };
+
+    match path {
+        "/count" => {
+            let n = required("n")?;
+            let count = cache.sieve_up_to(n)?.count_primes_up_to(n);
+            Ok(Response::ok(format!("{{\"n\":{},\"count\":{}}}", n, count)))
+        }
+        "/isprime" => {
+            // Beyond the cached sieve, Miller-Rabin answers at once and for any n.
+            let n = required("n")?;
+            let prime = cache.is_prime_cached(n).unwrap_or_else(|| is_prime(n));
+            Ok(Response::ok(format!("{{\"n\":{},\"prime\":{}}}", n, prime)))
+        }
+        "/primes" => {
+            let (lo, hi) = (required("lo")?, required("hi")?);
+            let limit = match param("limit")? {
+                Some(0) => return Err(Response::error(400, "limit must be positive")),
+                Some(limit) => (limit as usize).min(MAX_LIMIT),
+                None => MAX_LIMIT,
+            };
+            let from = match param("after")? {
+                Some(after) => lo.max(after.saturating_add(1)),
+                None => lo,
+            };
+            let sieve = cache.sieve_up_to(hi)?;
+            let mut primes = sieve.primes();
+            primes.skip_to(from);
+            let mut primes = primes.take_while(|&p| p <= hi);
+            let page: Vec<u64> = primes.by_ref().take(limit).collect();
+            let next = match (primes.next(), page.last()) {
+                (Some(_), Some(last)) => last.to_string(),
+                _ => "null".to_string(),
+            };
+            let page: Vec<String> = page.iter().map(u64::to_string).collect();
+            Ok(Response::ok(format!(
+                "{{\"lo\":{},\"hi\":{},\"primes\":[{}],\"next\":{}}}",
+                lo,
+                hi,
+                page.join(","),
+                next
+            )))
+        }
+        _ => Err(Response::error(404, &format!("no such endpoint {}; expected /count, /primes or /isprime", path))),
+    }
+}
@@ -0,0 +1,50 @@
+/*
+ * C interface to the eratosthenes crate, built as a cdylib with the `ffi` feature.
+ *
+ * Every function that can fail returns ERAT_OK or a negative ERAT_ERR_* code and writes its
+ * outputs only on success. No function lets a Rust panic unwind into the caller; one is
+ * reported as ERAT_ERR_PANIC.
+ *
+ * Ownership: an array from erat_sieve belongs to the caller, who must release it with
+ * erat_free, passing the length it came with. It was not allocated with malloc; never pass it
+ * to free.
+ */
+
+#ifndef ERATOSTHENES_H
+#define ERATOSTHENES_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define ERAT_OK 0
+/* An output pointer was null. */
+#define ERAT_ERR_NULL (-1)
+/* The sieve or its result could not be allocated. */
+#define ERAT_ERR_ALLOC (-2)
+/* The library panicked; the panic was stopped at the boundary. */
+#define ERAT_ERR_PANIC (-3)
+
+/*
+ * The primes up to and including n, ascending, as a new array in *out of *len entries. An empty
+ * result sets *out to NULL and *len to 0. Release the array with erat_free.
+ */
+int erat_sieve(uint64_t n, uint64_t **out, size_t *len);
+
+/* Releases an array from erat_sieve; len must be the length it came with. NULL is ignored. */
+void erat_free(uint64_t *ptr, size_t len);
+
+/* The number of primes up to and including n, in *out_count, counted without storing them. */
+int erat_count(uint64_t n, uint64_t *out_count);
+
+/* 1 if n is prime and 0 if not, by deterministic Miller-Rabin; no sieve is built. */
+int erat_is_prime(uint64_t n);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ERATOSTHENES_H */
@@ -0,0 +1,66 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Exit status for a run stopped by Ctrl+C, including by a second Ctrl+C that cannot wait for a
+// summary.
+const EXIT_INTERRUPTED: i32 = crate::exit::Kind::Interrupted.code();
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Set by the first Ctrl+C. Sieves poll it and wind down; a second Ctrl+C exits at once.
+pub fn flag() -> &'static AtomicBool {
+    &INTERRUPTED
+}
+
+pub fn requested() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+// Only an atomic swap and _exit happen in the handler, both of which are async-signal-safe.
+#[cfg(unix)]
+pub fn install() {
+    use std::os::raw::c_int;
+
+    const SIGINT: c_int = 2;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+        fn _exit(status: c_int) -> !;
+    }
+
+    extern "C" fn on_sigint(_: c_int) {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            unsafe { _exit(EXIT_INTERRUPTED) }
+        }
+    }
+
+    unsafe {
+        signal(SIGINT, on_sigint);
+    }
+}
+
+#[cfg(windows)]
+pub fn install() {
+    const CTRL_C_EVENT: u32 = 0;
+
+    extern "system" {
+        fn SetConsoleCtrlHandler(handler: extern "system" fn(u32) -> i32, add: i32) -> i32;
+    }
+
+    // Windows runs console handlers on their own thread, so exiting from here is fine.
+    extern "system" fn on_ctrl(event: u32) -> i32 {
+        if event != CTRL_C_EVENT {
+            return 0;
+        }
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+        1
+    }
+
+    unsafe {
+        SetConsoleCtrlHandler(on_ctrl, 1);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn install() {}
@@ -0,0 +1,298 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::checked_backward5;
+use crate::error::SieveError;
+use crate::sieve::Sieve;
+
+/// Version of the layout written by [`Sieve::to_json`].
+pub const SIEVE_JSON_VERSION: u64 = 1;
+
+const WHEEL: u64 = 30;
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl Sieve {
+    /// The sieve as one JSON object, for tools in other languages:
+    ///
+    /// ```text
+    /// {"version":1,"n":100,"wheel":30,"bits":"..."}
+    /// ```
+    ///
+    /// `bits` is standard padded base64 of a bitset over the integers up to `n` that are coprime
+    /// to `wheel` (1, 7, 11, 13, 17, 19, 23, 29, 31, ...), least significant bit first within
+    /// each byte: bit `k` is set when the `k`-th of them is prime. 2, 3 and 5 are implied.
+    ///
+    /// # Panics
+    ///
+    /// If the sieve is over a range (see [`range`](Self::range)): the layout starts at 0.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        assert_eq!(self.lo(), 0, "only a sieve from 0 can be written as JSON");
+        let count = wheel_values_up_to(self.n());
+        let marks = self.marks();
+        let mut bits = vec![0u8; count.div_ceil(8)];
+        // Wheel index k + 1 holds the k-th value; index 1 is 1 itself, never prime.
+        for k in 1..count {
+            if !marks[k + 1] {
+                bits[k / 8] |= 1 << (k % 8);
+            }
+        }
+        format!(
+            "{{\"version\":{},\"n\":{},\"wheel\":{},\"bits\":\"{}\"}}",
+            SIEVE_JSON_VERSION,
+            self.n(),
+            WHEEL,
+            base64_encode(&bits)
+        )
+    }
+
+    /// Reads back what [`to_json`](Self::to_json) wrote. Other keys are ignored. The layout is
+    /// checked, but the primes are taken on trust; see
+    /// [`verify_against_next_prime_sequence`](Self::verify_against_next_prime_sequence).
+    pub fn from_json(s: &str) -> Result<Sieve, SieveError> {
+        let fields = parse_flat_object(s).map_err(invalid)?;
+        let field = |key: &str| fields.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+        let number = |key: &str| match field(key) {
+            Some(Value::Number(v)) => Ok(*v),
+            Some(_) => Err(invalid(format!("{} must be a number", key))),
+            None => Err(invalid(format!("missing {}", key))),
+        };
+
+        let version = number("version")?;
+        if version != SIEVE_JSON_VERSION {
+            return Err(invalid(format!("unsupported version {}", version)));
+        }
+        if number("wheel")? != WHEEL {
+            return Err(invalid(format!("only a wheel of {} is supported", WHEEL)));
+        }
+        let n = number("n")?;
+        let bits = match field("bits") {
+            Some(Value::String(bits)) => base64_decode(bits).ok_or_else(|| invalid("bits is not valid base64"))?,
+            Some(_) => return Err(invalid("bits must be a string")),
+            None => return Err(invalid("missing bits")),
+        };
+
+        let count = wheel_values_up_to(n);
+        if bits.len() != count.div_ceil(8) {
+            return Err(invalid(format!("{} bytes of bits for n = {}, expected {}", bits.len(), n, count.div_ceil(8))));
+        }
+        let bit = |k: usize| bits[k / 8] >> (k % 8) & 1 == 1;
+        if (count..bits.len() * 8).any(bit) || (count > 0 && bit(0)) {
+            return Err(invalid("bits set outside the range"));
+        }
+        let cardinality =
+            checked_backward5(n).and_then(|c| c.checked_add(1)).ok_or(SieveError::AllocationFailed { n })?;
+        let mut not_prime = Vec::new();
+        not_prime.try_reserve_exact(cardinality).map_err(|_| SieveError::AllocationFailed { n })?;
+        not_prime.resize(cardinality, false);
+        for k in 1..count {
+            not_prime[k + 1] = !bit(k);
+        }
+        Ok(Sieve::from_marks(n, not_prime))
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> SieveError {
+    SieveError::InvalidJson { reason: reason.into() }
+}
+
+// How many integers in 1..=n are coprime to 30.
+fn wheel_values_up_to(n: u64) -> usize {
+    const RESIDUES: [u64; 8] = [1, 7, 11, 13, 17, 19, 23, 29];
+    let count = n / WHEEL * 8 + RESIDUES.iter().filter(|&&r| r <= n % WHEEL).count() as u64;
+    usize::try_from(count).unwrap_or(usize::MAX)
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let word = chunk.iter().enumerate().fold(0u32, |word, (i, &b)| word | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(word >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    for (n, chunk) in text.chunks(4).enumerate() {
+        let last = n + 1 == text.len() / 4;
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return None;
+        }
+        let mut word = 0u32;
+        for &c in &chunk[..4 - padding] {
+            let digit = BASE64.iter().position(|&d| d == c)? as u32;
+            word = word << 6 | digit;
+        }
+        word <<= 6 * padding as u32;
+        out.extend_from_slice(&word.to_be_bytes()[1..4 - padding]);
+    }
+    Some(out)
+}
+
+enum Value {
+    Number(u64),
+    String(String),
+}
+
+// A JSON object whose values are all strings or non-negative integers, which is all the sieve
+// layout uses.
+fn parse_flat_object(s: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut chars = s.trim().chars().peekable();
+    if chars.next() != Some('{') {
+        return Err("expected an object".to_string());
+    }
+    let mut fields: Vec<(String, Value)> = Vec::new();
+    skip_space(&mut chars);
+    if chars.next_if_eq(&'}').is_none() {
+        loop {
+            skip_space(&mut chars);
+            if chars.next() != Some('"') {
+                return Err("expected a key".to_string());
+            }
+            let key = parse_string(&mut chars)?;
+            skip_space(&mut chars);
+            if chars.next() != Some(':') {
+                return Err(format!("expected ':' after {:?}", key));
+            }
+            skip_space(&mut chars);
+            let value = match chars.next() {
+                Some('"') => Value::String(parse_string(&mut chars)?),
+                Some(c) if c.is_ascii_digit() => {
+                    let mut digits = c.to_string();
+                    while let Some(d) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(d);
+                    }
+                    Value::Number(digits.parse().map_err(|_| format!("{} is out of range", key))?)
+                }
+                _ => return Err(format!("{} must be a string or a non-negative integer", key)),
+            };
+            if fields.iter().any(|(k, _)| *k == key) {
+                return Err(format!("duplicate key {:?}", key));
+            }
+            fields.push((key, value));
+            skip_space(&mut chars);
+            match chars.next() {
+                Some(',') => {}
+                Some('}') => break,
+                _ => return Err("expected ',' or '}'".to_string()),
+            }
+        }
+    }
+    if chars.next().is_some() {
+        return Err("unexpected data after the object".to_string());
+    }
+    Ok(fields)
+}
+
+fn skip_space(chars: &mut Peekable<Chars<'_>>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+// The rest of a string whose opening quote has been read.
+fn parse_string(chars: &mut impl Iterator<Item = char>) -> Result<String, String> {
+    let mut out = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(out),
+            '\\' => out.push(match chars.next().ok_or("unterminated string")? {
+                '"' => '"',
+                '\\' => '\\',
+                '/' => '/',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16).map_err(|_| "bad \\u escape")?;
+                    char::from_u32(code).ok_or("unsupported \\u escape")?
+                }
+                c => return Err(format!("bad escape \\{}", c)),
+            }),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn primes(sieve: &Sieve) -> Vec<u64> {
+        sieve.primes().collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        // Each bound on either side of a wheel turn and of a byte of bits, and one far enough to
+        // pad the base64.
+        for n in (0..=100).chain([239, 240, 241, 1_000_000]) {
+            let sieve = Sieve::new(n).unwrap();
+            let json = sieve.to_json();
+            let read = Sieve::from_json(&json).unwrap_or_else(|e| panic!("n = {}: {}", n, e));
+            assert_eq!(read.n(), n);
+            assert_eq!(primes(&read), primes(&sieve), "n = {}", n);
+            assert_eq!(read.to_json(), json, "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn layout() {
+        // 7 11 13 17 19 23 29 are bits 1 to 7 of the first byte; 31 and 37 bits 0 and 1 of the next.
+        assert_eq!(Sieve::new(40).unwrap().to_json(), r#"{"version":1,"n":40,"wheel":30,"bits":"/gM="}"#);
+        // Key order, spacing and keys it does not know are all left to the writer.
+        let spaced = r#" { "bits" : "/gM=", "wheel" : 30, "comment" : "by hand", "n" : 40, "version" : 1 } "#;
+        assert_eq!(primes(&Sieve::from_json(spaced).unwrap()), primes(&Sieve::new(40).unwrap()));
+    }
+
+    #[test]
+    fn rejects_a_missing_or_other_version() {
+        let cases = [
+            (r#"{"n":40,"wheel":30,"bits":"/gM="}"#, "missing version"),
+            (r#"{"version":2,"n":40,"wheel":30,"bits":"/gM="}"#, "unsupported version 2"),
+            (r#"{"version":0,"n":40,"wheel":30,"bits":"/gM="}"#, "unsupported version 0"),
+            (r#"{"version":"1","n":40,"wheel":30,"bits":"/gM="}"#, "version must be a number"),
+        ];
+        for (json, reason) in cases {
+            assert_eq!(Sieve::from_json(json).err(), Some(invalid(reason)), "{}", json);
+        }
+    }
+
+    #[test]
+    fn rejects_a_bad_layout() {
+        let cases = [
+            (r#"{"version":1,"n":40,"wheel":6,"bits":"/gM="}"#, "only a wheel of 30 is supported"),
+            (r#"{"version":1,"n":40,"wheel":30}"#, "missing bits"),
+            (r#"{"version":1,"n":40,"wheel":30,"bits":"/gM"}"#, "bits is not valid base64"),
+            (r#"{"version":1,"n":20,"wheel":30,"bits":"/gM="}"#, "2 bytes of bits for n = 20, expected 1"),
+            (r#"{"version":1,"n":100,"wheel":30,"bits":"/gM="}"#, "2 bytes of bits for n = 100, expected 4"),
+            (r#"{"version":1,"n":40,"wheel":30,"bits":"/gc="}"#, "bits set outside the range"),
+            (r#"{"version":1,"n":40,"wheel":30,"bits":"/wM="}"#, "bits set outside the range"),
+            (r#"{"version":1,"n":40,"n":40,"wheel":30,"bits":"/gM="}"#, "duplicate key \"n\""),
+            (r#"{"version":1,"n":-40,"wheel":30,"bits":"/gM="}"#, "n must be a string or a non-negative integer"),
+            (r#"{"version":1,"n":40,"wheel":30,"bits":"/gM="} x"#, "unexpected data after the object"),
+        ];
+        for (json, reason) in cases {
+            assert_eq!(Sieve::from_json(json).err(), Some(invalid(reason)), "{}", json);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "only a sieve from 0 can be written as JSON")]
+    fn a_range_cannot_be_written() {
+        let _ = Sieve::range(100, 200).unwrap().to_json();
+    }
+}
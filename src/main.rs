@@ -0,0 +1,1540 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use eratosthenes::{
+    decompress, default_threads, factor_tree, factorize, is_prime, nth_prime_upper_bound, nth_primes, plan_within,
+    prime_count_upper_bound, read_prime_file, segmented_sieve_range_each, sieve_each_threaded, sieve_segments_ordered,
+    ulam_bounds, ulam_value, verify_with_threads, write_prime_file, DeltaEncoder, DeltaReader, FactorMethod, FactorTree,
+    Plan, Sieve, SieveError, Segments, DEFAULT_SEGMENT_SIZE, VERIFY_LIMIT,
+};
+use eratosthenes::format::{DELTA_COUNT_OFFSET, PRIME_FILE_MAGIC};
+
+mod bench;
+mod bound;
+mod checkpoint;
+mod compress;
+mod exit;
+mod filter;
+#[cfg(feature = "http")]
+mod http;
+mod interrupt;
+mod merge;
+mod plan;
+mod range;
+mod serve;
+mod shard;
+mod stats;
+mod throughput;
+mod validate;
+
+use bound::parse_bound;
+use checkpoint::{hash_primes, Checkpoint};
+use compress::{Codec, Output};
+use exit::Kind;
+use filter::Filter;
+use plan::SievePlan;
+use shard::ShardWriter;
+use stats::RunStats;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    BinDelta,
+}
+
+impl OutputFormat {
+    fn name(format: Option<OutputFormat>) -> &'static str {
+        match format {
+            None => "count",
+            Some(OutputFormat::Text) => "text",
+            Some(OutputFormat::BinDelta) => "bin-delta",
+        }
+    }
+}
+
+struct Options {
+    format: Option<OutputFormat>,
+    binary: bool,
+    output: Option<PathBuf>,
+    segmented: bool,
+    checkpoint: Option<PathBuf>,
+    checkpoint_interval: Duration,
+    resume: Option<PathBuf>,
+    batch: bool,
+    verify: bool,
+    threads: usize,
+    memory_limit: Option<usize>,
+    stats: bool,
+    dry_run: bool,
+    throughput: bool,
+    limit_count: Option<u64>,
+    filter: Option<Filter>,
+    append: bool,
+    shard_size: Option<u64>,
+    compress: Option<Codec>,
+    compress_level: Option<i32>,
+    bounds: Vec<String>,
+}
+
+impl Options {
+    // Runs that must go segment by segment whatever the memory limit.
+    fn needs_segments(&self) -> bool {
+        self.segmented || self.append || self.checkpoint.is_some() || self.resume.is_some()
+    }
+
+    // Decides how to sieve up to n: the strategy, given the memory limit and the bytes the
+    // output will accumulate, and the threads.
+    fn resolve(&self, n: u64) -> SievePlan {
+        let output_bytes = match self.binary {
+            true => prime_count_upper_bound(n).saturating_mul(8),
+            false => buffered_output_bytes(n, self.format, self.shard_size),
+        };
+        let plan = match self.memory_limit {
+            Some(limit) => plan_within(n, limit, output_bytes).unwrap_or_else(|e| fail_sieve(e)),
+            None => Plan::monolithic(n, output_bytes),
+        };
+        // A resumed run keeps the segment size it was started with.
+        let saved = self.resume.as_ref().map(|path| Checkpoint::load(path).unwrap_or_else(|e| fail(e)));
+        let plan = match (plan, saved) {
+            (_, Some(saved)) if saved.segment_size > 0 => Plan::segmented(n, saved.segment_size, output_bytes),
+            (Plan::Monolithic { .. }, _) if self.needs_segments() => Plan::segmented(n, DEFAULT_SEGMENT_SIZE, output_bytes),
+            (plan, _) => plan,
+        };
+        let threads = match plan {
+            Plan::Segmented { .. } => 1,
+            Plan::Monolithic { .. } if self.threads == 0 => default_threads(),
+            Plan::Monolithic { .. } => self.threads,
+        };
+        let output = if self.binary { "binary" } else { OutputFormat::name(self.format) };
+        SievePlan { n, threads, plan, output, output_bytes, memory_limit: self.memory_limit }
+    }
+
+    fn report(&self, plan: &SievePlan, primes: u64, started: Instant) {
+        let rates = throughput::stop();
+        if !self.stats {
+            return;
+        }
+        let stats = RunStats {
+            n: plan.n,
+            threads: plan.threads,
+            plan: plan.plan,
+            memory_limit: plan.memory_limit,
+            primes,
+            elapsed: started.elapsed(),
+            rates,
+        };
+        eprintln!("{}", stats.to_json());
+    }
+
+    // With --dry-run, prints the plan instead of running it: as JSON with --stats. Otherwise
+    // the run is about to start, and so is the --throughput status line.
+    fn dry_run(&self, plan: &SievePlan) -> bool {
+        if self.dry_run {
+            println!("{}", if self.stats { plan.to_json() } else { plan.describe() });
+        } else if self.throughput {
+            throughput::start(plan.n);
+        }
+        self.dry_run
+    }
+}
+
+fn fail(msg: impl std::fmt::Display) -> ! {
+    exit::report(Kind::Failure, &msg.to_string(), &[]);
+}
+
+// Running out of memory, or past --memory-limit, is a resource failure with its own status.
+fn fail_sieve(e: SieveError) -> ! {
+    let context = match e {
+        SieveError::AllocationFailed { n } => vec![("n", n.to_string())],
+        SieveError::MemoryLimitExceeded { n, required, limit } => {
+            vec![("n", n.to_string()), ("required_bytes", required.to_string()), ("limit_bytes", limit.to_string())]
+        }
+        _ => fail(e),
+    };
+    exit::report(Kind::Resource, &e.to_string(), &context);
+}
+
+fn report_write_error(e: io::Error) {
+    // A closed pipe (e.g. `| head`) just means the reader has seen enough.
+    if e.kind() != io::ErrorKind::BrokenPipe {
+        fail(e);
+    }
+}
+
+// How far a run got, for the summary printed when it is interrupted. With a `limit` it also
+// ends the run at that many primes.
+#[derive(Default)]
+struct Progress {
+    count: u64,
+    last_prime: u64,
+    limit: Option<u64>,
+}
+
+impl Progress {
+    fn limited(limit: Option<u64>) -> Self {
+        Progress { limit, ..Progress::default() }
+    }
+
+    // Breaking stops the sieve where it is, abandoning the marking still queued, just as a
+    // cancellation would.
+    fn record(&mut self, p: u64) -> ControlFlow<()> {
+        self.record_run(1, Some(p))
+    }
+
+    // Records `count` primes at once, the last of them `last` if there were any.
+    fn record_run(&mut self, count: u64, last: Option<u64>) -> ControlFlow<()> {
+        self.count += count;
+        self.last_prime = last.unwrap_or(self.last_prime);
+        throughput::record(self.count, self.last_prime);
+        if interrupt::requested() || self.limit.is_some_and(|limit| self.count >= limit) {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+}
+
+// Reports how much of the range was covered and exits; output must already be flushed.
+fn exit_interrupted(n: u64, count: u64, last_prime: u64) -> ! {
+    throughput::stop();
+    let message = format!(
+        "interrupted: {} primes emitted, largest {}, range covered up to {} of {} ({:.1}%)",
+        count,
+        last_prime,
+        last_prime,
+        n,
+        last_prime as f64 * 100.0 / n.max(1) as f64
+    );
+    let context = [("n", n.to_string()), ("primes", count.to_string()), ("last_prime", last_prime.to_string())];
+    exit::report(Kind::Interrupted, &message, &context);
+}
+
+fn open_output(options: &Options) -> Output {
+    let writer: Box<dyn Write> = match &options.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(io::BufWriter::new(file)),
+            Err(e) => fail(format_args!("{}: {}", path.display(), e)),
+        },
+        None => stdout_writer(),
+    };
+    match options.compress {
+        None => Output::plain(writer),
+        Some(codec) => {
+            let level = options.compress_level.unwrap_or(codec.default_level());
+            Output::compressed(writer, codec, level).unwrap_or_else(|e| fail(e))
+        }
+    }
+}
+
+// Buffer for primes written to a pipe or file, where nobody watches them arrive.
+const PIPE_BUFFER_BYTES: usize = 1 << 16;
+
+// Line-buffered on a terminal so primes show up as they are found; block-buffered otherwise.
+fn stdout_writer() -> Box<dyn Write> {
+    let stdout = io::stdout();
+    if stdout.is_terminal() {
+        Box::new(io::LineWriter::new(stdout.lock()))
+    } else {
+        Box::new(io::BufWriter::with_capacity(PIPE_BUFFER_BYTES, stdout.lock()))
+    }
+}
+
+// Feeds the primes up to n to emit as the plan says. Segmented plans only notice an interrupt
+// between segments, so emit should also check for one.
+fn stream_primes<F: FnMut(u64) -> ControlFlow<()>>(
+    n: u64,
+    threads: usize,
+    plan: &Plan,
+    mut emit: F,
+) -> Result<(), SieveError> {
+    match plan.segment_size() {
+        None => sieve_each_threaded(n, threads, interrupt::flag(), emit),
+        Some(segment_size) => {
+            let mut segments = Segments::new(n, segment_size)?;
+            for k in 0..segments.len() {
+                if interrupt::requested() || segments.sieve_segment(k, &mut emit).is_break() {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+// stream_primes with only the primes that pass --filter, if there is one, reaching emit.
+fn stream_selected<F: FnMut(u64) -> ControlFlow<()>>(
+    n: u64,
+    threads: usize,
+    plan: &Plan,
+    filter: Option<&Filter>,
+    emit: F,
+) -> Result<(), SieveError> {
+    let Some(filter) = filter else {
+        return stream_primes(n, threads, plan, emit);
+    };
+    let mut filtered = filter.wrap(emit);
+    stream_primes(n, threads, plan, |p| filtered.push(p))?;
+    filtered.finish();
+    Ok(())
+}
+
+fn print_primes(
+    n: u64,
+    threads: usize,
+    plan: &Plan,
+    format: OutputFormat,
+    mut out: Output,
+    options: &Options,
+) -> Result<u64, SieveError> {
+    let filter = options.filter.as_ref();
+    let mut progress = Progress::limited(options.limit_count);
+    // With no filter or count limit to apply prime by prime, a monolithic run formats its text
+    // on the marking threads instead, segment by segment, which needs far less than the plan's
+    // memory.
+    let parallel_text =
+        filter.is_none() && options.limit_count.is_none() && threads > 1 && matches!(plan, Plan::Monolithic { .. });
+    let written = match format {
+        OutputFormat::Text if parallel_text => {
+            let mut write_error = None;
+            sieve_segments_ordered(n, DEFAULT_SEGMENT_SIZE, threads, interrupt::flag(), format_text, |(text, count, last)| {
+                match out.write_all(&text) {
+                    Ok(()) => progress.record_run(count, last),
+                    Err(e) => {
+                        write_error = Some(e);
+                        ControlFlow::Break(())
+                    }
+                }
+            })?;
+            write_error.map_or_else(|| out.finish(), Err)
+        }
+        OutputFormat::Text => {
+            let mut write_error = None;
+            stream_selected(n, threads, plan, filter, |p| match writeln!(out, "{}", p) {
+                Ok(()) => progress.record(p),
+                Err(e) => {
+                    write_error = Some(e);
+                    ControlFlow::Break(())
+                }
+            })?;
+            // Finishing on an interrupt too leaves a compressed stream complete, if short.
+            write_error.map_or_else(|| out.finish(), Err)
+        }
+        OutputFormat::BinDelta => {
+            // An interrupted run still gets a valid stream, holding the primes found so far.
+            let mut encoder = DeltaEncoder::new(0);
+            stream_selected(n, threads, plan, filter, |p| {
+                encoder.push(p);
+                progress.record(p)
+            })?;
+            encoder.finish(&mut out).and_then(|()| out.finish())
+        }
+    };
+    if let Err(e) = written {
+        report_write_error(e);
+        return Ok(progress.count);
+    }
+    if interrupt::requested() {
+        exit_interrupted(n, progress.count, progress.last_prime);
+    }
+    Ok(progress.count)
+}
+
+// Like print_primes, into shards. An interrupted run still closes the shard it was writing and
+// lists what it wrote in the manifest.
+fn print_shards(n: u64, threads: usize, plan: &Plan, mut shards: ShardWriter, options: &Options) -> Result<u64, SieveError> {
+    let mut progress = Progress::limited(options.limit_count);
+    let mut write_error = None;
+    stream_selected(n, threads, plan, options.filter.as_ref(), |p| match shards.push(p) {
+        Ok(()) => progress.record(p),
+        Err(e) => {
+            write_error = Some(e);
+            ControlFlow::Break(())
+        }
+    })?;
+    if let Err(e) = write_error.map_or_else(|| shards.finish(), Err) {
+        fail(e);
+    }
+    if interrupt::requested() {
+        exit_interrupted(n, progress.count, progress.last_prime);
+    }
+    Ok(progress.count)
+}
+
+// Like print_primes, as a prime file. Its header holds the count, so the primes are kept until
+// the end; an interrupted run still writes the ones it found.
+fn print_prime_file(n: u64, threads: usize, plan: &Plan, mut out: Output, options: &Options) -> Result<u64, SieveError> {
+    let mut progress = Progress::limited(options.limit_count);
+    let mut primes = Vec::new();
+    stream_selected(n, threads, plan, options.filter.as_ref(), |p| {
+        primes.push(p);
+        progress.record(p)
+    })?;
+    if let Err(e) = write_prime_file(&mut out, n, &primes).and_then(|()| out.finish()) {
+        report_write_error(e);
+        return Ok(progress.count);
+    }
+    if interrupt::requested() {
+        exit_interrupted(n, progress.count, progress.last_prime);
+    }
+    Ok(progress.count)
+}
+
+// A segment's primes as lines of text, with how many there were and the last, for Progress.
+fn format_text(primes: &[u64]) -> (Vec<u8>, u64, Option<u64>) {
+    let mut text = Vec::with_capacity(primes.len() * 12);
+    for p in primes {
+        // Writing to a Vec cannot fail.
+        let _ = writeln!(text, "{}", p);
+    }
+    (text, primes.len() as u64, primes.last().copied())
+}
+
+// Bytes the output keeps in memory until the end: bin-delta buffers a gap of at most 2 bytes
+// per prime (gaps below 2^64 stay under 2^14), for one shard at a time when sharded; text and
+// counts stream.
+fn buffered_output_bytes(n: u64, format: Option<OutputFormat>, shard_size: Option<u64>) -> usize {
+    match format {
+        Some(OutputFormat::BinDelta) => {
+            let primes = prime_count_upper_bound(n);
+            let primes = shard_size.map_or(primes, |size| primes.min(usize::try_from(size).unwrap_or(usize::MAX)));
+            primes.saturating_mul(2)
+        }
+        _ => 0,
+    }
+}
+
+fn run_segmented(n: u64, segment_size: usize, options: &Options) -> Result<u64, SieveError> {
+    let format_name = OutputFormat::name(options.format);
+    let checkpointing = options.checkpoint.is_some() || options.resume.is_some();
+    if checkpointing && options.format == Some(OutputFormat::BinDelta) && options.shard_size.is_none() {
+        fail("bin-delta output cannot be checkpointed, since its header holds the final count; use text or shards");
+    }
+    if checkpointing && options.format.is_some() && options.output.is_none() {
+        fail("--checkpoint and --resume need --output to append printed primes to");
+    }
+
+    // A resumed run keeps the segment size it was started with.
+    let saved = options.resume.as_ref().map(|path| (path, Checkpoint::load(path).unwrap_or_else(|e| fail(e))));
+    let segment_size = saved.as_ref().map_or(segment_size, |(_, saved)| saved.segment_size);
+    if segment_size == 0 {
+        fail("checkpoint is corrupt: segment_size=0");
+    }
+
+    let mut segments = Segments::new(n, segment_size)?;
+    let mut state = Checkpoint {
+        n,
+        segment_size: segments.segment_size(),
+        format: format_name.to_string(),
+        base_primes: segments.base_primes().len(),
+        base_primes_hash: hash_primes(segments.base_primes()),
+        next_segment: 0,
+        count: 0,
+        last_prime: 0,
+        output_offset: 0,
+        shard_size: options.shard_size.unwrap_or(0),
+        shards: Vec::new(),
+    };
+
+    let mut file = None;
+    let mut shards = None;
+    if let Some((path, saved)) = saved {
+        let mismatch = |what: &str| fail(format_args!("checkpoint {} does not match this run: {}", path.display(), what));
+        if saved.n != state.n {
+            mismatch(&format!("it is for n = {}", saved.n));
+        }
+        if saved.format != state.format {
+            mismatch(&format!("it is for --format {}", saved.format));
+        }
+        if saved.base_primes != state.base_primes || saved.base_primes_hash != state.base_primes_hash {
+            mismatch("different base primes");
+        }
+        if saved.next_segment > segments.len() {
+            mismatch("segment index out of range");
+        }
+        if saved.shard_size != state.shard_size {
+            mismatch(&match saved.shard_size {
+                0 => "it is not sharded".to_string(),
+                size => format!("it is for --shard-size {}", size),
+            });
+        }
+        if let (Some(format), Some(prefix), Some(shard_size)) = (options.format, &options.output, options.shard_size) {
+            let resumed = ShardWriter::resume(prefix, format, shard_size, saved.shards.clone(), saved.output_offset);
+            shards = Some(resumed.unwrap_or_else(|e| fail(e)));
+        } else if let (Some(_), Some(out)) = (options.format, &options.output) {
+            // Drop anything written after the checkpoint, then carry on from there.
+            let mut f = OpenOptions::new().write(true).open(out).unwrap_or_else(|e| fail(format_args!("{}: {}", out.display(), e)));
+            let len = f.metadata().map(|m| m.len()).unwrap_or(0);
+            if len < saved.output_offset {
+                fail(format_args!("{} is shorter than checkpoint {} recorded", out.display(), path.display()));
+            }
+            f.set_len(saved.output_offset).and_then(|_| f.seek(SeekFrom::End(0))).unwrap_or_else(|e| fail(e));
+            file = Some(f);
+        }
+        state = saved;
+    } else if let (Some(format), Some(prefix), Some(shard_size)) = (options.format, &options.output, options.shard_size) {
+        shards = Some(ShardWriter::new(prefix, format, shard_size));
+    } else if let (Some(_), Some(out)) = (options.format, &options.output) {
+        file = Some(File::create(out).unwrap_or_else(|e| fail(format_args!("{}: {}", out.display(), e))));
+    }
+
+    // Shards write themselves; otherwise the primes go to the output file or stdout.
+    let mut out: Option<Box<dyn Write>> = match (options.format, file) {
+        (None, _) => None,
+        (Some(_), Some(f)) => Some(Box::new(io::BufWriter::new(f))),
+        (Some(_), None) if shards.is_some() => None,
+        (Some(_), None) => Some(Box::new(io::BufWriter::new(io::stdout().lock()))),
+    };
+    let mut encoder = match options.format {
+        Some(OutputFormat::BinDelta) if shards.is_none() => Some(DeltaEncoder::new(0)),
+        _ => None,
+    };
+
+    let mut last_checkpoint = Instant::now();
+    let mut write_error = None;
+    let mut interrupted = false;
+    for k in state.next_segment..segments.len() {
+        // Segments are only abandoned between segments, so a checkpoint written on the way out
+        // always describes whole segments.
+        if interrupt::requested() {
+            interrupted = true;
+            break;
+        }
+        let flow = segments.sieve_segment(k, &mut |p| {
+            state.count += 1;
+            state.last_prime = p;
+            throughput::record(state.count, p);
+            if let Some(shards) = &mut shards {
+                if let Err(e) = shards.push(p) {
+                    write_error = Some(e);
+                    return ControlFlow::Break(());
+                }
+            } else if let Some(encoder) = &mut encoder {
+                encoder.push(p);
+            } else if let Some(out) = &mut out {
+                match writeln!(out, "{}", p) {
+                    Ok(()) => state.output_offset += count_digits(p) + 1,
+                    Err(e) => {
+                        write_error = Some(e);
+                        return ControlFlow::Break(());
+                    }
+                }
+            }
+            ControlFlow::Continue(())
+        });
+        if flow.is_break() {
+            break;
+        }
+        state.next_segment = k + 1;
+
+        if let Some(path) = &options.checkpoint {
+            let due = last_checkpoint.elapsed() >= options.checkpoint_interval || interrupt::requested();
+            if due || state.next_segment == segments.len() {
+                if let Some(out) = &mut out {
+                    if let Err(e) = out.flush() {
+                        write_error = Some(e);
+                        break;
+                    }
+                }
+                if let Some(shards) = &mut shards {
+                    if let Err(e) = shards.sync() {
+                        write_error = Some(e);
+                        break;
+                    }
+                    state.output_offset = shards.offset();
+                    state.shards = shards.shards().to_vec();
+                } else if let Some(f) = &options.output {
+                    File::open(f).and_then(|f| f.sync_data()).unwrap_or_else(|e| fail(e));
+                }
+                state.save(path).unwrap_or_else(|e| fail(format_args!("{}: {}", path.display(), e)));
+                last_checkpoint = Instant::now();
+            }
+        }
+    }
+
+    let written = match (write_error, encoder, &mut out) {
+        (Some(e), _, _) => Err(e),
+        (None, Some(encoder), Some(out)) => encoder.finish(out),
+        (None, None, Some(out)) => out.flush(),
+        (None, _, None) => Ok(()),
+    };
+    let written = written.and_then(|()| shards.map_or(Ok(()), ShardWriter::finish));
+    if let Err(e) = written {
+        report_write_error(e);
+        return Ok(state.count);
+    }
+    if interrupted {
+        exit_interrupted(n, state.count, state.last_prime);
+    }
+
+    if options.format.is_none() {
+        throughput::stop();
+        eprintln!("Following is the count of prime numbers smaller than or equal to {}:", n);
+        println!("{}", state.count);
+    }
+    Ok(state.count)
+}
+
+fn count_digits(p: u64) -> u64 {
+    p.checked_ilog10().map_or(1, |d| d as u64 + 1)
+}
+
+// Answers every limit from a single pass up to the largest, printing `n count` lines in input order.
+fn count_batch(limits: &[u64], options: &Options) -> Result<(), SieveError> {
+    let started = Instant::now();
+    let mut order: Vec<usize> = (0..limits.len()).collect();
+    order.sort_by_key(|&i| limits[i]);
+    let max = order.last().map_or(0, |&i| limits[i]);
+    let plan = options.resolve(max);
+    if options.dry_run(&plan) {
+        return Ok(());
+    }
+
+    let mut counts = vec![0u64; limits.len()];
+    let mut pending = order.iter().peekable();
+    let mut progress = Progress::default();
+    stream_primes(max, plan.threads, &plan.plan, |p| {
+        while let Some(&&i) = pending.peek() {
+            if limits[i] >= p {
+                break;
+            }
+            counts[i] = progress.count;
+            pending.next();
+        }
+        progress.record(p)
+    })?;
+    if interrupt::requested() {
+        exit_interrupted(max, progress.count, progress.last_prime);
+    }
+    throughput::stop();
+    for &i in pending {
+        counts[i] = progress.count;
+    }
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let written = limits
+        .iter()
+        .zip(&counts)
+        .try_for_each(|(n, count)| writeln!(out, "{} {}", n, count))
+        .and_then(|_| out.flush());
+    if let Err(e) = written {
+        report_write_error(e);
+    }
+    options.report(&plan, progress.count, started);
+    Ok(())
+}
+
+// Reads the bound from stdin. Only when someone is typing it does it prompt (on stderr) and ask
+// again after a bad answer.
+fn prompt_bound() -> u64 {
+    let interactive = io::stdin().is_terminal();
+    loop {
+        if interactive {
+            eprint!("Count primes up to number: ");
+        }
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) => fail("no bound given"),
+            Ok(_) => {}
+            Err(e) => fail(e),
+        }
+        match parse_bound(&input) {
+            Ok(n) => return n,
+            Err(e) if interactive => eprintln!("{}", e),
+            Err(e) => fail(e),
+        }
+    }
+}
+
+fn parse_limit(arg: &str) -> u64 {
+    parse_bound(arg).unwrap_or_else(|e| usage_error(&e))
+}
+
+fn decode(path: Option<String>) {
+    let input: Box<dyn io::Read> = match &path {
+        Some(path) => match File::open(path) {
+            Ok(file) => Box::new(file),
+            Err(e) => fail(format_args!("{}: {}", path, e)),
+        },
+        None => Box::new(io::stdin().lock()),
+    };
+    let mut input = io::BufReader::new(decompress(input).unwrap_or_else(|e| fail(e)));
+    // Prime files are read whole, having been checked against their header.
+    let primes: Box<dyn Iterator<Item = io::Result<u64>>> = match input.fill_buf() {
+        Ok(head) if head.starts_with(&PRIME_FILE_MAGIC) => {
+            let (_, primes) = read_prime_file(input).unwrap_or_else(|e| fail(e));
+            Box::new(primes.into_iter().map(Ok))
+        }
+        _ => Box::new(DeltaReader::new(input).unwrap_or_else(|e| fail(e))),
+    };
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    for p in primes {
+        let written = match p {
+            Ok(p) => writeln!(out, "{}", p),
+            Err(e) => {
+                let _ = out.flush();
+                fail(e);
+            }
+        };
+        if let Err(e) = written {
+            report_write_error(e);
+            return;
+        }
+    }
+    if let Err(e) = out.flush() {
+        report_write_error(e);
+    }
+}
+
+// How much of the end of a text file --append reads to find and check its last primes.
+const APPEND_TAIL_BYTES: u64 = 4096;
+
+// Extends the prime list in path up to n, sieving only (last, n]. Returns the primes appended.
+fn append_primes(n: u64, format: OutputFormat, path: &Path) -> u64 {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .unwrap_or_else(|e| fail(format_args!("{}: {}", path.display(), e)));
+    let refuse = |e: io::Error| -> ! { fail(format_args!("{}: refusing to append: {}", path.display(), e)) };
+
+    let mut progress = Progress::default();
+    let (last, written) = match format {
+        OutputFormat::Text => {
+            let last = last_listed_prime(&mut file).unwrap_or_else(|e| refuse(e));
+            let mut out = io::BufWriter::new(&file);
+            let mut write_error = None;
+            segmented_sieve_range_each(last + 1, n, |p| match writeln!(out, "{}", p) {
+                Ok(()) => progress.record(p),
+                Err(e) => {
+                    write_error = Some(e);
+                    ControlFlow::Break(())
+                }
+            })
+            .unwrap_or_else(|e| fail_sieve(e));
+            (last, write_error.map_or_else(|| out.flush(), Err))
+        }
+        OutputFormat::BinDelta => {
+            let existing = if file.metadata().map(|m| m.len()).unwrap_or(0) == 0 {
+                None
+            } else {
+                Some(delta_tail(&file).unwrap_or_else(|e| refuse(e)))
+            };
+            let last = existing.map_or(0, |(_, last)| last);
+            let mut encoder = DeltaEncoder::new(last);
+            segmented_sieve_range_each(last + 1, n, |p| {
+                encoder.push(p);
+                progress.record(p)
+            })
+            .unwrap_or_else(|e| fail_sieve(e));
+            let written = match existing {
+                None => encoder.finish(&file),
+                Some((count, _)) => append_delta(&file, count, &encoder),
+            };
+            (last, written)
+        }
+    };
+    if let Err(e) = written {
+        fail(format_args!("{}: {}", path.display(), e));
+    }
+    if interrupt::requested() {
+        exit_interrupted(n, progress.count, progress.last_prime);
+    }
+    if last >= n {
+        eprintln!("{} already lists the primes up to {}; nothing to append", path.display(), n);
+    }
+    progress.count
+}
+
+// The last prime in a text prime list (0 if empty), leaving the file positioned at its end.
+// Checks that the tail is whole lines of strictly increasing numbers ending in a prime.
+fn last_listed_prime(file: &mut File) -> io::Result<u64> {
+    let corrupt = |what: String| io::Error::new(io::ErrorKind::InvalidData, what);
+    let len = file.seek(SeekFrom::End(0))?;
+    if len == 0 {
+        return Ok(0);
+    }
+    let start = len.saturating_sub(APPEND_TAIL_BYTES);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = String::new();
+    file.read_to_string(&mut tail).map_err(|_| corrupt("the file is not text".to_string()))?;
+    let Some(body) = tail.strip_suffix('\n') else {
+        return Err(corrupt("the last line is incomplete".to_string()));
+    };
+    let mut lines = body.split('\n');
+    if start > 0 {
+        // The first line of the tail may have been cut.
+        lines.next();
+    }
+
+    let mut last: Option<u64> = None;
+    for line in lines {
+        let v: u64 = line.trim().parse().map_err(|_| corrupt(format!("{:?} is not a number", line)))?;
+        if let Some(previous) = last.filter(|&previous| v <= previous) {
+            return Err(corrupt(format!("the list is not sorted: {} follows {}", v, previous)));
+        }
+        last = Some(v);
+    }
+    let last = last.ok_or_else(|| corrupt("no complete line at the end".to_string()))?;
+    if !is_prime(last) {
+        return Err(corrupt(format!("the last value {} is not prime", last)));
+    }
+    Ok(last)
+}
+
+// The prime count and last prime of a bin-delta file, decoding it all: the gaps are checked to
+// be increasing on the way, and nothing may follow the last one.
+fn delta_tail(file: &File) -> io::Result<(u64, u64)> {
+    let mut reader = DeltaReader::new(io::BufReader::new(file))?;
+    for p in &mut reader {
+        p?;
+    }
+    let (count, last) = (reader.prime_count(), reader.last_prime());
+    if reader.into_inner().read(&mut [0; 1])? != 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected data after the last prime"));
+    }
+    Ok((count, last))
+}
+
+// Appends the encoder's gaps to a bin-delta file holding count primes, then updates the count.
+// The count is the commit point: a crash before it leaves trailing bytes that are refused later,
+// never a header claiming primes that are not there.
+fn append_delta(mut file: &File, count: u64, encoder: &DeltaEncoder) -> io::Result<()> {
+    file.seek(SeekFrom::End(0))?;
+    file.write_all(encoder.gaps())?;
+    file.sync_data()?;
+    file.seek(SeekFrom::Start(DELTA_COUNT_OFFSET))?;
+    file.write_all(&(count + encoder.prime_count()).to_le_bytes())?;
+    file.sync_data()
+}
+
+// `eratosthenes nth [--count-from 0|1] [--stats] K...`: the K-th prime for each K, from one pass.
+fn nth(args: impl Iterator<Item = String>) {
+    let started = Instant::now();
+    let mut count_from = 1;
+    let mut stats = false;
+    let mut ks = Vec::new();
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--count-from" => {
+                count_from = match args.next().as_deref() {
+                    Some("0") => 0,
+                    Some("1") => 1,
+                    _ => usage_error("--count-from takes 0 or 1"),
+                }
+            }
+            "--stats" => stats = true,
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option: {}", arg)),
+            _ => ks.push(parse_limit(&arg)),
+        }
+    }
+    if ks.is_empty() {
+        usage_error("nth needs at least one K");
+    }
+
+    // The library counts from 1.
+    let indices: Vec<u64> = ks
+        .iter()
+        .map(|&k| match (count_from, k.checked_add(1 - count_from)) {
+            (1, Some(0)) => usage_error("K counts from 1 (the 1st prime is 2); use --count-from 0 for 0-based K"),
+            (_, Some(index)) => index,
+            (_, None) => fail(format_args!("K = {} is past the last u64 prime", k)),
+        })
+        .collect();
+    let Some(primes) = nth_primes(&indices) else {
+        fail("K is past the last u64 prime");
+    };
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let written = if ks.len() == 1 {
+        writeln!(out, "{}", primes[0])
+    } else {
+        ks.iter().zip(&primes).try_for_each(|(k, p)| writeln!(out, "{} {}", k, p))
+    };
+    if let Err(e) = written.and_then(|_| out.flush()) {
+        report_write_error(e);
+    }
+
+    if stats {
+        let largest = indices.iter().copied().max().unwrap_or(0);
+        let bound = nth_prime_upper_bound(largest).unwrap_or(0);
+        let plan = Plan::segmented(bound, DEFAULT_SEGMENT_SIZE, 0);
+        let stats = RunStats {
+            n: bound,
+            threads: 1,
+            plan,
+            memory_limit: None,
+            primes: largest,
+            elapsed: started.elapsed(),
+            rates: None,
+        };
+        eprintln!("{}", stats.to_json());
+    }
+}
+
+// `eratosthenes factor [--tree | --json] [N...]`: each N's prime factors in GNU `factor` style,
+// reading whitespace-separated numbers from stdin when none are given. A bad token is reported
+// and skipped. --tree draws how the factors were split off and by which method; --json writes a
+// line per N: {"n":N,"factors":[{"p":P,"e":E},...],"method":"trial"|"rho"}, where "rho" means
+// Pollard's rho was needed somewhere.
+fn factor(args: impl Iterator<Item = String>) {
+    let mut style = FactorStyle::Flat;
+    let mut numbers = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--tree" | "--json" if style != FactorStyle::Flat => usage_error("--tree and --json are exclusive"),
+            "--tree" => style = FactorStyle::Tree,
+            "--json" => style = FactorStyle::Json,
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option: {}", arg)),
+            _ => numbers.push(arg),
+        }
+    }
+
+    let mut out = io::stdout().lock();
+    let mut all_valid = true;
+    let mut factor_one = |token: &str| {
+        let Ok(n) = token.parse::<u64>() else {
+            eprintln!("factor: '{}' is not a valid positive integer", token);
+            all_valid = false;
+            return;
+        };
+        let written = match style {
+            FactorStyle::Flat => write!(out, "{}:", n)
+                .and_then(|_| factorize(n).iter().try_for_each(|p| write!(out, " {}", p)))
+                .and_then(|_| writeln!(out)),
+            FactorStyle::Tree => write_factor_tree(&mut out, &factor_tree(n), ""),
+            FactorStyle::Json => writeln!(out, "{}", factor_json(&factor_tree(n))),
+        };
+        if let Err(e) = written {
+            report_write_error(e);
+            std::process::exit(if all_valid { 0 } else { Kind::Failure.code() });
+        }
+    };
+
+    if numbers.is_empty() {
+        for line in io::stdin().lock().lines() {
+            let line = line.unwrap_or_else(|e| fail(e));
+            line.split_whitespace().for_each(&mut factor_one);
+        }
+    } else {
+        numbers.iter().for_each(|arg| factor_one(arg));
+    }
+    if !all_valid {
+        std::process::exit(Kind::Failure.code());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FactorStyle {
+    Flat,
+    Tree,
+    Json,
+}
+
+// The node's number, with the method that split it if it was split, then its two factors
+// indented beneath it.
+fn write_factor_tree(out: &mut impl Write, tree: &FactorTree, indent: &str) -> io::Result<()> {
+    let FactorTree::Split { n, method, left, right } = tree else {
+        return writeln!(out, "{}", tree.n());
+    };
+    writeln!(out, "{} ({})", n, method.name())?;
+    write!(out, "{}├── ", indent)?;
+    write_factor_tree(out, left, &format!("{}│   ", indent))?;
+    write!(out, "{}└── ", indent)?;
+    write_factor_tree(out, right, &format!("{}    ", indent))
+}
+
+fn factor_json(tree: &FactorTree) -> String {
+    let mut exponents: Vec<(u64, u32)> = Vec::new();
+    let mut primes = tree.primes();
+    primes.sort_unstable();
+    for p in primes {
+        match exponents.last_mut() {
+            Some((q, e)) if *q == p => *e += 1,
+            _ => exponents.push((p, 1)),
+        }
+    }
+    let factors: Vec<String> = exponents.iter().map(|(p, e)| format!("{{\"p\":{},\"e\":{}}}", p, e)).collect();
+    let method = if tree.used_rho() { FactorMethod::PollardRho } else { FactorMethod::TrialDivision };
+    format!("{{\"n\":{},\"factors\":[{}],\"method\":\"{}\"}}", tree.n(), factors.join(","), method.name())
+}
+
+// `eratosthenes gaps [--min-gap G] N`: the record prime gaps up to N (or every gap of at least G) as
+// `gap start end` lines, then the largest. Runs segmented, so memory stays bounded for any N.
+fn gaps(args: impl Iterator<Item = String>) {
+    let mut min_gap = None;
+    let mut n = None;
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--min-gap" => match args.next().map(|g| parse_bound(&g)) {
+                Some(Ok(g)) => min_gap = Some(g),
+                Some(Err(e)) => usage_error(&format!("--min-gap: {}", e)),
+                None => usage_error("--min-gap needs a value"),
+            },
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option: {}", arg)),
+            _ if n.is_none() => n = Some(parse_limit(&arg)),
+            _ => usage_error(&format!("unexpected argument: {}", arg)),
+        }
+    }
+    let Some(n) = n else {
+        usage_error("gaps needs N");
+    };
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let mut progress = Progress::default();
+    let mut largest = (0, 0);
+    let mut write_error = None;
+    let plan = Plan::segmented(n, DEFAULT_SEGMENT_SIZE, 0);
+    stream_primes(n, 1, &plan, |p| {
+        let previous = progress.last_prime;
+        if previous != 0 {
+            let gap = p - previous;
+            let report = match min_gap {
+                Some(min_gap) => gap >= min_gap,
+                None => gap > largest.0,
+            };
+            if gap > largest.0 {
+                largest = (gap, previous);
+            }
+            if report {
+                if let Err(e) = writeln!(out, "{} {} {}", gap, previous, p) {
+                    write_error = Some(e);
+                    return ControlFlow::Break(());
+                }
+            }
+        }
+        progress.record(p)
+    })
+    .unwrap_or_else(|e| fail_sieve(e));
+    if interrupt::requested() && write_error.is_none() {
+        let _ = out.flush();
+        exit_interrupted(n, progress.count, progress.last_prime);
+    }
+
+    let (gap, start) = largest;
+    let written = match write_error {
+        Some(e) => Err(e),
+        None if gap == 0 => writeln!(out, "no gaps up to {}", n),
+        None => writeln!(out, "largest gap {} between {} and {}", gap, start, start + gap),
+    };
+    if let Err(e) = written.and_then(|_| out.flush()) {
+        report_write_error(e);
+    }
+}
+
+// `eratosthenes spiral SIZE [--output FILE]`: the Ulam spiral of side SIZE as an 8-bit grayscale
+// image with primes dark, from one sieve up to SIZE^2. PGM by default; PNG for a `.png` output
+// with the `png` feature. Rows are rendered one at a time from the sieve's bitmap.
+fn spiral(args: impl Iterator<Item = String>) {
+    let mut size = None;
+    let mut output: Option<PathBuf> = None;
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => usage_error("--output needs a file name"),
+            },
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option: {}", arg)),
+            _ if size.is_none() => size = Some(parse_limit(&arg)),
+            _ => usage_error(&format!("unexpected argument: {}", arg)),
+        }
+    }
+    let Some(size) = size else {
+        usage_error("spiral needs SIZE");
+    };
+    let Some(width) = u32::try_from(size).ok().filter(|&w| w > 0) else {
+        usage_error("SIZE must be between 1 and 4294967295");
+    };
+    let png = output.as_ref().is_some_and(|path| path.extension().is_some_and(|e| e.eq_ignore_ascii_case("png")));
+    if png && cfg!(not(feature = "png")) {
+        fail("PNG output needs the `png` feature; write a .pgm instead");
+    }
+
+    let n = size.checked_mul(size).unwrap_or_else(|| fail(format_args!("SIZE {} is too large: SIZE^2 overflows", size)));
+    let sieve = Sieve::new(n).unwrap_or_else(|e| fail_sieve(e));
+    let (low, high) = ulam_bounds(size);
+    let rows = (low..=high).rev().map(|y| {
+        (low..=high).map(|x| if sieve.is_prime(ulam_value(x, y)) { 0 } else { 255 }).collect::<Vec<u8>>()
+    });
+
+    let out: Box<dyn Write> = match &output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(io::BufWriter::new(file)),
+            Err(e) => fail(format_args!("{}: {}", path.display(), e)),
+        },
+        None => Box::new(io::BufWriter::new(io::stdout().lock())),
+    };
+    let written = if png { write_png(out, width, rows) } else { write_pgm(out, width, rows) };
+    if let Err(e) = written {
+        report_write_error(e);
+    }
+}
+
+fn write_pgm(mut out: impl Write, width: u32, rows: impl Iterator<Item = Vec<u8>>) -> io::Result<()> {
+    write!(out, "P5\n{} {}\n255\n", width, width)?;
+    for row in rows {
+        out.write_all(&row)?;
+    }
+    out.flush()
+}
+
+#[cfg(feature = "png")]
+fn write_png(out: impl Write, width: u32, rows: impl Iterator<Item = Vec<u8>>) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(out, width, width);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    let mut stream = writer.stream_writer().map_err(io::Error::other)?;
+    for row in rows {
+        stream.write_all(&row)?;
+    }
+    stream.finish().map_err(io::Error::other)
+}
+
+#[cfg(not(feature = "png"))]
+fn write_png(_: impl Write, _: u32, _: impl Iterator<Item = Vec<u8>>) -> io::Result<()> {
+    unreachable!("PNG output is rejected up front without the png feature")
+}
+
+// `--verify` without N: past the first segment boundary, yet done in a moment.
+const DEFAULT_VERIFY_BOUND: u64 = 10_000_000;
+
+// `--serve --listen ADDR`, answering the same queries over HTTP; needs the `http` feature.
+#[cfg(feature = "http")]
+fn listen_http(addr: &str) {
+    if let Err(e) = http::listen(addr) {
+        fail(format!("{}: {}", addr, e));
+    }
+}
+
+#[cfg(not(feature = "http"))]
+fn listen_http(_addr: &str) {
+    fail("--listen needs the `http` feature");
+}
+
+const USAGE: &str = "usage: eratosthenes [--threads N] [--memory-limit SIZE] [--stats] [--dry-run] [--throughput] [--print] [--format text|bin-delta]
+                    [--output FILE] [--segmented] [--checkpoint FILE [--checkpoint-interval 60s]] [--resume FILE] [N]
+       eratosthenes [--print] [--format text|bin-delta] [--output FILE] --limit-count K [N]
+       eratosthenes [--print] [--format text|bin-delta] [--output FILE] [--limit-count K] --filter EXPR N
+       eratosthenes [--format text|bin-delta] --output PREFIX --shard-size COUNT [--checkpoint FILE] [--resume FILE] N
+       eratosthenes [--format text|bin-delta] --compress gzip|zstd [--compress-level L] [--output FILE] N
+       eratosthenes --binary [--compress gzip|zstd] [--output FILE] N
+       eratosthenes [--format text|bin-delta] --append --output FILE --up-to N
+       eratosthenes [--memory-limit SIZE] [--stats] [--throughput] N N...
+       eratosthenes [--memory-limit SIZE] [--stats] [--throughput] --batch [N...] < LIMITS
+       eratosthenes [--threads N] --verify [N]
+       eratosthenes nth [--count-from 0|1] [--stats] K...
+       eratosthenes factor [--tree | --json] [N...]
+       eratosthenes gaps [--min-gap G] N
+       eratosthenes range [--count] [--base-primes FILE] [--save-base-primes FILE] LO..HI
+       eratosthenes bench --limits N,N... [--threads T,T...] [--repeat R] [--warmup W] [--reuse-buffer] [--output FILE]
+       eratosthenes spiral SIZE [--output FILE.pgm|FILE.png]
+       eratosthenes decode [FILE]
+       eratosthenes merge [--format text|bin-delta|binary] OUT IN...
+       eratosthenes validate [--format text|bin-delta] [--complete LO..HI] [--max-errors K] FILE
+       eratosthenes --serve < QUERIES
+       eratosthenes --serve --listen ADDR
+
+Any of these also takes --error-format text|json; json reports an error as one line of JSON on
+stderr. Exit status: 0 success, 1 failure, 2 usage error, 3 out of memory or over --memory-limit,
+4 interrupted, 5 internal error.
+
+--filter EXPR keeps the primes meeting every one of its comma-separated conditions: `mod M = R`,
+`lastdigit D`, `digitsum mod M = R` or `twin` (p + 2 is prime too). Counts count those primes.";
+
+fn usage_error(msg: &str) -> ! {
+    exit::report(Kind::Usage, msg, &[]);
+}
+
+fn parse_duration(arg: &str) -> Option<Duration> {
+    let (digits, unit) = match arg.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => arg.split_at(i),
+        None => (arg, "s"),
+    };
+    let value: u64 = digits.parse().ok()?;
+    let seconds = match unit {
+        "ms" => return Some(Duration::from_millis(value)),
+        "s" => value,
+        "m" => value.checked_mul(60)?,
+        "h" => value.checked_mul(3600)?,
+        _ => return None,
+    };
+    Some(Duration::from_secs(seconds))
+}
+
+fn main() {
+    #[cfg(feature = "log")]
+    env_logger::init();
+    interrupt::install();
+    exit::install_panic_hook();
+
+    let args = exit::take_error_format(std::env::args().skip(1)).unwrap_or_else(|e| usage_error(&e));
+    let mut args = args.into_iter().peekable();
+    if args.peek().map(String::as_str) == Some("decode") {
+        args.next();
+        let path = args.next();
+        if let Some(arg) = args.next() {
+            usage_error(&format!("unexpected argument: {}", arg));
+        }
+        decode(path);
+        return;
+    }
+    if matches!(args.peek().map(String::as_str), Some("--serve" | "serve")) {
+        args.next();
+        let listen = match args.next().as_deref() {
+            None => None,
+            Some("--listen") => Some(args.next().unwrap_or_else(|| usage_error("--listen needs an address"))),
+            Some(arg) => usage_error(&format!("unexpected argument: {}", arg)),
+        };
+        if let Some(arg) = args.next() {
+            usage_error(&format!("unexpected argument: {}", arg));
+        }
+        match listen {
+            Some(addr) => listen_http(&addr),
+            None => {
+                if let Err(e) = serve::serve() {
+                    report_write_error(e);
+                }
+            }
+        }
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("nth") {
+        args.next();
+        nth(args);
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("factor") {
+        args.next();
+        factor(args);
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("gaps") {
+        args.next();
+        gaps(args);
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("validate") {
+        args.next();
+        validate::validate(args);
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("range") {
+        args.next();
+        range::range(args);
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("merge") {
+        args.next();
+        merge::merge(args);
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("bench") {
+        args.next();
+        bench::bench(args);
+        return;
+    }
+    if args.peek().map(String::as_str) == Some("spiral") {
+        args.next();
+        spiral(args);
+        return;
+    }
+
+    let mut options = Options {
+        format: None,
+        binary: false,
+        output: None,
+        segmented: false,
+        checkpoint: None,
+        checkpoint_interval: Duration::from_secs(60),
+        resume: None,
+        batch: false,
+        verify: false,
+        threads: 0,
+        memory_limit: None,
+        stats: false,
+        dry_run: false,
+        throughput: false,
+        limit_count: None,
+        filter: None,
+        append: false,
+        shard_size: None,
+        compress: None,
+        compress_level: None,
+        bounds: Vec::new(),
+    };
+    let value = |args: &mut dyn Iterator<Item = String>, flag: &str| {
+        args.next().unwrap_or_else(|| usage_error(&format!("{} needs a value", flag)))
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--print" => options.format = options.format.or(Some(OutputFormat::Text)),
+            "--format" => {
+                options.format = match value(&mut args, "--format").as_str() {
+                    "text" => Some(OutputFormat::Text),
+                    "bin-delta" => Some(OutputFormat::BinDelta),
+                    other => usage_error(&format!("unknown format: {}", other)),
+                }
+            }
+            "--binary" => options.binary = true,
+            "--output" => options.output = Some(value(&mut args, "--output").into()),
+            "--segmented" => options.segmented = true,
+            "--checkpoint" => options.checkpoint = Some(value(&mut args, "--checkpoint").into()),
+            "--checkpoint-interval" => {
+                let interval = value(&mut args, "--checkpoint-interval");
+                options.checkpoint_interval = parse_duration(&interval)
+                    .unwrap_or_else(|| usage_error(&format!("bad interval: {} (e.g. 60s, 5m, 1h)", interval)));
+            }
+            "--resume" => options.resume = Some(value(&mut args, "--resume").into()),
+            "--batch" => options.batch = true,
+            "--verify" => options.verify = true,
+            "--threads" => {
+                let threads = value(&mut args, "--threads");
+                options.threads = threads
+                    .trim()
+                    .parse()
+                    .unwrap_or_else(|_| usage_error(&format!("--threads needs a whole number, not {}", threads)));
+            }
+            "--memory-limit" => {
+                let limit = parse_bound(&value(&mut args, "--memory-limit")).unwrap_or_else(|e| usage_error(&e));
+                options.memory_limit = Some(usize::try_from(limit).unwrap_or(usize::MAX));
+            }
+            "--stats" => options.stats = true,
+            "--dry-run" => options.dry_run = true,
+            "--throughput" => options.throughput = true,
+            "--limit-count" => {
+                let k = parse_bound(&value(&mut args, "--limit-count")).unwrap_or_else(|e| usage_error(&e));
+                options.limit_count = Some(k);
+            }
+            "--filter" => {
+                let expr = value(&mut args, "--filter");
+                let filter = Filter::parse(&expr).unwrap_or_else(|e| usage_error(&format!("--filter: {}", e)));
+                options.filter = Some(filter);
+            }
+            "--append" => options.append = true,
+            "--up-to" => options.bounds.push(value(&mut args, "--up-to")),
+            "--compress" => {
+                let name = value(&mut args, "--compress");
+                let codec = Codec::parse(&name).unwrap_or_else(|| usage_error(&format!("unknown codec: {}", name)));
+                options.compress = Some(codec);
+            }
+            "--compress-level" => {
+                let level = value(&mut args, "--compress-level");
+                let level = level.trim().parse().unwrap_or_else(|_| usage_error(&format!("bad level: {}", level)));
+                options.compress_level = Some(level);
+            }
+            "--shard-size" => {
+                let size = parse_bound(&value(&mut args, "--shard-size")).unwrap_or_else(|e| usage_error(&e));
+                if size == 0 {
+                    usage_error("--shard-size needs at least one prime per shard");
+                }
+                options.shard_size = Some(size);
+            }
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option: {}", arg)),
+            _ => options.bounds.push(arg),
+        }
+    }
+
+    if options.threads > default_threads() {
+        eprintln!(
+            "warning: --threads {} is more than the {} hardware threads available",
+            options.threads,
+            default_threads()
+        );
+    }
+
+    if options.binary {
+        if options.format.is_some() {
+            usage_error("--binary is an output format of its own; drop --print and --format");
+        }
+        if options.batch || options.bounds.len() > 1 || options.verify {
+            usage_error("--binary takes at most one N, and no --batch or --verify");
+        }
+        if options.append || options.shard_size.is_some() || options.checkpoint.is_some() || options.resume.is_some() {
+            usage_error("--binary cannot be combined with --append, --shard-size, --checkpoint or --resume");
+        }
+    }
+
+    if options.append {
+        if options.output.is_none() {
+            usage_error("--append needs --output FILE");
+        }
+        if options.checkpoint.is_some() || options.resume.is_some() {
+            usage_error("--append cannot be combined with --checkpoint or --resume");
+        }
+        options.format = options.format.or(Some(OutputFormat::Text));
+    }
+
+    if options.shard_size.is_some() {
+        if options.output.is_none() {
+            usage_error("--shard-size needs --output PREFIX to name the shards after");
+        }
+        if options.append {
+            usage_error("--append cannot be combined with --shard-size");
+        }
+        options.format = options.format.or(Some(OutputFormat::Text));
+    }
+
+    if let Some(codec) = options.compress {
+        if cfg!(not(feature = "compression")) {
+            fail("--compress needs the `compression` feature");
+        }
+        if options.append || options.shard_size.is_some() || options.checkpoint.is_some() || options.resume.is_some() {
+            usage_error("--compress cannot be combined with --append, --shard-size, --checkpoint or --resume");
+        }
+        if let Some(level) = options.compress_level {
+            if !codec.levels().contains(&level) {
+                let levels = codec.levels();
+                usage_error(&format!("--compress-level must be from {} to {}", levels.start(), levels.end()));
+            }
+        }
+        if !options.binary {
+            options.format = options.format.or(Some(OutputFormat::Text));
+        }
+    } else if options.compress_level.is_some() {
+        usage_error("--compress-level needs --compress");
+    }
+
+    if options.limit_count.is_some() {
+        if options.batch || options.bounds.len() > 1 || options.verify {
+            usage_error("--limit-count takes at most one N, and no --batch or --verify");
+        }
+        if options.append || options.checkpoint.is_some() || options.resume.is_some() {
+            usage_error("--limit-count cannot be combined with --append, --checkpoint or --resume");
+        }
+    }
+
+    if options.filter.is_some() {
+        if options.batch || options.bounds.len() > 1 || options.verify {
+            usage_error("--filter takes at most one N, and no --batch or --verify");
+        }
+        if options.append || options.checkpoint.is_some() || options.resume.is_some() {
+            usage_error("--filter cannot be combined with --append, --checkpoint or --resume");
+        }
+        // The bound --limit-count derives is for the first K primes, not the first K that pass.
+        if options.limit_count.is_some() && options.bounds.is_empty() {
+            usage_error("--limit-count with --filter needs N");
+        }
+    }
+
+    if options.batch || options.bounds.len() > 1 {
+        let exclusive = options.format.is_some() || options.output.is_some();
+        if exclusive || options.checkpoint.is_some() || options.resume.is_some() {
+            usage_error("batch queries only count primes; drop the output and checkpoint options");
+        }
+        let mut limits: Vec<u64> = options.bounds.iter().map(|arg| parse_limit(arg)).collect();
+        if options.batch {
+            for line in io::stdin().lines() {
+                let line = line.unwrap_or_else(|e| fail(e));
+                if !line.trim().is_empty() {
+                    limits.push(parse_limit(&line));
+                }
+            }
+        }
+        if let Err(e) = count_batch(&limits, &options) {
+            fail_sieve(e);
+        }
+        return;
+    }
+
+    // The first K primes all lie below the K-th prime's upper bound, so that is as far as a run
+    // with --limit-count can need to go, N or no N.
+    let count_bound = options.limit_count.map(|k| match k {
+        0 => 0,
+        k => nth_prime_upper_bound(k).unwrap_or(u64::MAX),
+    });
+    let n: u64 = match (options.bounds.first(), &options.resume, count_bound) {
+        (Some(arg), _, bound) => parse_limit(arg).min(bound.unwrap_or(u64::MAX)),
+        (None, _, Some(bound)) => bound,
+        (None, None, _) if options.verify => DEFAULT_VERIFY_BOUND,
+        (None, Some(path), _) => Checkpoint::load(path).unwrap_or_else(|e| fail(e)).n,
+        (None, None, _) => prompt_bound(),
+    };
+
+    if options.verify {
+        if options.dry_run {
+            usage_error("--verify has no plan for --dry-run to print");
+        }
+        if options.throughput {
+            usage_error("--throughput measures sieving runs, not --verify");
+        }
+        if n > VERIFY_LIMIT {
+            fail(format_args!("--verify is limited to n <= {}; the reference sieve is the slow path", VERIFY_LIMIT));
+        }
+        match verify_with_threads(n, options.threads) {
+            Ok(()) => println!("wheel and segmented sieves agree with the reference sieve up to {}", n),
+            Err(mismatch) => fail(mismatch),
+        }
+        return;
+    }
+
+    let started = Instant::now();
+    let plan = options.resolve(n);
+    if options.dry_run(&plan) {
+        return;
+    }
+
+    if let (true, Some(format), Some(path)) = (options.append, options.format, &options.output) {
+        let count = append_primes(n, format, path);
+        options.report(&plan, count, started);
+        return;
+    }
+
+    if options.checkpoint.is_some() || options.resume.is_some() {
+        let segment_size = plan.plan.segment_size().unwrap_or(DEFAULT_SEGMENT_SIZE);
+        let count = run_segmented(n, segment_size, &options).unwrap_or_else(|e| fail_sieve(e));
+        options.report(&plan, count, started);
+        return;
+    }
+
+    if let (Some(format), Some(shard_size), Some(prefix)) = (options.format, options.shard_size, &options.output) {
+        let shards = ShardWriter::new(prefix, format, shard_size);
+        let count = print_shards(n, plan.threads, &plan.plan, shards, &options).unwrap_or_else(|e| fail_sieve(e));
+        options.report(&plan, count, started);
+        return;
+    }
+
+    if options.binary {
+        let out = open_output(&options);
+        let count = print_prime_file(n, plan.threads, &plan.plan, out, &options).unwrap_or_else(|e| fail_sieve(e));
+        options.report(&plan, count, started);
+        return;
+    }
+
+    if let Some(format) = options.format {
+        let out = open_output(&options);
+        let count = print_primes(n, plan.threads, &plan.plan, format, out, &options).unwrap_or_else(|e| fail_sieve(e));
+        options.report(&plan, count, started);
+        return;
+    }
+
+    let matching = match &options.filter {
+        Some(filter) => format!(" matching \"{}\"", filter),
+        None => String::new(),
+    };
+    match options.limit_count {
+        None => eprintln!("Following is the count of prime numbers{} smaller than or equal to {}:", matching, n),
+        Some(k) => eprintln!(
+            "Following is the count of prime numbers{} smaller than or equal to {}, up to the first {}:",
+            matching, n, k
+        ),
+    }
+    let mut progress = Progress::limited(options.limit_count);
+    if let Err(e) = stream_selected(n, plan.threads, &plan.plan, options.filter.as_ref(), |p| progress.record(p)) {
+        fail_sieve(e);
+    }
+    if interrupt::requested() {
+        exit_interrupted(n, progress.count, progress.last_prime);
+    }
+    throughput::stop();
+    println!("{}", progress.count);
+    options.report(&plan, progress.count, started);
+}
@@ -0,0 +1,193 @@
+//! Where the wheel sieve keeps its marks: one `bool` per wheel candidate up to `n`, which is the
+//! only allocation of any size a sieve makes.
+//!
+//! The allocator API (`Vec::new_in`) is still unstable, so on stable Rust the sieve is generic
+//! over [`MarkAllocator`] instead: anything that can hand out a zeroed, mutable `[bool]` of a
+//! given length, whether an arena slice, a buffer from a pool or a memory-mapped region. With the
+//! `allocator_api` feature, which needs a nightly compiler, every cloneable
+//! `std::alloc::Allocator` is a `MarkAllocator` too, with the marks in a `Vec<bool, A>`.
+//!
+//! Nothing changes unless an allocator is passed in: [`primes_up_to`](crate::primes_up_to) and
+//! friends use [`GlobalMarks`], a plain `Vec<bool>` from the global allocator.
+
+use std::cell::UnsafeCell;
+use std::ops::{ControlFlow, DerefMut};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::{default_threads, prime_count_upper_bound, sieve_each_marked, worker_threads, QueueConfig, SieveError};
+
+/// A source of mark buffers for the wheel sieve.
+pub trait MarkAllocator {
+    /// The buffer handed out. Marking workers write to it from other threads, hence `Send`.
+    type Marks: DerefMut<Target = [bool]> + Send + 'static;
+
+    /// A buffer of exactly `len` flags, all `false`, or `None` if it cannot be had.
+    fn allocate_marks(&self, len: usize) -> Option<Self::Marks>;
+}
+
+/// The default: marks in a `Vec<bool>` from the global allocator.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GlobalMarks;
+
+impl MarkAllocator for GlobalMarks {
+    type Marks = Vec<bool>;
+
+    fn allocate_marks(&self, len: usize) -> Option<Vec<bool>> {
+        let mut marks = Vec::new();
+        marks.try_reserve_exact(len).ok()?;
+        marks.resize(len, false);
+        Some(marks)
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<A> MarkAllocator for A
+where
+    A: std::alloc::Allocator + Clone + Send + 'static,
+{
+    type Marks = Vec<bool, A>;
+
+    fn allocate_marks(&self, len: usize) -> Option<Vec<bool, A>> {
+        let mut marks = Vec::new_in(self.clone());
+        marks.try_reserve_exact(len).ok()?;
+        marks.resize(len, false);
+        Some(marks)
+    }
+}
+
+// The ordering of the marking stores. Relaxed is enough, and the `seqcst-marks` feature, there to
+// check that claim, swaps in SeqCst; the two must sieve alike.
+//
+// Why relaxed suffices: a mark is only ever set, never cleared, so the stores to one flag all
+// agree and no marking task reads the marks at all. What needs ordering is the reader's view,
+// and that comes from the dispatch queue, not the flags: a worker ends each task by taking the
+// queue's mutex to record it done, and DispatchQueue::finish takes the same mutex before it
+// returns. Unlocking synchronizes with the next lock, so every store of a finished task
+// happens-before whatever the caller does after finish(), including the plain reads of
+// SharedMarks::settled and, once the workers are joined, of the buffer itself. Stores still in
+// flight are never read: settled() is only called between finish() and the next dispatch.
+pub(crate) const MARK_ORDERING: Ordering =
+    if cfg!(feature = "seqcst-marks") { Ordering::SeqCst } else { Ordering::Relaxed };
+
+// Marks from an allocator, shared with the marking workers, who set them as atomics: no lock is
+// taken per mark. AtomicBool has the layout of bool, so the allocator's [bool] is viewed in
+// place.
+pub(crate) struct SharedMarks<M> {
+    marks: UnsafeCell<M>,
+    // The buffer `marks` derefs to, taken once so that later accesses go through neither Deref
+    // impl; fixed for as long as the Arc holding this lives, as nothing moves `marks` before then.
+    flags: *const [AtomicBool],
+}
+
+// SAFETY: the buffer is only written through atomics, and only read plainly when no write is in
+// flight (see settled); `marks` itself is touched again only when the last handle unwraps it.
+unsafe impl<M: Send> Send for SharedMarks<M> {}
+unsafe impl<M: Send> Sync for SharedMarks<M> {}
+
+impl<M: DerefMut<Target = [bool]>> SharedMarks<M> {
+    pub(crate) fn new(marks: M) -> Arc<Self> {
+        let empty = ptr::slice_from_raw_parts(ptr::null(), 0);
+        let mut shared = Arc::new(SharedMarks { marks: UnsafeCell::new(marks), flags: empty });
+        let this = Arc::get_mut(&mut shared).expect("the handle was just made");
+        let flags: *mut [bool] = this.marks.get_mut().deref_mut();
+        this.flags = flags as *const [AtomicBool];
+        shared
+    }
+
+    // Marks wheel candidate `i` composite; called from any number of workers at once.
+    pub(crate) fn set(&self, i: usize) {
+        // SAFETY: the pointer is into a buffer that lives as long as self; see `flags`.
+        let flags = unsafe { &*self.flags };
+        flags[i].store(true, MARK_ORDERING);
+    }
+
+    // The marks as they stand, read plainly.
+    //
+    // SAFETY: no marking task may be running or queued until the result is dropped; in the sieve
+    // that means calling it only between DispatchQueue::finish and the next dispatch.
+    pub(crate) unsafe fn settled(&self) -> &[bool] {
+        unsafe { &*(self.flags as *const [bool]) }
+    }
+
+    // The buffer back, once this is its last handle.
+    pub(crate) fn into_inner(shared: Arc<Self>) -> Option<M> {
+        Arc::try_unwrap(shared).ok().map(|shared| shared.marks.into_inner())
+    }
+}
+
+/// Like [`sieve_of_eratosthenes`](crate::sieve_of_eratosthenes), with the marks allocated by
+/// `alloc`; fails with [`SieveError::AllocationFailed`] if it has none to give. The primes
+/// themselves are still collected in an ordinary `Vec`.
+pub fn try_primes_up_to_in<A: MarkAllocator>(n: u64, alloc: &A) -> Result<Vec<u64>, SieveError> {
+    let mut primes = Vec::new();
+    primes
+        .try_reserve_exact(prime_count_upper_bound(n))
+        .map_err(|_| SieveError::AllocationFailed { n })?;
+    sieve_each_marked(n, default_threads(), QueueConfig::default(), true, &AtomicBool::new(false), alloc, |p| {
+        primes.push(p);
+        ControlFlow::Continue(())
+    })?;
+    Ok(primes)
+}
+
+/// Streams the primes up to `n` to `emit` like
+/// [`sieve_each_threaded`](crate::sieve_each_threaded), marking with `threads` workers (0 means
+/// all of them) in marks from `alloc`. When the sieve runs to completion the marks are handed
+/// back, so that an allocator that pools them can give them out again; `None` if it stopped
+/// early or `n` was too small to need any.
+pub fn sieve_each_in<A: MarkAllocator, F: FnMut(u64) -> ControlFlow<()>>(
+    n: u64,
+    threads: usize,
+    cancel: &AtomicBool,
+    alloc: &A,
+    emit: F,
+) -> Result<Option<A::Marks>, SieveError> {
+    let threads = worker_threads(threads);
+    sieve_each_marked(n, threads, QueueConfig::default(), true, cancel, alloc, emit)
+}
+
+/// Returns the primes up to and including `n`, sieving in marks from `alloc`; panics if it cannot
+/// provide them.
+#[must_use]
+pub fn primes_up_to_in<A: MarkAllocator>(n: u64, alloc: A) -> Vec<u64> {
+    try_primes_up_to_in(n, &alloc).unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prime_gen_trial_division;
+
+    #[test]
+    fn mark_ordering_follows_the_feature() {
+        let expected = if cfg!(feature = "seqcst-marks") { Ordering::SeqCst } else { Ordering::Relaxed };
+        assert_eq!(MARK_ORDERING, expected);
+    }
+
+    fn marked(n: u64, threads: usize) -> (Vec<u64>, Vec<bool>) {
+        let mut primes = Vec::new();
+        let marks = sieve_each_in(n, threads, &AtomicBool::new(false), &GlobalMarks, |p| {
+            primes.push(p);
+            ControlFlow::Continue(())
+        })
+        .unwrap()
+        .unwrap();
+        (primes, marks)
+    }
+
+    // Run under both orderings (CI builds this with and without `seqcst-marks`), the pinned
+    // values are what makes the two sieves comparable: each must land on the same marks.
+    #[test]
+    fn marks_are_the_same_under_either_ordering() {
+        let (primes, marks) = marked(100_000, 4);
+        let expected: Vec<u64> = prime_gen_trial_division().take_while(|&p| p <= 100_000).collect();
+        assert_eq!(primes, expected);
+        for threads in [1, 2, 3] {
+            assert_eq!(marked(100_000, threads), (primes.clone(), marks.clone()), "{} threads", threads);
+        }
+        let (primes, _) = marked(2_000_000, 4);
+        assert_eq!((primes.len(), primes.iter().sum::<u64>()), (148_933, 142_913_828_922));
+    }
+}
@@ -0,0 +1,182 @@
+// `merge [--format text|bin-delta|binary] OUT IN...`: one sorted list without duplicates from
+// prime lists that overlap, such as the shards and reruns of interrupted runs. The inputs may be
+// in any format the CLI writes, compressed or not, and are read in step, one buffered reader
+// each, so memory stays the same however large they are. Each input must be strictly increasing
+// on its own; the first that is not, or that cannot be read, stops the merge and is named.
+//
+// The bin-delta and binary headers hold the count, so OUT is written with a count of 0 and the
+// count is filled in at the end.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+
+use eratosthenes::format::{
+    DELTA_COUNT_OFFSET, DELTA_MAGIC, PRIME_FILE_BOUND_OFFSET, PRIME_FILE_COUNT_OFFSET, PRIME_FILE_MAGIC,
+};
+use eratosthenes::{decompress, write_prime_file, DeltaEncoder, DeltaReader, PrimeFileReader};
+
+use crate::{fail, usage_error, OutputFormat};
+
+// Bin-delta gaps held before they are written out.
+const GAP_BUFFER_BYTES: usize = 1 << 16;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Listed(OutputFormat),
+    Binary,
+}
+
+type Primes = Box<dyn Iterator<Item = io::Result<u64>>>;
+
+pub fn merge(args: impl Iterator<Item = String>) {
+    let mut format = Format::Listed(OutputFormat::Text);
+    let mut paths = Vec::new();
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("text") => Format::Listed(OutputFormat::Text),
+                    Some("bin-delta") => Format::Listed(OutputFormat::BinDelta),
+                    Some("binary") => Format::Binary,
+                    Some(other) => usage_error(&format!("unknown format: {}", other)),
+                    None => usage_error("--format needs a value"),
+                }
+            }
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option: {}", arg)),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() < 2 {
+        usage_error("merge needs OUT and at least one IN");
+    }
+    let out_path = paths.remove(0);
+    // Creating OUT truncates it, so it must not be one of the inputs.
+    if let Ok(out) = fs::canonicalize(&out_path) {
+        if paths.iter().any(|path| fs::canonicalize(path).is_ok_and(|path| path == out)) {
+            usage_error(&format!("{} is both OUT and an input", out_path));
+        }
+    }
+
+    let mut inputs: Vec<Primes> =
+        paths.iter().map(|path| open(path).unwrap_or_else(|e| fail(format_args!("{}: {}", path, e)))).collect();
+    let file = File::create(&out_path).unwrap_or_else(|e| fail(format_args!("{}: {}", out_path, e)));
+    let mut out = Merged::new(file, format).unwrap_or_else(|e| fail(format_args!("{}: {}", out_path, e)));
+    let abandon = |message: String| -> ! {
+        let _ = fs::remove_file(&out_path);
+        fail(message);
+    };
+
+    let next = |i: usize, inputs: &mut [Primes]| match inputs[i].next() {
+        Some(Ok(p)) => Some(Reverse((p, i))),
+        Some(Err(e)) => abandon(format!("{}: {}", paths[i], e)),
+        None => None,
+    };
+    let mut heap: BinaryHeap<_> = (0..inputs.len()).filter_map(|i| next(i, &mut inputs)).collect();
+    while let Some(Reverse((p, i))) = heap.pop() {
+        if p != out.last {
+            out.push(p).unwrap_or_else(|e| abandon(format!("{}: {}", out_path, e)));
+        }
+        heap.extend(next(i, &mut inputs));
+    }
+    let count = out.count;
+    out.finish().unwrap_or_else(|e| abandon(format!("{}: {}", out_path, e)));
+    eprintln!("merged {} inputs into {}: {} primes", paths.len(), out_path, count);
+}
+
+// The primes in the file at path, in whichever format it turns out to hold.
+fn open(path: &str) -> io::Result<Primes> {
+    let mut input = BufReader::new(decompress(File::open(path)?)?);
+    let head = input.fill_buf()?;
+    if head.starts_with(&DELTA_MAGIC) {
+        return Ok(Box::new(DeltaReader::new(input)?));
+    }
+    if head.starts_with(&PRIME_FILE_MAGIC) {
+        return Ok(Box::new(PrimeFileReader::new(input)?));
+    }
+    Ok(Box::new(text_primes(input)))
+}
+
+// A text list, one prime per line, checked to be strictly increasing as it is read.
+fn text_primes(input: impl BufRead + 'static) -> impl Iterator<Item = io::Result<u64>> {
+    let mut last = 0;
+    input.lines().enumerate().filter_map(move |(i, line)| {
+        let invalid = |what: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, what));
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        if line.trim().is_empty() {
+            return None;
+        }
+        let p = match line.trim().parse::<u64>() {
+            Ok(p) => p,
+            Err(_) => return Some(Err(invalid(format!("not a number: {:?}", line)))),
+        };
+        if p <= last {
+            return Some(Err(invalid(format!("{} does not follow {}; the list must be strictly increasing", p, last))));
+        }
+        last = p;
+        Some(Ok(p))
+    })
+}
+
+// OUT as it is written.
+struct Merged {
+    out: BufWriter<File>,
+    format: Format,
+    count: u64,
+    last: u64,
+    encoder: DeltaEncoder,
+}
+
+impl Merged {
+    fn new(file: File, format: Format) -> io::Result<Self> {
+        let mut out = BufWriter::new(file);
+        match format {
+            Format::Listed(OutputFormat::Text) => {}
+            Format::Listed(OutputFormat::BinDelta) => DeltaEncoder::new(0).write(&mut out)?,
+            Format::Binary => write_prime_file(&mut out, 0, &[])?,
+        }
+        Ok(Merged { out, format, count: 0, last: 0, encoder: DeltaEncoder::new(0) })
+    }
+
+    fn push(&mut self, p: u64) -> io::Result<()> {
+        match self.format {
+            Format::Listed(OutputFormat::Text) => writeln!(self.out, "{}", p)?,
+            Format::Listed(OutputFormat::BinDelta) => {
+                self.encoder.push(p);
+                if self.encoder.gaps().len() >= GAP_BUFFER_BYTES {
+                    self.out.write_all(self.encoder.gaps())?;
+                    self.encoder = DeltaEncoder::new(p);
+                }
+            }
+            Format::Binary => self.out.write_all(&p.to_le_bytes())?,
+        }
+        self.count += 1;
+        self.last = p;
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        self.out.write_all(self.encoder.gaps())?;
+        let mut file = self.out.into_inner().map_err(|e| e.into_error())?;
+        match self.format {
+            Format::Listed(OutputFormat::Text) => {}
+            Format::Listed(OutputFormat::BinDelta) => {
+                file.seek(SeekFrom::Start(DELTA_COUNT_OFFSET))?;
+                file.write_all(&self.count.to_le_bytes())?;
+            }
+            Format::Binary => {
+                // The bound is the largest prime: nothing is claimed about the gaps between inputs.
+                file.seek(SeekFrom::Start(PRIME_FILE_BOUND_OFFSET))?;
+                file.write_all(&self.last.to_le_bytes())?;
+                file.seek(SeekFrom::Start(PRIME_FILE_COUNT_OFFSET))?;
+                file.write_all(&self.count.to_le_bytes())?;
+            }
+        }
+        file.sync_data()
+    }
+}
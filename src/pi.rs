@@ -0,0 +1,166 @@
+use crate::{is_prime, sieve_of_eratosthenes};
+
+// φ(x, SMALL_A) comes from one period of the wheel of the first SMALL_A primes.
+const SMALL_A: usize = 6;
+const SMALL_PRIMORIAL: u64 = 2 * 3 * 5 * 7 * 11 * 13;
+// Euler phi of SMALL_PRIMORIAL: the product of p - 1 over those primes.
+const SMALL_TOTIENT: u64 = 2 * 4 * 6 * 10 * 12;
+
+/// π(n) by Lehmer's form of the Meissel-Lehmer method: with `a = π(n^(1/4))`,
+/// `π(n) = φ(n, a) + a - 1 - P2(n, a) - P3(n, a)`, where `φ(x, a)` counts the integers up to `x`
+/// free of the first `a` primes and `P2`, `P3` count those with exactly two or three larger prime
+/// factors. The `π` values those terms need are found recursively, bottoming out in the primes up
+/// to `sqrt(n)`, so memory is `O(sqrt(n))` instead of the full sieve's `O(n)`.
+///
+/// Far faster than [`count_primes_up_to`](crate::count_primes_up_to) for `n` beyond about 10^10.
+///
+/// # Panics
+///
+/// If the primes up to `sqrt(n)` cannot be allocated.
+#[must_use]
+pub fn compute_pi_x_meissel_lehmer(n: u64) -> u64 {
+    let primes = sieve_of_eratosthenes(n.isqrt().max(SMALL_PRIMORIAL)).unwrap_or_else(|e| panic!("{}", e));
+    let mut small_phi = vec![0u32; SMALL_PRIMORIAL as usize];
+    let mut count = 0;
+    for (v, phi) in small_phi.iter_mut().enumerate() {
+        if primes[..SMALL_A].iter().all(|&p| !(v as u64).is_multiple_of(p)) {
+            count += 1;
+        }
+        *phi = count;
+    }
+    Lehmer { primes, small_phi }.pi(n)
+}
+
+/// The position of `p` among the primes, counting 2 as the first: the inverse of
+/// [`nth_prime`](crate::nth_prime), and π(p) for prime `p`. `None` if `p` is not prime, which
+/// Miller-Rabin settles before any counting; a prime costs one [`compute_pi_x_meissel_lehmer`].
+///
+/// ```
+/// assert_eq!(eratosthenes::prime_rank(5), Some(3));
+/// assert_eq!(eratosthenes::prime_rank(6), None);
+/// ```
+#[must_use]
+pub fn prime_rank(p: u64) -> Option<u64> {
+    is_prime(p).then(|| compute_pi_x_meissel_lehmer(p))
+}
+
+struct Lehmer {
+    // The primes up to at least sqrt(n); π is a binary search below their last.
+    primes: Vec<u64>,
+    // small_phi[r] = φ(r, SMALL_A) for r < SMALL_PRIMORIAL.
+    small_phi: Vec<u32>,
+}
+
+impl Lehmer {
+    fn pi(&self, x: u64) -> u64 {
+        if x <= *self.primes.last().unwrap() {
+            return self.primes.partition_point(|&p| p <= x) as u64;
+        }
+        let a = self.pi(x.isqrt().isqrt()) as usize;
+        let b = self.pi(x.isqrt()) as usize;
+        let c = self.pi(cbrt(x)) as usize;
+
+        // φ(x, a) + a - 1, less P2 with the (b + a - 2)(b - a + 1) / 2 of its index correction.
+        let mut sum = self.phi(x, a) + ((b + a - 2) * (b - a + 1) / 2) as u64;
+        for i in a..b {
+            let w = x / self.primes[i];
+            sum -= self.pi(w);
+            if i < c {
+                // P3: products p_i p_j p_k with i <= j <= k.
+                let bi = self.pi(w.isqrt()) as usize;
+                for j in i..bi {
+                    sum -= self.pi(w / self.primes[j]) - j as u64;
+                }
+            }
+        }
+        sum
+    }
+
+    // Integers in 1..=x with no prime factor among the first a primes.
+    fn phi(&self, x: u64, a: usize) -> u64 {
+        if a == 0 {
+            return x;
+        }
+        if a == SMALL_A {
+            return x / SMALL_PRIMORIAL * SMALL_TOTIENT + self.small_phi[(x % SMALL_PRIMORIAL) as usize] as u64;
+        }
+        if x < self.primes[a] {
+            return 1;
+        }
+        // Below p_(a+1)^2 everything left is 1 or a prime past the first a.
+        if x < self.primes[a] * self.primes[a] && x <= *self.primes.last().unwrap() {
+            return self.pi(x) - a as u64 + 1;
+        }
+        self.phi(x, a - 1) - self.phi(x / self.primes[a - 1], a - 1)
+    }
+}
+
+// floor(x^(1/3)), correcting the float estimate by at most a step either way.
+fn cbrt(x: u64) -> u64 {
+    let mut r = (x as f64).cbrt() as u64;
+    while r.checked_pow(3).is_none_or(|cube| cube > x) {
+        r -= 1;
+    }
+    while (r + 1).checked_pow(3).is_some_and(|cube| cube <= x) {
+        r += 1;
+    }
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primes_up_to;
+
+    #[test]
+    fn pi_of_10_to_the_9() {
+        assert_eq!(compute_pi_x_meissel_lehmer(1_000_000_000), 50_847_534);
+    }
+
+    #[test]
+    fn pi_of_powers_of_10() {
+        let expected = [0, 4, 25, 168, 1_229, 9_592, 78_498, 664_579, 5_761_455];
+        for (k, &pi) in expected.iter().enumerate().skip(1) {
+            assert_eq!(compute_pi_x_meissel_lehmer(10u64.pow(k as u32)), pi, "10^{}", k);
+        }
+        assert_eq!(compute_pi_x_meissel_lehmer(10_000_000_000), 455_052_511);
+    }
+
+    #[test]
+    fn pi_of_small_n_and_around_the_tables() {
+        let primes = primes_up_to(200_000);
+        let pi = |x: u64| primes.partition_point(|&p| p <= x) as u64;
+        // Up to 30029, the last prime below SMALL_PRIMORIAL, π is a search of the sieved primes;
+        // past it the recursion runs, and φ(x, 6) reads the table modulo SMALL_PRIMORIAL.
+        let edges = (0..=1_000).chain((29_990..=30_100).chain([
+            2 * SMALL_PRIMORIAL - 1,
+            2 * SMALL_PRIMORIAL,
+            2 * SMALL_PRIMORIAL + 1,
+            30_029 * 2,
+            30_029 * 30_029 / 5_000,
+            199_999,
+            200_000,
+        ]));
+        for x in edges {
+            assert_eq!(compute_pi_x_meissel_lehmer(x), pi(x), "π({})", x);
+        }
+        assert_eq!([0, 1, 2, 3].map(compute_pi_x_meissel_lehmer), [0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn pi_once_sqrt_n_outgrows_the_table() {
+        // Past 30030^2 the primes are sieved up to sqrt(n) rather than SMALL_PRIMORIAL; each step
+        // across that boundary adds exactly the primes a range sieve finds in between.
+        let square = SMALL_PRIMORIAL * SMALL_PRIMORIAL;
+        let steps = [square - 2_000, square - 1, square, 30_031 * 30_031, 30_047 * 30_047];
+        for w in steps.windows(2) {
+            let between = crate::Sieve::range(w[0] + 1, w[1]).unwrap().primes().count() as u64;
+            assert_eq!(
+                compute_pi_x_meissel_lehmer(w[1]),
+                compute_pi_x_meissel_lehmer(w[0]) + between,
+                "π({})",
+                w[1]
+            );
+        }
+    }
+}
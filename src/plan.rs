@@ -0,0 +1,100 @@
+// What a run resolves its options into before it sieves anything. `--dry-run` prints it and
+// every other run executes it, so the two cannot disagree.
+
+use eratosthenes::{memory_usage_estimate, prime_count_upper_bound, Plan};
+
+use crate::stats::json_option;
+
+// Past this a whole-range plan gets a note pointing at --memory-limit.
+const LARGE_BYTES: usize = 1_000_000_000;
+
+pub struct SievePlan {
+    pub n: u64,
+    // Marking threads; a segmented plan sieves on one.
+    pub threads: usize,
+    pub plan: Plan,
+    // "count", "text" or "bin-delta".
+    pub output: &'static str,
+    // Bytes the output accumulates in memory before it is written; included in the plan's.
+    pub output_bytes: usize,
+    pub memory_limit: Option<usize>,
+}
+
+impl SievePlan {
+    // An upper bound on the primes up to sqrt(n) that the marking walks.
+    pub fn base_primes(&self) -> usize {
+        prime_count_upper_bound(self.n.isqrt())
+    }
+
+    // Marks, and for a segmented plan the base primes, excluding the output.
+    pub fn sieve_bytes(&self) -> usize {
+        self.plan.bytes() - self.output_bytes
+    }
+
+    fn strategy(&self) -> &'static str {
+        match self.plan {
+            Plan::Monolithic { .. } => "monolithic",
+            Plan::Segmented { .. } => "segmented",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let strategy = match self.plan {
+            Plan::Monolithic { .. } => "monolithic, one pass over the whole range".to_string(),
+            Plan::Segmented { segment_size, segments, .. } => {
+                format!("segmented, {} segments of {} wheel positions", segments, segment_size)
+            }
+        };
+        let memory_limit = self.memory_limit.map_or_else(|| "none".to_string(), |limit| format!("{} bytes", limit));
+        let note = match self.plan {
+            Plan::Monolithic { .. } if memory_usage_estimate(self.n) > LARGE_BYTES => {
+                "\nnote           over 1 GB of marks; --memory-limit would sieve in segments instead"
+            }
+            _ => "",
+        };
+        format!(
+            "n              {}\n\
+             strategy       {}\n\
+             threads        {}\n\
+             output         {}\n\
+             base primes    at most {} (up to {})\n\
+             sieve memory   {} bytes\n\
+             output memory  {} bytes\n\
+             planned total  {} bytes\n\
+             memory limit   {}{}",
+            self.n,
+            strategy,
+            self.threads,
+            self.output,
+            self.base_primes(),
+            self.n.isqrt(),
+            self.sieve_bytes(),
+            self.output_bytes,
+            self.plan.bytes(),
+            memory_limit,
+            note
+        )
+    }
+
+    pub fn to_json(&self) -> String {
+        let (segment_size, segments) = match self.plan {
+            Plan::Monolithic { .. } => (None, None),
+            Plan::Segmented { segment_size, segments, .. } => (Some(segment_size), Some(segments)),
+        };
+        format!(
+            "{{\"n\":{},\"strategy\":\"{}\",\"threads\":{},\"output\":\"{}\",\"segment_size\":{},\"segments\":{},\
+             \"base_primes\":{},\"sieve_bytes\":{},\"output_bytes\":{},\"planned_bytes\":{},\"memory_limit\":{}}}",
+            self.n,
+            self.strategy(),
+            self.threads,
+            self.output,
+            json_option(segment_size),
+            json_option(segments),
+            self.base_primes(),
+            self.sieve_bytes(),
+            self.output_bytes,
+            self.plan.bytes(),
+            json_option(self.memory_limit)
+        )
+    }
+}
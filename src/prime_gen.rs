@@ -1,169 +1,359 @@
+// The `allocator_api` feature lets std allocators back the sieve's marks; it needs nightly.
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+use std::hint;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, Condvar};
 use std::thread;
 use std::collections::VecDeque;
+use std::ops::ControlFlow;
 use std::thread::available_parallelism;
 
+use crate::marks::SharedMarks;
+
+// Diagnostics for long runs, compiled in with the `log` feature (`RUST_LOG=eratosthenes=debug`).
+// Without it the arguments are still type-checked but nothing is formatted or emitted. Keep
+// these out of the marking and emitting loops.
+#[cfg(feature = "log")]
+macro_rules! debug {
+    ($($arg:tt)*) => { log::debug!(target: "eratosthenes", $($arg)*) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+#[cfg(feature = "log")]
+macro_rules! trace {
+    ($($arg:tt)*) => { log::trace!(target: "eratosthenes", $($arg)*) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+#[cfg(feature = "log")]
+macro_rules! warn {
+    ($($arg:tt)*) => { log::warn!(target: "eratosthenes", $($arg)*) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+// Scheduling noise at the dispatch queue's hand-off points, to make its races reproducible; see
+// the `delays` module. Expands to nothing unless the `test-delays` feature is on.
+#[cfg(feature = "test-delays")]
+macro_rules! test_delay {
+    () => {
+        crate::delays::pause()
+    };
+}
+
+#[cfg(not(feature = "test-delays"))]
+macro_rules! test_delay {
+    () => {};
+}
+
+mod affinity;
+pub mod arith;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "bloom")]
+pub mod bloom;
+pub mod budget;
+pub mod cache;
+pub mod classic;
+#[cfg(feature = "parquet")]
+pub mod columnar;
+#[cfg(feature = "test-delays")]
+mod delays;
+#[cfg(feature = "ecpp")]
+pub mod ecpp;
+pub mod error;
+pub mod factor;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod field;
+pub mod format;
+pub mod json;
+pub mod marks;
+pub mod narrow;
+pub mod pi;
+pub mod segmented;
+pub mod sieve;
+mod simd;
+pub mod spiral;
+pub mod sublinear;
+pub mod verify;
+
+pub use arith::{
+    hexagonal_root, is_prime, nearest_prime, next_prime, number_of_divisors_up_to, prime_hexagonal_numbers,
+    prime_recurrence_fibonacci,
+};
+#[cfg(feature = "bloom")]
+pub use bloom::BloomFilter;
+pub use budget::{memory_usage_estimate, plan_within, primes_up_to_within, Plan, MIN_SEGMENT_SIZE};
+pub use cache::{cached_primes, clear_sieve_cache, CachedPrimes};
+pub use classic::{prime_gen_trial_division, sieve_of_atkin, sundaram_sieve};
+#[cfg(feature = "ecpp")]
+pub use ecpp::{ecpp_verify_certificate, point_add, point_double, scalar_mul, EcppStep, EllipticCurve, Point};
+pub use error::SieveError;
+pub use factor::{
+    divisor_count, divisor_sum, factor_tree, factorize, factorize_map, is_prime_power, pollard_p1,
+    quadratic_sieve_factor, FactorMethod, FactorTree,
+};
+pub use field::{discrete_root, mod_prime_field, tonelli_shanks, PrimeField};
+// `#[derive(PrimeField)]` for a field with its prime fixed at compile time; the derive lives in
+// the macro namespace, beside the runtime `PrimeField` type.
+#[cfg(feature = "derive")]
+pub use eratosthenes_derive::PrimeField;
+pub use format::{
+    decompress, read_delta_primes, read_prime_file, rle_decode_primes, rle_encode_primes, write_delta_primes,
+    write_prime_file, DeltaEncoder, DeltaReader, PrimeFileReader,
+};
+pub use json::SIEVE_JSON_VERSION;
+pub use marks::{primes_up_to_in, sieve_each_in, try_primes_up_to_in, GlobalMarks, MarkAllocator};
+pub use narrow::primes_up_to_u32;
+pub use pi::{compute_pi_x_meissel_lehmer, prime_rank};
+pub use segmented::{
+    first_k_primes, nth_prime, nth_prime_upper_bound, nth_primes, prime_enumerator_infinite, prime_gap_positions,
+    prime_search_parallel, range_sieve_parallel, range_sieve_parallel_with, segmented_sieve_each,
+    segmented_sieve_range_each, segmented_sieve_range_each_with, sieve_segments_ordered, BasePrimes, SegmentedSieve,
+    Segments, DEFAULT_SEGMENT_SIZE,
+};
+pub use sieve::{find_constellation, merge_sieves, prime_triplet_primes, GapStats, Primes, Sieve, SieveBuilder};
+#[cfg(feature = "rayon")]
+pub use sieve::par_primes_up_to;
+pub use spiral::{ulam_bounds, ulam_position, ulam_value};
+pub use sublinear::nth_prime_fast;
+pub use verify::{reference_primes_up_to, verify, verify_goldbach, verify_with_threads, Mismatch, VERIFY_LIMIT};
+
 type DispatchFn = dyn Fn() -> bool + Send + 'static;
 
+struct DispatchState {
+    queue: VecDeque<Box<DispatchFn>>,
+    quit: bool,
+    is_finished: bool,
+    is_started: bool,
+    result: bool,
+    running: usize,
+}
+
+struct DispatchShared {
+    state: Mutex<DispatchState>,
+    cv: Condvar,
+    cv_finished: Condvar,
+    // Mirrors state.queue.len(), so spinning workers can poll it without taking the lock.
+    queued: AtomicUsize,
+    spin_before_park: u32,
+}
+
+// How a dispatch queue's workers wait and where they run.
+#[derive(Debug, Clone, Copy, Default)]
+struct QueueConfig {
+    // Polls for the next task before parking on the condvar; 0 parks at once.
+    spin_before_park: u32,
+    // Pins worker i to the i-th CPU the process may use, with the `thread-affinity` feature on
+    // Linux; a no-op elsewhere.
+    pin_to_core: bool,
+}
+
 struct DispatchQueue {
     threads: Vec<thread::JoinHandle<()>>,
-    queue: Arc<(Mutex<VecDeque<Box<DispatchFn>>>, Condvar)>,
-    quit: Arc<(Mutex<bool>, Condvar)>,
-    is_finished: Arc<(Mutex<bool>, Condvar)>,
-    is_started: Arc<(Mutex<bool>, Condvar)>,
-    result: Arc<(Mutex<bool>, Condvar)>,
+    shared: Arc<DispatchShared>,
 }
 
 impl DispatchQueue {
-    fn new(n: usize) -> Self {
-        let queue = Arc::new((Mutex::new(VecDeque::new()), Condvar::new()));
-        let quit = Arc::new((Mutex::new(false), Condvar::new()));
-        let is_finished = Arc::new((Mutex::new(true), Condvar::new()));
-        let is_started = Arc::new((Mutex::new(false), Condvar::new()));
-        let result = Arc::new((Mutex::new(false), Condvar::new()));
+    fn new(n: usize, config: QueueConfig) -> Self {
+        Self::with_capacity(n, config, 0)
+    }
+
+    // Like new, with room for `capacity` tasks queued at once before the queue has to grow,
+    // which it would otherwise do under the lock while workers wait on it.
+    fn with_capacity(n: usize, config: QueueConfig, capacity: usize) -> Self {
+        let spin_before_park = config.spin_before_park;
+        debug!("starting dispatch queue with {} worker threads, spinning {} times before parking", n, spin_before_park);
+        let shared = Arc::new(DispatchShared {
+            state: Mutex::new(DispatchState {
+                queue: VecDeque::with_capacity(capacity),
+                quit: false,
+                is_finished: true,
+                is_started: false,
+                result: false,
+                running: 0,
+            }),
+            cv: Condvar::new(),
+            cv_finished: Condvar::new(),
+            queued: AtomicUsize::new(0),
+            spin_before_park,
+        });
+
+        let cpus = if config.pin_to_core { affinity::allowed_cpus() } else { Vec::new() };
+        if config.pin_to_core && cpus.is_empty() {
+            debug!("thread pinning requested but unsupported here; workers are left unpinned");
+        }
+        if !cpus.is_empty() && n > cpus.len() {
+            warn!("pinning {} workers to {} CPUs: some will share a core", n, cpus.len());
+        }
 
         let mut threads = Vec::new();
-        for _ in 0..n {
-            let queue = Arc::clone(&queue);
-            let quit = Arc::clone(&quit);
-            let is_finished = Arc::clone(&is_finished);
-            let is_started = Arc::clone(&is_started);
-            let result = Arc::clone(&result);
+        for i in 0..n {
+            let shared = Arc::clone(&shared);
+            let cpu = (!cpus.is_empty()).then(|| cpus[i % cpus.len()]);
 
             let handle = thread::spawn(move || {
-                DispatchQueue::dispatch_thread_handler(queue, quit, is_finished, is_started, result);
+                if let Some(cpu) = cpu {
+                    if !affinity::pin_current_thread(cpu) {
+                        debug!("could not pin a worker to CPU {}", cpu);
+                    }
+                }
+                DispatchQueue::dispatch_thread_handler(shared);
             });
 
             threads.push(handle);
         }
 
-        DispatchQueue {
-            threads,
-            queue,
-            quit,
-            is_finished,
-            is_started,
-            result,
-        }
-    }
-
-    fn reset_result(&self) {
-        let (lock, _) = &*self.result;
-        let mut result = lock.lock().unwrap();
-        *result = false;
+        DispatchQueue { threads, shared }
     }
 
     fn dispatch(&self, op: Box<DispatchFn>) {
-        let (lock, cvar) = &*self.queue;
-        let mut queue = lock.lock().unwrap();
-        queue.push_back(op);
-
-        let (lock, _cvar) = &*self.is_finished;
-        let mut is_finished = lock.lock().unwrap();
-        *is_finished = false;
-
-        let (lock, _cvar) = &*self.is_started;
-        let mut is_started = lock.lock().unwrap();
-        if !*is_started {
-            *is_started = true;
-            cvar.notify_all();
+        let mut state = self.shared.state.lock().unwrap();
+        if state.quit {
+            return;
         }
-    }
+        state.queue.push_back(op);
+        self.shared.queued.fetch_add(1, Ordering::Relaxed);
+        state.is_finished = false;
+        state.is_started = true;
 
-    fn finish(&self) -> bool {
-        let (lock, _cvar) = &*self.quit;
-        let quit = lock.lock().unwrap();
-        if *quit {
-            return false;
-        }
+        // Unlock before notifying, so the woken worker doesn't immediately block on the lock.
+        drop(state);
+        test_delay!();
+        self.shared.cv.notify_one();
+    }
 
-        let (lock, _cvar) = &*self.is_finished;
-        let is_finished = lock.lock().unwrap();
-        if *is_finished {
-            return false;
+    // Waits for the tasks dispatched since the last finish. `None` if there were none; otherwise
+    // the OR of every task's result, which may already be in if they all completed.
+    fn finish(&self) -> Option<bool> {
+        test_delay!();
+        let mut state = self.shared.state.lock().unwrap();
+        if !state.is_started {
+            return None;
         }
 
-        let (lock, cvar) = &*self.is_finished;
-        let mut is_finished = lock.lock().unwrap();
-        while !*is_finished {
-            is_finished = cvar.wait(is_finished).unwrap();
+        while !(state.is_finished || state.quit) {
+            state = self.shared.cv_finished.wait(state).unwrap();
         }
 
-        let (lock, _cvar) = &*self.result;
-        let result = lock.lock().unwrap();
-        *result
+        state.is_started = false;
+        Some(state.result)
     }
 
     fn dump(&self) {
-        let (lock, _cvar) = &*self.quit;
-        let quit = lock.lock().unwrap();
-        if *quit {
+        test_delay!();
+        let mut state = self.shared.state.lock().unwrap();
+        if state.quit || state.is_finished {
             return;
         }
 
-        let (lock, _cvar) = &*self.is_finished;
-        let is_finished = lock.lock().unwrap();
-        if *is_finished {
-            return;
-        }
-
-        let (lock, _cvar) = &*self.queue;
-        let mut queue = lock.lock().unwrap();
-        queue.clear();
-
-        let (lock, cvar) = &*self.is_finished;
-        let mut is_finished = lock.lock().unwrap();
-        *is_finished = true;
-        cvar.notify_all();
+        state.queue.clear();
+        self.shared.queued.store(0, Ordering::Relaxed);
+        state.is_finished = true;
+        drop(state);
+        self.shared.cv_finished.notify_all();
     }
 
-    fn dispatch_thread_handler(
-        queue: Arc<(Mutex<VecDeque<Box<DispatchFn>>>, Condvar)>,
-        quit: Arc<(Mutex<bool>, Condvar)>,
-        is_finished: Arc<(Mutex<bool>, Condvar)>,
-        is_started: Arc<(Mutex<bool>, Condvar)>,
-        result: Arc<(Mutex<bool>, Condvar)>
-    ) {
-        let (lock, cvar) = &*queue;
-        let (lock_quit, _cvar_quit) = &*quit;
-        let (lock_is_finished, cvar_is_finished) = &*is_finished;
-        let (lock_is_started, _cvar_is_started) = &*is_started;
-        let (lock_result, _cvar_result) = &*result;
+    fn dispatch_thread_handler(shared: Arc<DispatchShared>) {
+        let mut state = shared.state.lock().unwrap();
 
         loop {
-            let mut queue = lock.lock().unwrap();
-            while queue.is_empty() {
-                let is_started = lock_is_started.lock().unwrap();
-                if !*is_started {
-                    queue = cvar.wait(queue).unwrap();
-                } else {
-                    let quit = lock_quit.lock().unwrap();
-                    if *quit {
-                        return;
+            // Spin a while for the next task before parking; a condvar wake-up costs more than
+            // a very short task.
+            if state.queue.is_empty() && !state.quit && shared.spin_before_park > 0 {
+                drop(state);
+                for _ in 0..shared.spin_before_park {
+                    if shared.queued.load(Ordering::Relaxed) > 0 {
+                        break;
                     }
-                    queue = cvar.wait(queue).unwrap();
+                    hint::spin_loop();
                 }
+                test_delay!();
+                state = shared.state.lock().unwrap();
             }
 
-            let op = queue.pop_front().unwrap();
+            // Wait until we have work or a quit signal.
+            while state.queue.is_empty() && !state.quit {
+                state = shared.cv.wait(state).unwrap();
+            }
+            if state.quit {
+                return;
+            }
 
-            drop(queue);
+            let op = state.queue.pop_front().unwrap();
+            shared.queued.fetch_sub(1, Ordering::Relaxed);
+            state.running += 1;
+            drop(state);
 
-            let mut result = lock_result.lock().unwrap();
-            *result |= op();
+            test_delay!();
+            let result = op();
+            test_delay!();
 
-            let mut quit = lock_quit.lock().unwrap();
-            *quit |= *result;
+            state = shared.state.lock().unwrap();
+            state.running -= 1;
+            state.result |= result;
+            state.quit |= state.result;
 
-            let mut is_finished = lock_is_finished.lock().unwrap();
-            if queue.is_empty() {
-                *is_finished = true;
-                cvar_is_finished.notify_all();
+            // The queue is only finished once no worker is still running an earlier task.
+            if state.quit || (state.queue.is_empty() && state.running == 0) {
+                state.is_finished = true;
+                shared.cv_finished.notify_all();
+            }
+            if state.quit {
+                shared.cv.notify_all();
+            }
+            // Let others in between a task's completion and this worker's next pick.
+            #[cfg(feature = "test-delays")]
+            {
+                drop(state);
+                test_delay!();
+                state = shared.state.lock().unwrap();
             }
         }
     }
 }
 
+impl Drop for DispatchQueue {
+    fn drop(&mut self) {
+        test_delay!();
+        let mut state = self.shared.state.lock().unwrap();
+        state.queue.clear();
+        state.quit = true;
+        drop(state);
+        self.shared.cv.notify_all();
+
+        // Wait for the workers to finish before we exit.
+        for handle in self.threads.drain(..) {
+            handle.join().unwrap();
+        }
+    }
+}
+
 fn backward(n: u64) -> u64 {
     ((!(!n | 1)) / 3) + 1
 }
@@ -178,42 +368,249 @@ fn backward5(n: u64) -> usize {
     ((n + 1) >> 1) as usize
 }
 
-fn get_wheel5_increment(wheel5: &mut u32) -> usize {
-    let mut wheel_increment = 0;
-    let mut is_wheel_multiple;
-    loop {
-        is_wheel_multiple = *wheel5 & 1 == 1;
-        *wheel5 >>= 1;
-        if is_wheel_multiple {
-            *wheel5 |= 1 << 9;
+fn forward5(i: usize) -> u64 {
+    const WHEEL30: [u64; 8] = [1, 7, 11, 13, 17, 19, 23, 29];
+    let i = i - 1;
+    ((i / 8) as u64).saturating_mul(30).saturating_add(WHEEL30[i % 8])
+}
+
+fn checked_backward5(n: u64) -> Option<usize> {
+    let n = n as u128;
+    let n = ((n + 1) << 2) / 5;
+    let n = ((n + 1) << 1) / 3;
+    usize::try_from((n + 1) >> 1).ok()
+}
+
+// backward5 for any value, including those past 2^62 where its shifts overflow.
+fn wheel_position(v: u64) -> usize {
+    if v < 1 << 62 {
+        backward5(v)
+    } else {
+        checked_backward5(v).expect("a wheel position fits in usize")
+    }
+}
+
+/// An upper bound on the number of primes up to `n`, from Rosser and Schoenfeld:
+/// `pi(x) < 1.25506 x / ln(x)` for `x > 1`.
+#[must_use]
+pub fn prime_count_upper_bound(n: u64) -> usize {
+    if n < 2 {
+        return 0;
+    }
+    let n = n as f64;
+    (1.25506 * n / n.ln()) as usize + 1
+}
+
+fn try_alloc<T: Clone>(len: usize, value: T, n: u64) -> Result<Vec<T>, SieveError> {
+    debug!("allocating {} bytes of sieve storage for n = {}", len.saturating_mul(std::mem::size_of::<T>()), n);
+    let mut v = Vec::new();
+    v.try_reserve_exact(len).map_err(|_| SieveError::AllocationFailed { n })?;
+    v.resize(len, value);
+    Ok(v)
+}
+
+// Over ten consecutive 6k +/- 1 candidates (30 numbers), the set bits are the multiples of 5.
+const WHEEL5_PATTERN: u32 = (1 << 7) | 1;
+
+// For each position in the 10-candidate cycle: how many candidates to step to pass the next
+// multiple of 5, and the position that leaves the wheel at.
+const WHEEL5_STEPS: [(usize, usize); 10] = {
+    let mut steps = [(0, 0); 10];
+    let mut position = 0;
+    while position < 10 {
+        let mut increment = 1;
+        while (WHEEL5_PATTERN >> ((position + increment - 1) % 10)) & 1 == 1 {
+            increment += 1;
         }
-        wheel_increment += 1;
-        if !is_wheel_multiple {
-            break;
+        steps[position] = (increment, (position + increment) % 10);
+        position += 1;
+    }
+    steps
+};
+
+// The primes laid into fresh marks by `presieve` rather than marked one multiple at a time.
+const PRESIEVE_PRIMES: [u64; 3] = [7, 11, 13];
+// Wheel positions before the multiples of all three line up again: 8 per 30 numbers, over
+// 30 * 7 * 11 * 13.
+const PRESIEVE_PERIOD: usize = 8 * 7 * 11 * 13;
+
+// The marks for wheel positions 1..=PRESIEVE_PERIOD with only the multiples of PRESIEVE_PRIMES
+// set, the primes included.
+static PRESIEVE_PATTERN: [bool; PRESIEVE_PERIOD] = {
+    const WHEEL30: [u64; 8] = [1, 7, 11, 13, 17, 19, 23, 29];
+    let mut pattern = [false; PRESIEVE_PERIOD];
+    let mut i = 0;
+    while i < PRESIEVE_PERIOD {
+        let v = (i / 8) as u64 * 30 + WHEEL30[i % 8];
+        pattern[i] = v.is_multiple_of(7) || v.is_multiple_of(11) || v.is_multiple_of(13);
+        i += 1;
+    }
+    pattern
+};
+
+// Sets the marks of the multiples of PRESIEVE_PRIMES, whatever was there before, in marks whose
+// first flag is wheel position `first` (at least 1). The pattern repeats, so this is a run of
+// copies, which the standard library vectorizes with whatever the target offers, rather than a
+// division per multiple; every other mark is cleared. The primes themselves are left unmarked.
+fn presieve(marks: &mut [bool], first: usize) {
+    let mut offset = (first - 1) % PRESIEVE_PERIOD;
+    let mut start = 0;
+    while start < marks.len() {
+        let len = (PRESIEVE_PERIOD - offset).min(marks.len() - start);
+        marks[start..start + len].copy_from_slice(&PRESIEVE_PATTERN[offset..offset + len]);
+        start += len;
+        offset = 0;
+    }
+    for p in PRESIEVE_PRIMES {
+        if let Some(flag) = backward5(p).checked_sub(first).and_then(|i| marks.get_mut(i)) {
+            *flag = false;
         }
     }
-    wheel_increment
 }
 
-fn sieve_of_eratosthenes(n: u64) -> Vec<u64> {
-    let mut known_primes = vec![2, 3, 5];
+// `wheel5` is the position in WHEEL5_STEPS, starting at 0.
+fn get_wheel5_increment(wheel5: &mut usize) -> usize {
+    let (increment, next) = WHEEL5_STEPS[*wheel5];
+    *wheel5 = next;
+    increment
+}
+
+pub fn sieve_of_eratosthenes(n: u64) -> Result<Vec<u64>, SieveError> {
+    sieve_where(n, |_| true, prime_count_upper_bound(n))
+}
+
+fn sieve_where<F: Fn(u64) -> bool>(n: u64, pred: F, capacity: usize) -> Result<Vec<u64>, SieveError> {
+    let mut known_primes = Vec::new();
+    known_primes
+        .try_reserve_exact(capacity)
+        .map_err(|_| SieveError::AllocationFailed { n })?;
+    sieve_each(n, |p| {
+        if pred(p) {
+            known_primes.push(p);
+        }
+        ControlFlow::Continue(())
+    })?;
+    Ok(known_primes)
+}
+
+/// Feeds the primes up to and including `n` to `emit` in ascending order, as soon as each is
+/// final, stopping early (and abandoning outstanding marking) if `emit` breaks.
+pub fn sieve_each<F: FnMut(u64) -> ControlFlow<()>>(n: u64, emit: F) -> Result<(), SieveError> {
+    sieve_each_cancellable(n, &AtomicBool::new(false), emit)
+}
+
+/// Like [`sieve_each`], but also stops, without an error, once `cancel` is set. The flag is
+/// checked before each base prime is marked, so a stop is honoured even while no primes are being
+/// emitted; whatever was emitted up to then is correct and in order.
+pub fn sieve_each_cancellable<F: FnMut(u64) -> ControlFlow<()>>(
+    n: u64,
+    cancel: &AtomicBool,
+    emit: F,
+) -> Result<(), SieveError> {
+    sieve_each_threaded(n, 0, cancel, emit)
+}
+
+/// The marking phase of the wheel sieve on its own: the marks up to `n` from `threads` workers (0
+/// means all of them), returned as a [`Sieve`] with no prime collected. Walking
+/// [`Sieve::primes`] is the collection phase, so timing the two apart shows where a bound
+/// spends its time; `src/bin/phases.rs` does exactly that.
+pub fn sieve_bitmap_only(n: u64, threads: usize) -> Result<Sieve, SieveError> {
+    Sieve::with_threads(n, threads)
+}
+
+// Whether this build may spawn threads at all. Plain wasm32 has none (std's thread::spawn panics
+// there), and the `single-thread` feature opts out anywhere else. Without them every parallel
+// entry point does its work inline on the calling thread, whatever thread count it is given, and
+// no dispatch queue is ever started.
+const THREADS: bool =
+    !cfg!(any(feature = "single-thread", all(target_family = "wasm", not(target_feature = "atomics"))));
+
+/// The number of hardware threads, which marking uses unless told otherwise; always 1 where
+/// threads are unavailable (plain wasm32, or with the `single-thread` feature).
+#[must_use]
+pub fn default_threads() -> usize {
+    if !THREADS {
+        return 1;
+    }
+    available_parallelism().map_or(1, |threads| threads.get())
+}
+
+// A caller's thread count made real: 0 picks default_threads, and without threads it is always 1.
+fn worker_threads(threads: usize) -> usize {
+    if threads == 0 || !THREADS {
+        default_threads()
+    } else {
+        threads
+    }
+}
+
+/// Like [`sieve_each_cancellable`], with the marking spread over `threads` workers; 0 picks
+/// [`default_threads`]. One thread marks inline without a queue.
+pub fn sieve_each_threaded<F: FnMut(u64) -> ControlFlow<()>>(
+    n: u64,
+    threads: usize,
+    cancel: &AtomicBool,
+    mut emit: F,
+) -> Result<(), SieveError> {
+    let threads = worker_threads(threads);
+    let mut count = 0u64;
+    let _ = sieve_each_marked(n, threads, QueueConfig::default(), true, cancel, &GlobalMarks, |p| {
+        count += 1;
+        emit(p)
+    })?;
+    debug!("sieve up to {} emitted {} primes", n, count);
+    Ok(())
+}
+
+// Runs the sieve in marks from `alloc`, returning them (indexed by backward5) if it ran to
+// completion with a non-empty wheel range. The workers, if more than one, wait and run as
+// `config` says. Without `collect` only the marking runs and nothing reaches `emit`.
+fn sieve_each_marked<A: MarkAllocator, F: FnMut(u64) -> ControlFlow<()>>(
+    n: u64,
+    threads: usize,
+    config: QueueConfig,
+    collect: bool,
+    cancel: &AtomicBool,
+    alloc: &A,
+    mut emit: F,
+) -> Result<Option<A::Marks>, SieveError> {
+    let small_primes = [2, 3, 5];
     if n < 2 {
-        return Vec::new();
+        return Ok(None);
+    }
+    for &p in small_primes.iter().filter(|&&p| p <= n && collect) {
+        if emit(p).is_break() {
+            return Ok(None);
+        }
     }
-    if n < known_primes.last().unwrap() + 2 {
-        let highest_prime_it = known_primes.iter().position(|&x| x > n).unwrap();
-        return known_primes[..highest_prime_it].to_vec();
+    if n < small_primes.last().unwrap() + 2 {
+        return Ok(None);
     }
 
-    let cardinality = backward5(n);
+    let cardinality = checked_backward5(n)
+        .and_then(|c| c.checked_add(1))
+        .ok_or(SieveError::AllocationFailed { n })?;
 
-    let not_prime = Arc::new(Mutex::new(vec![false; cardinality + 1]));
+    debug!("allocating {} bytes of marks for n = {}", cardinality, n);
+    let mut marks = alloc.allocate_marks(cardinality).ok_or(SieveError::AllocationFailed { n })?;
+    presieve(&mut marks[1..], 1);
+    let not_prime = SharedMarks::new(marks);
 
     let mut thread_boundary = 36;
-    let mut wheel5 = (1 << 7) | 1;
+    let mut wheel5 = 0;
     let mut o = 1;
+    let mut collected = WheelWalk::new();
 
-    let dispatch = DispatchQueue::new(available_parallelism().unwrap().get());
+    // With a single thread, the queue's handshakes buy nothing: mark inline instead. Otherwise
+    // there is a task per base prime, and without barriers they are all queued at once.
+    let dispatch = if threads > 1 {
+        Some(DispatchQueue::with_capacity(threads, config, prime_count_upper_bound(n.isqrt())))
+    } else {
+        None
+    };
+    if dispatch.is_none() {
+        debug!("single thread available, marking inline");
+    }
 
     loop {
         o += get_wheel5_increment(&mut wheel5);
@@ -221,69 +618,576 @@ fn sieve_of_eratosthenes(n: u64) -> Vec<u64> {
         if p * p > n {
             break;
         }
-        if thread_boundary < p {
-            dispatch.finish();
-            thread_boundary *= thread_boundary;
+        if cancel.load(Ordering::Relaxed) {
+            debug!("sieve up to {} cancelled while marking p = {}", n, p);
+            if let Some(dispatch) = &dispatch {
+                dispatch.dump();
+            }
+            return Ok(None);
         }
-        let not_prime = Arc::clone(&not_prime);
-        let p_clone = p;
-        dispatch.dispatch(Box::new(move || {
-            let p2 = p_clone << 1;
-            let p4 = p_clone << 2;
-            let mut i = p_clone * p_clone;
-
-            if p_clone % 3 == 2 {
-                let mut not_prime = not_prime.lock().unwrap();
-                not_prime[backward5(i)] = true;
-                i += p2;
-                if i > n {
-                    return false;
-                }
+        if collect && thread_boundary < p {
+            if let Some(dispatch) = &dispatch {
+                dispatch.finish();
             }
-
-            loop {
-                let mut not_prime = not_prime.lock().unwrap();
-                if i % 5 != 0 {
-                    not_prime[backward5(i)] = true;
-                }
-                i += p4;
-                if i > n {
-                    return false;
-                }
-                if i % 5 != 0 {
-                    not_prime[backward5(i)] = true;
-                }
-                i += p2;
-                if i > n {
-                    return false;
+            trace!("marking barrier at p = {}, emitting primes below {}", p, p * p);
+            thread_boundary *= thread_boundary;
+            // Every base prime below p is fully marked, so everything below p * p is final.
+            // SAFETY: the queue is finished and nothing new is dispatched until this is done.
+            let settled = unsafe { not_prime.settled() };
+            if collected.emit_up_to(p * p - 1, settled, &mut emit).is_break() {
+                if let Some(dispatch) = &dispatch {
+                    dispatch.dump();
                 }
+                return Ok(None);
             }
-        }));
+        }
+        match &dispatch {
+            _ if PRESIEVE_PRIMES.contains(&p) => {}
+            Some(dispatch) => {
+                let not_prime = Arc::clone(&not_prime);
+                dispatch.dispatch(Box::new(move || {
+                    mark_multiples(p, n, |i| not_prime.set(i));
+                    false
+                }));
+            }
+            None => mark_multiples(p, n, |i| not_prime.set(i)),
+        }
     }
 
-    dispatch.finish();
+    if let Some(dispatch) = &dispatch {
+        dispatch.finish();
+    }
+    trace!("marking finished, emitting remaining primes up to {}", n);
+    if cancel.load(Ordering::Relaxed) {
+        return Ok(None);
+    }
+
+    // Joining the workers drops the last of their handles on the marks.
+    drop(dispatch);
+    let Some(not_prime) = SharedMarks::into_inner(not_prime) else {
+        unreachable!("the marking workers are joined")
+    };
+    if collect && collected.emit_up_to(n, &not_prime, &mut emit).is_break() {
+        return Ok(None);
+    }
+
+    Ok(Some(not_prime))
+}
+
+// Marks the odd multiples of p from p * p up to n, skipping those divisible by 3 or 5.
+fn mark_multiples<M: FnMut(usize)>(p: u64, n: u64, mut mark: M) {
+    let p2 = p << 1;
+    let p4 = p << 2;
+    let mut i = p * p;
+
+    if p % 3 == 2 {
+        mark(backward5(i));
+        i += p2;
+        if i > n {
+            return;
+        }
+    }
 
     loop {
-        let p = forward(o);
-        if p > n {
-            break;
+        if !i.is_multiple_of(5) {
+            mark(backward5(i));
         }
-        o += get_wheel5_increment(&mut wheel5);
-        let not_prime = not_prime.lock().unwrap();
-        if !not_prime[backward5(p)] {
-            known_primes.push(p);
+        i += p4;
+        if i > n {
+            return;
+        }
+        if !i.is_multiple_of(5) {
+            mark(backward5(i));
+        }
+        i += p2;
+        if i > n {
+            return;
         }
     }
+}
 
-    known_primes
+// Resumable walk over the wheel candidates, for emitting primes in order as they become final.
+struct WheelWalk {
+    o: usize,
+    wheel5: usize,
 }
 
-fn main() {
-    let n; // = 1000000000;
-    println!("Count primes up to number: ");
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input).unwrap();
-    n = input.trim().parse().unwrap();
-    println!("Following is the count of prime numbers smaller than or equal to {}:", n);
-    println!("{}", sieve_of_eratosthenes(n).len());
+impl WheelWalk {
+    fn new() -> Self {
+        WheelWalk { o: 1, wheel5: 0 }
+    }
+
+    // A walk whose next candidate is the first at or after `p` (at least 7). The wheel position
+    // is always (o - 1) % 10, whether or not candidate o itself is a multiple of 5.
+    fn starting_at(p: u64) -> Self {
+        // backward is exact on candidates and can land one short of p between them.
+        let mut o = backward(p) as usize;
+        while forward(o) < p {
+            o += 1;
+        }
+        WheelWalk { o: o - 1, wheel5: (o - 2) % 10 }
+    }
+
+    // Steps to the next wheel candidate and returns it.
+    fn advance(&mut self) -> u64 {
+        self.o += get_wheel5_increment(&mut self.wheel5);
+        forward(self.o)
+    }
+
+    fn emit_up_to<F: FnMut(u64) -> ControlFlow<()>>(
+        &mut self,
+        limit: u64,
+        not_prime: &[bool],
+        emit: &mut F,
+    ) -> ControlFlow<()> {
+        loop {
+            let mut wheel5 = self.wheel5;
+            let o = self.o + get_wheel5_increment(&mut wheel5);
+            let p = forward(o);
+            if p > limit {
+                return ControlFlow::Continue(());
+            }
+            self.o = o;
+            self.wheel5 = wheel5;
+            if !not_prime[backward5(p)] {
+                emit(p)?;
+            }
+        }
+    }
+}
+
+/// Returns the primes up to and including `n`, panicking if the sieve cannot be allocated.
+#[must_use]
+pub fn primes_up_to(n: u64) -> Vec<u64> {
+    sieve_of_eratosthenes(n).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// The number of primes up to and including `n`, counted as they stream past without being
+/// stored; panics if the sieve cannot be allocated.
+#[must_use]
+pub fn count_primes_up_to(n: u64) -> u64 {
+    let mut count = 0;
+    sieve_each(n, |_| {
+        count += 1;
+        ControlFlow::Continue(())
+    })
+    .unwrap_or_else(|e| panic!("{}", e));
+    count
+}
+
+/// The number of primes in `lo..=hi`, sieving only the range and the base primes up to
+/// `sqrt(hi)`; panics if the sieve cannot be allocated.
+#[must_use]
+pub fn count_primes_in_range(lo: u64, hi: u64) -> u64 {
+    let mut count = 0;
+    if lo <= hi {
+        segmented_sieve_range_each(lo, hi, |_| {
+            count += 1;
+            ControlFlow::Continue(())
+        })
+        .unwrap_or_else(|e| panic!("{}", e));
+    }
+    count
+}
+
+/// Whether `lo..=hi` holds no primes at all: a prime desert, such as the gaps between
+/// consecutive primes. The range is sieved a small segment at a time from `lo` and the search
+/// stops at the first prime, so a wide window that does hold one costs about a segment rather
+/// than a full count. An empty range (`lo > hi`) is prime-free.
+///
+/// ```
+/// use eratosthenes::is_prime_free;
+///
+/// // 23 and 29 are consecutive primes.
+/// assert!(is_prime_free(24, 28));
+/// assert!(!is_prime_free(24, 29));
+/// ```
+///
+/// # Panics
+///
+/// If the base primes up to `sqrt(hi)` cannot be allocated.
+#[must_use]
+pub fn is_prime_free(lo: u64, hi: u64) -> bool {
+    if lo > hi {
+        return true;
+    }
+    let mut segments = Segments::new(hi, MIN_SEGMENT_SIZE).unwrap_or_else(|e| panic!("{}", e));
+    let mut in_range = |p| if p < lo { ControlFlow::Continue(()) } else { ControlFlow::Break(()) };
+    !(segments.segment_containing(lo)..segments.len()).any(|k| segments.sieve_segment(k, &mut in_range).is_break())
+}
+
+/// [`count_primes_in_range`] for each of `ranges` (inclusive, as `(lo, hi)`), for building
+/// tables of pi. The base primes are sieved once, up to the square root of the highest bound,
+/// and the ranges are handed out whole to the dispatch queue's workers, so the parallelism is
+/// across ranges rather than within one sieve. An empty range (`lo > hi`) counts 0.
+///
+/// ```
+/// use eratosthenes::{count_primes_in_range, count_primes_in_ranges};
+///
+/// let ranges = [(0, 100), (1_000_000, 2_000_000), (10, 1), (999_999_000, 1_000_000_000)];
+/// let counts = count_primes_in_ranges(&ranges);
+/// assert_eq!(counts, [25, 70_435, 0, 45]);
+/// assert!(ranges.iter().zip(&counts).all(|(&(lo, hi), &count)| count_primes_in_range(lo, hi) == count));
+/// ```
+///
+/// # Panics
+///
+/// If a range's sieve cannot be allocated.
+#[must_use]
+pub fn count_primes_in_ranges(ranges: &[(u64, u64)]) -> Vec<u64> {
+    let max = ranges.iter().filter(|&&(lo, hi)| lo <= hi).map(|&(_, hi)| hi).max().unwrap_or(0);
+    let base = Arc::new(BasePrimes::for_range(max).unwrap_or_else(|e| panic!("{}", e)));
+
+    // One worker counts the ranges inline, without a queue.
+    let workers = default_threads().min(ranges.len());
+    if workers <= 1 {
+        return ranges
+            .iter()
+            .map(|&(lo, hi)| count_range(&base, lo, hi).unwrap_or_else(|e| panic!("{}", e)))
+            .collect();
+    }
+
+    let counts: Arc<Vec<AtomicU64>> = Arc::new(ranges.iter().map(|_| AtomicU64::new(0)).collect());
+    let failure = Arc::new(Mutex::new(None));
+    let dispatch = DispatchQueue::new(workers, QueueConfig::default());
+    for (i, &(lo, hi)) in ranges.iter().enumerate().filter(|&(_, &(lo, hi))| lo <= hi) {
+        let (base, counts, failure) = (Arc::clone(&base), Arc::clone(&counts), Arc::clone(&failure));
+        dispatch.dispatch(Box::new(move || match count_range(&base, lo, hi) {
+            Ok(count) => {
+                counts[i].store(count, Ordering::Relaxed);
+                false
+            }
+            // Stops the queue: the other ranges are not worth finishing.
+            Err(e) => {
+                *failure.lock().unwrap() = Some(e);
+                true
+            }
+        }));
+    }
+    dispatch.finish();
+    drop(dispatch);
+
+    if let Some(e) = failure.lock().unwrap().take() {
+        panic!("{}", e);
+    }
+    counts.iter().map(|count| count.load(Ordering::Relaxed)).collect()
+}
+
+// The primes in lo..=hi, counted against `base`; 0 for an empty range.
+fn count_range(base: &BasePrimes, lo: u64, hi: u64) -> Result<u64, SieveError> {
+    let mut count = 0;
+    if lo <= hi {
+        segmented_sieve_range_each_with(base, lo, hi, |_| {
+            count += 1;
+            ControlFlow::Continue(())
+        })?;
+    }
+    Ok(count)
+}
+
+/// [`count_primes_up_to`] on a background thread, with the fraction of the range covered sent on
+/// the returned channel as the sieve advances (ending at 1.0). Updates are dropped rather than
+/// queued when the receiver falls behind, and the sieve carries on if it is dropped, so the
+/// channel never needs draining. Join the handle for the count; it carries the panic if the
+/// sieve cannot be allocated.
+///
+/// Missing where threads are unavailable: plain wasm32, or with the `single-thread` feature.
+#[cfg(not(any(feature = "single-thread", all(target_family = "wasm", not(target_feature = "atomics")))))]
+#[must_use]
+pub fn count_primes_with_progress(n: u64) -> (thread::JoinHandle<u64>, std::sync::mpsc::Receiver<f64>) {
+    // Progress updates sent over a whole run.
+    const PROGRESS_STEPS: u64 = 100;
+    let (sender, receiver) = std::sync::mpsc::sync_channel(PROGRESS_STEPS as usize + 1);
+    let handle = thread::spawn(move || {
+        let step = (n / PROGRESS_STEPS).max(1);
+        let mut next_report = step;
+        let mut count = 0;
+        sieve_each(n, |p| {
+            count += 1;
+            if p >= next_report {
+                let _ = sender.try_send(p as f64 / n as f64);
+                next_report = (p / step + 1).saturating_mul(step);
+            }
+            ControlFlow::Continue(())
+        })
+        .unwrap_or_else(|e| panic!("{}", e));
+        let _ = sender.try_send(1.0);
+        count
+    });
+    (handle, receiver)
+}
+
+/// Returns the primes up to and including `n` for which `pred` holds, filtered during collection.
+#[must_use]
+pub fn primes_up_to_where(n: u64, pred: impl Fn(u64) -> bool) -> Vec<u64> {
+    sieve_where(n, pred, 0).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Counts the primes up to `n` in each residue class modulo `m`: index `r` of the result holds
+/// the number of primes `p <= n` with `p % m == r`.
+pub fn residue_class_counts(n: u64, m: u64) -> Result<Vec<u64>, SieveError> {
+    if m == 0 {
+        return Err(SieveError::ZeroModulus);
+    }
+    let classes = usize::try_from(m).map_err(|_| SieveError::AllocationFailed { n })?;
+    let mut counts = try_alloc(classes, 0u64, n)?;
+    sieve_each(n, |p| {
+        counts[(p % m) as usize] += 1;
+        ControlFlow::Continue(())
+    })?;
+    Ok(counts)
+}
+
+/// The number of primes in each window `k * window .. (k + 1) * window` covering `0..=n`, in one
+/// pass over the sieve; the last window stops at `n` and may be partial.
+///
+/// ```
+/// use eratosthenes::{count_primes_up_to, windowed_prime_counts};
+///
+/// assert_eq!(windowed_prime_counts(100, 10), [4, 4, 2, 2, 3, 2, 2, 3, 2, 1, 0]);
+/// assert_eq!(windowed_prime_counts(100_000, 999).iter().sum::<u64>(), count_primes_up_to(100_000));
+/// ```
+///
+/// # Panics
+///
+/// If `window` is zero, or the counts or the sieve cannot be allocated.
+#[must_use]
+pub fn windowed_prime_counts(n: u64, window: u64) -> Vec<u64> {
+    assert!(window > 0, "window must be positive");
+    let windows = usize::try_from(n / window + 1).unwrap_or(usize::MAX);
+    let mut counts = try_alloc(windows, 0u64, n).unwrap_or_else(|e| panic!("{}", e));
+    sieve_each(n, |p| {
+        counts[(p / window) as usize] += 1;
+        ControlFlow::Continue(())
+    })
+    .unwrap_or_else(|e| panic!("{}", e));
+    counts
+}
+
+/// Returns the primes up to `n` together with a sampled prime-counting index: `index[k]` is
+/// `pi(k * stride)` for `k * stride <= n`.
+///
+/// To count the primes up to any `x <= n`, take `k = x / stride` and start from `index[k]`; since
+/// that is also the position in the prime list of the first prime above `k * stride`, scan
+/// forward from there while primes are `<= x`. At most `stride` values lie between samples, so
+/// the scan is bounded by the stride; `x` that is a multiple of `stride` needs no scan at all.
+///
+/// # Panics
+///
+/// If `stride` is zero, or the storage cannot be allocated.
+#[must_use]
+pub fn sieve_with_pi_index(n: u64, stride: u64) -> (Vec<u64>, Vec<u64>) {
+    assert!(stride > 0, "stride must be positive");
+    let primes = primes_up_to(n);
+    let mut index = Vec::with_capacity((n / stride).saturating_add(1).min(1 << 20) as usize);
+    let mut count = 0;
+    for k in 0..=n / stride {
+        let x = k * stride;
+        while count < primes.len() && primes[count] <= x {
+            count += 1;
+        }
+        index.push(count as u64);
+    }
+    (primes, index)
+}
+
+/// Evaluates `f` at every prime `p <= n`, in ascending order of `p`.
+#[must_use]
+pub fn prime_indexed_sequence<F: Fn(u64) -> u64>(f: F, n: u64) -> Vec<u64> {
+    let mut values = Vec::new();
+    sieve_each(n, |p| {
+        values.push(f(p));
+        ControlFlow::Continue(())
+    })
+    .unwrap_or_else(|e| panic!("{}", e));
+    values
+}
+
+/// Sum of `f(p)` over the primes `p <= n`, or `None` if it overflows u64.
+#[must_use]
+pub fn prime_indexed_sum<F: Fn(u64) -> u64>(f: F, n: u64) -> Option<u64> {
+    prime_indexed_fold(n, 0, |acc, p| acc.checked_add(f(p)))
+}
+
+/// Product of `f(p)` over the primes `p <= n`, or `None` if it overflows u64.
+#[must_use]
+pub fn prime_indexed_product<F: Fn(u64) -> u64>(f: F, n: u64) -> Option<u64> {
+    prime_indexed_fold(n, 1, |acc, p| acc.checked_mul(f(p)))
+}
+
+// Folds over the primes up to n, stopping the sieve as soon as `step` overflows.
+fn prime_indexed_fold<S: Fn(u64, u64) -> Option<u64>>(n: u64, init: u64, step: S) -> Option<u64> {
+    let mut acc = Some(init);
+    sieve_each(n, |p| {
+        acc = acc.and_then(|acc| step(acc, p));
+        if acc.is_some() {
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(())
+        }
+    })
+    .unwrap_or_else(|e| panic!("{}", e));
+    acc
+}
+
+/// Returns the Sophie Germain primes up to `n`: primes `p` for which `2p + 1` is also prime.
+///
+/// Testing the safe prime `2p + 1` means the sieve runs past the requested bound, up to `2n + 1`.
+#[must_use]
+pub fn sophie_germain_primes_up_to(n: u64) -> Vec<u64> {
+    let limit = n.checked_mul(2).and_then(|m| m.checked_add(1)).expect("2n + 1 overflows u64");
+    let primes = primes_up_to(limit);
+    let mut safe_primes = primes.iter().peekable();
+    primes
+        .iter()
+        .copied()
+        .take_while(|&p| p <= n)
+        .filter(|&p| {
+            let q = 2 * p + 1;
+            while safe_primes.next_if(|&&s| s < q).is_some() {}
+            safe_primes.peek() == Some(&&q)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_prime_free_around_a_gap() {
+        assert!(is_prime_free(24, 28));
+        assert!(!is_prime_free(23, 28));
+        assert!(is_prime_free(1_328, 1_360));
+        assert!(!is_prime_free(1_328, 1_361));
+        assert!(is_prime_free(10, 9));
+        assert!(is_prime_free(0, 1));
+    }
+
+    // A range reaching u64::MAX needs the primes up to 2^32 as its base, which takes most of a
+    // minute even optimized; CI runs these with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore = "sieves the base primes up to 2^32"]
+    fn is_prime_free_near_u64_max() {
+        // 2^64 - 59 is the last prime below 2^64.
+        assert!(!is_prime_free(u64::MAX - 100, u64::MAX));
+        assert!(!is_prime_free(u64::MAX - 58, u64::MAX));
+        assert!(is_prime_free(u64::MAX - 57, u64::MAX));
+    }
+
+    #[test]
+    fn count_primes_in_ranges_matches_separate_counts() {
+        let ranges = [
+            (0, 0),
+            (2, 2),
+            (0, 1_000_000),
+            (999_000, 1_001_000),
+            (500, 400),
+            (7, 7),
+            (8, 10),
+            (10u64.pow(9), 10u64.pow(9) + 100_000),
+            (123_456, 7_654_321),
+        ];
+        let counts = count_primes_in_ranges(&ranges);
+        assert_eq!(counts.len(), ranges.len());
+        for (&(lo, hi), &count) in ranges.iter().zip(&counts) {
+            assert_eq!(count, count_primes_in_range(lo, hi), "{}..={}", lo, hi);
+        }
+        assert_eq!(counts[..3], [0, 1, 78_498]);
+    }
+
+    fn primes_threaded(n: u64, threads: usize) -> Vec<u64> {
+        let mut primes = Vec::new();
+        sieve_each_threaded(n, threads, &AtomicBool::new(false), |p| {
+            primes.push(p);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        primes
+    }
+
+    #[test]
+    fn sieve_each_threaded_matches_inline_marking() {
+        // One thread marks inline; more go through the dispatch queue. Small n cross the points
+        // where the queue is drained, at the squares of 7, 11, 13 and so on.
+        for n in (0..=200).chain([841, 961, 1_000_000, 2_000_003]) {
+            let inline = primes_threaded(n, 1);
+            assert_eq!(primes_threaded(n, 4), inline, "n = {}", n);
+            assert_eq!(primes_threaded(n, 2), inline, "n = {}", n);
+        }
+        assert_eq!(primes_threaded(1_000_000, 1).len(), 78_498);
+    }
+
+    #[test]
+    fn without_threads_every_thread_count_marks_inline() {
+        // THREADS is false on plain wasm32 and with `single-thread`, which CI builds this under.
+        if THREADS {
+            assert_eq!(worker_threads(4), 4);
+            assert_eq!(worker_threads(0), default_threads());
+        } else {
+            assert_eq!(default_threads(), 1);
+            for threads in [0, 1, 2, 4, 64] {
+                assert_eq!(worker_threads(threads), 1, "{} threads", threads);
+            }
+        }
+        // One thread takes the inline path in any build; the rest take it only without threads.
+        let expected: Vec<u64> = prime_gen_trial_division().take_while(|&p| p <= 200_000).collect();
+        for threads in [1, 0, 2, 4] {
+            assert_eq!(primes_threaded(200_000, threads), expected, "{} threads", threads);
+        }
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "single-thread", all(target_family = "wasm", not(target_feature = "atomics")))))]
+    fn progress_rises_to_1_and_the_count_joins() {
+        for (n, expected) in [(0, 0), (1, 0), (2, 1), (100, 25), (1_000_000, 78_498), (10_000_000, 664_579)] {
+            let (handle, progress) = count_primes_with_progress(n);
+            let updates: Vec<f64> = progress.iter().collect();
+            assert_eq!(handle.join().unwrap(), expected, "n = {}", n);
+            assert_eq!(updates.last(), Some(&1.0), "n = {}", n);
+            assert!(updates.windows(2).all(|w| w[0] <= w[1]), "n = {}: {:?}", n, updates);
+            assert!(updates.iter().all(|&f| f > 0.0 && f <= 1.0), "n = {}: {:?}", n, updates);
+            assert!(updates.len() <= 101, "n = {}: {} updates", n, updates.len());
+        }
+
+        // The sieve runs on whether the receiver is dropped at once or after the first update.
+        let (handle, progress) = count_primes_with_progress(10_000_000);
+        drop(progress);
+        assert_eq!(handle.join().unwrap(), 664_579);
+        let (handle, progress) = count_primes_with_progress(10_000_000);
+        assert!(progress.recv().unwrap() > 0.0);
+        drop(progress);
+        assert_eq!(handle.join().unwrap(), 664_579);
+    }
+
+    fn digit_sum(mut v: u64) -> u64 {
+        let mut sum = 0;
+        while v > 0 {
+            sum += v % 10;
+            v /= 10;
+        }
+        sum
+    }
+
+    #[test]
+    fn primes_up_to_where_filters_the_primes() {
+        let n = 100_000;
+        let expected: Vec<u64> = primes_up_to(n).into_iter().filter(|&p| arith::is_prime(digit_sum(p))).collect();
+        let found = primes_up_to_where(n, |p| arith::is_prime(digit_sum(p)));
+        assert_eq!(found, expected);
+        assert_eq!(found[..8], [2, 3, 5, 7, 11, 23, 29, 41]);
+        // The predicate also sees 2, 3 and 5, which never reach the wheel.
+        assert_eq!(primes_up_to_where(n, |p| p < 10), [2, 3, 5, 7]);
+        assert!(primes_up_to_where(n, |_| false).is_empty());
+        assert_eq!(primes_up_to_where(n, |_| true).len(), 9_592);
+        assert!(primes_up_to_where(1, |_| true).is_empty());
+    }
+
+    #[test]
+    #[ignore = "sieves the base primes up to 2^32"]
+    fn count_primes_in_ranges_up_to_u64_max() {
+        // 2^64 - 59, 2^64 - 83 and 2^64 - 95.
+        assert_eq!(count_primes_in_ranges(&[(u64::MAX - 100, u64::MAX), (0, 100)]), [3, 25]);
+        assert_eq!(count_primes_in_range(u64::MAX - 100, u64::MAX), 3);
+    }
 }
@@ -0,0 +1,102 @@
+// `range LO..HI`: the primes in a window, sieved against the base primes up to sqrt(HI). Far
+// windows spend most of their time on those base primes, so --save-base-primes writes them out
+// and --base-primes reads them back for any later window below the square of their limit.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::ControlFlow;
+
+use eratosthenes::format::DELTA_MAGIC;
+use eratosthenes::{decompress, segmented_sieve_range_each_with, write_delta_primes, BasePrimes, DeltaReader};
+
+use crate::bound::parse_range;
+use crate::{exit_interrupted, fail, fail_sieve, interrupt, report_write_error, usage_error, Progress};
+
+pub fn range(mut args: impl Iterator<Item = String>) {
+    let mut count = false;
+    let mut load = None;
+    let mut save = None;
+    let mut bounds = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--count" => count = true,
+            "--base-primes" => load = Some(args.next().unwrap_or_else(|| usage_error("--base-primes needs a FILE"))),
+            "--save-base-primes" => {
+                save = Some(args.next().unwrap_or_else(|| usage_error("--save-base-primes needs a FILE")));
+            }
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option: {}", arg)),
+            _ if bounds.is_none() => bounds = Some(parse_range(&arg).unwrap_or_else(|e| usage_error(&e))),
+            _ => usage_error(&format!("unexpected argument: {}", arg)),
+        }
+    }
+    let Some((lo, hi)) = bounds else {
+        usage_error("range needs LO..HI");
+    };
+
+    let base = match &load {
+        Some(path) => {
+            let primes = load_primes(path).unwrap_or_else(|e| fail(format_args!("{}: {}", path, e)));
+            let base = BasePrimes::from_primes(primes).unwrap_or_else(|e| fail(format_args!("{}: {}", path, e)));
+            if !base.covers(hi) {
+                fail(format_args!(
+                    "{}: the base primes cover up to {}, but {}..{} needs them up to {}",
+                    path,
+                    base.limit(),
+                    lo,
+                    hi,
+                    hi.isqrt()
+                ));
+            }
+            base
+        }
+        None => BasePrimes::for_range(hi).unwrap_or_else(|e| fail_sieve(e)),
+    };
+    if let Some(path) = &save {
+        let written = File::create(path).and_then(|file| write_delta_primes(io::BufWriter::new(file), 0, base.primes()));
+        written.unwrap_or_else(|e| fail(format_args!("{}: {}", path, e)));
+    }
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let mut progress = Progress::default();
+    let mut write_error = None;
+    segmented_sieve_range_each_with(&base, lo, hi, |p| {
+        if !count {
+            if let Err(e) = writeln!(out, "{}", p) {
+                write_error = Some(e);
+                return ControlFlow::Break(());
+            }
+        }
+        progress.record(p)
+    })
+    .unwrap_or_else(|e| fail_sieve(e));
+    if count && write_error.is_none() {
+        write_error = writeln!(out, "{}", progress.count).err();
+    }
+    if let Err(e) = write_error.map_or_else(|| out.flush(), Err) {
+        report_write_error(e);
+        return;
+    }
+    if interrupt::requested() {
+        exit_interrupted(hi, progress.count, progress.last_prime);
+    }
+}
+
+// A prime list in any format the CLI writes: text or bin-delta, possibly compressed.
+fn load_primes(path: &str) -> io::Result<Vec<u64>> {
+    let mut input = BufReader::new(decompress(File::open(path)?)?);
+    if input.fill_buf()?.starts_with(&DELTA_MAGIC) {
+        return DeltaReader::new(input)?.collect();
+    }
+    let mut primes = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let p = line.trim().parse().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: not a number: {:?}", i + 1, line))
+        })?;
+        primes.push(p);
+    }
+    Ok(primes)
+}
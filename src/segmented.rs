@@ -0,0 +1,837 @@
+use std::collections::BTreeMap;
+use std::ops::{ControlFlow, Range};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+
+use crate::arith::next_prime;
+use crate::simd::simd_mark_multiples;
+use crate::{
+    checked_backward5, forward5, presieve, sieve_of_eratosthenes, try_alloc, DispatchQueue, QueueConfig, SieveError,
+    MIN_SEGMENT_SIZE, PRESIEVE_PRIMES,
+};
+
+/// Wheel positions per segment: 2^21 flags, covering about 7.9 million numbers.
+pub const DEFAULT_SEGMENT_SIZE: usize = 1 << 21;
+
+/// The sieve up to `n`, cut into fixed-size runs of wheel positions that are sieved one at a
+/// time against the base primes up to `sqrt(n)`. Segments are independent, so a run can start
+/// (or resume) at any segment, and memory stays at one segment plus the base primes.
+pub struct Segments {
+    n: u64,
+    segment_size: usize,
+    last_index: usize,
+    // Shared with the BasePrimes they came from, and with every other Segments sieved against
+    // them; only the first `base_len`, up to sqrt(n), are used.
+    base_primes: Arc<Vec<u64>>,
+    base_len: usize,
+    not_prime: Vec<bool>,
+}
+
+impl Segments {
+    pub fn new(n: u64, segment_size: usize) -> Result<Self, SieveError> {
+        assert!(segment_size > 0, "segment size must be positive");
+        checked_backward5(n).ok_or(SieveError::AllocationFailed { n })?;
+        Self::from_base_primes(n, segment_size, Arc::new(sieve_of_eratosthenes(n.isqrt())?))
+    }
+
+    /// Like [`new`](Self::new), taking the base primes from `base` instead of sieving them;
+    /// fails with [`SieveError::BasePrimesTooShort`] unless they reach `sqrt(n)`.
+    pub fn with_base_primes(n: u64, segment_size: usize, base: &BasePrimes) -> Result<Self, SieveError> {
+        assert!(segment_size > 0, "segment size must be positive");
+        if !base.covers(n) {
+            return Err(SieveError::BasePrimesTooShort { n, limit: base.limit() });
+        }
+        Self::from_base_primes(n, segment_size, Arc::clone(&base.primes))
+    }
+
+    fn from_base_primes(n: u64, segment_size: usize, base_primes: Arc<Vec<u64>>) -> Result<Self, SieveError> {
+        let last_index = checked_backward5(n).ok_or(SieveError::AllocationFailed { n })?;
+        let base_len = base_primes.partition_point(|&p| p <= n.isqrt());
+        let not_prime = try_alloc(segment_size.min(last_index.max(1)), false, n)?;
+        debug!(
+            "segmented sieve up to {}: {} segments of {} positions, {} base primes",
+            n,
+            last_index.div_ceil(segment_size),
+            segment_size,
+            base_len
+        );
+        Ok(Segments { n, segment_size, last_index, base_primes, base_len, not_prime })
+    }
+
+    #[must_use]
+    pub fn n(&self) -> u64 {
+        self.n
+    }
+
+    #[must_use]
+    pub fn segment_size(&self) -> usize {
+        self.segment_size
+    }
+
+    /// The primes up to `sqrt(n)` that each segment is sieved against.
+    #[must_use]
+    pub fn base_primes(&self) -> &[u64] {
+        &self.base_primes[..self.base_len]
+    }
+
+    /// Number of segments.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.last_index.div_ceil(self.segment_size)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The first segment that can hold a prime `>= v`.
+    #[must_use]
+    pub fn segment_containing(&self, v: u64) -> usize {
+        (checked_backward5(v).unwrap_or(usize::MAX).max(1) - 1) / self.segment_size
+    }
+
+    /// Sieves segment `k` and feeds its primes to `emit` in ascending order.
+    pub fn sieve_segment<F: FnMut(u64) -> ControlFlow<()>>(&mut self, k: usize, emit: &mut F) -> ControlFlow<()> {
+        let n = self.n;
+        trace!("sieving segment {} of {}", k, self.len());
+        if k == 0 {
+            for p in [2, 3, 5] {
+                if p <= n {
+                    emit(p)?;
+                }
+            }
+        }
+
+        let lo = 1 + k * self.segment_size;
+        let hi = (lo + self.segment_size).min(self.last_index + 1);
+        let not_prime = &mut self.not_prime[..hi - lo];
+        presieve(not_prime, lo);
+
+        let high_value = forward5(hi - 1).min(n);
+        for &p in self.base_primes[..self.base_len].iter().skip_while(|&&p| p < 7 || PRESIEVE_PRIMES.contains(&p)) {
+            if p * p > high_value {
+                break;
+            }
+            simd_mark_multiples(not_prime, lo, p);
+        }
+
+        for (i, &is_composite) in not_prime.iter().enumerate() {
+            let p = forward5(lo + i);
+            if p > n {
+                break;
+            }
+            if !is_composite && p > 1 {
+                emit(p)?;
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+}
+
+/// The primes up to a limit, kept to sieve ranges against: one set covers every range up to the
+/// square of its limit, so a run of far ranges can compute it once, or load it from a file.
+///
+/// ```
+/// use eratosthenes::{segmented_sieve_range_each_with, BasePrimes};
+///
+/// let base = BasePrimes::for_range(10u64.pow(12)).unwrap();
+/// let mut primes = Vec::new();
+/// segmented_sieve_range_each_with(&base, 10u64.pow(12) - 100, 10u64.pow(12), |p| {
+///     primes.push(p);
+///     std::ops::ControlFlow::Continue(())
+/// })
+/// .unwrap();
+/// assert_eq!(primes, [999_999_999_937, 999_999_999_959, 999_999_999_961, 999_999_999_989]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasePrimes {
+    // Shared, not copied, by the Segments sieved against them.
+    primes: Arc<Vec<u64>>,
+    // Every prime up to here is in `primes`.
+    limit: u64,
+}
+
+impl BasePrimes {
+    /// The primes up to `limit`, sieved.
+    pub fn up_to(limit: u64) -> Result<Self, SieveError> {
+        Ok(BasePrimes { primes: Arc::new(sieve_of_eratosthenes(limit)?), limit })
+    }
+
+    /// The base primes for ranges up to `hi`: the primes up to `sqrt(hi)`.
+    pub fn for_range(hi: u64) -> Result<Self, SieveError> {
+        Self::up_to(hi.isqrt())
+    }
+
+    /// Takes a saved list as the primes up to just below the prime after its last. The list
+    /// must start at 2 and be strictly increasing; a prime missing from the middle goes
+    /// unnoticed, and leaves every range sieved against it with composites let through.
+    pub fn from_primes(primes: Vec<u64>) -> Result<Self, SieveError> {
+        let invalid = |reason: String| Err(SieveError::InvalidBasePrimes { reason });
+        match primes.first() {
+            None => return Ok(BasePrimes { primes: Arc::new(primes), limit: 1 }),
+            Some(&2) => {}
+            Some(&first) => return invalid(format!("the list starts at {} rather than 2", first)),
+        }
+        if let Some(i) = primes.windows(2).position(|w| w[0] >= w[1]) {
+            return invalid(format!("{} is followed by {}, out of order", primes[i], primes[i + 1]));
+        }
+        let last = *primes.last().unwrap();
+        let limit = next_prime(last).map_or(u64::MAX, |next| next - 1);
+        Ok(BasePrimes { primes: Arc::new(primes), limit })
+    }
+
+    /// The bound up to which the set holds every prime.
+    #[must_use]
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    #[must_use]
+    pub fn primes(&self) -> &[u64] {
+        &self.primes
+    }
+
+    /// Whether the set reaches `sqrt(n)`, as sieving up to `n` needs.
+    #[must_use]
+    pub fn covers(&self, n: u64) -> bool {
+        self.limit >= n.isqrt()
+    }
+}
+
+/// The primes up to `n` a segment at a time, for pipelines: [`segments`](Self::segments) sieves
+/// each segment only when the previous one has been taken, so the caller can work through one
+/// segment's primes before the next is computed.
+#[derive(Debug, Clone)]
+pub struct SegmentedSieve {
+    n: u64,
+    segment_size: usize,
+}
+
+impl SegmentedSieve {
+    /// Segments of [`DEFAULT_SEGMENT_SIZE`] wheel positions until told otherwise.
+    #[must_use]
+    pub fn new(n: u64) -> Self {
+        SegmentedSieve { n, segment_size: DEFAULT_SEGMENT_SIZE }
+    }
+
+    /// Wheel positions per segment, each standing for about 3.75 integers.
+    ///
+    /// # Panics
+    ///
+    /// If `segment_size` is 0.
+    #[must_use]
+    pub fn with_segment_size(mut self, segment_size: usize) -> Self {
+        assert!(segment_size > 0, "segment size must be positive");
+        self.segment_size = segment_size;
+        self
+    }
+
+    /// Each segment's integers as a half-open range, the ranges together covering `0..=n` in
+    /// order, with the primes in it.
+    ///
+    /// # Panics
+    ///
+    /// If the base primes or the segment buffer cannot be allocated.
+    pub fn segments(&self) -> impl Iterator<Item = (Range<u64>, Vec<u64>)> {
+        let (n, segment_size) = (self.n, self.segment_size);
+        let mut segments = Segments::new(n, segment_size).unwrap_or_else(|e| panic!("{}", e));
+        (0..segments.len()).map(move |k| {
+            // Segment k holds wheel positions from 1 + k * segment_size; the first also holds
+            // 0 and 2, 3, 5 below the wheel. The last position can lie just past n.
+            let limit = n.saturating_add(1);
+            let start = if k == 0 { 0 } else { forward5(1 + k * segment_size).min(limit) };
+            let end = forward5(1 + (k + 1) * segment_size).min(limit);
+            let mut primes = Vec::new();
+            let _ = segments.sieve_segment(k, &mut |p| {
+                primes.push(p);
+                ControlFlow::Continue(())
+            });
+            (start..end, primes)
+        })
+        .filter(|(range, _)| !range.is_empty())
+    }
+}
+
+/// Like [`sieve_each`](crate::sieve_each), but sieves segment by segment in bounded memory.
+pub fn segmented_sieve_each<F: FnMut(u64) -> ControlFlow<()>>(n: u64, mut emit: F) -> Result<(), SieveError> {
+    let mut segments = Segments::new(n, DEFAULT_SEGMENT_SIZE)?;
+    for k in 0..segments.len() {
+        if segments.sieve_segment(k, &mut emit).is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// The first bound an unbounded enumeration sieves to; each later one is four times larger.
+const INFINITE_START: u64 = 1 << 16;
+
+/// Every prime in ascending order, with no upper bound: sieves segment by segment and, when the
+/// current bound runs out, starts over with a bound four times larger from the segment it had
+/// reached. Base primes and the segment buffer stay `O(sqrt(p))` for the largest `p` so far.
+///
+/// Ends only after the last prime below 2^64.
+///
+/// # Panics
+///
+/// If a segment or its base primes cannot be allocated.
+pub fn prime_enumerator_infinite() -> impl Iterator<Item = u64> {
+    PrimeEnumerator {
+        segments: enumerator_segments(INFINITE_START),
+        next_segment: 0,
+        buffer: Vec::new(),
+        position: 0,
+        last: 0,
+    }
+}
+
+/// Each prime `p <= n` paired with the gap up to the next prime, for plotting gaps against
+/// where they occur. Built on [`prime_enumerator_infinite`] with one prime of lookahead, so the
+/// last pair's gap reaches past `n`; a prime with no successor below 2^64 is left out.
+///
+/// ```
+/// use eratosthenes::prime_gap_positions;
+///
+/// let gaps: Vec<(u64, u32)> = prime_gap_positions(30).collect();
+/// assert_eq!(gaps[..4], [(2, 1), (3, 2), (5, 2), (7, 4)]);
+/// assert_eq!(gaps.last(), Some(&(29, 2)));
+/// assert_eq!(prime_gap_positions(1_000).max_by_key(|&(_, gap)| gap), Some((887, 20)));
+/// ```
+///
+/// # Panics
+///
+/// If a segment or its base primes cannot be allocated.
+pub fn prime_gap_positions(n: u64) -> impl Iterator<Item = (u64, u32)> {
+    let mut primes = prime_enumerator_infinite().peekable();
+    std::iter::from_fn(move || {
+        let p = primes.next().filter(|&p| p <= n)?;
+        let next = *primes.peek()?;
+        Some((p, (next - p) as u32))
+    })
+}
+
+struct PrimeEnumerator {
+    segments: Segments,
+    next_segment: usize,
+    // The primes of the last segment sieved, from `position` on not yet returned.
+    buffer: Vec<u64>,
+    position: usize,
+    last: u64,
+}
+
+impl Iterator for PrimeEnumerator {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        loop {
+            if let Some(&p) = self.buffer.get(self.position) {
+                self.position += 1;
+                self.last = p;
+                return Some(p);
+            }
+            if self.next_segment == self.segments.len() {
+                if self.segments.n() == u64::MAX {
+                    return None;
+                }
+                self.segments = enumerator_segments(self.segments.n().saturating_mul(4));
+                // Re-sieve the segment holding the last prime; everything up to it is skipped.
+                self.next_segment = self.segments.segment_containing(self.last);
+            }
+
+            let last = self.last;
+            let buffer = &mut self.buffer;
+            buffer.clear();
+            self.position = 0;
+            let _ = self.segments.sieve_segment(self.next_segment, &mut |p| {
+                if p > last {
+                    buffer.push(p);
+                }
+                ControlFlow::Continue(())
+            });
+            self.next_segment += 1;
+        }
+    }
+}
+
+fn enumerator_segments(n: u64) -> Segments {
+    let segment_size = (n.isqrt() as usize).clamp(MIN_SEGMENT_SIZE, DEFAULT_SEGMENT_SIZE);
+    Segments::new(n, segment_size).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Like [`segmented_sieve_each`], but only for the primes in `low..=n`: the segments below `low`
+/// are skipped, so only the base primes and the range itself are sieved.
+pub fn segmented_sieve_range_each<F: FnMut(u64) -> ControlFlow<()>>(
+    low: u64,
+    n: u64,
+    emit: F,
+) -> Result<(), SieveError> {
+    sieve_range(Segments::new(n, DEFAULT_SEGMENT_SIZE)?, low, emit)
+}
+
+/// Like [`segmented_sieve_range_each`], sieving against `base` rather than computing the base
+/// primes afresh; fails with [`SieveError::BasePrimesTooShort`] unless they reach `sqrt(n)`.
+pub fn segmented_sieve_range_each_with<F: FnMut(u64) -> ControlFlow<()>>(
+    base: &BasePrimes,
+    low: u64,
+    n: u64,
+    emit: F,
+) -> Result<(), SieveError> {
+    sieve_range(Segments::with_base_primes(n, DEFAULT_SEGMENT_SIZE, base)?, low, emit)
+}
+
+fn sieve_range<F: FnMut(u64) -> ControlFlow<()>>(
+    mut segments: Segments,
+    low: u64,
+    mut emit: F,
+) -> Result<(), SieveError> {
+    let mut in_range = |p| if p < low { ControlFlow::Continue(()) } else { emit(p) };
+    for k in segments.segment_containing(low)..segments.len() {
+        if segments.sieve_segment(k, &mut in_range).is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The primes in `lo..=hi`, with the range cut into `threads` equal parts (0 picks
+/// [`default_threads`](crate::default_threads)) that are sieved side by side, each with its own
+/// segment buffer, and joined in order.
+///
+/// # Panics
+///
+/// If the sieve or the primes cannot be allocated.
+#[must_use]
+pub fn range_sieve_parallel(lo: u64, hi: u64, threads: usize) -> Vec<u64> {
+    if lo > hi {
+        return Vec::new();
+    }
+    let base = BasePrimes::for_range(hi).unwrap_or_else(|e| panic!("{}", e));
+    range_sieve_parallel_with(&base, lo, hi, threads)
+}
+
+/// Like [`range_sieve_parallel`], with every part sieved against `base`.
+///
+/// # Panics
+///
+/// If `base` does not reach `sqrt(hi)`, or the sieve or the primes cannot be allocated.
+#[must_use]
+pub fn range_sieve_parallel_with(base: &BasePrimes, lo: u64, hi: u64, threads: usize) -> Vec<u64> {
+    if lo > hi {
+        return Vec::new();
+    }
+    let threads = crate::worker_threads(threads);
+    if threads == 1 {
+        let mut primes = Vec::new();
+        segmented_sieve_range_each_with(base, lo, hi, |p| {
+            primes.push(p);
+            ControlFlow::Continue(())
+        })
+        .unwrap_or_else(|e| panic!("{}", e));
+        return primes;
+    }
+    let width = (hi - lo) / threads as u64 + 1;
+    let parts: Vec<Vec<u64>> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads as u64)
+            .map_while(|i| lo.checked_add(i * width).filter(|&start| start <= hi))
+            .map(|start| {
+                let end = start.saturating_add(width - 1).min(hi);
+                scope.spawn(move || {
+                    let mut primes = Vec::new();
+                    segmented_sieve_range_each_with(base, start, end, |p| {
+                        primes.push(p);
+                        ControlFlow::Continue(())
+                    })
+                    .unwrap_or_else(|e| panic!("{}", e));
+                    primes
+                })
+            })
+            .collect();
+        workers.into_iter().map(|worker| worker.join().unwrap_or_else(|e| std::panic::resume_unwind(e))).collect()
+    });
+    parts.concat()
+}
+
+/// The primes up to `n` for which `predicate` holds, in increasing order. The range is cut into
+/// several chunks per [`default_threads`](crate::default_threads) worker, each worker taking the
+/// next unclaimed chunk, sieving it against shared base primes and testing its primes, so a
+/// predicate that costs more for some primes than others still spreads evenly. The chunks come
+/// back over a channel and are put in order at the end.
+///
+/// ```
+/// use eratosthenes::prime_search_parallel;
+///
+/// let palindrome = |p: u64| {
+///     let digits = p.to_string();
+///     digits.chars().eq(digits.chars().rev())
+/// };
+/// assert_eq!(prime_search_parallel(palindrome, 200), [2, 3, 5, 7, 11, 101, 131, 151, 181, 191]);
+/// ```
+///
+/// # Panics
+///
+/// If the sieve or the primes cannot be allocated, or if `predicate` panics.
+#[must_use]
+pub fn prime_search_parallel(predicate: impl Fn(u64) -> bool + Send + Sync, n: u64) -> Vec<u64> {
+    const CHUNKS_PER_THREAD: u64 = 8;
+    if n < 2 {
+        return Vec::new();
+    }
+    let base = BasePrimes::for_range(n).unwrap_or_else(|e| panic!("{}", e));
+    let threads = crate::default_threads();
+    // No narrower than a segment, which holds 8 of every 30 numbers.
+    let width = (n / (threads as u64 * CHUNKS_PER_THREAD)).max(DEFAULT_SEGMENT_SIZE as u64 * 30 / 8);
+    let chunks = n / width + 1;
+    let next = AtomicU64::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let work = |sender: mpsc::Sender<(u64, Vec<u64>)>| loop {
+        let k = next.fetch_add(1, Ordering::Relaxed);
+        if k >= chunks {
+            break;
+        }
+        let start = k * width;
+        let end = start.saturating_add(width - 1).min(n);
+        let mut found = Vec::new();
+        segmented_sieve_range_each_with(&base, start, end, |p| {
+            if predicate(p) {
+                found.push(p);
+            }
+            ControlFlow::Continue(())
+        })
+        .unwrap_or_else(|e| panic!("{}", e));
+        if sender.send((k, found)).is_err() {
+            break;
+        }
+    };
+    // One worker takes every chunk itself, on this thread.
+    if threads == 1 {
+        work(sender);
+    } else {
+        std::thread::scope(|scope| {
+            for _ in 0..threads.min(chunks as usize) {
+                let (sender, work) = (sender.clone(), &work);
+                scope.spawn(move || work(sender));
+            }
+        });
+        drop(sender);
+    }
+    let mut parts: Vec<(u64, Vec<u64>)> = receiver.into_iter().collect();
+    parts.sort_unstable_by_key(|&(k, _)| k);
+    parts.into_iter().flat_map(|(_, found)| found).collect()
+}
+
+/// The primes up to `n`, a segment of `segment_size` wheel positions at a time, with each
+/// segment's primes turned into a `T` by `render` on the dispatch queue's `threads` workers (0
+/// picks [`default_threads`](crate::default_threads)) and handed to `write` strictly in segment
+/// order. Only `write` runs on the calling thread, so work such as formatting the primes as text
+/// is taken off whatever thread writes the output; with a single worker, `render` runs there
+/// too. At most two segments per worker are sieved ahead of `write`, so a slow writer holds the
+/// workers back rather than letting rendered segments pile up in memory.
+///
+/// Stops early when `write` breaks or `cancel` is set.
+///
+/// ```
+/// use std::ops::ControlFlow;
+/// use std::sync::atomic::AtomicBool;
+/// use eratosthenes::{sieve_segments_ordered, MIN_SEGMENT_SIZE};
+///
+/// let mut text = String::new();
+/// let render = |primes: &[u64]| primes.iter().map(|p| format!("{}\n", p)).collect::<String>();
+/// sieve_segments_ordered(100_000, MIN_SEGMENT_SIZE, 4, &AtomicBool::new(false), render, |part| {
+///     text.push_str(&part);
+///     ControlFlow::Continue(())
+/// })
+/// .unwrap();
+/// assert_eq!(text.lines().count(), 9592);
+/// assert_eq!(text.lines().last(), Some("99991"));
+/// ```
+pub fn sieve_segments_ordered<T, R, W>(
+    n: u64,
+    segment_size: usize,
+    threads: usize,
+    cancel: &AtomicBool,
+    render: R,
+    mut write: W,
+) -> Result<(), SieveError>
+where
+    T: Send + 'static,
+    R: Fn(&[u64]) -> T + Send + Sync + 'static,
+    W: FnMut(T) -> ControlFlow<()>,
+{
+    let threads = crate::worker_threads(threads);
+    let base = BasePrimes::for_range(n)?;
+    let first = Segments::with_base_primes(n, segment_size, &base)?;
+    let len = first.len();
+    let workers = threads.min(len).max(1);
+    // One worker renders and writes each segment in turn on this thread, without a queue.
+    if workers == 1 {
+        drop(base);
+        let mut segments = first;
+        let mut primes = Vec::new();
+        for k in 0..len {
+            if cancel.load(Ordering::Relaxed) {
+                break;
+            }
+            primes.clear();
+            let _ = segments.sieve_segment(k, &mut |p| {
+                primes.push(p);
+                ControlFlow::Continue(())
+            });
+            if write(render(&primes)).is_break() {
+                break;
+            }
+        }
+        return Ok(());
+    }
+    // A segment buffer per worker, taken for each segment and put back after.
+    let mut idle = vec![first];
+    for _ in 1..workers {
+        idle.push(Segments::with_base_primes(n, segment_size, &base)?);
+    }
+    drop(base);
+    let idle = Arc::new(Mutex::new(idle));
+    let render = Arc::new(render);
+    let (sender, receiver) = mpsc::channel();
+
+    let dispatch = DispatchQueue::new(workers, QueueConfig::default());
+    let sieve_later = |k: usize| {
+        let (idle, render, sender) = (Arc::clone(&idle), Arc::clone(&render), sender.clone());
+        dispatch.dispatch(Box::new(move || {
+            let mut segments = idle.lock().unwrap().pop().expect("a segment buffer per worker");
+            let mut primes = Vec::new();
+            let _ = segments.sieve_segment(k, &mut |p| {
+                primes.push(p);
+                ControlFlow::Continue(())
+            });
+            idle.lock().unwrap().push(segments);
+            // Stops the queue once nobody is left to write.
+            sender.send((k, render(&primes))).is_err()
+        }));
+    };
+
+    let ahead = 2 * workers;
+    let mut dispatched = 0;
+    while dispatched < len.min(ahead) {
+        sieve_later(dispatched);
+        dispatched += 1;
+    }
+    let mut rendered = BTreeMap::new();
+    let mut written = 0;
+    while written < len && !cancel.load(Ordering::Relaxed) {
+        let (k, part) = receiver.recv().expect("the sender is still held here");
+        rendered.insert(k, part);
+        while let Some(part) = rendered.remove(&written) {
+            written += 1;
+            // Keep the workers busy while this part is written.
+            if dispatched < len {
+                sieve_later(dispatched);
+                dispatched += 1;
+            }
+            if write(part).is_break() {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// An upper bound on the `k`-th prime (counting 2 as the first): Rosser's `k (ln k + ln ln k)`
+/// for `k >= 6`. `None` for `k == 0` or if the bound passes u64.
+#[must_use]
+pub fn nth_prime_upper_bound(k: u64) -> Option<u64> {
+    match k {
+        0 => None,
+        1..=5 => Some(11),
+        _ => {
+            let k = k as f64;
+            let bound = (k * (k.ln() + k.ln().ln())).ceil();
+            (bound < u64::MAX as f64).then_some(bound as u64)
+        }
+    }
+}
+
+/// The first `k` primes, sieved segment by segment up to [`nth_prime_upper_bound`] and stopping
+/// as soon as `k` have been found, so only the segments actually needed are sieved.
+///
+/// # Panics
+///
+/// If the primes cannot be allocated.
+#[must_use]
+pub fn first_k_primes(k: usize) -> Vec<u64> {
+    let mut primes = Vec::with_capacity(k);
+    let Some(bound) = nth_prime_upper_bound(k as u64) else {
+        return primes;
+    };
+    sieve_segments_until(bound, |p| {
+        primes.push(p);
+        primes.len() < k
+    });
+    primes
+}
+
+/// The `k`-th prime, counting 2 as the first; `None` for `k == 0` or beyond the u64 primes.
+#[must_use]
+pub fn nth_prime(k: u64) -> Option<u64> {
+    nth_primes(&[k]).map(|primes| primes[0])
+}
+
+/// The `k`-th prime for each `k` in `ks`, in the same order, from a single pass up to the bound
+/// for the largest; `None` if any `k` is 0 or too large for u64.
+#[must_use]
+pub fn nth_primes(ks: &[u64]) -> Option<Vec<u64>> {
+    let mut order: Vec<usize> = (0..ks.len()).collect();
+    order.sort_by_key(|&i| ks[i]);
+    let Some(&largest) = order.last() else {
+        return Some(Vec::new());
+    };
+    if ks[order[0]] == 0 {
+        return None;
+    }
+    let bound = nth_prime_upper_bound(ks[largest])?;
+
+    let mut answers = vec![0; ks.len()];
+    let mut pending = order.iter().peekable();
+    let mut count = 0;
+    sieve_segments_until(bound, |p| {
+        count += 1;
+        while let Some(&i) = pending.next_if(|&&i| ks[i] == count) {
+            answers[i] = p;
+        }
+        pending.peek().is_some()
+    });
+    // The bound holds for every k, so only a k past the last u64 prime goes unanswered.
+    if pending.peek().is_some() {
+        return None;
+    }
+    Some(answers)
+}
+
+// Streams the primes up to `bound` to `more` until it returns false.
+fn sieve_segments_until<F: FnMut(u64) -> bool>(bound: u64, mut more: F) {
+    let mut segments = Segments::new(bound, DEFAULT_SEGMENT_SIZE).unwrap_or_else(|e| panic!("{}", e));
+    for i in 0..segments.len() {
+        let flow = segments.sieve_segment(i, &mut |p| {
+            if more(p) {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        });
+        if flow.is_break() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Against the primes up to 1000 alone, a segment keeps the numbers with no factor that small:
+    // cheap to check by trial division, and enough to cover the wheel indices past 2^62.
+    #[test]
+    fn sieve_segment_near_u64_max() {
+        let base = crate::primes_up_to(1000);
+        let mut segments = Segments::from_base_primes(u64::MAX, MIN_SEGMENT_SIZE, Arc::new(base.clone())).unwrap();
+        for k in [segments.segment_containing(u64::MAX - 100_000), segments.len() - 1] {
+            let mut kept = Vec::new();
+            let _ = segments.sieve_segment(k, &mut |p| {
+                kept.push(p);
+                ControlFlow::Continue(())
+            });
+            let first = forward5(1 + k * MIN_SEGMENT_SIZE);
+            // Saturates at u64::MAX for a last segment that runs past it.
+            let last = forward5((k + 1) * MIN_SEGMENT_SIZE);
+            let rough: Vec<u64> = (first..=last).filter(|&v| base.iter().all(|&p| v % p != 0)).collect();
+            assert!(!rough.is_empty());
+            assert_eq!(kept, rough);
+        }
+    }
+
+    #[test]
+    fn range_sieve_parallel_matches_serial() {
+        let far = 10u64.pow(10);
+        let ranges = [(0, 0), (0, 100), (2, 2), (90, 96), (999_000, 1_001_000), (far, far + 10_000)];
+        for (lo, hi) in ranges {
+            let mut serial = Vec::new();
+            segmented_sieve_range_each(lo, hi, |p| {
+                serial.push(p);
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+            for threads in [1, 2, 3, 7] {
+                assert_eq!(range_sieve_parallel(lo, hi, threads), serial, "{}..={} on {} threads", lo, hi, threads);
+            }
+        }
+        assert!(range_sieve_parallel(10, 9, 2).is_empty());
+    }
+
+    fn text(primes: &[u64]) -> Vec<u8> {
+        primes.iter().flat_map(|p| format!("{}\n", p).into_bytes()).collect()
+    }
+
+    // Renders and writes through sieve_segments_ordered, stopping after `stop_after` segments.
+    fn ordered_text(n: u64, segment_size: usize, threads: usize, stop_after: usize) -> Vec<u8> {
+        let (mut out, mut written) = (Vec::new(), 0);
+        sieve_segments_ordered(n, segment_size, threads, &AtomicBool::new(false), text, |part| {
+            out.extend(part);
+            written += 1;
+            if written == stop_after {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .unwrap();
+        out
+    }
+
+    #[test]
+    fn ordered_segments_match_the_serial_text() {
+        for n in [0, 1, 2, 7, 30, 31, 1_000, 245_760, 245_761, 1_000_003] {
+            let serial = text(&crate::primes_up_to(n));
+            for threads in [1, 2, 3, 8] {
+                let parallel = ordered_text(n, MIN_SEGMENT_SIZE, threads, usize::MAX);
+                assert!(parallel == serial, "n = {} on {} threads", n, threads);
+            }
+        }
+        // Bigger segments than the range, and one segment per wheel turn.
+        assert!(ordered_text(100_000, DEFAULT_SEGMENT_SIZE, 4, usize::MAX) == text(&crate::primes_up_to(100_000)));
+        assert!(ordered_text(100_000, 8, 4, usize::MAX) == text(&crate::primes_up_to(100_000)));
+    }
+
+    #[test]
+    fn ordered_segments_stop_when_write_breaks() {
+        let whole = ordered_text(1_000_000, MIN_SEGMENT_SIZE, 1, usize::MAX);
+        for threads in [1, 4] {
+            let part = ordered_text(1_000_000, MIN_SEGMENT_SIZE, threads, 3);
+            assert!(whole.starts_with(&part) && part.len() < whole.len(), "on {} threads", threads);
+            // Three segments of MIN_SEGMENT_SIZE wheel positions reach 3 * 4096 * 30 / 8.
+            assert!(part.ends_with(b"46073\n"), "on {} threads", threads);
+        }
+    }
+
+    #[test]
+    fn first_k_primes_is_the_start_of_the_enumeration() {
+        assert_eq!(first_k_primes(0), []);
+        assert_eq!(first_k_primes(3), [2, 3, 5]);
+        // Either side of π(INFINITE_START) = 6542, where the enumeration raises its bound.
+        let all: Vec<u64> = prime_enumerator_infinite().take(200_000).collect();
+        for k in [1, 2, 4, 25, 26, 168, 6_541, 6_542, 6_543, 78_498, 200_000] {
+            assert_eq!(first_k_primes(k), all[..k], "k = {}", k);
+        }
+        assert_eq!(first_k_primes(1_000_000).last(), Some(&15_485_863));
+    }
+
+    #[test]
+    #[ignore = "sieves the base primes up to 2^32"]
+    fn range_sieve_parallel_up_to_u64_max() {
+        let base = BasePrimes::for_range(u64::MAX).unwrap();
+        let top = [u64::MAX - 94, u64::MAX - 82, u64::MAX - 58];
+        for threads in [1, 2, 3, 64] {
+            assert_eq!(range_sieve_parallel_with(&base, u64::MAX - 100, u64::MAX, threads), top);
+        }
+        assert_eq!(range_sieve_parallel(u64::MAX - 60, u64::MAX, 2), top[2..]);
+    }
+}
@@ -0,0 +1,150 @@
+use std::io::{self, BufRead, Write};
+
+use eratosthenes::{is_prime, nth_prime_upper_bound, Sieve, SieveError};
+
+use crate::bound::parse_bound;
+
+// The first sieve a session builds, however small the first query; later ones at least double.
+const MIN_SIEVE: u64 = 1 << 20;
+
+/// `--serve`: answers newline-delimited queries from stdin with one line of JSON each on stdout,
+/// keeping one sieve between queries and growing it when a query needs more:
+///
+/// ```text
+/// count N      {"n":N,"count":C}
+/// nth K        {"k":K,"prime":P}
+/// isprime N    {"n":N,"prime":true}
+/// range LO HI  {"lo":LO,"hi":HI,"primes":[...]}
+/// quit         {"quit":true}, then exits
+/// ```
+///
+/// A bad query gets `{"error":"..."}` and the session carries on. EOF ends it like `quit`.
+pub fn serve() -> io::Result<()> {
+    let mut server = Server { sieve: None };
+    let mut out = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (answer, quit) = match server.answer(&line) {
+            Ok(Some(answer)) => (answer, false),
+            Ok(None) => ("{\"quit\":true}".to_string(), true),
+            Err(e) => (format!("{{\"error\":{}}}", json_string(&e)), false),
+        };
+        writeln!(out, "{}", answer)?;
+        out.flush()?;
+        if quit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+struct Server {
+    sieve: Option<Sieve>,
+}
+
+impl Server {
+    // The answer to one query, or None for quit.
+    fn answer(&mut self, line: &str) -> Result<Option<String>, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let mut number = |name: &str| match words.next() {
+            Some(word) => parse_bound(word).map_err(|e| format!("{}: {}", name, e)),
+            None => Err(format!("{} needs {}", command, name)),
+        };
+        let answer = match command {
+            "count" => {
+                let n = number("N")?;
+                let count = self.sieve_up_to(n)?.count_primes_up_to(n);
+                format!("{{\"n\":{},\"count\":{}}}", n, count)
+            }
+            "nth" => {
+                let k = number("K")?;
+                if k == 0 {
+                    return Err("K counts from 1".to_string());
+                }
+                let bound = nth_prime_upper_bound(k).ok_or_else(|| format!("no {}th prime below 2^64", k))?;
+                let p = self.sieve_up_to(bound)?.primes().nth((k - 1) as usize).ok_or("K is out of range")?;
+                format!("{{\"k\":{},\"prime\":{}}}", k, p)
+            }
+            "isprime" => {
+                // Beyond the sieve, Miller-Rabin answers at once; growing the sieve would not.
+                let n = number("N")?;
+                let prime = match &self.sieve {
+                    Some(sieve) if n <= sieve.n() => sieve.is_prime(n),
+                    _ => is_prime(n),
+                };
+                format!("{{\"n\":{},\"prime\":{}}}", n, prime)
+            }
+            "range" => {
+                let (lo, hi) = (number("LO")?, number("HI")?);
+                let sieve = self.sieve_up_to(hi)?;
+                let mut primes = sieve.primes();
+                primes.skip_to(lo);
+                let primes: Vec<String> = primes.take_while(|&p| p <= hi).map(|p| p.to_string()).collect();
+                format!("{{\"lo\":{},\"hi\":{},\"primes\":[{}]}}", lo, hi, primes.join(","))
+            }
+            "quit" => return Ok(None),
+            _ => return Err(format!("unknown command {:?}; expected count, nth, isprime, range or quit", command)),
+        };
+        match words.next() {
+            Some(extra) => Err(format!("unexpected argument {:?}", extra)),
+            None => Ok(Some(answer)),
+        }
+    }
+
+    // The cached sieve, rebuilt larger if it does not reach n.
+    fn sieve_up_to(&mut self, n: u64) -> Result<&Sieve, String> {
+        let current = self.sieve.as_ref().map_or(0, Sieve::n);
+        if n > current {
+            let n = next_sieve_bound(current, n);
+            self.sieve = None;
+            self.sieve = Some(Sieve::new(n).map_err(|e: SieveError| e.to_string())?);
+        }
+        Ok(self.sieve.as_ref().unwrap())
+    }
+}
+
+/// The bound to rebuild a cached sieve reaching `current` at so that it covers `n`: at least double
+/// the old one, so that a run of growing queries costs a few rebuilds rather than one each.
+pub fn next_sieve_bound(current: u64, n: u64) -> u64 {
+    n.max(current.saturating_mul(2)).max(MIN_SIEVE)
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sieves_grow_at_least_twofold() {
+        assert_eq!(next_sieve_bound(0, 10), MIN_SIEVE);
+        assert_eq!(next_sieve_bound(MIN_SIEVE, MIN_SIEVE + 1), 2 * MIN_SIEVE);
+        assert_eq!(next_sieve_bound(MIN_SIEVE, 10 * MIN_SIEVE), 10 * MIN_SIEVE);
+        assert_eq!(next_sieve_bound(u64::MAX / 2 + 1, u64::MAX / 2 + 2), u64::MAX);
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(json_string("plain"), r#""plain""#);
+        assert_eq!(json_string(r#"say "hi" \ bye"#), r#""say \"hi\" \\ bye""#);
+        assert_eq!(json_string("tab\there\n"), r#""tab\u0009here\u000a""#);
+        assert_eq!(json_string("π ≈ 3"), "\"π ≈ 3\"");
+    }
+}
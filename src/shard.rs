@@ -0,0 +1,192 @@
+// `--shard-size COUNT`: output split across numbered files PREFIX-00000.txt (or .bin for
+// bin-delta), PREFIX-00001.txt and so on, each holding at most COUNT primes and cut only between
+// primes, with PREFIX.manifest listing every shard's first and last prime and count at the end.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use eratosthenes::{DeltaEncoder, DeltaReader};
+
+use crate::{count_digits, OutputFormat};
+
+const MANIFEST_HEADER: &str = "# shard first last count";
+
+/// One shard's range, as listed in the manifest and saved in checkpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardInfo {
+    pub first: u64,
+    pub last: u64,
+    pub count: u64,
+}
+
+// The shard being written.
+enum OpenShard {
+    // Text streams to the file; `offset` is how many bytes it holds.
+    Text { out: BufWriter<File>, offset: u64 },
+    // A bin-delta header holds the count, so the shard is kept in memory and written whole on
+    // every sync and when it closes. Each shard starts from the previous shard's last prime.
+    BinDelta { encoder: DeltaEncoder },
+}
+
+pub struct ShardWriter {
+    prefix: PathBuf,
+    format: OutputFormat,
+    shard_size: u64,
+    // Every shard begun so far; the last is the open one, if any is open.
+    shards: Vec<ShardInfo>,
+    open: Option<OpenShard>,
+}
+
+impl ShardWriter {
+    pub fn new(prefix: &Path, format: OutputFormat, shard_size: u64) -> Self {
+        assert!(shard_size > 0, "shards must hold at least one prime");
+        ShardWriter { prefix: prefix.to_path_buf(), format, shard_size, shards: Vec::new(), open: None }
+    }
+
+    /// Carries on a checkpointed run from `shards` as saved, reopening the last of them: a text
+    /// shard is cut back to the `offset` bytes recorded, a bin-delta one to its saved count.
+    pub fn resume(prefix: &Path, format: OutputFormat, shard_size: u64, shards: Vec<ShardInfo>, offset: u64) -> io::Result<Self> {
+        let mut writer = ShardWriter::new(prefix, format, shard_size);
+        let Some(last) = shards.last().cloned() else {
+            return Ok(writer);
+        };
+        let path = writer.path(shards.len() - 1);
+        let short = || with_path(&path, invalid_data("shorter than the checkpoint recorded"));
+        writer.open = Some(match format {
+            OutputFormat::Text => {
+                let mut file = OpenOptions::new().write(true).open(&path).map_err(|e| with_path(&path, e))?;
+                if file.metadata()?.len() < offset {
+                    return Err(short());
+                }
+                file.set_len(offset)?;
+                file.seek(SeekFrom::End(0))?;
+                OpenShard::Text { out: BufWriter::new(file), offset }
+            }
+            OutputFormat::BinDelta => {
+                let file = File::open(&path).map_err(|e| with_path(&path, e))?;
+                let mut reader = DeltaReader::new(BufReader::new(file)).map_err(|e| with_path(&path, e))?;
+                let mut encoder = DeltaEncoder::new(reader.start());
+                for p in reader.by_ref().take(last.count as usize) {
+                    encoder.push(p.map_err(|e| with_path(&path, e))?);
+                }
+                if encoder.prime_count() < last.count {
+                    return Err(short());
+                }
+                if reader.last_prime() != last.last {
+                    return Err(with_path(&path, invalid_data("does not match the checkpoint")));
+                }
+                OpenShard::BinDelta { encoder }
+            }
+        });
+        writer.shards = shards;
+        Ok(writer)
+    }
+
+    pub fn push(&mut self, p: u64) -> io::Result<()> {
+        if self.open.is_some() && self.shards.last().is_some_and(|shard| shard.count >= self.shard_size) {
+            self.close()?;
+        }
+        if self.open.is_none() {
+            self.start(p)?;
+        }
+        let shard = self.shards.last_mut().unwrap();
+        shard.last = p;
+        shard.count += 1;
+        match self.open.as_mut().unwrap() {
+            OpenShard::Text { out, offset } => {
+                writeln!(out, "{}", p)?;
+                *offset += count_digits(p) + 1;
+            }
+            OpenShard::BinDelta { encoder } => encoder.push(p),
+        }
+        Ok(())
+    }
+
+    /// Gets the open shard onto disk, for a checkpoint to point at.
+    pub fn sync(&mut self) -> io::Result<()> {
+        let path = self.path(self.shards.len().saturating_sub(1));
+        match &mut self.open {
+            None => Ok(()),
+            Some(OpenShard::Text { out, .. }) => out.flush().and_then(|_| out.get_ref().sync_data()),
+            Some(OpenShard::BinDelta { encoder }) => {
+                let mut out = BufWriter::new(File::create(&path)?);
+                encoder.write(&mut out)?;
+                out.into_inner().map_err(|e| e.into_error())?.sync_data()
+            }
+        }
+        .map_err(|e| with_path(&path, e))
+    }
+
+    /// Bytes of text in the open shard, which is where a resumed run cuts it back to.
+    pub fn offset(&self) -> u64 {
+        match &self.open {
+            Some(OpenShard::Text { offset, .. }) => *offset,
+            _ => 0,
+        }
+    }
+
+    pub fn shards(&self) -> &[ShardInfo] {
+        &self.shards
+    }
+
+    /// Closes the last shard and writes the manifest.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.close()?;
+        let mut manifest = format!("{}\n", MANIFEST_HEADER);
+        for (i, shard) in self.shards.iter().enumerate() {
+            let path = self.path(i);
+            let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
+            manifest.push_str(&format!("{} {} {} {}\n", name, shard.first, shard.last, shard.count));
+        }
+        let path = self.manifest_path();
+        fs::write(&path, manifest).map_err(|e| with_path(&path, e))
+    }
+
+    fn start(&mut self, first: u64) -> io::Result<()> {
+        let path = self.path(self.shards.len());
+        self.open = Some(match self.format {
+            OutputFormat::Text => {
+                let file = File::create(&path).map_err(|e| with_path(&path, e))?;
+                OpenShard::Text { out: BufWriter::new(file), offset: 0 }
+            }
+            OutputFormat::BinDelta => OpenShard::BinDelta { encoder: DeltaEncoder::new(self.shards.last().map_or(0, |s| s.last)) },
+        });
+        self.shards.push(ShardInfo { first, last: first, count: 0 });
+        Ok(())
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        let path = self.path(self.shards.len().saturating_sub(1));
+        match self.open.take() {
+            None => Ok(()),
+            Some(OpenShard::Text { mut out, .. }) => out.flush(),
+            Some(OpenShard::BinDelta { encoder }) => File::create(&path).and_then(|f| encoder.finish(BufWriter::new(f))),
+        }
+        .map_err(|e| with_path(&path, e))
+    }
+
+    fn path(&self, index: usize) -> PathBuf {
+        let extension = match self.format {
+            OutputFormat::Text => "txt",
+            OutputFormat::BinDelta => "bin",
+        };
+        let mut name = self.prefix.clone().into_os_string();
+        name.push(format!("-{:05}.{}", index, extension));
+        name.into()
+    }
+
+    fn manifest_path(&self) -> PathBuf {
+        let mut name = self.prefix.clone().into_os_string();
+        name.push(".manifest");
+        name.into()
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn with_path(path: &Path, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
@@ -0,0 +1,840 @@
+use std::ops::ControlFlow;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, OnceLock};
+use std::thread;
+
+use crate::arith::{self, next_prime};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{
+    backward5, checked_backward5, forward5, segmented_sieve_range_each, sieve_each_marked, try_alloc, wheel_position,
+    GlobalMarks, QueueConfig, SieveError, WheelWalk,
+};
+
+/// A finished sieve up to `n`, kept as its wheel-30 marks so it can be queried repeatedly without
+/// collecting the primes: about 0.27 bytes per integer in range.
+///
+/// The marks never change after construction, so a sieve is `Send + Sync` and one instance can
+/// serve any number of reader threads behind an [`Arc`]:
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::thread;
+/// use eratosthenes::Sieve;
+///
+/// let sieve = Arc::new(Sieve::new(1_000_000).unwrap());
+/// let readers: Vec<_> = (0..4)
+///     .map(|t| {
+///         let sieve = Arc::clone(&sieve);
+///         thread::spawn(move || (t * 1000..(t + 1) * 1000).filter(|&v| sieve.is_prime(v)).count())
+///     })
+///     .collect();
+/// let count: usize = readers.into_iter().map(|r| r.join().unwrap()).sum();
+/// assert_eq!(count, 550);
+/// assert_eq!(sieve.query_many(vec![2, 9, 999_983]), [true, false, true]);
+/// ```
+pub struct Sieve {
+    // The range sieved is lo..=n; lo is 0 except for a sieve over a range (see Sieve::range).
+    lo: u64,
+    n: u64,
+    // The wheel position of not_prime[0]: 0 from 0, the first candidate at or above lo otherwise.
+    first: usize,
+    not_prime: Vec<bool>,
+    // Built on first use by rank and unrank; see Sieve::rank_blocks.
+    rank_blocks: OnceLock<Vec<u64>>,
+}
+
+// Wheel positions per entry of the rank table: about 15k integers, 8 bytes per entry.
+const RANK_BLOCK: usize = 1 << 12;
+
+// Readers share one sieve across threads; keep it that way.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Sieve>();
+};
+
+impl Sieve {
+    /// Sieves up to and including `n` using all hardware threads.
+    pub fn new(n: u64) -> Result<Self, SieveError> {
+        Self::with_threads(n, 0)
+    }
+
+    /// Sieves up to and including `n` with `threads` marking workers; 0 means all of them.
+    pub fn with_threads(n: u64, threads: usize) -> Result<Self, SieveError> {
+        Self::builder(n).threads(threads).build()
+    }
+
+    /// A [`SieveBuilder`] for tuning how the sieve up to `n` is run.
+    #[must_use]
+    pub fn builder(n: u64) -> SieveBuilder {
+        SieveBuilder { n, threads: 0, queue: QueueConfig::default() }
+    }
+
+    /// Builds the same sieve by running deterministic Miller-Rabin on every wheel candidate
+    /// instead of marking multiples. Much slower; it exists as an independent reference and as a
+    /// workload for benchmarking [`is_prime`](crate::is_prime).
+    ///
+    /// # Panics
+    ///
+    /// If the marks cannot be allocated.
+    #[must_use]
+    pub fn from_miller_rabin_table(n: u64) -> Self {
+        let cardinality = checked_backward5(n).and_then(|c| c.checked_add(1)).expect("n is too large to sieve");
+        let mut not_prime = vec![false; cardinality];
+        let mut walk = WheelWalk::new();
+        loop {
+            let p = walk.advance();
+            if p > n {
+                break;
+            }
+            not_prime[backward5(p)] = !arith::is_prime(p);
+        }
+        Sieve::from_marks(n, not_prime)
+    }
+
+    /// Sieves `lo..=hi` alone, laid out on the same wheel as a sieve from 0: a worker's share of
+    /// a sieve split across machines, to be put back together with [`merge_sieves`]. Only the
+    /// range and the base primes up to `sqrt(hi)` are sieved. A range with `lo > hi` holds
+    /// nothing.
+    ///
+    /// Queries on the result are confined to the range: [`is_prime`](Self::is_prime) takes
+    /// values in `lo..=hi`, and [`primes`](Self::primes), [`count_primes_up_to`] and the ranks
+    /// count from `lo`.
+    ///
+    /// [`count_primes_up_to`]: Self::count_primes_up_to
+    pub fn range(lo: u64, hi: u64) -> Result<Self, SieveError> {
+        // From 0 it is the whole sieve, laid out as any other.
+        if lo == 0 {
+            return Sieve::new(hi);
+        }
+        let first = position_at_or_above(lo.max(7));
+        // One past the last candidate at or below hi; backward5 is exact on candidates.
+        let end = match hi {
+            0..7 => first,
+            _ => {
+                let last = wheel_position(hi);
+                if forward5(last) > hi { last } else { last + 1 }.max(first)
+            }
+        };
+        let mut not_prime = try_alloc(end.saturating_sub(first), true, hi)?;
+        if !not_prime.is_empty() {
+            segmented_sieve_range_each(lo.max(7), hi, |p| {
+                not_prime[wheel_position(p) - first] = false;
+                ControlFlow::Continue(())
+            })?;
+        }
+        Ok(Sieve { lo, n: hi, first, not_prime, rank_blocks: OnceLock::new() })
+    }
+
+    // A sieve from 0 from marks built elsewhere, laid out as sieve_each_marked leaves them.
+    pub(crate) fn from_marks(n: u64, not_prime: Vec<bool>) -> Self {
+        Sieve { lo: 0, n, first: 0, not_prime, rank_blocks: OnceLock::new() }
+    }
+
+    // The marks of a sieve from 0, indexed by wheel position.
+    pub(crate) fn marks(&self) -> &[bool] {
+        debug_assert_eq!(self.lo, 0, "only a sieve from 0 has every mark");
+        &self.not_prime
+    }
+
+    // Whether the candidate at wheel position `i`, which the sieve must hold, is marked composite.
+    fn marked(&self, i: usize) -> bool {
+        self.not_prime[i - self.first]
+    }
+
+    // This sieve carried on up to `n`: the marks so far are copied and only (self.n, n] is sieved,
+    // segment by segment.
+    pub(crate) fn extended_to(&self, n: u64) -> Result<Sieve, SieveError> {
+        debug_assert!(n > self.n, "a sieve only grows");
+        debug_assert_eq!(self.lo, 0, "only a sieve from 0 is extended");
+        let cardinality = checked_backward5(n)
+            .and_then(|c| c.checked_add(1))
+            .ok_or(SieveError::AllocationFailed { n })?;
+        let mut not_prime = try_alloc(cardinality, true, n)?;
+        // Everything from the first candidate past the old bound on is sieved again.
+        let kept = self.candidate_marks();
+        not_prime[..kept].copy_from_slice(&self.not_prime[..kept]);
+        segmented_sieve_range_each(self.n + 1, n, |p| {
+            if p >= 7 {
+                not_prime[backward5(p)] = false;
+            }
+            ControlFlow::Continue(())
+        })?;
+        Ok(Sieve::from_marks(n, not_prime))
+    }
+
+    // How many of the marks stand for candidates up to n. The last may stand for one past the
+    // bound, which nothing marked.
+    fn candidate_marks(&self) -> usize {
+        let end = self.first + self.not_prime.len();
+        let mut i = backward5(self.n).max(2);
+        while i < end && forward5(i) <= self.n {
+            i += 1;
+        }
+        i.clamp(self.first, end) - self.first
+    }
+
+    // The first mark standing for a candidate: position 1 is 1 itself, and 7 is at 2.
+    fn first_candidate_mark(&self) -> usize {
+        2usize.saturating_sub(self.first)
+    }
+
+    /// The low end of the sieved range: 0 unless the sieve was built with
+    /// [`range`](Self::range).
+    #[must_use]
+    pub fn lo(&self) -> u64 {
+        self.lo
+    }
+
+    /// The bound the sieve was built for.
+    #[must_use]
+    pub fn n(&self) -> u64 {
+        self.n
+    }
+
+    /// Whether `v` is prime, for `lo <= v <= n`.
+    ///
+    /// # Panics
+    ///
+    /// If `v` is outside the sieved range.
+    #[must_use]
+    pub fn is_prime(&self, v: u64) -> bool {
+        assert!(v <= self.n, "{} is above the sieved bound {}", v, self.n);
+        assert!(v >= self.lo, "{} is below the sieved range, which starts at {}", v, self.lo);
+        match v {
+            2 | 3 | 5 => true,
+            _ if v < 7 || v.is_multiple_of(2) || v.is_multiple_of(3) || v.is_multiple_of(5) => false,
+            _ => !self.marked(backward5(v)),
+        }
+    }
+
+    /// [`is_prime`](Self::is_prime) for each query, in order, with the lookups split across all
+    /// hardware threads sharing this sieve.
+    ///
+    /// # Panics
+    ///
+    /// If any query is outside the sieved range.
+    #[must_use]
+    pub fn query_many(self: &Arc<Self>, queries: Vec<u64>) -> Vec<bool> {
+        let threads = crate::default_threads().min(queries.len()).max(1);
+        if threads == 1 {
+            return queries.into_iter().map(|v| self.is_prime(v)).collect();
+        }
+        let chunk = queries.len().div_ceil(threads).max(1);
+        let mut queries = queries.into_iter();
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                let sieve = Arc::clone(self);
+                let part: Vec<u64> = queries.by_ref().take(chunk).collect();
+                thread::spawn(move || part.into_iter().map(|v| sieve.is_prime(v)).collect::<Vec<_>>())
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    }
+
+    /// The number of primes up to and including `x`, for `x <= n`; for a sieve over a range,
+    /// those from its low end.
+    ///
+    /// # Panics
+    ///
+    /// If `x` is above the sieved bound.
+    #[must_use]
+    pub fn count_primes_up_to(&self, x: u64) -> u64 {
+        assert!(x <= self.n, "{} is above the sieved bound {}", x, self.n);
+        self.primes().take_while(|&p| p <= x).count() as u64
+    }
+
+    /// The 1-based position of `p` among the primes (2 is 1), or `None` if `p` is not prime or
+    /// is outside the sieved range. The inverse of [`unrank`](Self::unrank). For a sieve over a
+    /// range, the position among the primes from its low end.
+    #[must_use]
+    pub fn rank(&self, p: u64) -> Option<usize> {
+        if !(self.lo..=self.n).contains(&p) || !self.is_prime(p) {
+            return None;
+        }
+        let small = SMALL_PRIMES.iter().filter(|&&q| (self.lo..=p).contains(&q)).count();
+        let end = if p < 7 { self.first_candidate_mark() } else { backward5(p) + 1 - self.first };
+        Some(small + self.wheel_primes_before(end))
+    }
+
+    /// The `k`-th prime, counting 2 as the first, if it is within the sieved bound. For a sieve
+    /// over a range, the `k`-th from its low end.
+    #[must_use]
+    pub fn unrank(&self, k: usize) -> Option<u64> {
+        let mut small = SMALL_PRIMES.into_iter().filter(|p| (self.lo..=self.n).contains(p));
+        let k = match (k.checked_sub(1)?, small.clone().count()) {
+            (i, count) if i < count => return small.nth(i),
+            (i, count) => i + 1 - count,
+        };
+        // The last block starting with fewer than k wheel primes before it holds the k-th.
+        let blocks = self.rank_blocks();
+        let block = blocks.partition_point(|&before| before < k as u64).checked_sub(1)?;
+        let mut seen = blocks[block] as usize;
+        for i in (block * RANK_BLOCK).max(self.first_candidate_mark())..self.not_prime.len() {
+            if !self.not_prime[i] {
+                let p = forward5(self.first + i);
+                if p > self.n {
+                    return None;
+                }
+                seen += 1;
+                if seen == k {
+                    return Some(p);
+                }
+            }
+        }
+        None
+    }
+
+    // Wheel primes (7 and up) at marks below `end`, from the rank table and at most one block of
+    // marks.
+    fn wheel_primes_before(&self, end: usize) -> usize {
+        let block = end / RANK_BLOCK;
+        let from = (block * RANK_BLOCK).max(self.first_candidate_mark());
+        self.rank_blocks()[block] as usize + self.not_prime[from.min(end)..end].iter().filter(|&&c| !c).count()
+    }
+
+    // rank_blocks[b]: the wheel primes at marks below b * RANK_BLOCK. In a sieve from 0, marks 0
+    // and 1 (standing for 1) are not candidates, and in any sieve neither is a mark past n.
+    fn rank_blocks(&self) -> &[u64] {
+        self.rank_blocks.get_or_init(|| {
+            let (start, candidates) = (self.first_candidate_mark(), self.candidate_marks());
+            let mut blocks = Vec::with_capacity(candidates / RANK_BLOCK + 2);
+            let mut before = 0;
+            for (b, chunk) in self.not_prime.chunks(RANK_BLOCK).enumerate() {
+                blocks.push(before);
+                let lo = b * RANK_BLOCK;
+                let valid = &chunk[start.saturating_sub(lo).min(chunk.len())..candidates.saturating_sub(lo).min(chunk.len())];
+                before += valid.iter().filter(|&&c| !c).count() as u64;
+            }
+            blocks.push(before);
+            blocks
+        })
+    }
+
+    /// The primes up to `n` in ascending order; for a sieve over a range, those from its low end.
+    #[must_use]
+    pub fn primes(&self) -> Primes<'_> {
+        let mut primes = Primes { sieve: self, small: 0, walk: WheelWalk::new(), done: false };
+        primes.skip_to(self.lo);
+        primes
+    }
+
+    /// Mean, variance and maximum of the gaps between consecutive primes up to `n` (from `lo`
+    /// for a sieve over a range), in one pass.
+    #[must_use]
+    pub fn gap_statistics(&self) -> GapStats {
+        let mut stats = GapStats { max_gap: 0, mean_gap: 0.0, variance_gap: 0.0, max_gap_after: 0, count: 0 };
+        // Welford's online update; `m2` is the running sum of squared deviations.
+        let mut m2 = 0.0;
+        let mut primes = self.primes();
+        let Some(mut previous) = primes.next() else {
+            return stats;
+        };
+        for p in primes {
+            let gap = p - previous;
+            previous = p;
+            stats.count += 1;
+            let delta = gap as f64 - stats.mean_gap;
+            stats.mean_gap += delta / stats.count as f64;
+            m2 += delta * (gap as f64 - stats.mean_gap);
+            if gap > stats.max_gap {
+                stats.max_gap = gap;
+                stats.max_gap_after = p;
+            }
+        }
+        if stats.count > 0 {
+            stats.variance_gap = m2 / stats.count as f64;
+        }
+        stats
+    }
+
+    /// Checks the sieve against Miller-Rabin: every prime `p` must satisfy `next_prime(p - 1) == p`,
+    /// and no value between consecutive primes (or before the first one from `lo`, or after the
+    /// last one, up to `n`) may test prime. Returns the first value where they disagree.
+    pub fn verify_against_next_prime_sequence(&self) -> Result<(), u64> {
+        let mut previous = self.lo.max(2) - 1;
+        for p in self.primes() {
+            if next_prime(p - 1) != Some(p) {
+                return Err(p);
+            }
+            if let Some(v) = (previous + 1..p).find(|&v| arith::is_prime(v)) {
+                return Err(v);
+            }
+            previous = p;
+        }
+        match (previous + 1..=self.n).find(|&v| arith::is_prime(v)) {
+            Some(v) => Err(v),
+            None => Ok(()),
+        }
+    }
+}
+
+// The first wheel position standing for a candidate at or above `v`; backward5 lands on it or
+// the one before.
+fn position_at_or_above(v: u64) -> usize {
+    let i = wheel_position(v);
+    if forward5(i) < v {
+        i + 1
+    } else {
+        i
+    }
+}
+
+/// Puts a sieve split across workers back together: the two sieves, each from 0 or over a range
+/// (see [`Sieve::range`]), become one over the union of their ranges, which must overlap or
+/// meet so that no value is left out; a gap between them fails with
+/// [`SieveError::SievesDisjoint`]. Where the two overlap their marks are checked against each
+/// other first, and any disagreement, from a worker that went wrong or data corrupted on the way,
+/// fails with [`SieveError::SievesDisagree`] naming the smallest value in question. Either may
+/// come first.
+///
+/// Folding over the parts rebuilds the whole sieve:
+///
+/// ```
+/// use eratosthenes::{merge_sieves, Sieve};
+///
+/// let parts = [Sieve::range(40_001, 70_000).unwrap(), Sieve::range(65_000, 100_000).unwrap()];
+/// let merged = parts.into_iter().try_fold(Sieve::new(40_000).unwrap(), merge_sieves).unwrap();
+/// assert_eq!((merged.lo(), merged.n()), (0, 100_000));
+/// assert!(merged.primes().eq(Sieve::new(100_000).unwrap().primes()));
+/// ```
+pub fn merge_sieves(a: Sieve, b: Sieve) -> Result<Sieve, SieveError> {
+    // A range holding nothing adds nothing.
+    if b.lo > b.n {
+        return Ok(a);
+    }
+    if a.lo > a.n {
+        return Ok(b);
+    }
+    // The lower one is carried on with the marks of the other.
+    let (a, b) = if b.lo < a.lo { (b, a) } else { (a, b) };
+    if b.lo > a.n.saturating_add(1) {
+        return Err(SieveError::SievesDisjoint { n: a.n, lo: b.lo });
+    }
+    let (a_end, b_end) = (a.first + a.candidate_marks(), b.first + b.candidate_marks());
+    let overlap = (a.first + a.first_candidate_mark()).max(b.first + b.first_candidate_mark())..a_end.min(b_end);
+    if let Some(i) = overlap.into_iter().find(|&i| a.marked(i) != b.marked(i)) {
+        return Err(SieveError::SievesDisagree { v: forward5(i) });
+    }
+    if b.n <= a.n {
+        return Ok(a);
+    }
+    let n = b.n;
+    // A sieve from 0 holds a mark for every wheel position up to n's.
+    let end = if a.first == 0 {
+        checked_backward5(n).and_then(|c| c.checked_add(1)).ok_or(SieveError::AllocationFailed { n })?
+    } else {
+        b.first + b.not_prime.len()
+    };
+    let mut not_prime = try_alloc(end - a.first, true, n)?;
+    not_prime[..a_end - a.first].copy_from_slice(&a.not_prime[..a_end - a.first]);
+    // A sieve below 7 holds fewer marks than the wheel positions before b's first.
+    let from = a_end.max(b.first);
+    not_prime[from - a.first..b.first + b.not_prime.len() - a.first].copy_from_slice(&b.not_prime[from - b.first..]);
+    Ok(Sieve { lo: a.lo, n, first: a.first, not_prime, rank_blocks: OnceLock::new() })
+}
+
+/// The start of every prime constellation in `sieve` with the shape `offsets`: each prime `p`
+/// for which `p + o` is prime too for every `o` in `offsets`, with all of them within the bound.
+/// Ascending; an offset of 0 is allowed and changes nothing.
+///
+/// ```
+/// use eratosthenes::{find_constellation, Sieve};
+///
+/// let sieve = Sieve::new(1_000).unwrap();
+/// // Twin primes, and prime quadruplets (p, p+2, p+6, p+8).
+/// assert_eq!(find_constellation(&sieve, &[2])[..5], [3, 5, 11, 17, 29]);
+/// assert_eq!(find_constellation(&sieve, &[2, 6, 8]), [5, 11, 101, 191, 821]);
+/// ```
+#[must_use]
+pub fn find_constellation(sieve: &Sieve, offsets: &[u64]) -> Vec<u64> {
+    let width = offsets.iter().copied().max().unwrap_or(0);
+    sieve
+        .primes()
+        .take_while(|&p| p.checked_add(width).is_some_and(|last| last <= sieve.n))
+        .filter(|&p| offsets.iter().all(|&o| sieve.is_prime(p + o)))
+        .collect()
+}
+
+/// The prime triplets up to `n`, by their first member and split by shape: those of the form
+/// `(p, p+2, p+6)` and those of the form `(p, p+4, p+6)`, each ascending. These are the only two
+/// shapes three primes this close can take beyond (2, 3, 5) and (3, 5, 7), which are not counted;
+/// a triplet is listed only if its largest member is at most `n`.
+///
+/// ```
+/// use eratosthenes::prime_triplet_primes;
+///
+/// let (narrow_first, wide_first) = prime_triplet_primes(400);
+/// assert_eq!(narrow_first[..10], [5, 11, 17, 41, 101, 107, 191, 227, 311, 347]);
+/// assert_eq!(wide_first[..10], [7, 13, 37, 67, 97, 103, 193, 223, 277, 307]);
+/// ```
+///
+/// # Panics
+///
+/// If the sieve cannot be allocated.
+#[must_use]
+pub fn prime_triplet_primes(n: u64) -> (Vec<u64>, Vec<u64>) {
+    let sieve = Sieve::new(n).unwrap_or_else(|e| panic!("{}", e));
+    (find_constellation(&sieve, &[2, 6]), find_constellation(&sieve, &[4, 6]))
+}
+
+/// The primes up to `n` as a rayon parallel iterator over a finished [`Sieve`], so downstream
+/// `map`/`filter`/`sum` run across the pool. Unlike [`Sieve::primes`] the order is not
+/// preserved unless collected.
+///
+/// # Panics
+///
+/// If the sieve cannot be allocated.
+#[cfg(feature = "rayon")]
+#[must_use]
+pub fn par_primes_up_to(n: u64) -> impl ParallelIterator<Item = u64> {
+    let sieve = Sieve::new(n).unwrap_or_else(|e| panic!("{}", e));
+    let len = sieve.not_prime.len();
+    let small = [2, 3, 5].into_par_iter().filter(move |&p| p <= n);
+    // Wheel position 1 stands for 1 itself; the candidates start at 7.
+    let wheel = (2..len).into_par_iter().filter_map(move |i| {
+        let p = crate::forward5(i);
+        (p <= sieve.n && !sieve.not_prime[i]).then_some(p)
+    });
+    small.chain(wheel)
+}
+
+/// Options for building a [`Sieve`]; see [`Sieve::builder`].
+#[derive(Debug, Clone)]
+pub struct SieveBuilder {
+    n: u64,
+    threads: usize,
+    queue: QueueConfig,
+}
+
+impl SieveBuilder {
+    /// Marking workers; 0 (the default) means all hardware threads.
+    #[must_use]
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// How many times an idle worker polls for the next task (with [`std::hint::spin_loop`])
+    /// before parking on a condvar. Spinning trades CPU for wake-up latency, which pays off when
+    /// the marking tasks are tiny; the default of 0 parks at once.
+    #[must_use]
+    pub fn spin_before_park(mut self, iters: u32) -> Self {
+        self.queue.spin_before_park = iters;
+        self
+    }
+
+    /// Pins each marking worker to its own CPU, in the order the process may use them, so the
+    /// scheduler cannot migrate it mid-sieve and cost it its cache. With more workers than CPUs
+    /// some share one, and a warning is logged. Needs the `thread-affinity` feature and Linux;
+    /// anywhere else this does nothing. Off by default.
+    #[must_use]
+    pub fn pin_to_core(mut self, pin: bool) -> Self {
+        self.queue.pin_to_core = pin;
+        self
+    }
+
+    pub fn build(self) -> Result<Sieve, SieveError> {
+        let n = self.n;
+        let threads = crate::worker_threads(self.threads);
+        let cancel = AtomicBool::new(false);
+        let marked = sieve_each_marked(n, threads, self.queue, false, &cancel, &GlobalMarks, |_| ControlFlow::Continue(()))?;
+        let not_prime = match marked {
+            Some(not_prime) => not_prime,
+            // Below 7 there is nothing on the wheel to mark.
+            None => {
+                let cardinality = checked_backward5(n).ok_or(SieveError::AllocationFailed { n })? + 1;
+                try_alloc(cardinality, false, n)?
+            }
+        };
+        Ok(Sieve::from_marks(n, not_prime))
+    }
+}
+
+/// Gaps between consecutive primes up to a bound.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GapStats {
+    /// The largest gap; the first one wins on ties.
+    pub max_gap: u64,
+    pub mean_gap: f64,
+    /// Population variance of the gaps.
+    pub variance_gap: f64,
+    /// The prime that ends the largest gap.
+    pub max_gap_after: u64,
+    /// The number of gaps, one less than the number of primes.
+    pub count: u64,
+}
+
+// The primes below the wheel's first candidate, 7.
+const SMALL_PRIMES: [u64; 3] = [2, 3, 5];
+
+/// Iterator over a [`Sieve`]'s primes; see [`Sieve::primes`].
+pub struct Primes<'a> {
+    sieve: &'a Sieve,
+    small: usize,
+    walk: WheelWalk,
+    done: bool,
+}
+
+impl Primes<'_> {
+    /// Moves on so that the next prime returned is the first one at or above `p`, in constant
+    /// time rather than by walking past the primes in between. A `p` at or below where the
+    /// iterator already is leaves it where it is: it never goes back.
+    pub fn skip_to(&mut self, p: u64) {
+        if self.done {
+            return;
+        }
+        if p <= SMALL_PRIMES[SMALL_PRIMES.len() - 1] {
+            let first = SMALL_PRIMES.iter().position(|&q| q >= p).unwrap();
+            self.small = self.small.max(first);
+            return;
+        }
+        self.small = SMALL_PRIMES.len();
+        if p > self.sieve.n {
+            self.done = true;
+            return;
+        }
+        let walk = WheelWalk::starting_at(p);
+        if walk.o > self.walk.o {
+            self.walk = walk;
+        }
+    }
+}
+
+impl Iterator for Primes<'_> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if let Some(&p) = SMALL_PRIMES.get(self.small) {
+            self.small += 1;
+            if p <= self.sieve.n {
+                return Some(p);
+            }
+            self.done = true;
+        }
+        while !self.done {
+            let p = self.walk.advance();
+            if p > self.sieve.n {
+                self.done = true;
+            } else if !self.sieve.marked(backward5(p)) {
+                return Some(p);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sieve_over_a_range_matches_sieve() {
+        let whole = Sieve::new(20_000).unwrap();
+        let ranges = [(0, 20_000), (0, 6), (5, 7), (7, 7), (8, 10), (30, 31), (29, 30), (9_999, 10_007), (100, 99)];
+        for (lo, hi) in ranges {
+            let part = Sieve::range(lo, hi).unwrap();
+            let expected: Vec<u64> = whole.primes().filter(|p| (lo..=hi).contains(p)).collect();
+            assert_eq!(part.primes().collect::<Vec<_>>(), expected, "{}..={}", lo, hi);
+            assert!((lo..=hi).all(|v| part.is_prime(v) == whole.is_prime(v)));
+            if lo <= hi {
+                assert_eq!(part.count_primes_up_to(hi), expected.len() as u64, "{}..={}", lo, hi);
+            }
+            for (k, &p) in expected.iter().enumerate() {
+                assert_eq!((part.rank(p), part.unrank(k + 1)), (Some(k + 1), Some(p)), "{}..={}", lo, hi);
+            }
+            assert_eq!(part.unrank(expected.len() + 1), None);
+            assert_eq!(part.verify_against_next_prime_sequence(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn rank_and_unrank_are_inverse() {
+        // Bounds at the small primes, the start of the wheel, and either side of a rank block.
+        let block_end = forward5(RANK_BLOCK);
+        let bounds = [0, 1, 2, 3, 5, 6, 7, 8, 29, 30, 31, block_end - 1, block_end, block_end + 1, 3 * block_end];
+        for n in bounds.into_iter().chain([100_000]) {
+            let sieve = Sieve::new(n).unwrap();
+            let primes: Vec<u64> = sieve.primes().collect();
+            for (i, &p) in primes.iter().enumerate() {
+                assert_eq!(sieve.unrank(i + 1), Some(p), "n = {}", n);
+                assert_eq!(sieve.rank(p), Some(i + 1), "n = {}", n);
+            }
+            assert_eq!(sieve.unrank(0), None, "n = {}", n);
+            assert_eq!(sieve.unrank(primes.len() + 1), None, "n = {}", n);
+            assert_eq!(sieve.unrank(usize::MAX), None, "n = {}", n);
+            // Composites, 0 and 1, and anything past the bound have no rank.
+            assert!((0..=n).filter(|&v| !sieve.is_prime(v)).all(|v| sieve.rank(v).is_none()), "n = {}", n);
+            assert_eq!(sieve.rank(n + 1), None);
+            assert_eq!(sieve.rank(u64::MAX), None);
+        }
+
+        // Further out, every 97th prime and the last.
+        let sieve = Sieve::new(2_000_000).unwrap();
+        let primes: Vec<u64> = sieve.primes().collect();
+        for k in (1..=primes.len()).step_by(97).chain([primes.len()]) {
+            assert_eq!(sieve.unrank(k), Some(primes[k - 1]));
+            assert_eq!(sieve.rank(primes[k - 1]), Some(k));
+        }
+        assert_eq!((sieve.rank(1_999_993), sieve.unrank(148_933)), (Some(148_933), Some(1_999_993)));
+        assert_eq!(sieve.unrank(148_934), None);
+        assert_eq!([1_999_995, 1_999_999, 2_000_000].map(|v| sieve.rank(v)), [None; 3]);
+    }
+
+    #[test]
+    fn gap_statistics_match_a_two_pass_computation() {
+        let n = 1_000_000;
+        let stats = Sieve::new(n).unwrap().gap_statistics();
+        assert!(stats.max_gap as f64 <= 2.0 * (n as f64).ln().powi(2), "{:?}", stats);
+        assert_eq!((stats.max_gap, stats.max_gap_after, stats.count), (114, 492_227, 78_497));
+
+        for (lo, hi) in [(0, 1_000_000), (0, 3), (0, 100), (1_000, 50_000), (10u64.pow(12), 10u64.pow(12) + 100_000)] {
+            let sieve = Sieve::range(lo, hi).unwrap();
+            let primes: Vec<u64> = sieve.primes().collect();
+            let gaps: Vec<f64> = primes.windows(2).map(|w| (w[1] - w[0]) as f64).collect();
+            let mean = gaps.iter().sum::<f64>() / gaps.len() as f64;
+            let variance = gaps.iter().map(|g| (g - mean).powi(2)).sum::<f64>() / gaps.len() as f64;
+            let stats = sieve.gap_statistics();
+            assert_eq!(stats.count, gaps.len() as u64, "{}..={}", lo, hi);
+            assert!((stats.mean_gap - mean).abs() <= 1e-9 * mean, "{}..={}: {:?}, mean {}", lo, hi, stats, mean);
+            let close = (stats.variance_gap - variance).abs() <= 1e-9 * variance.max(1.0);
+            assert!(close, "{}..={}: {:?}, variance {}", lo, hi, stats, variance);
+            // The first of the largest gaps.
+            let max = gaps.iter().copied().fold(0.0, f64::max);
+            let at = gaps.iter().position(|&g| g == max).unwrap();
+            assert_eq!((stats.max_gap as f64, stats.max_gap_after), (max, primes[at + 1]), "{}..={}", lo, hi);
+        }
+
+        // With fewer than two primes there is no gap.
+        for n in [0, 1, 2] {
+            let stats = Sieve::new(n).unwrap().gap_statistics();
+            let GapStats { max_gap, mean_gap, variance_gap, max_gap_after, count } = stats;
+            assert_eq!((max_gap, mean_gap, variance_gap, max_gap_after, count), (0, 0.0, 0.0, 0, 0), "n = {}", n);
+        }
+        let stats = Sieve::new(3).unwrap().gap_statistics();
+        assert_eq!((stats.max_gap, stats.mean_gap, stats.variance_gap, stats.max_gap_after), (1, 1.0, 0.0, 3));
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn par_primes_match_the_sieve() {
+        assert_eq!(par_primes_up_to(100).count(), 25);
+        for n in [0, 1, 2, 3, 5, 6, 7, 8, 30, 31, 1_000_000] {
+            let mut primes: Vec<u64> = par_primes_up_to(n).collect();
+            primes.sort_unstable();
+            assert!(primes.iter().copied().eq(Sieve::new(n).unwrap().primes()), "n = {}", n);
+        }
+        assert_eq!(par_primes_up_to(1_000_000).sum::<u64>(), 37_550_402_023);
+    }
+
+    #[test]
+    fn sieve_with_one_thread_matches_many() {
+        for n in [0, 1, 2, 7, 48, 49, 50, 120, 121, 1_000, 1_000_000, 3_000_017] {
+            let inline = Sieve::with_threads(n, 1).unwrap();
+            let queued = Sieve::with_threads(n, 4).unwrap();
+            assert!(inline.primes().eq(queued.primes()), "n = {}", n);
+            assert!((0..=n.min(10_000)).all(|v| inline.is_prime(v) == queued.is_prime(v)), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn miller_rabin_table_matches_the_sieve() {
+        for n in (0..30).chain([30, 31, 48, 49, 120, 121, 1_000, 9_999, 65_536, 99_991, 100_000]) {
+            let tested = Sieve::from_miller_rabin_table(n);
+            let sieved = Sieve::new(n).unwrap();
+            assert_eq!(tested.n(), n);
+            // The last mark may stand for the candidate past n, which neither construction looks at.
+            let candidates = sieved.candidate_marks();
+            assert_eq!(tested.candidate_marks(), candidates, "n = {}", n);
+            assert!(tested.marks()[..candidates] == sieved.marks()[..candidates], "n = {}", n);
+            assert!(tested.primes().eq(sieved.primes()), "n = {}", n);
+            assert!((0..=n).all(|v| tested.is_prime(v) == sieved.is_prime(v)), "n = {}", n);
+            assert_eq!(tested.count_primes_up_to(n), sieved.count_primes_up_to(n), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn sieve_over_a_range_far_out() {
+        let lo = 10u64.pow(12) - 1_000;
+        let part = Sieve::range(lo, lo + 2_000).unwrap();
+        let expected: Vec<u64> = (lo..=lo + 2_000).filter(|&v| arith::is_prime(v)).collect();
+        assert!(!expected.is_empty());
+        assert_eq!(part.primes().collect::<Vec<_>>(), expected);
+    }
+
+    // Sieving the halves separately and merging them gives the sieve of the whole, wherever the
+    // split falls and however much the halves overlap.
+    #[test]
+    fn merged_halves_match_a_single_sieve() {
+        let n = 200_000;
+        let whole = Sieve::new(n).unwrap();
+        for split in [0, 1, 6, 7, 29, 30, 31, 436, 3_000, 99_991, 150_000, n - 1, n] {
+            for overlap in [0, 1, 2, 59, 1_000] {
+                let lo = (split + 1).saturating_sub(overlap);
+                let merged = merge_sieves(Sieve::new(split).unwrap(), Sieve::range(lo, n).unwrap()).unwrap();
+                assert_eq!(merged.n(), n);
+                assert!(merged.primes().eq(whole.primes()), "split at {}, second half from {}", split, lo);
+                assert_eq!(merged.count_primes_up_to(n), 17_984);
+                // Merged the other way round, and as workers that each took a range.
+                let merged = merge_sieves(Sieve::range(lo, n).unwrap(), Sieve::new(split).unwrap()).unwrap();
+                assert!(merged.primes().eq(whole.primes()), "split at {}, first half second", split);
+                let (left, right) = (Sieve::range(1_000, split).unwrap(), Sieve::range(lo, n).unwrap());
+                let merged = merge_sieves(left, right).unwrap();
+                // Below 1000 the left range holds nothing, and the right is all there is.
+                let from = if split < 1_000 { lo } else { 1_000 };
+                assert_eq!((merged.lo(), merged.n()), (from, n));
+                assert!(merged.primes().eq(whole.primes().skip_while(|&p| p < from)), "ranges split at {}", split);
+            }
+        }
+    }
+
+    #[test]
+    fn merge_workers_in_any_order() {
+        let n = 100_000;
+        let parts = [(0, 20_000), (60_000, n), (20_001, 45_000), (40_000, 60_000)];
+        let sieves = parts.map(|(lo, hi)| Sieve::range(lo, hi).unwrap());
+        // Each part is merged in once everything below it is; the first starts at 0.
+        let [a, d, b, c] = sieves;
+        let merged = [b, c, d].into_iter().try_fold(a, merge_sieves).unwrap();
+        let whole = Sieve::new(n).unwrap();
+        assert!(merged.primes().eq(whole.primes()));
+        assert_eq!((merged.rank(99_991), merged.unrank(9_592)), (Some(9_592), Some(99_991)));
+        assert_eq!(merged.to_json(), whole.to_json());
+        // Two sieves from 0: the longer, once the shorter agrees with it.
+        let merged = merge_sieves(Sieve::new(60_000).unwrap(), Sieve::new(n).unwrap()).unwrap();
+        assert!(merged.primes().eq(whole.primes()));
+    }
+
+    #[test]
+    fn merge_with_a_range_inside_the_sieve() {
+        // The last mark of the sieve up to 436 stands for 437, which it never marked.
+        let merged = merge_sieves(Sieve::new(436).unwrap(), Sieve::range(0, 3_000).unwrap()).unwrap();
+        assert!(merged.primes().eq(Sieve::new(3_000).unwrap().primes()));
+        let merged = merge_sieves(Sieve::new(3_000).unwrap(), Sieve::range(400, 436).unwrap()).unwrap();
+        assert_eq!(merged.n(), 3_000);
+        let merged = merge_sieves(Sieve::new(3_000).unwrap(), Sieve::range(10, 9).unwrap()).unwrap();
+        assert_eq!(merged.n(), 3_000);
+    }
+
+    #[test]
+    fn merge_rejects_a_gap() {
+        let err = merge_sieves(Sieve::new(1_000).unwrap(), Sieve::range(1_002, 2_000).unwrap()).err();
+        assert_eq!(err, Some(SieveError::SievesDisjoint { n: 1_000, lo: 1_002 }));
+        let err = merge_sieves(Sieve::range(1_002, 2_000).unwrap(), Sieve::range(500, 1_000).unwrap()).err();
+        assert_eq!(err, Some(SieveError::SievesDisjoint { n: 1_000, lo: 1_002 }));
+    }
+
+    #[test]
+    fn merge_rejects_disagreeing_marks() {
+        let mut part = Sieve::range(900, 2_000).unwrap();
+        // 997 is prime; the corrupted part says otherwise.
+        let i = backward5(997) - part.first;
+        part.not_prime[i] = true;
+        let err = merge_sieves(Sieve::new(1_000).unwrap(), part).err();
+        assert_eq!(err, Some(SieveError::SievesDisagree { v: 997 }));
+    }
+}
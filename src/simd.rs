@@ -0,0 +1,154 @@
+//! Marking the multiples of a base prime in a run of wheel marks. The scalar loop steps from one
+//! multiple to the next, a division apiece to find its mark. The smallest primes have a multiple
+//! every few positions, so built with AVX2 they are marked a vector at a time instead: each lane
+//! keeps its position's residue mod `p` and marks where it is 0, 32 positions per step with no
+//! division.
+
+use crate::{backward5, forward5, wheel_position};
+
+// The largest prime marked a vector at a time; above it, stepping between the multiples is
+// cheaper than looking at every position.
+#[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+const SIMD_MARK_LIMIT: u64 = 31;
+
+/// Sets the marks of the multiples of `p` from `p * p` on, in marks whose first flag is wheel
+/// position `first` (at least 1). Other marks are left as they are. `p` must be at least 7.
+pub(crate) fn simd_mark_multiples(marks: &mut [bool], first: usize, p: u64) {
+    #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+    if p <= SIMD_MARK_LIMIT {
+        return avx2_mark_multiples(marks, first, p);
+    }
+    scalar_mark_multiples(marks, first, p);
+}
+
+// Steps through the odd multiples of p from p * p in the marks' range, skipping those divisible
+// by 3 or 5.
+fn scalar_mark_multiples(marks: &mut [bool], first: usize, p: u64) {
+    let Some(last) = marks.len().checked_sub(1) else {
+        return;
+    };
+    let (low, high) = (forward5(first), forward5(first + last));
+    let Some(m) = low.div_ceil(p).checked_mul(p) else {
+        return;
+    };
+    let m = m.max(p * p);
+    let Some(mut m) = m.checked_add(if m & 1 == 0 { p } else { 0 }) else {
+        return;
+    };
+    // backward5 shifts its argument left, overflowing from 2^62; past there it is worked out in
+    // u128, which marks below 2^62 never pay for.
+    let wide = high >= 1 << 62;
+    while m <= high {
+        if !m.is_multiple_of(3) && !m.is_multiple_of(5) {
+            let i = if wide { wheel_position(m) } else { backward5(m) };
+            marks[i - first] = true;
+        }
+        match m.checked_add(p << 1) {
+            Some(next) => m = next,
+            None => break,
+        }
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+fn avx2_mark_multiples(marks: &mut [bool], first: usize, p: u64) {
+    use std::arch::x86_64::*;
+
+    const WHEEL30: [u64; 8] = [1, 7, 11, 13, 17, 19, 23, 29];
+    // The lanes run a turn of the wheel (8 positions, 30 numbers) to a vector, 4 vectors a step.
+    const STEP: usize = 32;
+
+    // Nothing is marked below p * p; from there to the first turn of the wheel, and after the
+    // last whole step, the scalar loop marks.
+    let skip = wheel_position(p * p).saturating_sub(first).min(marks.len());
+    let (marks, first) = (&mut marks[skip..], first + skip);
+    let head = ((9 - first % 8) % 8).min(marks.len());
+    let (head_marks, body) = marks.split_at_mut(head);
+    scalar_mark_multiples(head_marks, first, p);
+    let start = first + head;
+    let steps = body.len() / STEP;
+    let (body, tail) = body.split_at_mut(steps * STEP);
+    scalar_mark_multiples(tail, start + steps * STEP, p);
+    if steps == 0 {
+        return;
+    }
+
+    // Lane j of vector t holds the residue of position start + 8t + j, which stands for
+    // 30 * (turn + t) + WHEEL30[j].
+    let turn = ((start - 1) / 8) as u64 % p;
+    let residue = |t: u64, j: usize| ((turn + t) * 30 + WHEEL30[j]) % p;
+    let lanes = |t: u64| std::array::from_fn::<i32, 8, _>(|j| residue(t, j) as i32);
+    let step = (STEP as u64 / 8 * 30 % p) as i32;
+
+    // SAFETY: the target has AVX2, as the cfg requires, and every load and store is an unaligned
+    // access within one 32-byte chunk of `body`. The bytes stored are 0 or 1: a mark ORed with 0
+    // or 1.
+    unsafe {
+        let mut residues = [lanes(0), lanes(1), lanes(2), lanes(3)].map(|r| _mm256_loadu_si256(r.as_ptr().cast()));
+        let (zero, one, step, modulus) =
+            (_mm256_setzero_si256(), _mm256_set1_epi8(1), _mm256_set1_epi32(step), _mm256_set1_epi32(p as i32));
+        // packs works within 128-bit halves; this puts the 4-byte groups back in position order.
+        let order = _mm256_setr_epi32(0, 4, 1, 5, 2, 6, 3, 7);
+        for chunk in body.chunks_exact_mut(STEP) {
+            let [r0, r1, r2, r3] = residues.map(|r| _mm256_cmpeq_epi32(r, zero));
+            let hits = _mm256_packs_epi16(_mm256_packs_epi32(r0, r1), _mm256_packs_epi32(r2, r3));
+            let hits = _mm256_and_si256(_mm256_permutevar8x32_epi32(hits, order), one);
+            let chunk = chunk.as_mut_ptr().cast::<__m256i>();
+            _mm256_storeu_si256(chunk, _mm256_or_si256(_mm256_loadu_si256(chunk), hits));
+            // r + step, less p once it reaches p: the wrapped difference is larger when it does not.
+            residues = residues.map(|r| {
+                let r = _mm256_add_epi32(r, step);
+                _mm256_min_epu32(r, _mm256_sub_epi32(r, modulus))
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Marks every multiple of p from p * p, one position at a time.
+    fn reference(marks: &mut [bool], first: usize, p: u64) {
+        for (i, mark) in marks.iter_mut().enumerate() {
+            let v = forward5(first + i);
+            if v >= p * p && v.is_multiple_of(p) {
+                *mark = true;
+            }
+        }
+    }
+
+    fn cases() -> impl Iterator<Item = (usize, usize, u64)> {
+        let primes = [7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47];
+        let starts = [1, 2, 3, 8, 9, 10, 17, 1_000, 1_001, 1_007, usize::MAX / 16];
+        let lengths = [0, 1, 7, 31, 32, 33, 95, 1_000, 4_099];
+        primes.into_iter().flat_map(move |p| {
+            starts.into_iter().flat_map(move |first| lengths.into_iter().map(move |len| (first, len, p)))
+        })
+    }
+
+    // A fixed scatter of marks already set, which marking must keep.
+    fn prefilled(len: usize) -> Vec<bool> {
+        (0..len).map(|i| i % 5 == 3).collect()
+    }
+
+    #[test]
+    fn scalar_marks_every_multiple() {
+        for (first, len, p) in cases() {
+            let (mut marks, mut expected) = (prefilled(len), prefilled(len));
+            scalar_mark_multiples(&mut marks, first, p);
+            reference(&mut expected, first, p);
+            assert_eq!(marks, expected, "p = {} from position {} for {}", p, first, len);
+        }
+    }
+
+    #[test]
+    fn simd_marks_as_the_scalar_loop_does() {
+        for (first, len, p) in cases() {
+            let (mut simd, mut scalar) = (prefilled(len), prefilled(len));
+            simd_mark_multiples(&mut simd, first, p);
+            scalar_mark_multiples(&mut scalar, first, p);
+            assert_eq!(simd, scalar, "p = {} from position {} for {}", p, first, len);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+/// Where `k` (from 1) sits on the Ulam spiral, as `(x, y)` from the centre with `y` upwards: 1 at
+/// the origin, 2 to its right, then counter-clockwise, so 3 is at `(1, 1)` and 9 at `(1, -1)`.
+///
+/// # Panics
+///
+/// If `k` is 0.
+#[must_use]
+pub fn ulam_position(k: u64) -> (i64, i64) {
+    assert!(k > 0, "the Ulam spiral starts at 1");
+    if k == 1 {
+        return (0, 0);
+    }
+    // Ring r holds (2r - 1)^2 + 1 ..= (2r + 1)^2, 2r values to a side.
+    let r = (k - 1).isqrt().div_ceil(2);
+    let t = k - (2 * r - 1) * (2 * r - 1);
+    let (r, t) = (r as i64, t as i64);
+    match (t - 1) / (2 * r) {
+        0 => (r, t - r),
+        1 => (3 * r - t, r),
+        2 => (-r, 5 * r - t),
+        _ => (t - 7 * r, -r),
+    }
+}
+
+/// The value at `(x, y)` on the Ulam spiral; the inverse of [`ulam_position`].
+#[must_use]
+pub fn ulam_value(x: i64, y: i64) -> u64 {
+    let r = x.unsigned_abs().max(y.unsigned_abs()) as i64;
+    if r == 0 {
+        return 1;
+    }
+    let before = (2 * r - 1) * (2 * r - 1);
+    let t = if x == r && y > -r {
+        y + r
+    } else if y == r {
+        3 * r - x
+    } else if x == -r {
+        5 * r - y
+    } else {
+        x + 7 * r
+    };
+    (before + t) as u64
+}
+
+/// The `(x, y)` range covered by `1..=size^2` on the spiral, as `(low, high)` for both axes: the
+/// centre is the middle cell for odd `size` and the lower-left of the middle four for even.
+#[must_use]
+pub fn ulam_bounds(size: u64) -> (i64, i64) {
+    (-(size.saturating_sub(1) as i64 / 2), size as i64 / 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ulam_position_of_the_first_ring() {
+        let ring = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+        assert_eq!(ulam_position(1), (0, 0));
+        for (k, &position) in (2..=9).zip(&ring) {
+            assert_eq!(ulam_position(k), position, "k = {}", k);
+        }
+        // The corners of ring 2, and the first of ring 3.
+        assert_eq!([13, 17, 21, 25, 26].map(ulam_position), [(2, 2), (-2, 2), (-2, -2), (2, -2), (3, -2)]);
+    }
+
+    #[test]
+    fn ulam_value_inverts_ulam_position() {
+        for k in 1..=10_000 {
+            let (x, y) = ulam_position(k);
+            assert_eq!(ulam_value(x, y), k);
+        }
+        // Consecutive values are neighbours.
+        for k in 1..10_000 {
+            let ((x0, y0), (x1, y1)) = (ulam_position(k), ulam_position(k + 1));
+            assert_eq!((x0 - x1).abs() + (y0 - y1).abs(), 1, "{} and {}", k, k + 1);
+        }
+    }
+
+    #[test]
+    fn ulam_bounds_cover_the_square() {
+        assert_eq!(ulam_bounds(1), (0, 0));
+        assert_eq!(ulam_bounds(2), (0, 1));
+        assert_eq!(ulam_bounds(11), (-5, 5));
+        assert_eq!(ulam_bounds(12), (-5, 6));
+        for size in 1..=12 {
+            let (low, high) = ulam_bounds(size);
+            let mut values: Vec<u64> =
+                (low..=high).flat_map(|y| (low..=high).map(move |x| ulam_value(x, y))).collect();
+            values.sort_unstable();
+            assert!(values.into_iter().eq(1..=size * size), "size {}", size);
+        }
+    }
+}
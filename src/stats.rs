@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use eratosthenes::Plan;
+
+use crate::throughput::Rates;
+
+/// What a run did, printed as one line of JSON on stderr with `--stats`.
+pub struct RunStats {
+    pub n: u64,
+    pub threads: usize,
+    pub plan: Plan,
+    pub memory_limit: Option<usize>,
+    pub primes: u64,
+    pub elapsed: Duration,
+    // Average rates, when --throughput measured them.
+    pub rates: Option<Rates>,
+}
+
+impl RunStats {
+    pub fn to_json(&self) -> String {
+        let (strategy, segment_size, segments) = match self.plan {
+            Plan::Monolithic { .. } => ("monolithic", None, None),
+            Plan::Segmented { segment_size, segments, .. } => ("segmented", Some(segment_size), Some(segments)),
+        };
+        format!(
+            "{{\"n\":{},\"threads\":{},\"strategy\":\"{}\",\"segment_size\":{},\"segments\":{},\
+             \"planned_bytes\":{},\"memory_limit\":{},\"primes\":{},\"elapsed_seconds\":{:.6},\
+             \"peak_rss_bytes\":{},\"candidates_per_second\":{},\"primes_per_second\":{}}}",
+            self.n,
+            self.threads,
+            strategy,
+            json_option(segment_size),
+            json_option(segments),
+            self.plan.bytes(),
+            json_option(self.memory_limit),
+            self.primes,
+            self.elapsed.as_secs_f64(),
+            json_option(peak_rss_bytes()),
+            json_option(self.rates.map(|r| format!("{:.1}", r.candidates_per_second))),
+            json_option(self.rates.map(|r| format!("{:.1}", r.primes_per_second))),
+        )
+    }
+}
+
+pub fn json_option<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "null".to_string(), |v| v.to_string())
+}
+
+/// The process's peak resident set size, where the platform reports it.
+pub fn peak_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: u64 = line.trim_start_matches("VmHWM:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Starts the peak resident set size afresh from the current one, where the platform allows,
+/// so that [`peak_rss_bytes`] covers only what follows.
+pub fn reset_peak_rss() -> bool {
+    std::fs::write("/proc/self/clear_refs", "5").is_ok()
+}
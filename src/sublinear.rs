@@ -0,0 +1,129 @@
+use std::ops::ControlFlow;
+
+use crate::{compute_pi_x_meissel_lehmer, nth_prime, nth_prime_upper_bound, segmented_sieve_range_each_with, BasePrimes};
+
+// Below this the sieve up to the Rosser bound is already cheap, and the estimate below is loose.
+const SIEVE_BELOW: u64 = 100_000;
+
+/// The `n`-th prime, counting 2 as the first, without sieving everything below it: an estimate
+/// `x` from the inverse of the logarithmic integral, `π(x)` by
+/// [`compute_pi_x_meissel_lehmer`], and a sieve of only the short stretch between `x` and the
+/// answer, whose length is about `sqrt(p_n) ln p_n`.
+///
+/// The count is Lehmer's formula, not the Lagarias-Miller-Odlyzko method: time is sub-linear in
+/// `p_n`, but above LMO's `O(p_n^(2/3))`, and memory is `O(sqrt(p_n))` rather than LMO's
+/// `O(p_n^(1/3))`, for the primes up to `sqrt(p_n)` that both the count and the sieve of the
+/// stretch are run against. That is still far below the `O(p_n)` of [`nth_prime`], which sieves
+/// from 2.
+///
+/// ```
+/// assert_eq!(eratosthenes::nth_prime_fast(1_000_000), 15_485_863);
+/// assert_eq!(eratosthenes::nth_prime_fast(1_000_000), eratosthenes::nth_prime(1_000_000).unwrap());
+/// ```
+///
+/// # Panics
+///
+/// If `n` is 0 or the `n`-th prime is beyond u64.
+#[must_use]
+pub fn nth_prime_fast(n: u64) -> u64 {
+    assert!(n > 0, "there is no 0th prime; counting starts at 1 for 2");
+    let bound = nth_prime_upper_bound(n).unwrap_or_else(|| panic!("the {}th prime is beyond u64", n));
+    if n < SIEVE_BELOW {
+        return nth_prime(n).unwrap();
+    }
+    let base = BasePrimes::for_range(bound).unwrap_or_else(|e| panic!("{}", e));
+    let x = (li_inverse(n as f64) as u64).min(bound);
+    let mut count = compute_pi_x_meissel_lehmer(x);
+
+    if count < n {
+        // Short of the n-th prime: count on past x.
+        let mut found = None;
+        segmented_sieve_range_each_with(&base, x + 1, bound, |p| {
+            count += 1;
+            if count < n {
+                return ControlFlow::Continue(());
+            }
+            found = Some(p);
+            ControlFlow::Break(())
+        })
+        .unwrap_or_else(|e| panic!("{}", e));
+        return found.unwrap_or_else(|| panic!("the {}th prime is beyond u64", n));
+    }
+
+    // At or past it: step back a window at a time, each holding `count` primes at its top end.
+    let width = x.isqrt().max(1 << 16);
+    let mut hi = x;
+    loop {
+        let lo = hi.saturating_sub(width - 1).max(2);
+        let mut window = Vec::new();
+        segmented_sieve_range_each_with(&base, lo, hi, |p| {
+            window.push(p);
+            ControlFlow::Continue(())
+        })
+        .unwrap_or_else(|e| panic!("{}", e));
+        let below = count - window.len() as u64;
+        if below < n {
+            return window[(n - below - 1) as usize];
+        }
+        count = below;
+        hi = lo - 1;
+    }
+}
+
+// The x with li(x) = y, by Newton's method: li'(x) = 1 / ln x. Off from p_n by about
+// sqrt(p_n) ln p_n, which is the stretch left to sieve.
+fn li_inverse(y: f64) -> f64 {
+    let mut x = y * y.ln();
+    for _ in 0..100 {
+        let step = (li(x) - y) * x.ln();
+        x -= step;
+        if step.abs() < 0.5 {
+            break;
+        }
+    }
+    x
+}
+
+// The logarithmic integral by Ramanujan's series, for x > 1.
+fn li(x: f64) -> f64 {
+    const EULER_GAMMA: f64 = 0.577_215_664_901_532_9;
+    let ln_x = x.ln();
+    let mut sum = 0.0;
+    let mut term = 1.0;
+    let mut inner = 0.0;
+    for k in 1..200 {
+        // term = (-1)^(k-1) (ln x)^k / (k! 2^(k-1)).
+        term *= ln_x / k as f64 * if k == 1 { 1.0 } else { -0.5 };
+        if (k - 1) % 2 == 0 {
+            inner += 1.0 / k as f64;
+        }
+        let next = sum + term * inner;
+        if next == sum {
+            break;
+        }
+        sum = next;
+    }
+    EULER_GAMMA + ln_x.ln() + x.sqrt() * sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nth_prime_fast_matches_the_sieve() {
+        assert_eq!(nth_prime_fast(10u64.pow(6)), 15_485_863);
+        assert_eq!(nth_prime_fast(10u64.pow(6)), nth_prime(10u64.pow(6)).unwrap());
+        // Either side of where the sieve takes over, and on to where the count does the work.
+        for n in [1, 2, 3, SIEVE_BELOW - 1, SIEVE_BELOW, SIEVE_BELOW + 1, 123_456, 777_777, 2_000_000] {
+            assert_eq!(nth_prime_fast(n), nth_prime(n).unwrap(), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn li_inverse_lands_near_the_nth_prime() {
+        // p_(10^7) = 179_424_673; the estimate is off by about sqrt(p) ln p.
+        let x = li_inverse(1e7);
+        assert!((x - 179_424_673.0).abs() < 179_424_673f64.sqrt() * 179_424_673f64.ln());
+    }
+}
@@ -0,0 +1,119 @@
+// `--throughput`: a status line on stderr showing how fast the run is going, refreshed a few
+// times a second from two relaxed atomics that every emitted prime updates.
+//
+// The rates are over a sliding window, so they follow a change of pace rather than averaging it
+// away: wheel candidates covered per second, counting the candidates below the largest prime
+// emitted so far, and primes emitted per second. Marking shows up as the candidate rate, since
+// the primes below p * p are only emitted once p is marked; output shows up as the prime rate.
+
+use std::collections::VecDeque;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const REFRESH: Duration = Duration::from_millis(250);
+const WINDOW: Duration = Duration::from_secs(5);
+
+static PRIMES: AtomicU64 = AtomicU64::new(0);
+static LAST_PRIME: AtomicU64 = AtomicU64::new(0);
+static STOP: AtomicBool = AtomicBool::new(false);
+static METER: Mutex<Option<(JoinHandle<()>, Instant)>> = Mutex::new(None);
+static AVERAGE: OnceLock<Rates> = OnceLock::new();
+
+#[derive(Clone, Copy)]
+pub struct Rates {
+    pub candidates_per_second: f64,
+    pub primes_per_second: f64,
+}
+
+impl Rates {
+    fn between(from: (Instant, u64, u64), to: (Instant, u64, u64)) -> Rates {
+        let seconds = to.0.duration_since(from.0).as_secs_f64().max(f64::MIN_POSITIVE);
+        Rates {
+            candidates_per_second: candidates(to.2).saturating_sub(candidates(from.2)) as f64 / seconds,
+            primes_per_second: to.1.saturating_sub(from.1) as f64 / seconds,
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("{} candidates/s  {} primes/s", si(self.candidates_per_second), si(self.primes_per_second))
+    }
+}
+
+// Wheel-30 candidates up to v: 8 in every 30.
+fn candidates(v: u64) -> u64 {
+    v / 30 * 8 + [1, 7, 11, 13, 17, 19, 23, 29].iter().filter(|&&r| r <= v % 30).count() as u64
+}
+
+fn si(rate: f64) -> String {
+    match rate {
+        r if r >= 1e9 => format!("{:.2}G", r / 1e9),
+        r if r >= 1e6 => format!("{:.2}M", r / 1e6),
+        r if r >= 1e3 => format!("{:.2}k", r / 1e3),
+        r => format!("{:.0}", r),
+    }
+}
+
+// Overwrites the status line on a terminal; elsewhere, such as a log file, each update gets a
+// line of its own.
+fn status(line: &str, last: bool) {
+    let mut stderr = io::stderr().lock();
+    let _ = if !stderr.is_terminal() {
+        writeln!(stderr, "{}", line)
+    } else if last {
+        writeln!(stderr, "\r{}\x1b[K", line)
+    } else {
+        write!(stderr, "\r{}\x1b[K", line).and_then(|()| stderr.flush())
+    };
+}
+
+fn sample() -> (Instant, u64, u64) {
+    (Instant::now(), PRIMES.load(Ordering::Relaxed), LAST_PRIME.load(Ordering::Relaxed))
+}
+
+/// Notes the `count`th prime, `p`. Cheap enough to call for every prime, meter or not.
+pub fn record(count: u64, p: u64) {
+    PRIMES.store(count, Ordering::Relaxed);
+    LAST_PRIME.store(p, Ordering::Relaxed);
+}
+
+/// Starts the status line for a run up to `n`.
+pub fn start(n: u64) {
+    let started = Instant::now();
+    let handle = thread::spawn(move || {
+        let mut window = VecDeque::new();
+        window.push_back(sample());
+        loop {
+            thread::park_timeout(REFRESH);
+            if STOP.load(Ordering::Relaxed) {
+                break;
+            }
+            let now = sample();
+            while window.len() > 1 && now.0.duration_since(window[0].0) > WINDOW {
+                window.pop_front();
+            }
+            let rates = Rates::between(window[0], now);
+            window.push_back(now);
+            let covered = now.2 as f64 * 100.0 / n.max(1) as f64;
+            status(&format!("{}  ({:.1}% of n)", rates.describe(), covered), false);
+        }
+    });
+    *METER.lock().unwrap() = Some((handle, started));
+}
+
+/// Stops the status line, replacing it with the average rates over the whole run, which it
+/// returns. Returns the same rates if already stopped, and `None` if it never started.
+pub fn stop() -> Option<Rates> {
+    let meter = METER.lock().unwrap().take();
+    if let Some((handle, started)) = meter {
+        STOP.store(true, Ordering::Relaxed);
+        handle.thread().unpark();
+        let _ = handle.join();
+        let rates = Rates::between((started, 0, 0), sample());
+        status(&format!("average {}", rates.describe()), true);
+        let _ = AVERAGE.set(rates);
+    }
+    AVERAGE.get().copied()
+}
@@ -0,0 +1,211 @@
+// `validate FILE`: checks a prime list as written by --print or --output, in either format and
+// possibly compressed. It reports values that are not prime or not strictly increasing, and with
+// --complete LO..HI, primes in that range that the list leaves out.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::iter::Peekable;
+use std::ops::ControlFlow;
+
+use eratosthenes::format::DELTA_MAGIC;
+use eratosthenes::{decompress, is_prime, segmented_sieve_range_each, DeltaReader, Sieve};
+
+use crate::bound::parse_range;
+use crate::exit::Kind;
+use crate::{fail, fail_sieve, parse_limit, usage_error, OutputFormat};
+
+// Values up to this are looked up in a sieve built once, about 4.5 MB of marks; larger ones go
+// to Miller-Rabin.
+const SIEVE_BOUND: u64 = 1 << 24;
+
+// One value from the file, or why it could not be read, with where it sits: a line number for
+// text, a position in the list for bin-delta.
+struct Entry {
+    at: String,
+    value: Result<u64, String>,
+}
+
+pub fn validate(args: impl Iterator<Item = String>) {
+    let mut path = None;
+    let mut format = None;
+    let mut complete = None;
+    let mut max_errors = 1;
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("text") => Some(OutputFormat::Text),
+                    Some("bin-delta") => Some(OutputFormat::BinDelta),
+                    Some(other) => usage_error(&format!("unknown format: {}", other)),
+                    None => usage_error("--format needs a value"),
+                }
+            }
+            "--complete" => {
+                let range = args.next().unwrap_or_else(|| usage_error("--complete needs a range LO..HI"));
+                complete = Some(parse_range(&range).unwrap_or_else(|e| usage_error(&format!("--complete: {}", e))));
+            }
+            "--max-errors" => {
+                max_errors = parse_limit(&args.next().unwrap_or_else(|| usage_error("--max-errors needs a value")));
+                if max_errors == 0 {
+                    usage_error("--max-errors must be at least 1");
+                }
+            }
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option: {}", arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => usage_error(&format!("unexpected argument: {}", arg)),
+        }
+    }
+    let Some(path) = path else {
+        usage_error("validate needs a FILE");
+    };
+
+    let file = File::open(&path).unwrap_or_else(|e| fail(format_args!("{}: {}", path, e)));
+    let mut input = BufReader::new(decompress(file).unwrap_or_else(|e| fail(format_args!("{}: {}", path, e))));
+    let format = format.unwrap_or_else(|| match input.fill_buf() {
+        Ok(head) if head.starts_with(&DELTA_MAGIC) => OutputFormat::BinDelta,
+        Ok(_) => OutputFormat::Text,
+        Err(e) => fail(format_args!("{}: {}", path, e)),
+    });
+    let entries: Box<dyn Iterator<Item = Entry>> = match format {
+        OutputFormat::Text => Box::new(text_entries(input, path.clone())),
+        OutputFormat::BinDelta => {
+            let reader = DeltaReader::new(input).unwrap_or_else(|e| fail(format_args!("{}: {}", path, e)));
+            Box::new(reader.enumerate().map(|(i, p)| Entry {
+                at: format!("prime #{}", i + 1),
+                value: p.map_err(|e| e.to_string()),
+            }))
+        }
+    };
+
+    let small = Sieve::new(SIEVE_BOUND).unwrap_or_else(|e| fail_sieve(e));
+    let mut validator = Validator { path: &path, small, previous: None, checked: 0, violations: 0, max_errors };
+    let mut entries = entries.peekable();
+    let _ = match complete {
+        Some((lo, hi)) => validator.check_complete(&mut entries, lo, hi),
+        None => validator.check_all(&mut entries),
+    };
+
+    if validator.violations == 0 {
+        let plural = if validator.checked == 1 { "" } else { "s" };
+        let mut summary = format!("{}: {} prime{}, all prime and strictly increasing", path, validator.checked, plural);
+        if let Some((lo, hi)) = complete {
+            summary.push_str(&format!(", none missing from {} to {}", lo, hi));
+        }
+        println!("{}", summary);
+    } else {
+        let plural = if validator.violations == 1 { "" } else { "s" };
+        if validator.violations >= max_errors {
+            println!(
+                "{}: stopped after {} problem{} in the first {} values; see --max-errors",
+                path, validator.violations, plural, validator.checked
+            );
+        } else {
+            println!("{}: {} problem{} in {} values", path, validator.violations, plural, validator.checked);
+        }
+        std::process::exit(Kind::Failure.code());
+    }
+}
+
+fn text_entries<R: BufRead>(input: R, path: String) -> impl Iterator<Item = Entry> {
+    input.lines().enumerate().map(move |(i, line)| {
+        let line = line.unwrap_or_else(|e| fail(format_args!("{}: line {}: {}", path, i + 1, e)));
+        let value = line.trim().parse().map_err(|_| format!("not a number: {:?}", line));
+        Entry { at: format!("line {}", i + 1), value }
+    })
+}
+
+struct Validator<'a> {
+    path: &'a str,
+    small: Sieve,
+    // The last value read and where it was.
+    previous: Option<(u64, String)>,
+    checked: u64,
+    violations: u64,
+    max_errors: u64,
+}
+
+impl Validator<'_> {
+    fn check_all(&mut self, entries: &mut impl Iterator<Item = Entry>) -> ControlFlow<()> {
+        entries.try_for_each(|entry| self.check(entry, None))
+    }
+
+    // Walks the file alongside a fresh sieve of lo..=hi: every prime the sieve finds must be the
+    // next value in the file, once the values below it have been checked. The values that match
+    // are known to be prime, which spares nearly all of them the primality test.
+    fn check_complete<I: Iterator<Item = Entry>>(&mut self, entries: &mut Peekable<I>, lo: u64, hi: u64) -> ControlFlow<()> {
+        let mut flow = ControlFlow::Continue(());
+        segmented_sieve_range_each(lo, hi, |q| {
+            flow = self.expect(entries, q);
+            flow
+        })
+        .unwrap_or_else(|e| fail_sieve(e));
+        flow?;
+        self.check_all(entries)
+    }
+
+    fn expect<I: Iterator<Item = Entry>>(&mut self, entries: &mut Peekable<I>, q: u64) -> ControlFlow<()> {
+        loop {
+            let p = match entries.peek().map(|entry| entry.value.as_ref()) {
+                Some(Ok(&p)) => p,
+                Some(Err(_)) => {
+                    self.check(entries.next().unwrap(), None)?;
+                    continue;
+                }
+                None => return self.report(&format!("{} is missing (expected after the last value)", q)),
+            };
+            if p == q {
+                return self.check(entries.next().unwrap(), Some(true));
+            }
+            // Below q, or a composite above it: out of place either way, and no reason yet to
+            // think q is missing.
+            if p < q || !self.is_prime(p) {
+                self.check(entries.next().unwrap(), None)?;
+                continue;
+            }
+            let at = &entries.peek().unwrap().at;
+            return self.report(&format!("{} is missing (expected before {})", q, at));
+        }
+    }
+
+    // `known` is whether the value is prime, when the caller already knows.
+    fn check(&mut self, entry: Entry, known: Option<bool>) -> ControlFlow<()> {
+        self.checked += 1;
+        let p = match entry.value {
+            Ok(p) => p,
+            Err(e) => return self.report(&format!("{}: {}", entry.at, e)),
+        };
+        if let Some((previous, previous_at)) = &self.previous {
+            if p <= *previous {
+                let msg = format!("{}: {} does not come after {} ({})", entry.at, p, previous, previous_at);
+                self.report(&msg)?;
+            }
+        }
+        if !known.unwrap_or_else(|| self.is_prime(p)) {
+            return self.report(&format!("{}: {} is not prime", entry.at, p));
+        }
+        // Order is judged among the primes only, and against the latest rather than the largest
+        // so far, so that one value out of place is reported once instead of against everything
+        // after it.
+        self.previous = Some((p, entry.at));
+        ControlFlow::Continue(())
+    }
+
+    fn is_prime(&self, p: u64) -> bool {
+        if p <= self.small.n() {
+            self.small.is_prime(p)
+        } else {
+            is_prime(p)
+        }
+    }
+
+    fn report(&mut self, msg: &str) -> ControlFlow<()> {
+        println!("{}: {}", self.path, msg);
+        self.violations += 1;
+        if self.violations >= self.max_errors {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+}
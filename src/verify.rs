@@ -0,0 +1,221 @@
+use std::fmt;
+use std::ops::ControlFlow;
+use std::sync::atomic::AtomicBool;
+
+use crate::{segmented_sieve_each, sieve_each_threaded, Sieve};
+
+/// Largest bound [`verify`] accepts: the reference sieve keeps a byte per odd number.
+pub const VERIFY_LIMIT: u64 = 100_000_000;
+
+/// The first point where an optimized sieve disagrees with [`reference_primes_up_to`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Which implementation disagreed: `"wheel"` or `"segmented"`.
+    pub implementation: &'static str,
+    /// Position in the ascending list of primes.
+    pub index: usize,
+    /// The reference prime at `index`, or `None` if the optimized output is longer.
+    pub expected: Option<u64>,
+    /// The optimized sieve's prime at `index`, or `None` if its output stopped short.
+    pub found: Option<u64>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} sieve disagrees at prime #{}: ", self.implementation, self.index + 1)?;
+        match (self.expected, self.found) {
+            (Some(expected), Some(found)) => write!(f, "expected {}, found {}", expected, found),
+            (Some(expected), None) => write!(f, "expected {}, but the output ends", expected),
+            (None, Some(found)) => write!(f, "found {} past the last prime", found),
+            (None, None) => write!(f, "no difference"),
+        }
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+/// The slow path: a plain odd-only, single-threaded sieve with no wheel, kept deliberately simple
+/// so that it is obviously correct. Use it to check the optimized sieves, not to get primes.
+#[must_use]
+pub fn reference_primes_up_to(n: u64) -> Vec<u64> {
+    if n < 2 {
+        return Vec::new();
+    }
+    // composite[i] stands for 2i + 1.
+    let len = ((n - 1) / 2 + 1) as usize;
+    let mut composite = vec![false; len];
+    let mut i = 1;
+    while (2 * i + 1) * (2 * i + 1) < 2 * len + 1 {
+        if !composite[i] {
+            let p = 2 * i + 1;
+            let mut j = p * p / 2;
+            while j < len {
+                composite[j] = true;
+                j += p;
+            }
+        }
+        i += 1;
+    }
+
+    let mut primes = vec![2];
+    primes.extend((1..len).filter(|&i| !composite[i]).map(|i| 2 * i as u64 + 1));
+    primes
+}
+
+/// Checks the wheel sieve and the segmented sieve against [`reference_primes_up_to`] for every
+/// prime up to `n`, returning the first disagreement.
+///
+/// # Panics
+///
+/// If `n` exceeds [`VERIFY_LIMIT`].
+pub fn verify(n: u64) -> Result<(), Mismatch> {
+    verify_with_threads(n, 0)
+}
+
+/// Like [`verify`], running the wheel sieve with `threads` marking workers (0 means all of them),
+/// since the threaded marking is where a platform is most likely to differ.
+///
+/// # Panics
+///
+/// If `n` exceeds [`VERIFY_LIMIT`].
+pub fn verify_with_threads(n: u64, threads: usize) -> Result<(), Mismatch> {
+    assert!(n <= VERIFY_LIMIT, "verify is limited to n <= {}", VERIFY_LIMIT);
+    let reference = reference_primes_up_to(n);
+    let mut wheel = Vec::with_capacity(reference.len());
+    sieve_each_threaded(n, threads, &AtomicBool::new(false), |p| {
+        wheel.push(p);
+        ControlFlow::Continue(())
+    })
+    .unwrap_or_else(|e| panic!("{}", e));
+    compare("wheel", &reference, wheel)?;
+
+    let mut segmented = Vec::with_capacity(reference.len());
+    segmented_sieve_each(n, |p| {
+        segmented.push(p);
+        ControlFlow::Continue(())
+    })
+    .unwrap_or_else(|e| panic!("{}", e));
+    compare("segmented", &reference, segmented)
+}
+
+/// Checks Goldbach's conjecture for every even number in `4..=max_even`, returning the first that
+/// is not the sum of two primes, or `None` if there is none. One sieve up to `max_even` answers
+/// every membership test; for each `n` the primes `p` are tried upward until `n - p` is prime,
+/// which almost always happens within the first few.
+///
+/// ```
+/// use eratosthenes::verify_goldbach;
+///
+/// assert_eq!(verify_goldbach(10_000), None);
+/// ```
+///
+/// # Panics
+///
+/// If the sieve cannot be allocated.
+#[must_use]
+pub fn verify_goldbach(max_even: u64) -> Option<u64> {
+    let sieve = Sieve::new(max_even).unwrap_or_else(|e| panic!("{}", e));
+    (4..=max_even)
+        .step_by(2)
+        .find(|&n| !sieve.primes().take_while(|&p| p <= n / 2).any(|p| sieve.is_prime(n - p)))
+}
+
+fn compare(implementation: &'static str, reference: &[u64], found: Vec<u64>) -> Result<(), Mismatch> {
+    let len = reference.len().max(found.len());
+    match (0..len).find(|&i| reference.get(i) != found.get(i)) {
+        None => Ok(()),
+        Some(index) => Err(Mismatch {
+            implementation,
+            index,
+            expected: reference.get(index).copied(),
+            found: found.get(index).copied(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Limits with every value below 50, where the wheel's special cases sit, and then random ones
+    // up to 2 * 10^6 from xorshift64, reproducible without a dependency.
+    fn limits() -> Vec<u64> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let random = (0..40).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % 2_000_000
+        });
+        (0..50).chain(random).collect()
+    }
+
+    fn trial_division(n: u64) -> Vec<u64> {
+        (2..=n).filter(|&v| (2..v).take_while(|d| d * d <= v).all(|d| !v.is_multiple_of(d))).collect()
+    }
+
+    #[test]
+    fn reference_is_trial_division() {
+        for n in (0..=1_000).chain([7_919, 7_920, 65_536]) {
+            assert_eq!(reference_primes_up_to(n), trial_division(n), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn optimized_sieves_agree_with_the_reference() {
+        for n in limits() {
+            for threads in [1, 2, 4] {
+                assert_eq!(verify_with_threads(n, threads), Ok(()), "n = {}, {} threads", n, threads);
+            }
+            assert_eq!(verify(n), Ok(()), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn first_disagreement_is_reported() {
+        let reference = reference_primes_up_to(100);
+        let mismatch = |implementation, index, expected, found| {
+            Err(Mismatch { implementation, index, expected, found })
+        };
+
+        let mut wrong = reference.clone();
+        wrong[10] = 33;
+        wrong[20] = 77;
+        assert_eq!(compare("wheel", &reference, wrong), mismatch("wheel", 10, Some(31), Some(33)));
+        let short = reference[..24].to_vec();
+        assert_eq!(compare("segmented", &reference, short), mismatch("segmented", 24, Some(97), None));
+        let long = [&reference[..], &[99]].concat();
+        assert_eq!(compare("wheel", &reference, long), mismatch("wheel", 25, None, Some(99)));
+        assert_eq!(compare("wheel", &reference, reference.clone()), Ok(()));
+
+        let messages = [
+            ("wheel", 10, Some(31), Some(33), "wheel sieve disagrees at prime #11: expected 31, found 33"),
+            (
+                "segmented",
+                24,
+                Some(97),
+                None,
+                "segmented sieve disagrees at prime #25: expected 97, but the output ends",
+            ),
+            ("wheel", 25, None, Some(99), "wheel sieve disagrees at prime #26: found 99 past the last prime"),
+        ];
+        for (implementation, index, expected, found, message) in messages {
+            assert_eq!(Mismatch { implementation, index, expected, found }.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn goldbach_holds_up_to_10_to_the_6() {
+        assert_eq!(verify_goldbach(1_000_000), None);
+        // Below 4 there is nothing to check; 4 = 2 + 2 is the one sum that needs 2.
+        for max_even in [0, 1, 2, 3, 4, 5] {
+            assert_eq!(verify_goldbach(max_even), None, "{}", max_even);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "verify is limited to n <= 100000000")]
+    fn verify_refuses_past_its_limit() {
+        let _ = verify(VERIFY_LIMIT + 1);
+    }
+}
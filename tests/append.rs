@@ -0,0 +1,91 @@
+mod common;
+
+use std::path::Path;
+
+fn append(format: &str, path: &Path, n: &str) -> std::process::Output {
+    common::run(&["--format", format, "--append", "--output", path.to_str().unwrap(), "--up-to", n])
+}
+
+#[test]
+fn appending_in_steps_matches_one_run() {
+    let dir = common::scratch_dir("append");
+    for format in ["text", "bin-delta"] {
+        let once = dir.join(format!("once.{}", format));
+        let output = common::run(&["--print", "--format", format, "--output", once.to_str().unwrap(), "1e6"]);
+        assert!(output.status.success(), "{}", format);
+
+        // From a file that does not exist yet, in two steps and in three with a bound that is
+        // itself prime.
+        for steps in [&["1e5", "1e6"][..], &["1000", "7919", "1e6"]] {
+            let grown = dir.join(format!("grown-{}.{}", steps.len(), format));
+            for n in steps {
+                let output = append(format, &grown, n);
+                assert!(output.status.success(), "{} {}", format, n);
+            }
+            assert!(std::fs::read(&grown).unwrap() == std::fs::read(&once).unwrap(), "{} in {:?}", format, steps);
+        }
+
+        // Appending up to a bound the file already covers changes nothing.
+        let before = std::fs::read(&once).unwrap();
+        let output = append(format, &once, "1000");
+        assert!(output.status.success(), "{}", format);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("already lists the primes up to 1000; nothing to append"), "{}", stderr);
+        assert!(std::fs::read(&once).unwrap() == before, "{}", format);
+    }
+}
+
+// Each damaged file is refused with the reason, and left as it was.
+fn assert_refused(format: &str, path: &Path, reason: &str) {
+    let before = std::fs::read(path).unwrap();
+    let output = append(format, path, "1e4");
+    assert_eq!(output.status.code(), Some(1), "{}", reason);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let expected = format!("{}: refusing to append: {}", path.display(), reason);
+    assert!(stderr.contains(&expected), "expected {:?} in {:?}", expected, stderr);
+    assert!(std::fs::read(path).unwrap() == before, "{}", reason);
+}
+
+#[test]
+fn appending_refuses_corrupt_or_unsorted_text() {
+    let dir = common::scratch_dir("append-text");
+    let path = dir.join("primes.txt");
+    let cases = [
+        ("2\n3\n5\n11\n7\n", "the list is not sorted: 7 follows 11"),
+        ("2\n3\n5\n7\n7\n", "the list is not sorted: 7 follows 7"),
+        ("2\n3\n5\n7\n9\n", "the last value 9 is not prime"),
+        ("2\n3\n5\n7\n1", "the last line is incomplete"),
+        ("2\n3\nfive\n7\n", "\"five\" is not a number"),
+    ];
+    for (text, reason) in cases {
+        std::fs::write(&path, text).unwrap();
+        assert_refused("text", &path, reason);
+    }
+}
+
+#[test]
+fn appending_refuses_corrupt_bin_delta() {
+    let dir = common::scratch_dir("append-bin-delta");
+    let path = dir.join("primes.bin");
+    assert!(append("bin-delta", &path, "1000").status.success());
+    let whole = std::fs::read(&path).unwrap();
+
+    let mut trailing = whole.clone();
+    trailing.push(2);
+    std::fs::write(&path, &trailing).unwrap();
+    assert_refused("bin-delta", &path, "unexpected data after the last prime");
+
+    std::fs::write(&path, &whole[..whole.len() - 1]).unwrap();
+    assert_refused("bin-delta", &path, "bin-delta stream is truncated");
+
+    let mut magic = whole.clone();
+    magic[0] = b'X';
+    std::fs::write(&path, &magic).unwrap();
+    assert_refused("bin-delta", &path, "not a bin-delta prime stream (bad magic)");
+
+    // A zero gap repeats a prime, so the list is not sorted.
+    let mut repeated = whole.clone();
+    *repeated.last_mut().unwrap() = 0;
+    std::fs::write(&path, &repeated).unwrap();
+    assert_refused("bin-delta", &path, "bin-delta primes are not strictly increasing");
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use std::io::Write;
+use std::process::{Output, Stdio};
+
+// Runs `eratosthenes --batch` with `args`, feeding it `input` on stdin.
+fn run_batch(args: &[&str], input: &str) -> Output {
+    let mut child = common::eratosthenes()
+        .arg("--batch")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("the binary runs");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn batch(args: &[&str], input: &str) -> String {
+    let output = run_batch(args, input);
+    assert!(output.status.success(), "{:?}: {}", input, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// The count from a run of its own.
+fn alone(n: &str) -> String {
+    let output = common::run(&[n]);
+    assert!(output.status.success(), "{}", n);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn batch_counts_match_separate_runs() {
+    // Out of order, with the largest in the middle, so the answers come from one sieve in the
+    // order asked rather than the order sieved.
+    let limits = [("100", 100), ("1e6", 1_000_000), ("2", 2), ("3e7", 30_000_000), ("7919", 7_919)];
+    let input: String = limits.iter().map(|(n, _)| format!("{}\n", n)).collect();
+    let output = batch(&[], &input);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), limits.len(), "{}", output);
+    for (line, (n, value)) in lines.iter().zip(limits) {
+        assert_eq!(format!("{}\n", line), format!("{} {}", value, alone(n)), "{}", n);
+    }
+}
+
+#[test]
+fn batch_takes_positional_limits_first_and_skips_blank_lines() {
+    assert_eq!(batch(&["50"], "  7 \n\n1e3\n"), "50 15\n7 4\n1000 168\n");
+    assert_eq!(batch(&[], ""), "");
+}
+
+#[test]
+fn a_bad_limit_on_stdin_is_a_usage_error() {
+    let output = run_batch(&[], "100\nabc\n");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("\"abc\" is not a whole number"));
+}
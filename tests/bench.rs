@@ -0,0 +1,38 @@
+mod common;
+
+#[test]
+fn bench_writes_a_row_per_run() {
+    let dir = common::scratch_dir("bench");
+    let csv = dir.join("tmp.csv");
+    let args = ["bench", "--limits", "1e3,1e4", "--threads", "1,2", "--repeat", "2", "--output", csv.to_str().unwrap()];
+    let output = common::run(&args);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let text = std::fs::read_to_string(&csv).unwrap();
+    let mut lines = text.lines();
+    assert_eq!(lines.next(), Some("limit,threads,repetition,seconds,numbers_per_second,primes,peak_rss_bytes"));
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+    assert_eq!(rows.len(), 8, "{}", text);
+
+    // Every limit at every thread count, each repeated, in that order.
+    let mut expected = Vec::new();
+    for (limit, primes) in [("1000", "168"), ("10000", "1229")] {
+        for threads in ["1", "2"] {
+            for repetition in ["1", "2"] {
+                expected.push((limit, threads, repetition, primes));
+            }
+        }
+    }
+    for (row, (limit, threads, repetition, primes)) in rows.iter().zip(expected) {
+        assert_eq!(row.len(), 7, "{:?}", row);
+        assert_eq!((row[0], row[1], row[2], row[5]), (limit, threads, repetition, primes), "{:?}", row);
+        let seconds: f64 = row[3].parse().unwrap();
+        let rate: f64 = row[4].parse().unwrap();
+        assert!(seconds > 0.0 && rate > 0.0, "{:?}", row);
+        // The rate is the limit over the time, give or take the rounding of each to print.
+        let limit: f64 = limit.parse().unwrap();
+        assert!((rate * seconds / limit - 1.0).abs() < 0.1 || seconds < 1e-5, "{:?}", row);
+        assert!(row[6].is_empty() || row[6].parse::<u64>().unwrap() > 0, "{:?}", row);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
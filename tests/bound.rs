@@ -0,0 +1,58 @@
+mod common;
+
+use std::io::Write;
+use std::process::{Output, Stdio};
+
+// Runs `eratosthenes` with no bound on the command line, feeding `input` to the bound prompt.
+fn with_input(input: &str) -> Output {
+    let mut child = common::eratosthenes()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("the binary runs");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn assert_no_panic(output: &Output) {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("panicked") && !stderr.contains("RUST_BACKTRACE"), "{}", stderr);
+    assert!(output.stdout.is_empty(), "{}", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn bad_piped_input_fails_without_a_panic() {
+    let cases = [
+        ("abc\n", "\"abc\" is not a whole number; accepted forms: "),
+        ("-5\n", "-5: the bound must be non-negative"),
+        ("3.14\n", "3.14: the bound must be a whole number; did you mean 3?"),
+        ("99999999999999999999\n", "99999999999999999999: the bound is too large"),
+        ("\n", "no bound given; accepted forms: "),
+        ("", "no bound given"),
+    ];
+    for (input, message) in cases {
+        let output = with_input(input);
+        assert_eq!(output.status.code(), Some(1), "{:?}", input);
+        assert_no_panic(&output);
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with(message), "{:?}", input);
+    }
+}
+
+#[test]
+fn good_piped_input_is_counted() {
+    for input in ["1e6\n", "1_000_000\r\n", " 1M ", "1000k\n"] {
+        let output = with_input(input);
+        assert!(output.status.success(), "{:?}", input);
+        assert_eq!(output.stdout, b"78498\n", "{:?}", input);
+    }
+}
+
+#[test]
+fn bad_arguments_are_usage_errors() {
+    for arg in ["abc", "-5", "3.14", "1e30", "1__0", ""] {
+        let output = common::run(&[arg]);
+        assert_eq!(output.status.code(), Some(2), "{:?}", arg);
+        assert_no_panic(&output);
+    }
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::kill_after_a_checkpoint;
+
+// 10^8 in segments of some 50,000 wheel positions: about 500 of them, a few seconds' work.
+const N: &str = "100000000";
+const SEGMENTS: [&str; 2] = ["--memory-limit", "64K"];
+
+#[test]
+fn resumed_output_matches_an_uninterrupted_run() {
+    let dir = common::scratch_dir("checkpoint-print");
+    let checkpoint = dir.join("run.checkpoint");
+    let (partial, whole) = (dir.join("partial.txt"), dir.join("whole.txt"));
+    let (partial_arg, whole_arg) = (partial.to_str().unwrap(), whole.to_str().unwrap());
+
+    kill_after_a_checkpoint(&[&SEGMENTS[..], &["--print", "--output", partial_arg, N]].concat(), &checkpoint);
+    // The killed run wrote past its checkpoint; resuming cuts that off and carries on.
+    let resumed = common::run(&["--print", "--output", partial_arg, "--resume", checkpoint.to_str().unwrap()]);
+    assert!(resumed.status.success(), "{}", String::from_utf8_lossy(&resumed.stderr));
+
+    let uninterrupted = common::run(&[&SEGMENTS[..], &["--print", "--output", whole_arg, N]].concat());
+    assert!(uninterrupted.status.success());
+    let (partial, whole) = (std::fs::read(partial).unwrap(), std::fs::read(whole).unwrap());
+    assert_eq!(partial.len(), whole.len());
+    assert!(partial == whole, "the resumed output differs from an uninterrupted run");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn resumed_count_matches_an_uninterrupted_run() {
+    let dir = common::scratch_dir("checkpoint-count");
+    let checkpoint = dir.join("run.checkpoint");
+    kill_after_a_checkpoint(&[&SEGMENTS[..], &[N]].concat(), &checkpoint);
+    let resumed = common::run(&["--resume", checkpoint.to_str().unwrap()]);
+    assert!(resumed.status.success(), "{}", String::from_utf8_lossy(&resumed.stderr));
+    assert_eq!(resumed.stdout, b"5761455\n");
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,104 @@
+// Helpers shared by the command-line tests; each test file uses its own subset.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The `eratosthenes` binary built for these tests.
+pub fn eratosthenes() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_eratosthenes"));
+    command.stdin(Stdio::null());
+    command
+}
+
+/// Runs `eratosthenes` with `args` to completion.
+pub fn run(args: &[&str]) -> Output {
+    eratosthenes().args(args).output().expect("the binary runs")
+}
+
+/// A fresh directory for one test's files, removed first if an earlier run left it behind.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("eratosthenes-test-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("the scratch directory can be created");
+    dir
+}
+
+/// Waits for `child` to exit, killing it and failing the test if it takes longer than `limit`.
+pub fn wait_within(child: &mut Child, limit: Duration) -> ExitStatus {
+    let deadline = Instant::now() + limit;
+    loop {
+        if let Some(status) = child.try_wait().expect("the child can be waited for") {
+            return status;
+        }
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            panic!("still running after {:?}", limit);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// The next_segment a checkpoint file records, once one has been written.
+pub fn next_segment(checkpoint: &Path) -> Option<u64> {
+    let text = std::fs::read_to_string(checkpoint).ok()?;
+    text.lines().find_map(|line| line.strip_prefix("next_segment="))?.parse().ok()
+}
+
+/// Starts a run that checkpoints after every segment and kills it, with no chance to clean up,
+/// once a few segments are saved.
+pub fn kill_after_a_checkpoint(args: &[&str], checkpoint: &Path) {
+    let mut child = eratosthenes()
+        .args(["--checkpoint", checkpoint.to_str().unwrap(), "--checkpoint-interval", "0s"])
+        .args(args)
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("the binary runs");
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while next_segment(checkpoint).is_none_or(|k| k < 5) {
+        assert!(Instant::now() < deadline, "no checkpoint after 30s");
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert!(child.try_wait().unwrap().is_none(), "{:?} finished before it could be killed", args);
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+/// Starts a sieve that would take hours, waits for its output to reach `path`, and interrupts it.
+/// Returns the exit code and stderr.
+#[cfg(unix)]
+pub fn interrupt_run(args: &[&str], path: &Path) -> (Option<i32>, String) {
+    use std::io::Read;
+
+    let mut child = eratosthenes().args(args).stderr(Stdio::piped()).spawn().expect("the binary runs");
+    let deadline = Instant::now() + Duration::from_secs(30);
+    while std::fs::metadata(path).map_or(0, |m| m.len()) == 0 {
+        assert!(Instant::now() < deadline, "no output after 30s");
+        thread::sleep(Duration::from_millis(20));
+    }
+    let sent = Command::new("kill").args(["-INT", &child.id().to_string()]).status().expect("kill runs");
+    assert!(sent.success());
+    let status = wait_within(&mut child, Duration::from_secs(30));
+    let mut stderr = String::new();
+    child.stderr.take().unwrap().read_to_string(&mut stderr).unwrap();
+    (status.code(), stderr)
+}
+
+/// The raw value of `key` in a flat JSON object such as the `--stats` line: a number, `null`, or
+/// a string with its quotes.
+pub fn json_field<'a>(json: &'a str, key: &str) -> &'a str {
+    let start = json.find(&format!("\"{}\":", key)).unwrap_or_else(|| panic!("no {} in {}", key, json)) + key.len() + 3;
+    let len = json[start..].find([',', '}']).unwrap_or_else(|| panic!("{} is not closed in {}", key, json));
+    &json[start..start + len]
+}
+
+/// Runs `eratosthenes --stats` with `args`, returning stdout and the stats line.
+pub fn run_with_stats(args: &[&str]) -> (String, String) {
+    let output = run(&[&["--stats"], args].concat());
+    assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    (String::from_utf8(output.stdout).unwrap(), stderr.lines().last().unwrap_or_default().to_string())
+}
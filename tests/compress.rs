@@ -0,0 +1,51 @@
+// `--compress` exists only with the `compression` feature.
+#![cfg(feature = "compression")]
+
+mod common;
+
+use std::fs::File;
+use std::io::Read;
+
+use eratosthenes::{decompress, primes_up_to, read_delta_primes};
+
+#[test]
+fn compressed_output_round_trips_through_decompress() {
+    let dir = common::scratch_dir("compress");
+    let primes = primes_up_to(1_000_000);
+    let text: String = primes.iter().map(|p| format!("{}\n", p)).collect();
+    for codec in ["gzip", "zstd"] {
+        for level in [None, Some("1")] {
+            let path = dir.join(format!("{}-{}", codec, level.unwrap_or("default")));
+            let path = path.to_str().unwrap();
+            let mut args = vec!["--print", "--compress", codec, "--output", path, "1e6"];
+            args.extend(level.iter().flat_map(|level| ["--compress-level", level]));
+            assert!(common::run(&args).status.success(), "{:?}", args);
+            let mut decompressed = String::new();
+            decompress(File::open(path).unwrap()).unwrap().read_to_string(&mut decompressed).unwrap();
+            assert_eq!(decompressed, text, "{:?}", args);
+            assert!(std::fs::metadata(path).unwrap().len() < text.len() as u64 / 2, "{:?}", args);
+
+            let delta = format!("{}.bin", path);
+            args.splice(0..1, ["--print", "--format", "bin-delta"]);
+            *args.iter_mut().find(|arg| **arg == path).unwrap() = &delta;
+            assert!(common::run(&args).status.success(), "{:?}", args);
+            assert_eq!(read_delta_primes(File::open(&delta).unwrap()).unwrap(), primes, "{:?}", args);
+        }
+    }
+}
+
+#[test]
+fn compressed_output_round_trips_through_decode_and_validate() {
+    let dir = common::scratch_dir("compress-decode");
+    for codec in ["gzip", "zstd"] {
+        let path = dir.join(codec);
+        let path = path.to_str().unwrap();
+        let write = common::run(&["--print", "--format", "bin-delta", "--compress", codec, "--output", path, "1000"]);
+        assert!(write.status.success(), "{}", codec);
+        let decoded = common::run(&["decode", path]);
+        assert!(decoded.status.success(), "{}", codec);
+        let text: String = primes_up_to(1000).iter().map(|p| format!("{}\n", p)).collect();
+        assert_eq!(String::from_utf8(decoded.stdout).unwrap(), text, "{}", codec);
+        assert_eq!(common::run(&["validate", path, "--complete", "2..1000"]).status.code(), Some(0), "{}", codec);
+    }
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::{json_field, run_with_stats};
+
+// The fields the dry run and the real run's --stats both report, which must agree exactly since
+// both come from the one plan.
+const PLAN_FIELDS: [&str; 6] = ["n", "threads", "strategy", "segment_size", "segments", "planned_bytes"];
+
+// The plan `--dry-run --stats` prints on stdout, in place of the count.
+fn dry_run(args: &[&str]) -> String {
+    let output = common::run(&[&["--dry-run", "--stats"], args].concat());
+    assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1, "{}", stdout);
+    stdout.trim_end().to_string()
+}
+
+#[test]
+fn a_tight_limit_plans_segments() {
+    let plan = dry_run(&["--memory-limit", "256K", "1e8"]);
+    assert_eq!(json_field(&plan, "strategy"), "\"segmented\"", "{}", plan);
+    let segment_size: u64 = json_field(&plan, "segment_size").parse().unwrap();
+    let segments: u64 = json_field(&plan, "segments").parse().unwrap();
+    // Segments cover the 8 wheel positions in each 30 numbers, the last one possibly in part.
+    assert_eq!(segments, (100_000_000u64 / 30 * 8 + 8).div_ceil(segment_size), "{}", plan);
+    assert!(json_field(&plan, "planned_bytes").parse::<u64>().unwrap() <= 256_000, "{}", plan);
+
+    // Planning does no marking, so even a bound that would take hours is planned at once.
+    assert_eq!(json_field(&dry_run(&["--memory-limit", "1G", "1e15"]), "strategy"), "\"segmented\"");
+
+    let (count, stats) = run_with_stats(&["--memory-limit", "256K", "1e8"]);
+    assert_eq!(count, "5761455\n");
+    for field in PLAN_FIELDS {
+        assert_eq!(json_field(&plan, field), json_field(&stats, field), "{}", field);
+    }
+}
+
+#[test]
+fn estimates_are_close_to_a_real_run() {
+    for (n, limit, sqrt_primes) in [("1e7", None, 446), ("3e7", None, 669), ("3e7", Some("256K"), 669)] {
+        let limit = limit.map_or(vec![], |limit| vec!["--memory-limit", limit]);
+        let plan = dry_run(&[&limit[..], &[n]].concat());
+        let (_, stats) = run_with_stats(&[&limit[..], &[n]].concat());
+        for field in PLAN_FIELDS {
+            assert_eq!(json_field(&plan, field), json_field(&stats, field), "{} {:?}: {}", n, limit, field);
+        }
+
+        // The base primes estimate is a bound on π(sqrt n), and not a loose one.
+        let base_primes: u64 = json_field(&plan, "base_primes").parse().unwrap();
+        assert!((sqrt_primes..sqrt_primes * 5 / 4).contains(&base_primes), "{} {:?}: {}", n, limit, plan);
+
+        // What the run grew by over one that barely allocates is the planned memory, give or take
+        // pages and the base primes.
+        let (_, baseline) = run_with_stats(&["100"]);
+        let peak = |stats: &str| json_field(stats, "peak_rss_bytes").parse::<u64>().ok();
+        if let (Some(peak), Some(baseline)) = (peak(&stats), peak(&baseline)) {
+            let planned: u64 = json_field(&plan, "planned_bytes").parse().unwrap();
+            let grown = peak.saturating_sub(baseline);
+            let what = format!("{} {:?}: grew {} bytes for {} planned", n, limit, grown, planned);
+            assert!(grown <= planned * 5 / 4 + 256 * 1024, "{}", what);
+            if limit.is_empty() {
+                assert!(grown >= planned * 3 / 4, "{}", what);
+            }
+        }
+    }
+}
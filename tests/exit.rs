@@ -0,0 +1,78 @@
+mod common;
+
+// Runs with `--error-format json`, expecting exit `code` with stderr one JSON error object of
+// `kind`, and returns that line.
+fn json_error(args: &[&str], code: i32, kind: &str) -> String {
+    let output = common::run(&[&["--error-format", "json"][..], args].concat());
+    assert_eq!(output.status.code(), Some(code), "{:?}", args);
+    assert!(output.stdout.is_empty(), "{:?}", args);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let line = stderr.strip_suffix('\n').unwrap_or_else(|| panic!("{:?}: {:?}", args, stderr));
+    assert!(!line.contains('\n'), "{:?}: more than one line: {:?}", args, stderr);
+    let head = format!("{{\"code\":{},\"kind\":\"{}\",\"message\":\"", code, kind);
+    assert!(line.starts_with(&head), "{:?}: {}", args, line);
+    assert!(line.contains("\",\"context\":{") && line.ends_with("}}"), "{:?}: {}", args, line);
+    line.to_string()
+}
+
+// The message, which may hold commas, so runs to the context rather than the next comma.
+fn message(json: &str) -> &str {
+    let start = json.find("\"message\":\"").unwrap() + "\"message\":\"".len();
+    &json[start..json.find("\",\"context\":").unwrap()]
+}
+
+#[test]
+fn usage_errors_exit_2() {
+    let json = json_error(&["--bogus"], 2, "usage");
+    assert_eq!(message(&json), "unknown option: --bogus");
+    assert!(json.ends_with("\"context\":{}}"), "{}", json);
+    let json = json_error(&["--threads", "x", "10"], 2, "usage");
+    assert_eq!(message(&json), "--threads needs a whole number, not x");
+    // The flag applies wherever it appears, subcommands included.
+    let output = common::run(&["range", "10..5", "--error-format", "json"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("{\"code\":2,\"kind\":\"usage\""));
+
+    // A bad format is itself a usage error, reported as prose with the usage.
+    let output = common::run(&["--error-format", "xml", "10"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("unknown error format: xml\nusage: "), "{}", stderr);
+}
+
+#[test]
+fn failures_exit_1() {
+    let dir = common::scratch_dir("exit-failure");
+    let missing = dir.join("missing.txt");
+    let json = json_error(&["validate", missing.to_str().unwrap()], 1, "failure");
+    assert!(message(&json).starts_with(&format!("{}: ", missing.display())), "{}", json);
+}
+
+#[test]
+fn an_absurd_n_under_a_memory_limit_exits_3() {
+    for args in [&["--memory-limit", "1K", "1e18"][..], &["--memory-limit", "1M", "--print", "1e18"]] {
+        let json = json_error(args, 3, "resource");
+        assert!(message(&json).starts_with("sieving up to n = 1000000000000000000 needs at least "), "{}", json);
+        assert_eq!(common::json_field(&json, "n"), "1000000000000000000");
+        let required: u64 = common::json_field(&json, "required_bytes").parse().unwrap();
+        let limit: u64 = common::json_field(&json, "limit_bytes").parse().unwrap();
+        assert!(required > limit, "{}", json);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn sigint_exits_4() {
+    let dir = common::scratch_dir("exit-interrupted");
+    let path = dir.join("primes.txt");
+    let args = ["--error-format", "json", "--print", "--segmented", "--output", path.to_str().unwrap(), "1e12"];
+    let (code, stderr) = common::interrupt_run(&args, &path);
+    assert_eq!(code, Some(4), "{}", stderr);
+    let json = stderr.lines().last().unwrap();
+    assert!(json.starts_with("{\"code\":4,\"kind\":\"interrupted\",\"message\":\"interrupted: "), "{}", json);
+    assert_eq!(common::json_field(json, "n"), "1000000000000");
+    let last_prime: u64 = common::json_field(json, "last_prime").parse().unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(text.lines().last().unwrap().parse::<u64>().unwrap(), last_prime);
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,172 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use eratosthenes::{factor_tree, is_prime};
+
+// xorshift64: reproducible 64-bit values without a dependency.
+fn random_u64s(count: usize) -> Vec<u64> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    (0..count)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        })
+        .collect()
+}
+
+fn inputs() -> Vec<String> {
+    let special = [0, 1, 2, 360, u64::MAX, 1 << 63, 4_294_967_291 * 4_294_967_291];
+    special.into_iter().chain(random_u64s(100)).map(|n| n.to_string()).collect()
+}
+
+fn factor(style: &str, numbers: &[String]) -> String {
+    let args: Vec<&str> = ["factor", style].into_iter().chain(numbers.iter().map(String::as_str)).collect();
+    let output = common::run(&args);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+// Just enough JSON for the factor output: objects, arrays, unsigned integers and plain strings.
+#[derive(Debug, PartialEq)]
+enum Json {
+    Object(BTreeMap<String, Json>),
+    Array(Vec<Json>),
+    Number(u64),
+    String(String),
+}
+
+fn parse_json(text: &str) -> Json {
+    let mut rest = text.as_bytes();
+    let json = parse_value(&mut rest);
+    assert!(rest.is_empty(), "trailing {:?} after the value in {}", String::from_utf8_lossy(rest), text);
+    json
+}
+
+fn parse_value(rest: &mut &[u8]) -> Json {
+    let expect = |rest: &mut &[u8], byte: u8| {
+        assert_eq!(rest.first(), Some(&byte), "expected {:?} at {:?}", byte as char, String::from_utf8_lossy(rest));
+        *rest = &rest[1..];
+    };
+    match rest.first() {
+        Some(b'{') => {
+            *rest = &rest[1..];
+            let mut object = BTreeMap::new();
+            while rest.first() != Some(&b'}') {
+                if !object.is_empty() {
+                    expect(rest, b',');
+                }
+                let Json::String(key) = parse_value(rest) else { panic!("object keys are strings") };
+                expect(rest, b':');
+                assert!(object.insert(key.clone(), parse_value(rest)).is_none(), "duplicate key {}", key);
+            }
+            expect(rest, b'}');
+            Json::Object(object)
+        }
+        Some(b'[') => {
+            *rest = &rest[1..];
+            let mut array = Vec::new();
+            while rest.first() != Some(&b']') {
+                if !array.is_empty() {
+                    expect(rest, b',');
+                }
+                array.push(parse_value(rest));
+            }
+            expect(rest, b']');
+            Json::Array(array)
+        }
+        Some(b'"') => {
+            let end = rest[1..].iter().position(|&b| b == b'"').expect("unterminated string") + 1;
+            let string = String::from_utf8(rest[1..end].to_vec()).unwrap();
+            *rest = &rest[end + 1..];
+            Json::String(string)
+        }
+        Some(b'0'..=b'9') => {
+            let end = rest.iter().position(|b| !b.is_ascii_digit()).unwrap_or(rest.len());
+            let number = std::str::from_utf8(&rest[..end]).unwrap().parse().expect("fits in a u64");
+            *rest = &rest[end..];
+            Json::Number(number)
+        }
+        _ => panic!("unexpected {:?}", String::from_utf8_lossy(rest)),
+    }
+}
+
+// {"n":N,"factors":[{"p":P,"e":E},...],"method":"trial"|"rho"}, with the primes ascending and
+// multiplying out to N.
+fn check_schema(json: &Json) -> u64 {
+    let Json::Object(fields) = json else { panic!("not an object: {:?}", json) };
+    assert_eq!(fields.keys().collect::<Vec<_>>(), ["factors", "method", "n"]);
+    let Json::Number(n) = fields["n"] else { panic!("n is not a number") };
+    let Json::Array(factors) = &fields["factors"] else { panic!("factors is not an array") };
+    let (mut product, mut last) = (1u128, 0);
+    for factor in factors {
+        let Json::Object(factor) = factor else { panic!("a factor is not an object") };
+        assert_eq!(factor.keys().collect::<Vec<_>>(), ["e", "p"]);
+        let (Json::Number(p), Json::Number(e)) = (&factor["p"], &factor["e"]) else { panic!("p and e are numbers") };
+        assert!(*p > last && is_prime(*p) && *e >= 1, "{:?}", factor);
+        product *= (*p as u128).pow(*e as u32);
+        last = *p;
+    }
+    assert_eq!(product, if n < 2 { 1 } else { n as u128 }, "{}", n);
+    let expected = if factor_tree(n).used_rho() { "rho" } else { "trial" };
+    assert_eq!(fields["method"], Json::String(expected.to_string()), "{}", n);
+    n
+}
+
+#[test]
+fn json_output_follows_the_schema() {
+    let inputs = inputs();
+    let text = factor("--json", &inputs);
+    let numbers: Vec<u64> = text.lines().map(|line| check_schema(&parse_json(line))).collect();
+    let expected: Vec<u64> = inputs.iter().map(|n| n.parse().unwrap()).collect();
+    assert_eq!(numbers, expected);
+    let exact = r#"{"n":360,"factors":[{"p":2,"e":3},{"p":3,"e":2},{"p":5,"e":1}],"method":"trial"}"#;
+    assert_eq!(text.lines().nth(3), Some(exact));
+}
+
+// One node of a drawn tree: its depth, number and method, if it was split.
+fn parse_node(line: &str) -> (usize, u64, Option<&str>) {
+    let start = line.find(|c: char| c.is_ascii_digit()).unwrap();
+    let depth = line[..start].chars().count() / 4;
+    let (n, method) = match line[start..].split_once(' ') {
+        Some((n, method)) => (n, Some(method.trim_start_matches('(').trim_end_matches(')'))),
+        None => (&line[start..], None),
+    };
+    (depth, n.parse().unwrap(), method)
+}
+
+// Checks the subtree at the front of `nodes`, at `depth`, and returns its number and its leaves'
+// product.
+fn check_drawn_tree(nodes: &mut std::slice::Iter<(usize, u64, Option<&str>)>, depth: usize) -> (u64, u128) {
+    let &(d, n, method) = nodes.next().expect("a node");
+    assert_eq!(d, depth, "{} is at the wrong depth", n);
+    match method {
+        None => {
+            assert!(n < 2 || is_prime(n), "leaf {} is not prime", n);
+            (n, n as u128)
+        }
+        Some(method) => {
+            assert!(method == "trial" || method == "rho", "{}", method);
+            let (left, left_product) = check_drawn_tree(nodes, depth + 1);
+            let (right, right_product) = check_drawn_tree(nodes, depth + 1);
+            assert_eq!(left as u128 * right as u128, n as u128);
+            (n, left_product * right_product)
+        }
+    }
+}
+
+#[test]
+fn tree_leaves_multiply_to_n() {
+    let inputs = inputs();
+    let text = factor("--tree", &inputs);
+    let nodes: Vec<_> = text.lines().map(parse_node).collect();
+    let mut nodes = nodes.iter();
+    for input in &inputs {
+        let (n, product) = check_drawn_tree(&mut nodes, 0);
+        assert_eq!(n.to_string(), *input);
+        assert_eq!(product, n as u128);
+    }
+    assert!(nodes.next().is_none());
+}
@@ -0,0 +1,137 @@
+// The C interface, through the raw declarations a C caller would use.
+#![cfg(feature = "ffi")]
+
+mod common;
+
+use std::os::raw::c_int;
+use std::path::Path;
+use std::process::Command;
+use std::ptr;
+
+use eratosthenes::ffi::{ERAT_ERR_ALLOC, ERAT_ERR_NULL, ERAT_OK};
+use eratosthenes::primes_up_to;
+
+extern "C" {
+    fn erat_sieve(n: u64, out: *mut *mut u64, len: *mut usize) -> c_int;
+    fn erat_free(ptr: *mut u64, len: usize);
+    fn erat_count(n: u64, out_count: *mut u64) -> c_int;
+    fn erat_is_prime(n: u64) -> c_int;
+}
+
+// erat_sieve into a Vec, releasing its array through erat_free.
+fn sieve(n: u64) -> Result<Vec<u64>, c_int> {
+    let (mut array, mut len) = (ptr::null_mut(), 0);
+    let status = unsafe { erat_sieve(n, &mut array, &mut len) };
+    if status != ERAT_OK {
+        return Err(status);
+    }
+    let primes = match len {
+        0 => {
+            assert!(array.is_null());
+            Vec::new()
+        }
+        _ => unsafe { std::slice::from_raw_parts(array, len) }.to_vec(),
+    };
+    unsafe { erat_free(array, len) };
+    Ok(primes)
+}
+
+#[test]
+fn sieve_round_trips_through_erat_free() {
+    for n in [0, 1, 2, 3, 100, 65_537, 1_000_000] {
+        assert_eq!(sieve(n), Ok(primes_up_to(n)), "n = {}", n);
+    }
+    assert_eq!(sieve(100).unwrap().len(), 25);
+    unsafe { erat_free(ptr::null_mut(), 0) };
+}
+
+#[test]
+fn count_matches_the_sieve() {
+    for (n, expected) in [(0, 0), (2, 1), (100, 25), (1_000_000, 78_498), (10_000_019, 664_580)] {
+        let mut count = u64::MAX;
+        assert_eq!(unsafe { erat_count(n, &mut count) }, ERAT_OK);
+        assert_eq!(count, expected, "n = {}", n);
+    }
+}
+
+#[test]
+fn is_prime_answers_one_or_zero() {
+    let cases = [(0, 0), (1, 0), (2, 1), (91, 0), (97, 1), ((1 << 61) - 1, 1), (u64::MAX - 58, 1), (u64::MAX, 0)];
+    for (n, expected) in cases {
+        assert_eq!(unsafe { erat_is_prime(n) }, expected, "n = {}", n);
+    }
+}
+
+#[test]
+fn errors_leave_the_outputs_alone() {
+    let (mut array, mut len, mut count) = (ptr::dangling_mut::<u64>(), 7, 7);
+    unsafe {
+        assert_eq!(erat_sieve(100, ptr::null_mut(), &mut len), ERAT_ERR_NULL);
+        assert_eq!(erat_sieve(100, &mut array, ptr::null_mut()), ERAT_ERR_NULL);
+        assert_eq!(erat_count(100, ptr::null_mut()), ERAT_ERR_NULL);
+        // No machine holds the marks up to 2^64.
+        assert_eq!(erat_sieve(u64::MAX, &mut array, &mut len), ERAT_ERR_ALLOC);
+        assert_eq!(erat_count(u64::MAX, &mut count), ERAT_ERR_ALLOC);
+    }
+    assert_eq!((array, len, count), (ptr::dangling_mut(), 7, 7));
+}
+
+// The header's error codes are the library's.
+#[test]
+fn header_matches_the_library() {
+    let header = include_str!("../src/include/eratosthenes.h");
+    for (name, code) in [("ERAT_OK", ERAT_OK), ("ERAT_ERR_NULL", ERAT_ERR_NULL), ("ERAT_ERR_ALLOC", ERAT_ERR_ALLOC)] {
+        let value = if code < 0 { format!("({})", code) } else { code.to_string() };
+        assert!(header.contains(&format!("#define {} {}\n", name, value)), "{} is not {} in the header", name, value);
+    }
+    assert!(header.contains(&format!("#define ERAT_ERR_PANIC ({})\n", eratosthenes::ffi::ERAT_ERR_PANIC)));
+    for declaration in [
+        "int erat_sieve(uint64_t n, uint64_t **out, size_t *len);",
+        "void erat_free(uint64_t *ptr, size_t len);",
+        "int erat_count(uint64_t n, uint64_t *out_count);",
+        "int erat_is_prime(uint64_t n);",
+    ] {
+        assert!(header.contains(declaration), "{}", declaration);
+    }
+}
+
+// A C program built against the header and the cdylib, as a C caller would. Cargo builds the
+// cdylib into deps/ beside the binary, and only copies it up for a plain build.
+#[cfg(target_os = "linux")]
+#[test]
+fn c_program_links_against_the_cdylib() {
+    const PROGRAM: &str = r#"
+#include <stdio.h>
+#include "eratosthenes.h"
+
+int main(void) {
+    uint64_t *primes;
+    size_t len;
+    uint64_t count;
+    if (erat_sieve(100, &primes, &len) != ERAT_OK || len != 25 || primes[24] != 97) return 1;
+    erat_free(primes, len);
+    if (erat_count(1000000, &count) != ERAT_OK || count != 78498) return 2;
+    if (erat_is_prime(97) != 1 || erat_is_prime(91) != 0) return 3;
+    if (erat_sieve(100, NULL, &len) != ERAT_ERR_NULL) return 4;
+    puts("ok");
+    return 0;
+}
+"#;
+    let dir = common::scratch_dir("ffi");
+    let lib_dir = Path::new(env!("CARGO_BIN_EXE_eratosthenes")).with_file_name("deps");
+    std::fs::write(dir.join("smoke.c"), PROGRAM).unwrap();
+    let compiled = Command::new("cc")
+        .arg("-I")
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("src/include"))
+        .arg(dir.join("smoke.c"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .args(["-leratosthenes", "-o"])
+        .arg(dir.join("smoke"))
+        .status()
+        .expect("a C compiler is installed as cc");
+    assert!(compiled.success());
+    let output = Command::new(dir.join("smoke")).env("LD_LIBRARY_PATH", &lib_dir).output().unwrap();
+    assert_eq!((output.status.code(), output.stdout), (Some(0), b"ok\n".to_vec()));
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,22 @@
+mod common;
+
+#[test]
+fn filtered_counts_match_the_filtered_output() {
+    for expr in ["lastdigit 7", "mod 4 = 1", "twin", "digitsum mod 3 = 2, mod 10 = 1"] {
+        let count = common::run(&["--filter", expr, "100000"]);
+        assert!(count.status.success(), "{}", expr);
+        let printed = common::run(&["--print", "--filter", expr, "100000"]);
+        assert!(printed.status.success(), "{}", expr);
+        let lines = String::from_utf8(printed.stdout).unwrap().lines().count();
+        assert_eq!(String::from_utf8(count.stdout).unwrap(), format!("{}\n", lines), "{}", expr);
+    }
+    assert_eq!(common::run(&["--filter", "twin", "100000"]).stdout, b"1224\n");
+}
+
+#[test]
+fn malformed_filters_are_usage_errors() {
+    let output = common::run(&["--filter", "mod 4 = 4", "100"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("--filter: no remainder mod 4 is 4; it must be below 4\n"), "{}", stderr);
+}
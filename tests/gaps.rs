@@ -0,0 +1,76 @@
+mod common;
+
+use eratosthenes::primes_up_to;
+
+fn gaps(args: &[&str]) -> Vec<String> {
+    let output = common::run(&[&["gaps"][..], args].concat());
+    assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().lines().map(str::to_owned).collect()
+}
+
+// The maximal prime gaps below 10^6 as tabulated (OEIS A005250 and A002386): each gap with the
+// prime it follows.
+const RECORDS: [(u64, u64); 18] = [
+    (1, 2),
+    (2, 3),
+    (4, 7),
+    (6, 23),
+    (8, 89),
+    (14, 113),
+    (18, 523),
+    (20, 887),
+    (22, 1129),
+    (34, 1327),
+    (36, 9551),
+    (44, 15683),
+    (52, 19609),
+    (72, 31397),
+    (86, 155921),
+    (96, 360653),
+    (112, 370261),
+    (114, 492113),
+];
+
+#[test]
+fn record_gaps_up_to_10_to_the_6() {
+    let lines = gaps(&["1e6"]);
+    let expected: Vec<String> = RECORDS.iter().map(|&(gap, p)| format!("{} {} {}", gap, p, p + gap)).collect();
+    assert_eq!(lines[..lines.len() - 1], expected);
+    assert_eq!(lines.last().unwrap(), "largest gap 114 between 492113 and 492227");
+
+    // A bound short of a record's end leaves it out, and the summary with it.
+    let lines = gaps(&["492226"]);
+    assert_eq!(lines[lines.len() - 2..], ["112 370261 370373", "largest gap 112 between 370261 and 370373"]);
+
+    // The next three records, past 10^6.
+    let lines = gaps(&["2.1e6"]);
+    assert_eq!(lines[..RECORDS.len()], expected);
+    let rest = ["118 1349533 1349651", "132 1357201 1357333", "148 2010733 2010881"];
+    assert_eq!(lines[RECORDS.len()..], [&rest[..], &["largest gap 148 between 2010733 and 2010881"]].concat());
+}
+
+#[test]
+fn min_gap_lists_every_gap_at_least_that_large() {
+    let primes = primes_up_to(1_000_000);
+    for min_gap in [1, 72, 100, 114] {
+        let lines = gaps(&["--min-gap", &min_gap.to_string(), "1e6"]);
+        let expected: Vec<String> = primes
+            .windows(2)
+            .filter(|w| w[1] - w[0] >= min_gap)
+            .map(|w| format!("{} {} {}", w[1] - w[0], w[0], w[1]))
+            .collect();
+        assert_eq!(lines.len(), expected.len() + 1, "--min-gap {}", min_gap);
+        assert!(lines[..expected.len()] == expected, "--min-gap {}", min_gap);
+        assert_eq!(lines.last().unwrap(), "largest gap 114 between 492113 and 492227", "--min-gap {}", min_gap);
+    }
+    // Past the largest gap, only the summary is left.
+    assert_eq!(gaps(&["--min-gap", "115", "1e6"]), ["largest gap 114 between 492113 and 492227"]);
+}
+
+#[test]
+fn fewer_than_two_primes_have_no_gaps() {
+    for n in ["0", "1", "2"] {
+        assert_eq!(gaps(&[n]), [format!("no gaps up to {}", n)]);
+    }
+    assert_eq!(gaps(&["3"]), ["1 2 3", "largest gap 1 between 2 and 3"]);
+}
@@ -0,0 +1,158 @@
+// `--serve --listen` exists only with the `http` feature.
+#![cfg(feature = "http")]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, Stdio};
+use std::thread;
+
+// A server on an ephemeral port, killed when dropped.
+struct Server {
+    child: Child,
+    addr: String,
+}
+
+impl Server {
+    fn start() -> Self {
+        let mut child = common::eratosthenes()
+            .args(["--serve", "--listen", "127.0.0.1:0"])
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("the binary runs");
+        let mut line = String::new();
+        BufReader::new(child.stderr.take().unwrap()).read_line(&mut line).unwrap();
+        let addr = line.trim().strip_prefix("listening on http://").unwrap_or_else(|| panic!("{}", line)).to_string();
+        Server { child, addr }
+    }
+
+    // Sends `request` as is and returns the status and body of the response.
+    fn raw(&self, request: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(&self.addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        let length: usize = head
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .and_then(|length| length.parse().ok())
+            .unwrap();
+        assert_eq!(body.len(), length, "{}", response);
+        assert!(head.contains("\r\nContent-Type: application/json\r\n"), "{}", head);
+        (status, body.trim_end().to_string())
+    }
+
+    fn get(&self, target: &str) -> (u16, String) {
+        self.raw(&format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target))
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// The primes on one /primes page, and its `next`.
+fn page(body: &str) -> (Vec<u64>, Option<u64>) {
+    let (_, rest) = body.split_once("\"primes\":[").unwrap();
+    let (primes, next) = rest.split_once("],\"next\":").unwrap();
+    let primes = primes.split(',').filter(|p| !p.is_empty()).map(|p| p.parse().unwrap()).collect();
+    (primes, next.trim_end_matches('}').parse().ok())
+}
+
+#[test]
+fn endpoints_answer_in_json() {
+    let server = Server::start();
+    let cases = [
+        ("/count?n=100", r#"{"n":100,"count":25}"#),
+        ("/count?n=1e6", r#"{"n":1000000,"count":78498}"#),
+        ("/count?n=0", r#"{"n":0,"count":0}"#),
+        ("/isprime?n=97", r#"{"n":97,"prime":true}"#),
+        ("/isprime?n=91", r#"{"n":91,"prime":false}"#),
+        // Past the cached sieve, and past the largest n a request may sieve to.
+        ("/isprime?n=2305843009213693951", r#"{"n":2305843009213693951,"prime":true}"#),
+        ("/primes?lo=90&hi=110", r#"{"lo":90,"hi":110,"primes":[97,101,103,107,109],"next":null}"#),
+        ("/primes?lo=24&hi=28", r#"{"lo":24,"hi":28,"primes":[],"next":null}"#),
+        ("/primes?hi=10&lo=0&limit=4", r#"{"lo":0,"hi":10,"primes":[2,3,5,7],"next":null}"#),
+        ("/primes?lo=0&hi=10&limit=3", r#"{"lo":0,"hi":10,"primes":[2,3,5],"next":5}"#),
+    ];
+    for (target, expected) in cases {
+        assert_eq!(server.get(target), (200, expected.to_string()), "{}", target);
+    }
+}
+
+#[test]
+fn primes_come_in_pages() {
+    let server = Server::start();
+    let (mut primes, mut after) = (Vec::new(), None);
+    loop {
+        let target = match after {
+            Some(after) => format!("/primes?lo=50&hi=1000&limit=30&after={}", after),
+            None => "/primes?lo=50&hi=1000&limit=30".to_string(),
+        };
+        let (status, body) = server.get(&target);
+        assert_eq!(status, 200, "{}", body);
+        let (found, next) = page(&body);
+        assert!(found.len() <= 30);
+        primes.extend(found);
+        match next {
+            Some(next) => after = Some(next),
+            None => break,
+        }
+    }
+    let expected: Vec<u64> = eratosthenes::primes_up_to(1000).into_iter().filter(|&p| p >= 50).collect();
+    assert_eq!(primes, expected);
+
+    // Without a limit, a page stops at the cap.
+    let (status, body) = server.get("/primes?lo=0&hi=1e6");
+    assert_eq!(status, 200);
+    let (capped, next) = page(&body);
+    assert_eq!((capped.len(), capped.last(), next), (10_000, Some(&104_729), Some(104_729)));
+    let (_, body) = server.get("/primes?lo=0&hi=1e6&limit=50000");
+    assert_eq!(page(&body).0.len(), 10_000);
+}
+
+#[test]
+fn bad_requests_get_an_error() {
+    let server = Server::start();
+    let cases = [
+        ("/count", 400, r#"{"error":"missing parameter n"}"#),
+        ("/isprime?m=5", 400, r#"{"error":"missing parameter n"}"#),
+        ("/primes?lo=5", 400, r#"{"error":"missing parameter hi"}"#),
+        ("/primes?lo=0&hi=10&limit=0", 400, r#"{"error":"limit must be positive"}"#),
+        ("/count?n=2e9", 422, r#"{"error":"n must be at most 1000000000"}"#),
+        ("/primes?lo=0&hi=1e12", 422, r#"{"error":"n must be at most 1000000000"}"#),
+        ("/nope", 404, r#"{"error":"no such endpoint /nope; expected /count, /primes or /isprime"}"#),
+    ];
+    for (target, status, expected) in cases {
+        assert_eq!(server.get(target), (status, expected.to_string()), "{}", target);
+    }
+    let (status, body) = server.get("/count?n=abc");
+    assert_eq!(status, 400);
+    assert!(body.starts_with(r#"{"error":"n: \"abc\" is not a whole number"#), "{}", body);
+    let (status, body) = server.raw("POST /count?n=5 HTTP/1.1\r\n\r\n");
+    assert_eq!((status, body.as_str()), (405, r#"{"error":"only GET is supported"}"#));
+    let (status, body) = server.raw("nonsense\r\n\r\n");
+    assert_eq!((status, body.as_str()), (400, r#"{"error":"malformed request line"}"#));
+}
+
+#[test]
+fn concurrent_requests_share_the_sieve() {
+    let server = Server::start();
+    let expected = [(1_000_000, 78_498), (2_000_000, 148_933), (5_000_000, 348_513), (10_000, 1_229)];
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for (n, count) in expected {
+                    let answer = server.get(&format!("/count?n={}", n));
+                    assert_eq!(answer, (200, format!("{{\"n\":{},\"count\":{}}}", n, count)));
+                }
+            });
+        }
+    });
+}
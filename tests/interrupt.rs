@@ -0,0 +1,39 @@
+// Sends SIGINT through kill(1), so this runs only where there is one.
+#![cfg(unix)]
+
+mod common;
+
+use eratosthenes::primes_up_to;
+
+// The `interrupted: C primes emitted, largest L, ...` summary, as (C, L).
+fn summary(stderr: &str) -> (u64, u64) {
+    let line = stderr.lines().find(|line| line.starts_with("interrupted: ")).unwrap_or_else(|| panic!("{}", stderr));
+    let words: Vec<&str> = line.split_whitespace().collect();
+    assert_eq!((words[2], words[4]), ("primes", "largest"), "{}", line);
+    (words[1].parse().unwrap(), words[5].trim_end_matches(',').parse().unwrap())
+}
+
+#[test]
+fn sigint_ends_the_output_at_a_prime_with_a_summary() {
+    let dir = common::scratch_dir("interrupt");
+    let path = dir.join("primes.txt");
+    let output = path.to_str().unwrap();
+    let runs: [&[&str]; 2] = [
+        &["--print", "--segmented", "--output", output, "1000000000000"],
+        &["--print", "--memory-limit", "64M", "--output", output, "1e12"],
+    ];
+    for args in runs {
+        let (code, stderr) = common::interrupt_run(args, &path);
+        assert_eq!(code, Some(4), "{:?}: {}", args, stderr);
+        let (count, largest) = summary(&stderr);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.ends_with('\n'), "{:?} left a torn last line", args);
+        let primes: Vec<u64> = text.lines().map(|line| line.parse().unwrap()).collect();
+        assert_eq!(primes.len() as u64, count);
+        assert_eq!(primes.last(), Some(&largest));
+        assert_eq!(primes, primes_up_to(largest), "{:?}", args);
+        std::fs::remove_file(&path).unwrap();
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use common::json_field;
+use eratosthenes::{first_k_primes, nth_prime_upper_bound};
+
+fn printed(args: &[&str]) -> Vec<u64> {
+    let output = common::run(&[&["--print"], args].concat());
+    assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().lines().map(|line| line.parse().unwrap()).collect()
+}
+
+#[test]
+fn limit_count_emits_exactly_k_primes() {
+    for k in [0, 1, 2, 3, 25, 1_000, 78_498, 100_000] {
+        let arg = k.to_string();
+        assert_eq!(printed(&["--limit-count", &arg]), first_k_primes(k), "K = {}", k);
+        let count = common::run(&["--limit-count", &arg]);
+        assert!(count.status.success(), "K = {}", k);
+        assert_eq!(String::from_utf8(count.stdout).unwrap(), format!("{}\n", k));
+    }
+    assert_eq!(printed(&["--limit-count", "1"]), [2]);
+    assert_eq!(printed(&["--limit-count", "0"]), []);
+}
+
+#[test]
+fn limit_count_or_n_whichever_comes_first() {
+    // N first: the primes up to 20 are only 8.
+    assert_eq!(printed(&["--limit-count", "10", "20"]), [2, 3, 5, 7, 11, 13, 17, 19]);
+    // K first, and K at N exactly.
+    assert_eq!(printed(&["--limit-count", "5", "1000"]), [2, 3, 5, 7, 11]);
+    assert_eq!(printed(&["--limit-count", "25", "97"]), first_k_primes(25));
+    assert_eq!(printed(&["--limit-count", "25", "96"]), first_k_primes(24));
+}
+
+#[test]
+fn limit_count_1000_stops_early() {
+    for args in [&["--limit-count", "1000"][..], &["--limit-count", "1000", "1e15"]] {
+        // Sieving up to 10^15 would take days; the first 1000 primes need a bound below 10^4.
+        let mut child = common::eratosthenes()
+            .args(["--stats"])
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("the binary runs");
+        let status = common::wait_within(&mut child, Duration::from_secs(10));
+        assert!(status.success(), "{:?}", args);
+        let output = child.wait_with_output().unwrap();
+        assert_eq!(output.stdout, b"1000\n", "{:?}", args);
+        let stats = String::from_utf8(output.stderr).unwrap();
+        let n: u64 = json_field(stats.lines().last().unwrap(), "n").parse().unwrap();
+        assert!((7_919..=nth_prime_upper_bound(1_000).unwrap()).contains(&n), "{:?}: {}", args, stats);
+    }
+}
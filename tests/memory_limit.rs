@@ -0,0 +1,46 @@
+mod common;
+
+use common::{json_field, run_with_stats};
+
+// Counts up to n under a memory limit, checking that the plan is segmented and within the limit
+// and that the count is right. Returns the peak RSS, where the platform reports it.
+fn count_within(n: &str, limit: &str, expected: u64) -> Option<u64> {
+    let (count, stats) = run_with_stats(&["--memory-limit", limit, n]);
+    assert_eq!(count, format!("{}\n", expected), "{} within {}", n, limit);
+    assert_eq!(json_field(&stats, "strategy"), "\"segmented\"", "{}", stats);
+    assert!(json_field(&stats, "segments").parse::<u64>().unwrap() > 1, "{}", stats);
+    let planned: u64 = json_field(&stats, "planned_bytes").parse().unwrap();
+    assert!(planned <= json_field(&stats, "memory_limit").parse().unwrap(), "{}", stats);
+    json_field(&stats, "peak_rss_bytes").parse().ok()
+}
+
+#[test]
+fn a_small_limit_segments_the_sieve() {
+    let limited = count_within("1e8", "256K", 5_761_455);
+    // Without the limit, the whole sieve is one allocation of some 26 MB.
+    let (count, stats) = run_with_stats(&["1e8"]);
+    assert_eq!(count, "5761455\n");
+    assert_eq!(json_field(&stats, "strategy"), "\"monolithic\"", "{}", stats);
+    let unlimited = json_field(&stats, "peak_rss_bytes").parse::<u64>().ok();
+    if let (Some(limited), Some(unlimited)) = (limited, unlimited) {
+        assert!(limited + 20_000_000 < unlimited, "peak of {} bytes within the limit, {} without", limited, unlimited);
+    }
+}
+
+#[test]
+#[ignore = "sieves up to 10^9"]
+fn a_small_limit_segments_the_sieve_up_to_10_to_the_9() {
+    // Monolithic, the sieve alone would take some 266 MB.
+    if let Some(peak) = count_within("1e9", "1M", 50_847_534) {
+        assert!(peak < 32_000_000, "peak of {} bytes", peak);
+    }
+}
+
+#[test]
+fn a_limit_below_one_segment_is_refused() {
+    let output = common::run(&["--memory-limit", "1K", "1e8"]);
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let expected = "sieving up to n = 100000000 needs at least 17668 bytes, more than the limit of 1000";
+    assert!(stderr.contains(expected), "{}", stderr);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use eratosthenes::{primes_up_to, write_delta_primes, write_prime_file};
+
+const N: u64 = 1_000_000;
+
+// Three shards covering 1..=N that overlap, one in each format merge reads: a text list, a
+// bin-delta stream and a prime file.
+fn write_shards(dir: &Path) -> [PathBuf; 3] {
+    let primes = primes_up_to(N);
+    let within = |lo: u64, hi: u64| -> Vec<u64> { primes.iter().copied().filter(|p| (lo..=hi).contains(p)).collect() };
+    let paths = [dir.join("low.txt"), dir.join("middle.bd"), dir.join("high.bin")];
+
+    let low: Vec<String> = within(1, 400_000).iter().map(|p| format!("{}\n", p)).collect();
+    fs::write(&paths[0], low.concat()).unwrap();
+    write_delta_primes(File::create(&paths[1]).unwrap(), 299_000, &within(300_000, 700_000)).unwrap();
+    write_prime_file(File::create(&paths[2]).unwrap(), N, &within(650_000, N)).unwrap();
+    paths
+}
+
+fn path_args(paths: &[PathBuf]) -> Vec<&str> {
+    paths.iter().map(|path| path.to_str().unwrap()).collect()
+}
+
+#[test]
+fn overlapping_shards_merge_into_a_single_run() {
+    let dir = common::scratch_dir("merge");
+    let shards = write_shards(&dir);
+    let (merged, reference) = (dir.join("merged.txt"), dir.join("reference.txt"));
+
+    // Whatever order the inputs come in.
+    for order in [[0, 1, 2], [2, 0, 1]] {
+        let inputs: Vec<PathBuf> = order.iter().map(|&i| shards[i].clone()).collect();
+        let output = common::run(&[&["merge", merged.to_str().unwrap()][..], &path_args(&inputs)].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.ends_with(": 78498 primes\n"), "{}", stderr);
+
+        let single = common::run(&["--print", "--output", reference.to_str().unwrap(), "1000000"]);
+        assert!(single.status.success());
+        assert!(fs::read(&merged).unwrap() == fs::read(&reference).unwrap(), "the merge differs from a single run");
+    }
+
+    // bin-delta out is the stream a single run writes.
+    let args = ["merge", "--format", "bin-delta", merged.to_str().unwrap()];
+    let output = common::run(&[&args[..], &path_args(&shards)].concat());
+    assert!(output.status.success());
+    let single = common::run(&["--format", "bin-delta", "--output", reference.to_str().unwrap(), "1000000"]);
+    assert!(single.status.success());
+    assert!(fs::read(&merged).unwrap() == fs::read(&reference).unwrap(), "the bin-delta merge differs");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn corrupt_inputs_are_named() {
+    let dir = common::scratch_dir("merge-corrupt");
+    let [low, middle, high] = write_shards(&dir);
+    let out = dir.join("merged.txt");
+
+    let unordered = dir.join("unordered.txt");
+    fs::write(&unordered, "2\n3\n7\n5\n11\n").unwrap();
+    let garbled = dir.join("garbled.txt");
+    fs::write(&garbled, "2\n3\nfive\n7\n").unwrap();
+    // A bin-delta stream cut off partway through its gaps.
+    let truncated = dir.join("truncated.bd");
+    let bytes = fs::read(&middle).unwrap();
+    fs::write(&truncated, &bytes[..bytes.len() / 2]).unwrap();
+
+    let cases = [
+        (&unordered, "line 4: 5 does not follow 7; the list must be strictly increasing"),
+        (&garbled, "line 3: not a number: \"five\""),
+        (&truncated, "bin-delta stream is truncated"),
+    ];
+    for (bad, message) in cases {
+        let inputs = [low.clone(), bad.clone(), high.clone()];
+        let output = common::run(&[&["merge", out.to_str().unwrap()][..], &path_args(&inputs)].concat());
+        assert_eq!(output.status.code(), Some(1), "{}", bad.display());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.starts_with(&format!("{}: {}", bad.display(), message)), "{}", stderr);
+        assert!(!out.exists(), "a failed merge left {} behind", out.display());
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,60 @@
+// The queries marked #[must_use], called the ways callers call them. A discarded result is a
+// hard error here, so each pattern below would fail to build if it dropped one, and an explicit
+// `let _ =` must stay allowed for callers who mean it.
+#![deny(unused_must_use)]
+
+use eratosthenes::{
+    count_primes_up_to, default_threads, discrete_root, is_prime, mod_prime_field, pollard_p1, prime_count_upper_bound,
+    primes_up_to, reference_primes_up_to, sieve_of_atkin, sundaram_sieve, tonelli_shanks, Sieve,
+};
+
+#[test]
+fn free_queries_in_expressions_and_as_function_values() {
+    // Bound, compared, and returned from a block.
+    let count = count_primes_up_to(1_000);
+    assert_eq!(count, 168);
+    assert!(count as usize <= prime_count_upper_bound(1_000));
+    let found = {
+        let primes = primes_up_to(100);
+        primes.len()
+    };
+    assert_eq!(found, 25);
+
+    // Passed by name to iterator adapters, and called inside closures.
+    assert_eq!((0..100).filter(|&n| is_prime(n)).count(), 25);
+    assert_eq!((0u64..10).map(is_prime).filter(|&p| p).count(), 4);
+    let counts: Vec<u64> = [10, 100, 1_000].into_iter().map(count_primes_up_to).collect();
+    assert_eq!(counts, [4, 25, 168]);
+
+    // In conditions and with `?`-style combinators.
+    if is_prime(97) && !is_prime(91) {
+        assert!(default_threads() >= 1);
+    }
+    let root = tonelli_shanks(2, 7).and_then(|r| discrete_root(r * r % 7, 2, 7));
+    assert!(matches!(root, Some(3 | 4)));
+    assert_eq!(pollard_p1(10_403, 100).map(|d| d.min(10_403 / d)), Some(101));
+
+    // Several sieves agreeing, each result used once.
+    assert_eq!(sundaram_sieve(1_000), sieve_of_atkin(1_000));
+    assert_eq!(reference_primes_up_to(1_000), primes_up_to(1_000));
+
+    // An explicit discard is still fine.
+    let _ = count_primes_up_to(10);
+    let _ = is_prime(7);
+}
+
+#[test]
+fn sieve_and_field_methods_chain() {
+    let sieve = Sieve::new(10_000).unwrap();
+    assert_eq!((sieve.n(), sieve.count_primes_up_to(10_000)), (10_000, 1_229));
+    assert_eq!(sieve.primes().take_while(|&p| p < 100).count() as u64, sieve.count_primes_up_to(100));
+    assert!((0..=10_000).filter(|&v| sieve.is_prime(v)).all(is_prime));
+    assert!(sieve.gap_statistics().max_gap >= 2);
+    let _ = sieve.count_primes_up_to(1);
+
+    let field = mod_prime_field(101).unwrap();
+    let x = field.mul(field.add(5, 7), field.sub(3, 9));
+    assert_eq!(field.div(x, field.pow(field.generator(), 0)), Some(x));
+    assert_eq!(field.inv(10).map(|inverse| field.mul(inverse, 10)), Some(1));
+    assert_eq!(field.modulus(), 101);
+}
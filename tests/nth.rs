@@ -0,0 +1,71 @@
+mod common;
+
+use common::json_field;
+
+fn nth(args: &[&str]) -> std::process::Output {
+    common::run(&[&["nth"], args].concat())
+}
+
+fn stdout(args: &[&str]) -> String {
+    let output = nth(args);
+    assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn canonical_primes() {
+    assert_eq!(stdout(&["1"]), "2\n");
+    assert_eq!(stdout(&["25"]), "97\n");
+    assert_eq!(stdout(&["1e6"]), "15485863\n");
+    assert_eq!(stdout(&["1", "25", "1000000"]), "1 2\n25 97\n1000000 15485863\n");
+    // Answers follow the order asked in, repeats included.
+    assert_eq!(stdout(&["25", "1", "25"]), "25 97\n1 2\n25 97\n");
+}
+
+#[test]
+fn count_from_zero_shifts_k() {
+    assert_eq!(stdout(&["--count-from", "0", "0", "24", "999999"]), "0 2\n24 97\n999999 15485863\n");
+    assert_eq!(stdout(&["--count-from", "1", "1"]), "2\n");
+}
+
+#[test]
+fn bad_k_is_a_clean_error() {
+    for (args, code, message) in [
+        (&["0"][..], 2, "K counts from 1 (the 1st prime is 2); use --count-from 0 for 0-based K\n"),
+        (&["--count-from", "1", "25", "0"], 2, "K counts from 1 (the 1st prime is 2)"),
+        (&["--count-from", "2", "1"], 2, "--count-from takes 0 or 1\n"),
+        (&["-5"], 2, "-5: the bound must be non-negative"),
+        (&["x"], 2, "\"x\" is not a whole number"),
+        (&[], 2, "nth needs at least one K\n"),
+        (&["18446744073709551615"], 1, "K is past the last u64 prime\n"),
+    ] {
+        let output = nth(args);
+        assert_eq!(output.status.code(), Some(code), "{:?}", args);
+        assert!(output.stdout.is_empty(), "{:?}", args);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.starts_with(message), "{:?}: {}", args, stderr);
+    }
+}
+
+// The stats line, with the bound sieved up to as n and the largest index asked for as primes.
+fn stats(ks: &[&str]) -> String {
+    let output = nth(&[&["--stats"], ks].concat());
+    assert!(output.status.success(), "{:?}", ks);
+    String::from_utf8(output.stderr).unwrap().lines().last().unwrap().to_string()
+}
+
+#[test]
+fn several_indices_take_one_sieve() {
+    let ks = ["10", "1000", "100000", "1000000"];
+    let together = stats(&ks);
+    assert_eq!(json_field(&together, "primes"), "1000000", "{}", together);
+    let sieved: u64 = json_field(&together, "n").parse().unwrap();
+    assert!(sieved >= 15_485_863, "{}", together);
+
+    // One run sieves as far as the largest K needs and no further, where separate runs would
+    // sieve up to each K's bound in turn.
+    let alone: Vec<u64> = ks.iter().map(|k| json_field(&stats(&[k]), "n").parse().unwrap()).collect();
+    assert_eq!(sieved, *alone.iter().max().unwrap());
+    assert!(sieved < alone.iter().sum(), "{} against {:?}", sieved, alone);
+    assert_eq!(json_field(&together, "segments"), json_field(&stats(&["1000000"]), "segments"));
+}
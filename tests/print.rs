@@ -0,0 +1,61 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Read};
+use std::process::Stdio;
+use std::time::Duration;
+
+// Reads the first 100 lines of `--print` and closes the pipe, as `| head -n 100` would. Sieving
+// all of n would take hours; the run must notice the closed pipe and stop quietly.
+fn print_into_closed_pipe(args: &[&str]) {
+    let mut child = common::eratosthenes()
+        .arg("--print")
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("the binary runs");
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let first: Vec<u64> = lines.by_ref().take(100).map(|line| line.unwrap().parse().unwrap()).collect();
+    drop(lines);
+    assert_eq!(first.len(), 100);
+    assert_eq!((first[0], first[99]), (2, 541));
+
+    let status = common::wait_within(&mut child, Duration::from_secs(30));
+    let mut stderr = String::new();
+    child.stderr.take().unwrap().read_to_string(&mut stderr).unwrap();
+    assert!(status.success(), "{:?} exited with {}: {}", args, status, stderr);
+    // A --threads above this machine's count is warned about, and nothing else is said.
+    let quiet = stderr.lines().all(|line| line.starts_with("warning: --threads"));
+    assert!(quiet, "{:?} wrote to stderr: {}", args, stderr);
+}
+
+#[test]
+fn print_stops_when_the_reader_closes() {
+    print_into_closed_pipe(&["--segmented", "1000000000000"]);
+    print_into_closed_pipe(&["--memory-limit", "64M", "1000000000000"]);
+    print_into_closed_pipe(&["--format", "text", "--segmented", "1e15"]);
+    // Monolithic: formatted on the marking threads, and marked inline on one.
+    print_into_closed_pipe(&["--threads", "2", "1e10"]);
+    print_into_closed_pipe(&["--threads", "1", "1e8"]);
+}
+
+// With more than one thread, a monolithic --print formats segments on the workers and writes
+// them in order; --segmented and a single thread format each prime as it is found.
+#[test]
+fn parallel_text_matches_the_serial_path() {
+    let dir = common::scratch_dir("print-parallel");
+    let path = dir.join("primes.txt");
+    // Three default segments' worth, the last one partial.
+    let n = "20000000";
+    let serial = common::run(&["--print", "--segmented", n]);
+    assert!(serial.status.success());
+    assert_eq!(serial.stdout.iter().filter(|&&b| b == b'\n').count(), 1_270_607);
+    for threads in ["1", "2", "4"] {
+        let output = common::run(&["--print", "--threads", threads, n]);
+        assert!(output.stdout == serial.stdout, "--threads {} differs on stdout", threads);
+        let output = common::run(&["--print", "--threads", threads, "--output", path.to_str().unwrap(), n]);
+        assert!(output.status.success());
+        assert!(std::fs::read(&path).unwrap() == serial.stdout, "--threads {} differs in a file", threads);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use std::fs::File;
+
+use eratosthenes::{is_prime, read_delta_primes, BasePrimes};
+
+fn range(args: &[&str]) -> (Option<i32>, String, String) {
+    let output = common::run(&[&["range"][..], args].concat());
+    let (stdout, stderr) = (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap());
+    (output.status.code(), stdout, stderr)
+}
+
+#[test]
+fn saved_base_primes_serve_later_far_ranges() {
+    let dir = common::scratch_dir("range-base-primes");
+    let saved = dir.join("base.bin");
+    let saved = saved.to_str().unwrap();
+    let (code, _, stderr) = range(&["--save-base-primes", saved, "999999900000..1000000000000"]);
+    assert_eq!(code, Some(0), "{}", stderr);
+    // The primes up to 10^6, good until the square of the next prime, 1000003.
+    let bases = read_delta_primes(File::open(saved).unwrap()).unwrap();
+    assert_eq!((bases.len(), bases.last()), (78_498, Some(&999_983)));
+    assert_eq!(BasePrimes::from_primes(bases).unwrap().limit(), 1_000_002);
+
+    // One window well below the one the bases were saved for and one at the top of their reach.
+    for window in ["500000000000..500000100000", "1000005900000..1000006000008"] {
+        let (code, reused, stderr) = range(&["--base-primes", saved, window]);
+        assert_eq!(code, Some(0), "{}: {}", window, stderr);
+        let (code, fresh, _) = range(&[window]);
+        assert_eq!(code, Some(0), "{}", window);
+        assert!(reused == fresh, "{}: the saved base primes give a different list", window);
+
+        let (lo, hi) = window.split_once("..").unwrap();
+        let expected: Vec<u64> = (lo.parse().unwrap()..=hi.parse().unwrap()).filter(|&n| is_prime(n)).collect();
+        let listed: Vec<u64> = reused.lines().map(|line| line.parse().unwrap()).collect();
+        assert!(listed == expected, "{}", window);
+        assert!(listed.len() > 3_000, "{}: {} primes", window, listed.len());
+    }
+
+    // Past the square of the bases' limit they no longer cover the window.
+    let (code, stdout, stderr) = range(&["--base-primes", saved, "1000006000000..1000006000009"]);
+    assert_eq!(code, Some(1));
+    assert!(stdout.is_empty());
+    let expected = format!(
+        "{}: the base primes cover up to 1000002, but 1000006000000..1000006000009 needs them up to 1000003",
+        saved
+    );
+    assert!(stderr.contains(&expected), "{}", stderr);
+}
+
+#[test]
+fn malformed_base_primes_are_refused() {
+    let dir = common::scratch_dir("range-bad-base-primes");
+    let path = dir.join("base.txt");
+    let cases = [
+        ("3\n5\n7\n", "the list starts at 3 rather than 2"),
+        ("2\n3\n7\n5\n", "7 is followed by 5, out of order"),
+        ("2\n3\n3\n5\n", "3 is followed by 3, out of order"),
+        ("2\n3\nfive\n", "line 3: not a number: \"five\""),
+    ];
+    for (text, reason) in cases {
+        std::fs::write(&path, text).unwrap();
+        let (code, stdout, stderr) = range(&["--base-primes", path.to_str().unwrap(), "100..200"]);
+        assert_eq!(code, Some(1), "{}", reason);
+        assert!(stdout.is_empty(), "{}", reason);
+        assert!(stderr.contains(reason), "expected {:?} in {:?}", reason, stderr);
+    }
+}
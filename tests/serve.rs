@@ -0,0 +1,112 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
+use std::time::Duration;
+
+struct Session {
+    child: Child,
+    input: Option<ChildStdin>,
+    output: BufReader<ChildStdout>,
+}
+
+impl Session {
+    fn start() -> Self {
+        let mut child = common::eratosthenes()
+            .arg("--serve")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("the binary runs");
+        let (input, output) = (child.stdin.take(), BufReader::new(child.stdout.take().unwrap()));
+        Session { child, input, output }
+    }
+
+    fn send(&mut self, query: &str) {
+        let input = self.input.as_mut().unwrap();
+        writeln!(input, "{}", query).unwrap();
+        input.flush().unwrap();
+    }
+
+    fn answer(&mut self) -> String {
+        let mut line = String::new();
+        self.output.read_line(&mut line).unwrap();
+        assert!(line.ends_with('\n'), "the session ended early");
+        line.trim_end().to_string()
+    }
+
+    // Sends one query and returns its answer, which comes before the next query is read.
+    fn ask(&mut self, query: &str) -> String {
+        self.send(query);
+        self.answer()
+    }
+
+    // Closes stdin and waits for the session to end, returning anything else it wrote.
+    fn finish(mut self) -> String {
+        drop(self.input.take());
+        let status = common::wait_within(&mut self.child, Duration::from_secs(30));
+        assert!(status.success(), "the session exited with {}", status);
+        let mut rest = String::new();
+        while self.output.read_line(&mut rest).unwrap() > 0 {}
+        rest
+    }
+}
+
+#[test]
+fn a_scripted_session_answers_each_query() {
+    let mut session = Session::start();
+    let script = [
+        ("count 100", r#"{"n":100,"count":25}"#),
+        ("count 1e7", r#"{"n":10000000,"count":664579}"#),
+        ("count 1", r#"{"n":1,"count":0}"#),
+        ("nth 1", r#"{"k":1,"prime":2}"#),
+        ("nth 10000", r#"{"k":10000,"prime":104729}"#),
+        ("isprime 97", r#"{"n":97,"prime":true}"#),
+        ("isprime 91", r#"{"n":91,"prime":false}"#),
+        // 2^61 - 1 is past the sieve, and prime.
+        ("isprime 2305843009213693951", r#"{"n":2305843009213693951,"prime":true}"#),
+        ("range 90 110", r#"{"lo":90,"hi":110,"primes":[97,101,103,107,109]}"#),
+        ("range 24 28", r#"{"lo":24,"hi":28,"primes":[]}"#),
+        ("  count   10  ", r#"{"n":10,"count":4}"#),
+    ];
+    for (query, expected) in script {
+        assert_eq!(session.ask(query), expected, "{}", query);
+    }
+    assert_eq!(session.finish(), "");
+}
+
+#[test]
+fn bad_queries_get_an_error_and_the_session_goes_on() {
+    let mut session = Session::start();
+    let script = [
+        ("frobnicate 3", r#"{"error":"unknown command \"frobnicate\"; expected count, nth, isprime, range or quit"}"#),
+        ("count", r#"{"error":"count needs N"}"#),
+        ("count 5 6", r#"{"error":"unexpected argument \"6\""}"#),
+        ("nth 0", r#"{"error":"K counts from 1"}"#),
+        ("range 10", r#"{"error":"range needs HI"}"#),
+    ];
+    for (query, expected) in script {
+        assert_eq!(session.ask(query), expected, "{}", query);
+    }
+    let answer = session.ask("isprime -7");
+    assert!(answer.starts_with(r#"{"error":"N: -7: the bound must be non-negative"#), "{}", answer);
+    // Blank lines are skipped, not answered.
+    session.send("");
+    assert_eq!(session.ask("count 30"), r#"{"n":30,"count":10}"#);
+    assert_eq!(session.finish(), "");
+}
+
+#[test]
+fn quit_ends_the_session() {
+    let mut session = Session::start();
+    session.send("count 10\nquit\ncount 20");
+    assert_eq!(session.answer(), r#"{"n":10,"count":4}"#);
+    assert_eq!(session.answer(), r#"{"quit":true}"#);
+    assert_eq!(session.finish(), "");
+}
+
+#[test]
+fn eof_ends_the_session() {
+    let session = Session::start();
+    assert_eq!(session.finish(), "");
+}
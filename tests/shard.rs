@@ -0,0 +1,91 @@
+mod common;
+
+use std::fs::File;
+use std::path::Path;
+
+use eratosthenes::{primes_up_to, read_delta_primes};
+
+// The primes in one shard, in either format.
+fn read_shard(path: &Path) -> Vec<u64> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("txt") => std::fs::read_to_string(path).unwrap().lines().map(|line| line.parse().unwrap()).collect(),
+        Some("bin") => read_delta_primes(File::open(path).unwrap()).unwrap(),
+        _ => panic!("unexpected shard {}", path.display()),
+    }
+}
+
+// Checks every shard the manifest next to `prefix` lists against the shard itself, and returns
+// the primes of all of them in order, along with the manifest's counts.
+fn read_shards(prefix: &Path) -> (Vec<u64>, Vec<u64>) {
+    let manifest = std::fs::read_to_string(prefix.with_extension("manifest")).unwrap();
+    let mut lines = manifest.lines();
+    assert_eq!(lines.next(), Some("# shard first last count"));
+    let (mut primes, mut counts) = (Vec::new(), Vec::new());
+    for (i, line) in lines.enumerate() {
+        let fields: Vec<&str> = line.split(' ').collect();
+        let [name, first, last, count] = fields[..] else { panic!("bad manifest line {:?}", line) };
+        let stem = prefix.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with(&format!("{}-{:05}.", stem, i)), "{}", line);
+        let shard = read_shard(&prefix.with_file_name(name));
+        assert_eq!(shard.first().unwrap().to_string(), first, "{}", line);
+        assert_eq!(shard.last().unwrap().to_string(), last, "{}", line);
+        assert_eq!(shard.len().to_string(), count, "{}", line);
+        counts.push(shard.len() as u64);
+        primes.extend(shard);
+    }
+    (primes, counts)
+}
+
+fn shard_args<'a>(format: &'a str, size: &'a str, prefix: &'a Path) -> [&'a str; 7] {
+    ["--print", "--format", format, "--shard-size", size, "--output", prefix.to_str().unwrap()]
+}
+
+#[test]
+fn shards_concatenate_to_the_whole_list() {
+    let dir = common::scratch_dir("shard");
+    let primes = primes_up_to(1_000_000);
+    for format in ["text", "bin-delta"] {
+        for (size, shards) in [("10000", 8), ("78498", 1), ("1000", 79)] {
+            let prefix = dir.join(format!("{}-{}", format, size));
+            let args = [&shard_args(format, size, &prefix)[..], &["1e6"]].concat();
+            let output = common::run(&args);
+            assert!(output.status.success(), "{:?}", args);
+
+            let (listed, counts) = read_shards(&prefix);
+            assert_eq!(counts.len(), shards, "{:?}", args);
+            let size: u64 = size.parse().unwrap();
+            assert!(counts[..shards - 1].iter().all(|&count| count == size), "{:?}", args);
+            assert_eq!(counts[shards - 1], 78_498 - size * (shards as u64 - 1), "{:?}", args);
+            assert!(listed == primes, "{:?}", args);
+        }
+    }
+}
+
+#[test]
+fn resumed_shards_match_an_uninterrupted_run() {
+    let dir = common::scratch_dir("shard-resume");
+    let checkpoint = dir.join("run.checkpoint");
+    // 10^8 in small segments, as in tests/checkpoint.rs. A bin-delta shard is held in memory
+    // until it closes, so the shards are small enough to leave room under the limit.
+    let (n, segments, size) = ("100000000", ["--memory-limit", "64K"], "10000");
+    for format in ["text", "bin-delta"] {
+        let (partial, whole) = (dir.join(format!("partial-{}", format)), dir.join(format!("whole-{}", format)));
+
+        let killed = [&segments[..], &shard_args(format, size, &partial), &[n]].concat();
+        common::kill_after_a_checkpoint(&killed, &checkpoint);
+        let resume = ["--resume", checkpoint.to_str().unwrap()];
+        let resumed = common::run(&[&shard_args(format, size, &partial)[..], &resume].concat());
+        assert!(resumed.status.success(), "{}: {}", format, String::from_utf8_lossy(&resumed.stderr));
+
+        let uninterrupted = common::run(&[&segments[..], &shard_args(format, size, &whole), &[n]].concat());
+        assert!(uninterrupted.status.success(), "{}", format);
+        let (resumed, resumed_counts) = read_shards(&partial);
+        let (whole, whole_counts) = read_shards(&whole);
+        assert_eq!(resumed_counts, whole_counts, "{}", format);
+        assert_eq!(resumed_counts.len(), 577, "{}", format);
+        assert_eq!(resumed_counts.last(), Some(&1_455), "{}", format);
+        assert!(resumed == whole, "{}: the resumed shards differ from an uninterrupted run", format);
+        std::fs::remove_file(&checkpoint).unwrap();
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,63 @@
+mod common;
+
+// The 11x11 spiral, top row first, with primes as '#': checked by hand against the numbers
+//
+//   101 100  99  98  97  96  95  94  93  92  91
+//   102  65  64  63  62  61  60  59  58  57  90
+//   103  66  37  36  35  34  33  32  31  56  89
+//   104  67  38  17  16  15  14  13  30  55  88
+//   105  68  39  18   5   4   3  12  29  54  87
+//   106  69  40  19   6   1   2  11  28  53  86
+//   107  70  41  20   7   8   9  10  27  52  85
+//   108  71  42  21  22  23  24  25  26  51  84
+//   109  72  43  44  45  46  47  48  49  50  83
+//   110  73  74  75  76  77  78  79  80  81  82
+//   111 112 113 114 115 116 117 118 119 120 121
+const GOLDEN_11: [&str; 11] = [
+    "#...#......",
+    ".....#.#...",
+    "#.#.....#.#",
+    ".#.#...#...",
+    "....#.#.#..",
+    "...#..##.#.",
+    "#.#.#......",
+    ".#...#.....",
+    "#.#...#...#",
+    ".#.....#...",
+    "..#........",
+];
+
+#[test]
+fn spiral_11_matches_the_golden_image() {
+    let output = common::run(&["spiral", "11"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let header = b"P5\n11 11\n255\n";
+    assert_eq!(&output.stdout[..header.len()], header);
+    let pixels = &output.stdout[header.len()..];
+    assert_eq!(pixels.len(), 11 * 11);
+    for (y, (row, golden)) in pixels.chunks(11).zip(GOLDEN_11).enumerate() {
+        for (x, (&pixel, expected)) in row.iter().zip(golden.bytes()).enumerate() {
+            let want = if expected == b'#' { 0 } else { 255 };
+            assert_eq!(pixel, want, "pixel ({}, {}) of the 11x11 spiral", x, y);
+        }
+    }
+}
+
+#[test]
+fn spiral_written_to_a_file() {
+    let dir = common::scratch_dir("spiral");
+    let path = dir.join("spiral.pgm");
+    let output = common::run(&["spiral", "11", "--output", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(std::fs::read(&path).unwrap(), common::run(&["spiral", "11"]).stdout);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn spiral_rejects_a_bad_size() {
+    for args in [&["spiral"][..], &["spiral", "0"], &["spiral", "eleven"]] {
+        let output = common::run(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+    }
+}
@@ -0,0 +1,45 @@
+mod common;
+
+// Runs `eratosthenes --threads <threads>` with `args`, returning its stdout.
+fn with_threads(threads: &str, args: &[&str]) -> Vec<u8> {
+    let output = common::run(&[&["--threads", threads], args].concat());
+    assert!(output.status.success(), "--threads {} {:?}: {}", threads, args, String::from_utf8_lossy(&output.stderr));
+    output.stdout
+}
+
+#[test]
+fn one_thread_and_four_give_the_same_output() {
+    for n in ["0", "2", "48", "49", "1000", "1000000", "10000019"] {
+        let count = with_threads("1", &[n]);
+        assert_eq!(with_threads("4", &[n]), count, "count up to {}", n);
+        assert_eq!(with_threads("0", &[n]), count, "count up to {}", n);
+    }
+    assert_eq!(with_threads("4", &["10000000"]), b"664579\n");
+    let printed: [&[&str]; 3] =
+        [&["--print", "3000000"], &["--format", "bin-delta", "3000000"], &["--print", "--limit-count", "5000"]];
+    for args in printed {
+        assert_eq!(with_threads("4", args), with_threads("1", args), "{:?}", args);
+    }
+}
+
+#[test]
+fn stats_report_the_threads_asked_for() {
+    for threads in ["1", "4"] {
+        let output = common::run(&["--threads", threads, "--stats", "100000"]);
+        assert!(output.status.success());
+        let stats = String::from_utf8(output.stderr).unwrap();
+        let json = stats.lines().last().unwrap();
+        assert!(json.contains(&format!("\"threads\":{},", threads)), "{}", json);
+        assert!(json.contains("\"primes\":9592,"), "{}", json);
+    }
+}
+
+#[test]
+fn threads_must_be_a_whole_number() {
+    for threads in ["-1", "two", "1.5", ""] {
+        let output = common::run(&["--threads", threads, "100"]);
+        assert_eq!(output.status.code(), Some(2), "--threads {:?}", threads);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.starts_with("--threads needs a whole number"), "{}", stderr);
+    }
+}
@@ -0,0 +1,48 @@
+mod common;
+
+// A rate as the status line shows it, such as "16.74M", back as a number.
+fn parse_rate(rate: &str) -> f64 {
+    let (digits, scale) = match rate.char_indices().last() {
+        Some((i, 'k')) => (&rate[..i], 1e3),
+        Some((i, 'M')) => (&rate[..i], 1e6),
+        Some((i, 'G')) => (&rate[..i], 1e9),
+        _ => (rate, 1.0),
+    };
+    digits.parse::<f64>().unwrap_or_else(|_| panic!("not a rate: {:?}", rate)) * scale
+}
+
+// The candidate and prime rates from "<rate> candidates/s  <rate> primes/s", with anything after.
+fn parse_rates(line: &str) -> (f64, f64) {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    assert!(words.len() >= 4, "{}", line);
+    assert_eq!((words[1], words[3]), ("candidates/s", "primes/s"), "{}", line);
+    (parse_rate(words[0]), parse_rate(words[2]))
+}
+
+#[test]
+fn throughput_shows_parsable_rates() {
+    // Small segments keep 10^8 going for a few refreshes of the status line.
+    let (_, stats) = common::run_with_stats(&["--throughput", "--memory-limit", "64K", "1e8"]);
+    let output = common::run(&["--throughput", "--memory-limit", "64K", "1e8"]);
+    assert!(output.status.success());
+    // Off a terminal, each update is a line of its own, after the heading, and the last is the
+    // average.
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<&str> = stderr.lines().skip_while(|line| line.starts_with("Following is")).collect();
+    let (average, live) = lines.split_last().unwrap();
+    assert!(!live.is_empty(), "no status line before the average: {:?}", stderr);
+    for line in live {
+        let (candidates, primes) = parse_rates(line);
+        assert!(candidates > 0.0 && primes > 0.0, "{}", line);
+        assert!(line.ends_with("% of n)"), "{}", line);
+    }
+    let (candidates, primes) = parse_rates(average.strip_prefix("average ").unwrap());
+    // About 8 candidates in 30 integers against about 1 prime in 18 below 10^8.
+    assert!(candidates > primes * 3.0 && candidates < primes * 6.0, "{}", average);
+
+    let candidates: f64 = common::json_field(&stats, "candidates_per_second").parse().unwrap();
+    let primes: f64 = common::json_field(&stats, "primes_per_second").parse().unwrap();
+    assert!(candidates > primes * 3.0 && primes > 0.0, "{}", stats);
+    let (_, stats) = common::run_with_stats(&["1e6"]);
+    assert_eq!(common::json_field(&stats, "candidates_per_second"), "null");
+}
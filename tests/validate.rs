@@ -0,0 +1,136 @@
+mod common;
+
+use std::fs::File;
+use std::path::Path;
+use std::process::Output;
+
+use eratosthenes::{primes_up_to, DeltaEncoder};
+
+fn write_text(path: &Path, values: &[u64]) {
+    let text: String = values.iter().map(|v| format!("{}\n", v)).collect();
+    std::fs::write(path, text).unwrap();
+}
+
+fn write_delta(path: &Path, values: &[u64]) {
+    let mut encoder = DeltaEncoder::new(0);
+    for &v in values {
+        encoder.push(v);
+    }
+    encoder.finish(File::create(path).unwrap()).unwrap();
+}
+
+fn validate(path: &Path, options: &[&str]) -> (Option<i32>, Vec<String>) {
+    let mut args = vec!["validate", path.to_str().unwrap()];
+    args.extend_from_slice(options);
+    let Output { status, stdout, .. } = common::run(&args);
+    let path = format!("{}: ", path.display());
+    let stdout = String::from_utf8(stdout).unwrap();
+    (status.code(), stdout.lines().map(|line| line.strip_prefix(&path).unwrap().to_owned()).collect())
+}
+
+#[test]
+fn validate_accepts_what_print_writes() {
+    let dir = common::scratch_dir("validate-clean");
+    let text = dir.join("primes.txt");
+    assert!(common::run(&["--print", "--output", text.to_str().unwrap(), "100"]).status.success());
+    let (code, lines) = validate(&text, &["--complete", "2..100"]);
+    assert_eq!(code, Some(0));
+    assert_eq!(lines, ["25 primes, all prime and strictly increasing, none missing from 2 to 100"]);
+
+    let delta = dir.join("primes.bin");
+    write_delta(&delta, &primes_up_to(100));
+    assert_eq!(validate(&delta, &[]), (Some(0), vec!["25 primes, all prime and strictly increasing".to_owned()]));
+}
+
+#[test]
+fn validate_reports_a_composite_with_its_line() {
+    let dir = common::scratch_dir("validate-composite");
+    let mut values = primes_up_to(100);
+    values.insert(24, 91);
+    let text = dir.join("primes.txt");
+    write_text(&text, &values);
+    let (code, lines) = validate(&text, &[]);
+    assert_eq!(code, Some(1));
+    assert_eq!(lines, ["line 25: 91 is not prime", "stopped after 1 problem in the first 25 values; see --max-errors"]);
+
+    let delta = dir.join("primes.bin");
+    write_delta(&delta, &values);
+    let (code, lines) = validate(&delta, &[]);
+    assert_eq!(code, Some(1));
+    assert_eq!(lines[0], "prime #25: 91 is not prime");
+}
+
+#[test]
+fn validate_reports_a_swapped_pair_once() {
+    let dir = common::scratch_dir("validate-swapped");
+    let mut values = primes_up_to(100);
+    values.swap(5, 6);
+    let text = dir.join("primes.txt");
+    write_text(&text, &values);
+    let (code, lines) = validate(&text, &["--max-errors", "10"]);
+    assert_eq!(code, Some(1));
+    assert_eq!(lines, ["line 7: 13 does not come after 17 (line 6)", "1 problem in 25 values"]);
+}
+
+#[test]
+fn validate_complete_reports_a_gap() {
+    let dir = common::scratch_dir("validate-gap");
+    let mut values = primes_up_to(100);
+    values.retain(|&p| p != 53);
+    let text = dir.join("primes.txt");
+    write_text(&text, &values);
+    // Without a range to check against, a list with a prime left out is still a valid list.
+    assert_eq!(validate(&text, &[]).0, Some(0));
+    let (code, lines) = validate(&text, &["--complete", "2..100"]);
+    assert_eq!(code, Some(1));
+    assert_eq!(lines[0], "53 is missing (expected before line 16)");
+    // A swapped pair under --complete also leaves the first of the two missing where it belongs.
+    let mut swapped = primes_up_to(100);
+    swapped.swap(5, 6);
+    write_text(&text, &swapped);
+    let (_, lines) = validate(&text, &["--complete", "2..100", "--max-errors", "10"]);
+    assert_eq!(lines[..2], ["13 is missing (expected before line 6)", "line 7: 13 does not come after 17 (line 6)"]);
+
+    let delta = dir.join("primes.bin");
+    write_delta(&delta, &values);
+    let (code, lines) = validate(&delta, &["--complete", "2..100"]);
+    assert_eq!(code, Some(1));
+    assert_eq!(lines[0], "53 is missing (expected before prime #16)");
+    // Missing from the end too: the range runs past the last value in the file.
+    let (code, lines) = validate(&delta, &["--complete", "2..110", "--max-errors", "10"]);
+    assert_eq!(code, Some(1));
+    assert_eq!(lines[1], "101 is missing (expected after the last value)");
+}
+
+#[test]
+fn max_errors_lists_each_problem_up_to_k() {
+    let dir = common::scratch_dir("validate-max-errors");
+    let mut values = primes_up_to(100);
+    values.swap(5, 6);
+    values.insert(15, 49);
+    values.insert(25, 91);
+    let text = dir.join("primes.txt");
+    write_text(&text, &values);
+    let expected = [
+        "line 7: 13 does not come after 17 (line 6)",
+        "line 16: 49 is not prime",
+        "line 26: 91 is not prime",
+    ];
+
+    let (code, lines) = validate(&text, &["--max-errors", "10"]);
+    assert_eq!(code, Some(1));
+    assert_eq!(lines[..3], expected);
+    assert_eq!(lines[3..], ["3 problems in 27 values"]);
+
+    for k in 1..=3 {
+        let (code, lines) = validate(&text, &["--max-errors", &k.to_string()]);
+        assert_eq!(code, Some(1), "--max-errors {}", k);
+        assert_eq!(lines[..k], expected[..k], "--max-errors {}", k);
+        let plural = if k == 1 { "" } else { "s" };
+        let summary = format!("stopped after {} problem{} in the first ", k, plural);
+        assert!(lines[k].starts_with(&summary), "--max-errors {}: {}", k, lines[k]);
+        assert_eq!(lines.len(), k + 1, "--max-errors {}", k);
+    }
+
+    assert_eq!(common::run(&["validate", text.to_str().unwrap(), "--max-errors", "0"]).status.code(), Some(2));
+}